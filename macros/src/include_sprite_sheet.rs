@@ -333,6 +333,7 @@ impl ToTokens for PixelState {
 	}
 }
 
+#[allow(dead_code)]
 struct PixelWrapper<'h>(&'h Pixel);
 impl<'h> ToTokens for PixelWrapper<'h> {
 	fn to_tokens(&self, tokens: &mut TokenStream) {
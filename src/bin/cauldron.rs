@@ -1,7 +1,7 @@
-use clap::{value_parser, Parser};
+use clap::{value_parser, ArgAction, Parser};
 use colors_transform::{Color, Hsl, Rgb};
 use image::{GenericImageView, ImageBuffer, Rgba, RgbaImage};
-use pdt::{pdtcolor, pdtfs, pdtthread};
+use pdt::{pdtcolor, pdtfs, pdtlog, pdtthread};
 use pdt::{pdtstdin, pdttrait::Vector};
 use std::path::MAIN_SEPARATOR as SLASH;
 use std::sync::Arc;
@@ -16,7 +16,6 @@ example: .{s}cauldron F5B7D0 .{s}images
 example: .{s}cauldron e6c343 -s 65 .{s}assets", s = SLASH),
 	long_about = None)
 ]
-
 struct Args {
 	/// Hex color to tint images
 	hex_color: String,
@@ -25,10 +24,17 @@ struct Args {
 	saturation: Option<u32>,
 	/// List of files and folders to tint
 	input_paths: Vec<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
 }
 
 fn main() {
 	let args = Args::parse();
+	pdtlog::init(args.verbose, args.quiet);
 	let paths = pdtstdin::get_stdin()
 		.unwrap_or_default()
 		.extend_vec(args.input_paths);
@@ -61,9 +67,9 @@ fn dye_images_in_cauldron(images: Vec<String>, color: Hsl, saturation: Option<f3
 		images,
 		None,
 		move |thread_num, (image, color, saturation)| {
-			println!(
+			log::info!(
 				"[thread {thread_num:02} cauldron] dying image: {}",
-				image.split(SLASH).last().unwrap()
+				image.split(SLASH).next_back().unwrap()
 			);
 			let img =
 				image::open(&image).unwrap_or_else(|_| panic!("Failed to load image: {image}"));
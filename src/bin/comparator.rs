@@ -30,8 +30,10 @@ fn main() {
 		Structure::Ordered => {}
 		Structure::Unordered => {}
 	}
-	let files = pdtfs::find_files_in_multiple_dirs(dirs.clone(), recursive, None, true, false)
-		.sort_and_dedup_vec();
+	let filter = pdtfs::FileFilter::default();
+	let files =
+		pdtfs::find_files_in_multiple_dirs(dirs.clone(), recursive, None, true, false, &filter)
+			.sort_and_dedup_vec();
 	let file_data = get_files_data(dirs.clone(), files);
 	let results = compare_files(dirs, file_data);
 	let mut changes: Vec<String> = vec![];
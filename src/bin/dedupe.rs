@@ -1,10 +1,11 @@
 use pdt::pdttrait::Vector;
-use pdt::{pdtfs, pdthash};
+use pdt::{pdtfs, pdthash, pdtlog};
 use std::path::MAIN_SEPARATOR as SLASH;
 use std::time::SystemTime;
 use std::{env, fs, io::Write};
 
 fn main() {
+	pdtlog::init(0, false);
 	let args: Vec<String> = env::args().collect();
 	let dir = args[1].to_string();
 	let dupes = dedupe(dir.clone());
@@ -158,7 +158,7 @@ fn main() {
 
 						for _ in 0..4 {
 							for pixel in pixel_row.iter_mut() {
-								current_row.extend(pixel.next().unwrap().into_iter());
+								current_row.extend(pixel.next().unwrap());
 							}
 
 							rows_of_upscaled_pixels.push(current_row);
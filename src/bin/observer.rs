@@ -67,7 +67,7 @@ fn observe(old_release: String, new_release: String) {
 		pdtfs::rename(&format!(".{SLASH}.git"), &format!(".{SLASH}.git_temp"));
 	}
 
-	pdtfs::copy_dir_to_dir(&".".to_string(), new_release, true);
+	pdtfs::copy_dir_to_dir(".", new_release, true);
 
 	if Utf8Path::new(&format!(".{SLASH}.git_temp")).is_dir() {
 		pdtfs::if_dir_exists_remove_it(&format!(".{SLASH}.git"));
@@ -0,0 +1,17 @@
+use clap::Parser;
+use pdt::pdtaudio::{self, AudioArgs};
+
+#[derive(Debug, Parser)]
+#[command(name = env!("CARGO_PKG_NAME"),
+	bin_name = env!("CARGO_BIN_NAME"),
+	version,
+	about = pdtaudio::about(),
+	long_about = None)]
+struct Args {
+	#[command(flatten)]
+	args: AudioArgs,
+}
+
+fn main() {
+	pdtaudio::run(Args::parse().args);
+}
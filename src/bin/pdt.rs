@@ -0,0 +1,123 @@
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use pdt::pdtaudio::{self, AudioArgs};
+use pdt::pdtblockify::{self, BlockifyArgs};
+use pdt::pdtchangelog::{self, ChangelogArgs};
+use pdt::pdtconvert::{self, ConvertArgs};
+use pdt::pdtcoverage::{self, CoverageArgs};
+use pdt::pdtcredits::{self, CreditsArgs};
+use pdt::pdtdiff::{self, DiffArgs};
+use pdt::pdtexport::{self, ExportArgs};
+use pdt::pdtfont::{self, FontArgs};
+use pdt::pdtjson::{self, JsonArgs};
+use pdt::pdtmerge::{self, MergeArgs};
+use pdt::pdtoptimize::{self, OptimizeArgs};
+use pdt::pdtpublish::{self, PublishArgs};
+use pdt::pdtrandom::{self, RandomArgs};
+use pdt::pdtrelease::{self, BuildArgs};
+use pdt::pdtselftest::{self, SelftestArgs};
+use pdt::pdtvalidate::{self, ValidateArgs};
+use pdt::pdtversion::{self, VersionArgs};
+use pdt::pdtwatch::{self, WatchArgs};
+use std::path::MAIN_SEPARATOR as SLASH;
+
+#[derive(Debug, Parser)]
+#[command(name = env!("CARGO_PKG_NAME"),
+bin_name = env!("CARGO_BIN_NAME"),
+	version,
+	about = format!("All of the pack dev tools in one.
+
+example: .{s}pdt blockify 16 .{s}blocks .{s}assets
+example: .{s}pdt build --config builder.toml
+example: .{s}pdt watch -o .{s}.minecraft{s}resourcepacks{s}dev
+example: .{s}pdt version bump minor
+example: .{s}pdt publish all v1.2.0
+example: .{s}pdt convert bedrock .{s}Love-and-Tolerance
+example: .{s}pdt completions bash > pdt.bash", s = SLASH),
+	long_about = None)
+]
+struct Args {
+	#[command(subcommand)]
+	command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// Blockify images by turning every pixel into a block texture
+	Blockify(BlockifyArgs),
+	/// Optimize all png files in given path(s)
+	Optimize(OptimizeArgs),
+	/// Convert wav and mp3 files in given path(s) to ogg vorbis
+	OptimizeAudio(AudioArgs),
+	/// Format or minify json and mcmeta files in given path(s)
+	FormatJson(JsonArgs),
+	/// Overlay addons on a base pack and zip every combination of them
+	Build(BuildArgs),
+	/// Stage a release in a folder and update it as the layers change
+	Watch(WatchArgs),
+	/// Upload the release zips of the last build
+	Publish(PublishArgs),
+	/// List the changes since the last git tag as a changelog section
+	Changelog(ChangelogArgs),
+	/// Print the credits from contributors.toml, or put them in README.md
+	Credits(CreditsArgs),
+	/// Lay packs over each other, combining sounds, languages, atlases and
+	/// fonts
+	Merge(MergeArgs),
+	/// List the files added, removed and changed between two packs
+	Diff(DiffArgs),
+	/// Convert a pack to another edition
+	Convert(ConvertArgs),
+	/// Export previews of a pack, like the texture gallery of the website
+	Export(ExportArgs),
+	/// Check a pack for problems that would only show up in-game
+	Validate(ValidateArgs),
+	/// Print the pack version, or bump it everywhere and tag it
+	Version(VersionArgs),
+	/// Add a folder of texture variants to a pack as OptiFine random
+	/// textures, with their properties
+	Random(RandomArgs),
+	/// Build a glyph sheet from a folder of character images and add it to
+	/// a font
+	Font(FontArgs),
+	/// Show how much of the vanilla textures, sounds and models a pack
+	/// replaces
+	Coverage(CoverageArgs),
+	/// Make a tiny pack and run it through every tool a release goes
+	/// through, to check pdt works
+	Selftest(SelftestArgs),
+	/// Print a shell completion script
+	Completions {
+		/// Shell to complete in
+		shell: Shell,
+	},
+}
+
+fn main() {
+	match Args::parse().command {
+		Command::Blockify(args) => pdtblockify::run(args),
+		Command::Optimize(args) => pdtoptimize::run(args),
+		Command::OptimizeAudio(args) => pdtaudio::run(args),
+		Command::FormatJson(args) => pdtjson::run(args),
+		Command::Build(args) => pdtrelease::build(args),
+		Command::Watch(args) => pdtwatch::run(args),
+		Command::Publish(args) => pdtpublish::run(args),
+		Command::Changelog(args) => pdtchangelog::run(args),
+		Command::Credits(args) => pdtcredits::run(args),
+		Command::Merge(args) => pdtmerge::run(args),
+		Command::Diff(args) => pdtdiff::run(args),
+		Command::Convert(args) => pdtconvert::run(args),
+		Command::Export(args) => pdtexport::run(args),
+		Command::Validate(args) => pdtvalidate::run(args),
+		Command::Version(args) => pdtversion::run(args),
+		Command::Random(args) => pdtrandom::run(args),
+		Command::Font(args) => pdtfont::run(args),
+		Command::Coverage(args) => pdtcoverage::run(args),
+		Command::Selftest(args) => pdtselftest::run(args),
+		Command::Completions { shell } => {
+			let mut command = Args::command();
+			let name = command.get_name().to_string();
+			clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+		}
+	}
+}
@@ -1,5 +1,7 @@
-use clap::Parser;
 use clap::ValueEnum;
+use clap::{ArgAction, Parser, Subcommand};
+use pdt::pdtlog;
+use pdt::pdtrelease::{self, BuildArgs};
 use serde::{Deserialize, Serialize};
 use std::path::MAIN_SEPARATOR as SLASH;
 
@@ -16,19 +18,39 @@ bin_name = env!("CARGO_BIN_NAME"),
 	version,
 	about = format!("Build Love & Tolerance release.
 
-example: .{SLASH}release-builder"),
+example: .{SLASH}release-builder
+example: .{SLASH}release-builder build --config builder.toml
+example: .{SLASH}release-builder build -b .{SLASH}base -a seasonal=.{SLASH}seasonal -c full=seasonal"),
 	long_about = None)
 ]
-
 struct Args {
+	#[command(subcommand)]
+	command: Option<Command>,
 	#[arg(short, long)]
 	/// Minecraft platform
 	platform: Option<MinecraftPlatform>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// Overlay addons on a base pack and zip every combination of them
+	Build(BuildArgs),
 }
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 	let args = Args::parse();
-	release_builder(args.platform).await?;
+	pdtlog::init(args.verbose, args.quiet);
+	match args.command {
+		Some(Command::Build(build_args)) => pdtrelease::build(build_args),
+		None => release_builder(args.platform).await?,
+	}
 	Ok(())
 }
 
@@ -36,7 +58,7 @@ async fn release_builder(
 	platform: Option<MinecraftPlatform>,
 ) -> Result<(), Box<dyn std::error::Error>> {
 	if platform.is_some() {
-		println!("{:#?}", platform);
+		log::debug!("{:#?}", platform);
 	}
 	let bedrock = reqwest::get(
 		"https://raw.githubusercontent.com/Love-and-Tolerance/pack-builder-assets/mane/assets/bedrock.json",
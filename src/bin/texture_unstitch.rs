@@ -1,9 +1,10 @@
 use image::{imageops, GenericImageView};
-use pdt::pdtfs;
+use pdt::{pdtfs, pdtlog};
 use std::env;
 use std::path::MAIN_SEPARATOR as SLASH;
 
 fn main() {
+	pdtlog::init(0, false);
 	let args: Vec<String> = env::args().collect();
 	let filename = args[1].to_string();
 	let width = args[2].to_string().parse::<u32>().unwrap();
@@ -18,7 +19,7 @@ fn unstitch_texture(filename: String, width: u32, height: u32) {
 	let image_width = img.dimensions().0;
 	let image_height = img.dimensions().1;
 
-	if image_width % width != 0 || image_height % height != 0 {
+	if !image_width.is_multiple_of(width) || !image_height.is_multiple_of(height) {
 		panic!("Image dimensions not divisible by supplied tile dimension!");
 	}
 
@@ -31,7 +32,7 @@ fn unstitch_texture(filename: String, width: u32, height: u32) {
 
 	for y in 0..width as usize {
 		for x in 0..height as usize {
-			println!("{x}, {y}");
+			log::debug!("{x}, {y}");
 			let subimg = imageops::crop(
 				&mut img,
 				x as u32 * sprite_width,
@@ -1,6 +1,6 @@
-use clap::Parser;
+use clap::{ArgAction, Parser};
 use image::{GenericImageView, ImageBuffer, Rgba, RgbaImage};
-use pdt::{pdtfs, pdtthread};
+use pdt::{pdtfs, pdtlog, pdtthread};
 use pdt::{pdtstdin, pdttrait::Vector};
 use std::path::MAIN_SEPARATOR as SLASH;
 use std::sync::Arc;
@@ -15,18 +15,24 @@ example: .{s}villager-infector .{s}assets{s}minelittlepony{s}textures{s}entity/p
 example: .{s}villager-infector -c .{s}Community-Skin-Pack", s = SLASH),
 	long_about = None)
 ]
-
 struct Args {
 	#[arg(short, long)]
 	/// Convert Community Skin Pack into Villager Skin Pack.
 	convert: bool,
 	/// List of files and folders to infect.
 	input_paths: Vec<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
 }
 
 fn main() {
 	let overlay = include_bytes!("../../assets/zompony_overlay.png");
 	let args = Args::parse();
+	pdtlog::init(args.verbose, args.quiet);
 	let paths = pdtstdin::get_stdin()
 		.unwrap_or_default()
 		.extend_vec(args.input_paths);
@@ -101,7 +107,7 @@ fn villager_infector(
 		ponies,
 		None,
 		move |thread_num, (pony, trigger_pixels, overlay_pixels)| {
-			println!(
+			log::info!(
 				"[thread {:02} villager-infector] infecting pony: {}",
 				thread_num,
 				pony.rsplit(SLASH)
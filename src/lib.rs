@@ -1,11 +1,59 @@
+#[cfg(feature = "audio")]
+pub mod pdtaudio;
+#[cfg(feature = "blockify")]
+pub mod pdtblockify;
+#[cfg(feature = "cache")]
+pub mod pdtcache;
+#[cfg(feature = "changelog")]
+pub mod pdtchangelog;
 #[cfg(feature = "cmd")]
 pub mod pdtcmd;
 #[cfg(feature = "color")]
 pub mod pdtcolor;
+#[cfg(feature = "convert")]
+pub mod pdtconvert;
+#[cfg(feature = "coverage")]
+pub mod pdtcoverage;
+#[cfg(feature = "credits")]
+pub mod pdtcredits;
+#[cfg(feature = "diff")]
+pub mod pdtdiff;
+#[cfg(feature = "export")]
+pub mod pdtexport;
+#[cfg(feature = "font")]
+pub mod pdtfont;
 #[cfg(feature = "fs")]
 pub mod pdtfs;
 #[cfg(feature = "hash")]
 pub mod pdthash;
+#[cfg(feature = "history")]
+pub mod pdthistory;
+#[cfg(feature = "json")]
+pub mod pdtjson;
+#[cfg(feature = "lock")]
+pub mod pdtlock;
+#[cfg(feature = "log")]
+pub mod pdtlog;
+#[cfg(feature = "match")]
+pub mod pdtmatch;
+#[cfg(feature = "merge")]
+pub mod pdtmerge;
+#[cfg(feature = "mojang")]
+pub mod pdtmojang;
+#[cfg(feature = "optimize")]
+pub mod pdtoptimize;
+#[cfg(feature = "pipeline")]
+pub mod pdtpipeline;
+#[cfg(feature = "publish")]
+pub mod pdtpublish;
+#[cfg(feature = "random")]
+pub mod pdtrandom;
+#[cfg(feature = "release")]
+pub mod pdtrelease;
+#[cfg(feature = "report")]
+pub mod pdtreport;
+#[cfg(feature = "selftest")]
+pub mod pdtselftest;
 #[cfg(feature = "stdin")]
 pub mod pdtstdin;
 #[cfg(feature = "struct")]
@@ -14,3 +62,11 @@ pub mod pdtstruct;
 pub mod pdtthread;
 #[cfg(feature = "trait")]
 pub mod pdttrait;
+#[cfg(feature = "validate")]
+pub mod pdtvalidate;
+#[cfg(feature = "version")]
+pub mod pdtversion;
+#[cfg(feature = "watch")]
+pub mod pdtwatch;
+#[cfg(feature = "zip")]
+pub mod pdtzip;
@@ -0,0 +1,360 @@
+use super::pdtfs::{get_files_in_list, FilterArgs};
+use super::{pdtlog, pdtstdin, pdtthread, pdttrait::Vector};
+use camino::Utf8Path;
+use clap::{value_parser, ArgAction};
+use serde::Deserialize;
+use std::f64::consts::PI;
+use std::fs;
+use std::num::{NonZeroU32, NonZeroU8};
+use std::path::MAIN_SEPARATOR as SLASH;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+/// Sources that are converted to ogg.
+pub const AUDIO_EXTENSIONS: [&str; 2] = [".wav", ".mp3"];
+/// Fixed so the same source always gives the same ogg.
+const STREAM_SERIAL: i32 = 0x706474;
+/// Taps on each side of a sample when resampling.
+const SINC_TAPS: i64 = 16;
+
+/// Help text for the optimize-audio tool.
+pub fn about() -> String {
+	format!(
+		"Convert wav and mp3 files in given path(s) to game-ready ogg vorbis.
+
+example: .{s}optimize-audio .{s}assets{s}minecraft{s}sounds
+example: .{s}optimize-audio -Q 0.8 --loudness -14 recording.wav",
+		s = SLASH
+	)
+}
+
+/// How sounds are converted, from the [audio] table of the build config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AudioConfig {
+	/// Vorbis quality [-0.2..1]
+	#[serde(default = "default_quality")]
+	pub quality: f32,
+	/// Sample rate of the ogg files
+	#[serde(default = "default_sample_rate")]
+	pub sample_rate: u32,
+	/// Integrated loudness to normalize to, in LUFS, or none to keep it
+	#[serde(default = "default_loudness")]
+	pub loudness: Option<f64>,
+}
+
+fn default_quality() -> f32 {
+	0.5
+}
+
+fn default_sample_rate() -> u32 {
+	44100
+}
+
+fn default_loudness() -> Option<f64> {
+	Some(-18.0)
+}
+
+impl Default for AudioConfig {
+	fn default() -> Self {
+		AudioConfig {
+			quality: default_quality(),
+			sample_rate: default_sample_rate(),
+			loudness: default_loudness(),
+		}
+	}
+}
+
+/// Decoded audio, with the samples of every channel after each other.
+pub struct Audio {
+	pub sample_rate: u32,
+	pub channels: Vec<Vec<f32>>,
+}
+
+/// Decodes a wav or mp3 file.
+pub fn decode(path: &str) -> Audio {
+	let file = fs::File::open(path).unwrap_or_else(|_| panic!("Failed to open file: {path}"));
+	let source = MediaSourceStream::new(Box::new(file), Default::default());
+	let mut hint = Hint::new();
+	if let Some(extension) = path.rsplit('.').next() {
+		hint.with_extension(extension);
+	}
+	let probed = symphonia::default::get_probe()
+		.format(
+			&hint,
+			source,
+			&FormatOptions::default(),
+			&MetadataOptions::default(),
+		)
+		.unwrap_or_else(|e| panic!("Failed to read audio {path}: {e}"));
+	let mut format = probed.format;
+	let track = format
+		.default_track()
+		.unwrap_or_else(|| panic!("No audio in {path}"));
+	let track_id = track.id;
+	let mut decoder = symphonia::default::get_codecs()
+		.make(&track.codec_params, &DecoderOptions::default())
+		.unwrap_or_else(|e| panic!("Failed to decode {path}: {e}"));
+
+	let mut sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
+	let mut channels: Vec<Vec<f32>> = vec![];
+	loop {
+		let packet = match format.next_packet() {
+			Ok(packet) => packet,
+			Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+			Err(e) => panic!("Failed to read audio {path}: {e}"),
+		};
+		if packet.track_id() != track_id {
+			continue;
+		}
+		let decoded = match decoder.decode(&packet) {
+			Ok(decoded) => decoded,
+			Err(DecodeError::DecodeError(e)) => {
+				log::debug!("skipping a broken packet of {path}: {e}");
+				continue;
+			}
+			Err(e) => panic!("Failed to decode {path}: {e}"),
+		};
+		let spec = *decoded.spec();
+		sample_rate = spec.rate;
+		let count = spec.channels.count();
+		if channels.is_empty() {
+			channels = vec![vec![]; count];
+		}
+		let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+		buffer.copy_interleaved_ref(decoded);
+		for frame in buffer.samples().chunks(count) {
+			for (channel, sample) in channels.iter_mut().zip(frame) {
+				channel.push(*sample);
+			}
+		}
+	}
+	Audio {
+		sample_rate,
+		channels,
+	}
+}
+
+/// Resamples a channel with a Hann windowed sinc.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+	if from == to || samples.is_empty() {
+		return samples.to_vec();
+	}
+	let ratio = from as f64 / to as f64;
+	// lower the cutoff when downsampling so nothing folds back
+	let cutoff = (1.0 / ratio).min(1.0);
+	let length = (samples.len() as f64 / ratio).round() as usize;
+	(0..length)
+		.map(|i| {
+			let t = i as f64 * ratio;
+			let center = t.floor() as i64;
+			let mut sum = 0.0;
+			for k in center - SINC_TAPS + 1..=center + SINC_TAPS {
+				let Some(sample) = usize::try_from(k).ok().and_then(|k| samples.get(k)) else {
+					continue;
+				};
+				let x = t - k as f64;
+				let sinc = match x == 0.0 {
+					true => 1.0,
+					false => (PI * cutoff * x).sin() / (PI * cutoff * x),
+				};
+				let window = 0.5 + 0.5 * (PI * x / SINC_TAPS as f64).cos();
+				sum += *sample as f64 * cutoff * sinc * window;
+			}
+			sum as f32
+		})
+		.collect()
+}
+
+/// Changes the gain so the integrated loudness is the target, but never so
+/// much that it clips. Sounds too short to measure are left as they are.
+pub fn normalize(audio: &mut Audio, target: f64) {
+	let Ok(mut meter) = ebur128::EbuR128::new(
+		audio.channels.len() as u32,
+		audio.sample_rate,
+		ebur128::Mode::I,
+	) else {
+		return;
+	};
+	let planar = audio
+		.channels
+		.iter()
+		.map(|c| c.as_slice())
+		.collect::<Vec<_>>();
+	if meter.add_frames_planar_f32(&planar).is_err() {
+		return;
+	}
+	let Ok(loudness) = meter.loudness_global() else {
+		return;
+	};
+	if !loudness.is_finite() {
+		log::debug!("too short or quiet to measure loudness, leaving it as is");
+		return;
+	}
+	let peak = audio
+		.channels
+		.iter()
+		.flatten()
+		.fold(0.0f32, |peak, s| peak.max(s.abs()));
+	let mut gain = 10f64.powf((target - loudness) / 20.0) as f32;
+	if peak > 0.0 && peak * gain > 0.99 {
+		gain = 0.99 / peak;
+	}
+	log::debug!("loudness {loudness:.1} LUFS, gain {gain:.2}");
+	for sample in audio.channels.iter_mut().flatten() {
+		*sample *= gain;
+	}
+}
+
+/// Encodes audio as ogg vorbis.
+pub fn encode(audio: &Audio, quality: f32) -> Vec<u8> {
+	let rate = NonZeroU32::new(audio.sample_rate).expect("Sample rate can't be 0.");
+	let channels = u8::try_from(audio.channels.len())
+		.ok()
+		.and_then(NonZeroU8::new)
+		.expect("Audio needs 1 to 255 channels.");
+	let mut encoder = VorbisEncoderBuilder::new_with_serial(rate, channels, vec![], STREAM_SERIAL)
+		.bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+			target_quality: quality,
+		})
+		.build()
+		.unwrap_or_else(|e| panic!("Failed to start encoding: {e}"));
+	let length = audio.channels[0].len();
+	for start in (0..length).step_by(1024) {
+		let end = (start + 1024).min(length);
+		let block = audio
+			.channels
+			.iter()
+			.map(|c| &c[start..end])
+			.collect::<Vec<_>>();
+		encoder
+			.encode_audio_block(block)
+			.unwrap_or_else(|e| panic!("Failed to encode audio: {e}"));
+	}
+	encoder
+		.finish()
+		.unwrap_or_else(|e| panic!("Failed to finish encoding: {e}"))
+}
+
+/// Converts a wav or mp3 file to an ogg file, resampled and normalized.
+pub fn convert(input: &str, output: &str, config: &AudioConfig) {
+	let mut audio = decode(input);
+	if audio.channels.is_empty() || audio.channels[0].is_empty() {
+		panic!("No audio in {input}");
+	}
+	audio.channels = audio
+		.channels
+		.iter()
+		.map(|c| resample(c, audio.sample_rate, config.sample_rate))
+		.collect();
+	audio.sample_rate = config.sample_rate;
+	if let Some(loudness) = config.loudness {
+		normalize(&mut audio, loudness);
+	}
+	let data = encode(&audio, config.quality);
+	fs::write(output, data).unwrap_or_else(|_| panic!("Failed to write file: {output}"));
+}
+
+/// Whether a file is a wav or mp3 source.
+pub fn is_source(path: &str) -> bool {
+	AUDIO_EXTENSIONS
+		.iter()
+		.any(|e| path.to_lowercase().ends_with(e))
+}
+
+/// Path of the ogg file a source becomes.
+pub fn ogg_path(path: &str) -> String {
+	match is_source(path) {
+		true => format!("{}.ogg", &path[..path.len() - 4]),
+		false => format!("{path}.ogg"),
+	}
+}
+
+#[derive(Debug, clap::Args)]
+pub struct AudioArgs {
+	#[arg(short = 'Q', long, default_value_t = 0.5, value_parser = parse_quality)]
+	/// Vorbis quality [-0.2..1]
+	quality: f32,
+	#[arg(short, long, default_value_t = 44100, value_parser = value_parser!(u32).range(8000..=192000))]
+	/// Sample rate of the ogg files
+	sample_rate: u32,
+	#[arg(short, long, default_value_t = -18.0, allow_negative_numbers = true)]
+	/// Integrated loudness to normalize to, in LUFS
+	loudness: f64,
+	#[arg(long)]
+	/// Keep the loudness of the sources
+	no_normalize: bool,
+	#[arg(long)]
+	/// Delete the sources after converting them
+	remove_sources: bool,
+	#[command(flatten)]
+	filter: FilterArgs,
+	#[command(flatten)]
+	threads: pdtthread::ThreadArgs,
+	/// List of files and folders to convert
+	paths: Vec<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+fn parse_quality(value: &str) -> Result<f32, String> {
+	match value.parse::<f32>() {
+		Ok(quality) if (-0.2..=1.0).contains(&quality) => Ok(quality),
+		_ => Err("quality must be a number from -0.2 to 1".to_string()),
+	}
+}
+
+pub fn run(args: AudioArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	args.threads.apply();
+	pdtthread::cancel_on_ctrl_c();
+	let paths = pdtstdin::get_stdin()
+		.unwrap_or_default()
+		.extend_vec(args.paths);
+	let config = AudioConfig {
+		quality: args.quality,
+		sample_rate: args.sample_rate,
+		loudness: (!args.no_normalize).then_some(args.loudness),
+	};
+	let extensions = Some(AUDIO_EXTENSIONS.map(|e| e.to_string()).to_vec());
+	let files = paths
+		.iter()
+		.filter(|p| Utf8Path::new(p).is_file() && is_source(p))
+		.cloned()
+		.collect::<Vec<_>>();
+	let sources = get_files_in_list(paths, true, extensions, false, true, &args.filter.filter())
+		.into_iter()
+		.filter(|p| is_source(p))
+		.collect::<Vec<_>>()
+		.extend_vec(files)
+		.sort_and_dedup_vec();
+	let remove = args.remove_sources;
+	let count = sources.len();
+	let result = pdtthread::try_multithread(sources, None, move |_, source| {
+		let output = ogg_path(&source);
+		log::info!("converting: {source}");
+		convert(&source, &output, &config);
+		if remove {
+			fs::remove_file(&source).map_err(|e| format!("Failed to remove {source}: {e}"))?;
+		}
+		Ok::<_, String>(())
+	});
+	match result {
+		Err(errors) => panic!("Failed to convert every sound: {errors}"),
+		Ok(done) if pdtthread::cancelled() => println!(
+			"Stopped after converting {} of {count} sounds, run again to convert the rest.",
+			done.len()
+		),
+		Ok(_) => (),
+	}
+}
@@ -0,0 +1,1205 @@
+use super::pdtfs::{FileFilter, Symlinks};
+use super::pdtlog::Progress;
+use super::pdtmatch::{self, match_pixel, Block, Palette};
+use super::pdtreport::Stats;
+use super::pdtzip::ZipStream;
+use super::{pdtcolor, pdtfont, pdtfs, pdthash, pdtlog, pdtmojang, pdtthread, pdtzip};
+use super::{pdtstdin, pdttrait::Vector};
+use camino::Utf8Path;
+use clap::{value_parser, ArgAction, Args, Command, FromArgMatches, ValueEnum};
+use deltae::*;
+use glob::Pattern;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, ImageBuffer, ImageFormat, Rgba, RgbaImage};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::Cursor;
+use std::path::MAIN_SEPARATOR as SLASH;
+use std::sync::Arc;
+
+/// Help text for the blockify tool.
+pub fn about() -> String {
+	format!(
+		"Blockify images by turning every pixel into a block texture.
+
+Textures unchanged since the last run are skipped, use --force to redo them all.
+Per-texture overrides are read from blockify.toml if it exists, see --config.
+Zipped packs and palettes are read directly, and zipped packs are written back as a zip.
+
+example: .{s}blockify 16 .{s}assets{s}minecraft{s}textures{s}blocks .{s}assets
+example: .{s}blockify 16 .{s}blocks.zip .{s}pack.zip
+example: .{s}blockify 16 --palette-version 1.20.4 .{s}pack.zip",
+		s = SLASH
+	)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BlockifyArgs {
+	#[arg(value_parser = value_parser!(u32).range(2..=32))]
+	/// Size each pixel becomes, palette blocks of other sizes are scaled to it [2..32]
+	block_pixels: u32,
+	#[arg(required_unless_present = "palette_version")]
+	/// Path to block textures, or any other square tiles like item textures,
+	/// or a zip to use every png in, left out with --palette-version
+	blocks_path: Option<String>,
+	/// List of files and folders to blockify
+	input_paths: Vec<String>,
+	#[arg(short, long)]
+	/// Reprocess every texture, even if it is unchanged since the last run
+	force: bool,
+	#[arg(short, long)]
+	/// Also write the output folder as a zip next to it, on by default for zipped packs
+	zip: bool,
+	#[arg(long, conflicts_with = "dry_run")]
+	/// Only write the zip, with the files that aren't blockified read straight
+	/// from the inputs instead of copied to the output folder first. Every
+	/// texture is blockified again on every run
+	zip_only: bool,
+	#[arg(long, value_name = "DIR", default_value = OUTPUT_DIR)]
+	/// Folder to write the blockified textures to, the palette preview is
+	/// saved next to it as <out-dir>-palette-preview.png
+	out_dir: String,
+	#[arg(long, value_name = "VERSION")]
+	/// Use the vanilla block textures of a Minecraft version as the palette,
+	/// downloaded once and cached, or latest or snapshot for the newest
+	palette_version: Option<String>,
+	#[arg(short, long)]
+	/// Per-texture overrides, defaults to blockify.toml if it exists
+	config: Option<String>,
+	#[arg(short = 'n', long)]
+	/// Only list the textures that would be blockified and how big they would get
+	dry_run: bool,
+	#[arg(short, long, value_enum, default_value_t = TieBreak::Path)]
+	/// How to choose between blocks that match a pixel equally well
+	tie_break: TieBreak,
+	#[arg(short, long, value_enum, default_value_t = AlphaMode::Copy)]
+	/// How to handle translucent pixels and palette blocks
+	alpha_mode: AlphaMode,
+	#[arg(long, default_value_t = 0)]
+	/// Seed for the seeded tie break and --variety
+	seed: u64,
+	#[arg(long, value_name = "DELTAE")]
+	/// Rotate through the blocks within this DeltaE of the closest match,
+	/// picking at random among the ones used longest ago, so flat areas
+	/// aren't all one block
+	variety: Option<f64>,
+	#[arg(long, value_name = "NAME[=HEX]")]
+	/// Tint textures and palette blocks named NAME (without .png) before matching,
+	/// by HEX or by the --colormap color if no color is given
+	tint: Vec<String>,
+	#[arg(long)]
+	/// Biome colormap to sample tints from, such as colormap/grass.png
+	colormap: Option<String>,
+	#[arg(long, default_value_t = 0.8)]
+	/// Biome temperature to sample the colormap at, defaults to plains
+	temperature: f32,
+	#[arg(long, default_value_t = 0.4)]
+	/// Biome downfall to sample the colormap at, defaults to plains
+	downfall: f32,
+	#[arg(long)]
+	/// Scale palette blocks to the block size in linear light, so their
+	/// colors average the way they look from afar
+	linear: bool,
+	#[arg(long)]
+	/// Also use the tiles in subfolders of the palette folder, zips always
+	/// are
+	recursive: bool,
+	#[arg(long, value_name = "HEX")]
+	/// Draw translucent palette tiles over this color, so tile sets with
+	/// transparent backgrounds like items can be used without --alpha-mode
+	/// match
+	background: Option<String>,
+	#[arg(long)]
+	/// Also write a heatmap of how close every pixel of the blockified textures
+	/// is to its block to <out-dir>-heatmap, from green for a perfect match to
+	/// red for a DeltaE of 20 or more, to find colors the palette is missing
+	heatmap: bool,
+	#[arg(long, value_name = "PIXELS", num_args = 0..=1, default_missing_value = "4")]
+	#[arg(value_parser = value_parser!(u32).range(1..=32))]
+	/// Write a quick mosaic to <out-dir>-preview instead, with the matched blocks
+	/// scaled down to this size (4 if not given) to check the matches before a
+	/// full run
+	preview: Option<u32>,
+	#[command(flatten)]
+	threads: pdtthread::ThreadArgs,
+	#[arg(long, value_name = "FILE")]
+	/// Write the totals of the run to a json file, for CI
+	stats_json: Option<String>,
+	#[arg(long, value_enum, default_value_t = Progress::Text)]
+	/// Show progress as log lines and bars, or as json events on stdout
+	progress: Progress,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Hide the progress bars and only print warnings and a summary at the end
+	quiet: bool,
+}
+
+/// Everything that changes how a texture gets blockified.
+#[derive(Debug)]
+struct Options {
+	block_pixels: u32,
+	tie_break: TieBreak,
+	alpha_mode: AlphaMode,
+	seed: u64,
+	/// DeltaE from the closest match other blocks can still be picked at
+	variety: Option<f64>,
+	/// Scale palette blocks in linear light
+	linear: bool,
+	/// Size the matched blocks are drawn at in a preview
+	preview: Option<u32>,
+	/// Color translucent palette tiles are drawn over
+	background: Option<[u8; 3]>,
+	/// Tint colors by texture or block name
+	tints: BTreeMap<String, [u8; 3]>,
+	overrides: Vec<Override>,
+	/// Folder the palette blocks are in, left out of their names when
+	/// breaking ties so every machine breaks them the same way
+	palette_dir: String,
+}
+
+impl Options {
+	fn new(args: &BlockifyArgs, palette_dir: &str) -> Options {
+		let colormap = args.colormap.as_ref().map(|path| {
+			let colormap = pdtcolor::open_srgb(pdtfs::long_path(path))
+				.unwrap_or_else(|_| panic!("Failed to load colormap: {path}"));
+			pdtcolor::sample_colormap(&colormap, args.temperature, args.downfall)
+		});
+		let tints = args
+			.tint
+			.iter()
+			.map(|tint| match tint.split_once('=') {
+				Some((name, hex)) => (
+					name.to_string(),
+					pdtcolor::hex_to_rgb_array(hex.to_string()),
+				),
+				None => (
+					tint.to_string(),
+					colormap
+						.unwrap_or_else(|| panic!("No color or --colormap given for tint {tint}")),
+				),
+			})
+			.collect();
+		Options {
+			block_pixels: args.block_pixels,
+			tie_break: args.tie_break,
+			alpha_mode: args.alpha_mode,
+			seed: args.seed,
+			variety: args.variety,
+			linear: args.linear,
+			preview: args.preview,
+			background: args
+				.background
+				.as_ref()
+				.map(|hex| pdtcolor::hex_to_rgb_array(hex.to_string())),
+			tints,
+			overrides: load_overrides(args.config.as_deref()),
+			palette_dir: palette_dir.to_string(),
+		}
+	}
+
+	fn tint(&self, path: &str) -> Option<[u8; 3]> {
+		let name = texture_stem(block_name(path));
+		self.tints.get(name).copied()
+	}
+
+	/// The first override whose glob matches the texture, if any.
+	fn texture_override(&self, file: &str) -> Option<&Override> {
+		let file = file.replace(SLASH, "/");
+		self.overrides.iter().find(|o| o.pattern.matches(&file))
+	}
+
+	fn skips(&self, file: &str) -> bool {
+		self.texture_override(file).is_some_and(|o| o.skip)
+	}
+
+	/// Size a block of a texture blockified at block_pixels is drawn at,
+	/// smaller in a preview.
+	fn tile_pixels(&self, block_pixels: u32) -> u32 {
+		self.preview.map_or(block_pixels, |p| p.min(block_pixels))
+	}
+
+	fn palette_key(&self, file: &str) -> PaletteKey {
+		let texture_override = self.texture_override(file);
+		PaletteKey {
+			block_pixels: texture_override
+				.and_then(|o| o.block_pixels)
+				.unwrap_or(self.block_pixels),
+			blocks: texture_override.and_then(|o| o.palette_patterns.clone()),
+		}
+	}
+
+	/// Blocks always used for some colors in the texture, by color.
+	fn forced_blocks(&self, file: &str, palette: &Palette) -> HashMap<[u8; 3], String> {
+		let Some(texture_override) = self.texture_override(file) else {
+			return HashMap::new();
+		};
+		texture_override
+			.colors
+			.iter()
+			.map(|(hex, name)| {
+				let block = palette
+					.images
+					.keys()
+					.find(|b| texture_stem(block_name(b)) == name)
+					.unwrap_or_else(|| {
+						panic!("Block {name} forced for {file} is not in its palette.")
+					});
+				(pdtcolor::hex_to_rgb_array(hex.to_string()), block.clone())
+			})
+			.collect()
+	}
+}
+
+const CONFIG_FILE: &str = "blockify.toml";
+
+#[derive(Deserialize)]
+struct Config {
+	#[serde(default, rename = "texture")]
+	textures: Vec<Override>,
+}
+
+/// Settings for the textures matching a glob, such as
+///
+/// ```toml
+/// [[texture]]
+/// glob = "gui/**"
+/// palette = ["*_wool", "*_concrete"]
+/// block_pixels = 8
+/// colors = { "FFFFFF" = "white_concrete" }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Override {
+	/// Matched against the texture path relative to the output directory
+	glob: String,
+	/// Copy the textures as they are instead of blockifying them
+	#[serde(default)]
+	skip: bool,
+	/// Globs of the palette block names (without .png) to match against
+	palette: Option<Vec<String>>,
+	/// Size each pixel becomes, instead of the one given on the command line
+	block_pixels: Option<u32>,
+	/// Hex colors mapped to the name of the block always used for them
+	#[serde(default)]
+	colors: BTreeMap<String, String>,
+	/// The glob, compiled once the config is read
+	#[serde(skip)]
+	pattern: Pattern,
+	/// The palette globs, compiled once the config is read
+	#[serde(skip)]
+	palette_patterns: Option<Vec<Pattern>>,
+}
+
+/// Reads the overrides from the given config, or from blockify.toml if
+/// there is one.
+fn load_overrides(path: Option<&str>) -> Vec<Override> {
+	let path = match path {
+		Some(path) => path,
+		None if Utf8Path::new(CONFIG_FILE).is_file() => CONFIG_FILE,
+		None => return vec![],
+	};
+	let data = fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read config: {path}"));
+	let mut config: Config =
+		toml::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse config {path}: {e}"));
+	let compile = |glob: &String| {
+		Pattern::new(glob).unwrap_or_else(|e| panic!("Invalid glob {glob} in {path}: {e}"))
+	};
+	for texture_override in &mut config.textures {
+		texture_override.pattern = compile(&texture_override.glob);
+		texture_override.palette_patterns = texture_override
+			.palette
+			.as_ref()
+			.map(|globs| globs.iter().map(compile).collect());
+		if let Some(pixels) = texture_override.block_pixels {
+			if !(2..=32).contains(&pixels) {
+				panic!(
+					"block_pixels for {} must be in 2..32",
+					texture_override.glob
+				);
+			}
+		}
+	}
+	config.textures
+}
+
+/// The block size and palette subset a texture is blockified with.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PaletteKey {
+	block_pixels: u32,
+	/// Globs of block names to use, or all blocks if none
+	blocks: Option<Vec<Pattern>>,
+}
+
+impl PaletteKey {
+	fn includes(&self, block: &str) -> bool {
+		let name = texture_stem(block_name(block));
+		match &self.blocks {
+			Some(globs) => globs.iter().any(|g| g.matches(name)),
+			None => true,
+		}
+	}
+}
+
+const OUTPUT_DIR: &str = "blockify_output";
+const MANIFEST_FILE: &str = ".blockify_manifest.json";
+const PREVIEW_FILE: &str = "palette-preview";
+
+/// Hashes of the textures written by the previous run, used to skip
+/// textures that have not changed since.
+#[derive(Default, Serialize, Deserialize)]
+struct Manifest {
+	/// Hash of the palette and the options used to build it
+	settings: String,
+	/// Relative path of each output file mapped to the hash of its source
+	textures: BTreeMap<String, String>,
+}
+
+impl Manifest {
+	fn load(path: &str) -> Option<Manifest> {
+		let data = fs::read_to_string(pdtfs::long_path(path)).ok()?;
+		serde_json::from_str(&data).ok()
+	}
+
+	fn save(&self, path: &str) {
+		let data = serde_json::to_string_pretty(self).expect("Failed to serialize manifest.");
+		fs::write(pdtfs::long_path(path), data)
+			.unwrap_or_else(|_| panic!("Failed to write manifest: {path}"));
+	}
+}
+
+impl BlockifyArgs {
+	/// Parses arguments the way the command line does, for running blockify
+	/// from a library.
+	pub fn parse(args: &[String]) -> BlockifyArgs {
+		let command = BlockifyArgs::augment_args(Command::new("blockify").no_binary_name(true));
+		command
+			.try_get_matches_from(args)
+			.and_then(|matches| BlockifyArgs::from_arg_matches(&matches))
+			.unwrap_or_else(|e| panic!("Invalid blockify arguments {args:?}: {e}"))
+	}
+}
+
+pub fn run(mut args: BlockifyArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	args.threads.apply();
+	pdtlog::set_progress(args.progress);
+	pdtthread::cancel_on_ctrl_c();
+	args.input_paths = pdtstdin::get_stdin()
+		.unwrap_or_default()
+		.extend_vec(args.input_paths);
+	let stats = blockify_paths(&args);
+	stats.finish(args.stats_json.as_deref());
+}
+
+/// Blockifies the way the blockify command does with these arguments,
+/// leaving the log, threads, progress and Ctrl+C handling as they were set
+/// up by the caller, like the build.
+pub fn blockify_paths(args: &BlockifyArgs) -> Stats {
+	let mut stats = Stats::new("blockify");
+	// without a blocks path the first positional path is an input
+	let (blocks_path, paths) = match &args.palette_version {
+		Some(version) => {
+			let inputs = args.blocks_path.iter().cloned();
+			let inputs = inputs.chain(args.input_paths.iter().cloned());
+			(pdtmojang::get_block_textures(version), inputs.collect())
+		}
+		None => (args.blocks_path.clone().unwrap(), args.input_paths.clone()),
+	};
+	let zip = args.zip || paths.iter().any(|p| pdtzip::is_zip(p));
+
+	let mut temp_dirs = vec![];
+	let mut unzip = |path: String| match pdtzip::is_zip(&path) {
+		true => {
+			let temp = pdtzip::extract_zip_to_temp(&path);
+			let dir = temp.dir.clone();
+			temp_dirs.push(temp);
+			dir
+		}
+		false => path,
+	};
+	let paths = paths.into_iter().map(&mut unzip).collect();
+	let recursive = args.recursive || pdtzip::is_zip(&blocks_path);
+	let blocks_path = unzip(blocks_path);
+	let extensions = Some(pdtfs::image_extensions());
+	let block_files = pdtfs::find_files_in_dir(&blocks_path, recursive, &extensions).sort_vec();
+
+	blockify(args, paths, &blocks_path, block_files, zip, &mut stats);
+	drop(temp_dirs);
+	stats
+}
+
+fn blockify(
+	args: &BlockifyArgs, paths: Vec<String>, blocks_path: &str, block_files: Vec<String>,
+	zip: bool, stats: &mut Stats,
+) {
+	let options = Arc::new(Options::new(args, blocks_path));
+	let progress = match args.quiet || pdtlog::json_progress() {
+		true => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+		false => MultiProgress::new(),
+	};
+	let target = pdtfs::trim_path(&args.out_dir);
+	let target = match args.preview {
+		Some(_) => format!("{target}-preview"),
+		None => target,
+	};
+	if args.zip_only {
+		blockify_zip(args, paths, block_files, options, &target, &progress, stats);
+		return;
+	}
+	let settings = get_settings_hash(&options, &block_files);
+
+	let previous = match args.force {
+		true => None,
+		false => Manifest::load(Utf8Path::new(&target).join(MANIFEST_FILE).as_str())
+			.filter(|m| m.settings == settings),
+	};
+	// a run from scratch is staged and swapped in when it is done, so a
+	// failed one keeps the previous output, later runs update it in place
+	let staged = match previous.is_none() && !args.dry_run {
+		true => Some(pdtfs::StagedDir::new(&target)),
+		false => None,
+	};
+	let output = staged.as_ref().map_or(target.clone(), |s| s.dir.clone());
+	let manifest_path = Utf8Path::new(&output).join(MANIFEST_FILE).to_string();
+	let previous = previous.unwrap_or_default();
+
+	let mut manifest = Manifest {
+		settings,
+		textures: BTreeMap::new(),
+	};
+	let sources = hash_source_files(paths);
+	let current = sources.iter().map(|s| s.0.clone()).collect::<HashSet<_>>();
+	let mut changed = vec![];
+	for (file, source, hash) in sources {
+		let dest = Utf8Path::new(&output).join(&file).to_string();
+		if previous.textures.get(&file) == Some(&hash) && pdtfs::is_file(&dest) {
+			manifest.textures.insert(file, hash);
+			continue;
+		}
+		let convert = pdtfs::is_image(&file) && !options.skips(&file);
+		if args.dry_run {
+			if convert {
+				changed.push((file, hash, source));
+			}
+			continue;
+		}
+		if convert {
+			changed.push((file, hash, source));
+		} else {
+			pdtfs::copy_file(&source, &dest);
+			manifest.textures.insert(file, hash);
+		}
+	}
+
+	if args.dry_run {
+		dry_run(changed, block_files, options, &progress);
+		return;
+	}
+
+	for file in previous.textures.keys() {
+		if !current.contains(file) {
+			let dest = Utf8Path::new(&output).join(file).to_string();
+			if pdtfs::is_file(&dest) {
+				fs::remove_file(pdtfs::long_path(&dest))
+					.unwrap_or_else(|_| panic!("Failed to remove file: {dest}"));
+			}
+		}
+	}
+
+	log::info!(
+		"{} of {} textures changed since the last run.",
+		changed.len(),
+		current.iter().filter(|f| pdtfs::is_image(f)).count()
+	);
+
+	// textures still being processed are left out until they are done,
+	// so an interrupted run picks them up again next time
+	manifest.save(&manifest_path);
+
+	let mut output_pixels = 0;
+	let mut done = HashSet::new();
+	if !changed.is_empty() {
+		let texture_files = changed
+			.iter()
+			.map(|(file, _, source)| {
+				let dest = Utf8Path::new(&output).join(file).to_string();
+				(file.clone(), source.clone(), Some(dest))
+			})
+			.collect();
+		let blockified;
+		let heatmap = args.heatmap.then(|| format!("{target}-heatmap"));
+		(output_pixels, blockified) = blockify_images(
+			texture_files,
+			block_files,
+			options,
+			&target,
+			heatmap,
+			&progress,
+			None,
+		);
+		done.extend(blockified);
+	}
+
+	let textures = done.len();
+	stats.files = textures;
+	stats.pixels = output_pixels;
+	for (file, hash, _) in &changed {
+		if done.contains(file) {
+			manifest.textures.insert(file.clone(), hash.clone());
+		}
+	}
+	// a stopped run from scratch is dropped with its staging folder, so the
+	// previous output stays whole, only an update in place keeps what it did
+	if pdtthread::cancelled() && staged.is_some() {
+		pdtlog::print(&format!(
+			"Stopped after blockifying {textures} of {} textures, {target} was left as it was.",
+			changed.len()
+		));
+		return;
+	}
+	manifest.save(&manifest_path);
+	if let Some(staged) = staged {
+		staged.commit();
+	}
+	if pdtthread::cancelled() {
+		pdtlog::print(&format!(
+			"Stopped after blockifying {textures} of {} textures, run again to do the rest.",
+			changed.len()
+		));
+		return;
+	}
+	if zip && args.preview.is_none() {
+		let zip_path = format!("{target}.zip");
+		pdtzip::zip_dir(&target, &zip_path, &[MANIFEST_FILE], None);
+		log::info!("Saved {zip_path}");
+	}
+
+	match args.preview {
+		Some(pixels) => pdtlog::print(&format!(
+			"Previewed {textures} textures at {pixels}px blocks in {target}."
+		)),
+		None => pdtlog::print(&format!(
+			"Blockified {textures} textures ({output_pixels} output pixels)."
+		)),
+	}
+}
+
+/// Blockifies straight into <target>.zip without an output folder. The
+/// textures are zipped as they are encoded and every other file is read
+/// from the inputs as it is zipped.
+fn blockify_zip(
+	args: &BlockifyArgs, paths: Vec<String>, block_files: Vec<String>, options: Arc<Options>,
+	target: &str, progress: &MultiProgress, stats: &mut Stats,
+) {
+	let mut files = BTreeMap::new();
+	let mut textures = vec![];
+	for (file, source) in source_files(paths) {
+		if pdtfs::is_image(&file) && !options.skips(&file) {
+			textures.push((file, source, None));
+		} else {
+			files.insert(file.replace(SLASH, "/"), source);
+		}
+	}
+	let count = textures.len();
+	let heatmap = args.heatmap.then(|| format!("{target}-heatmap"));
+	let zip_path = format!("{target}.zip");
+	let zip = Arc::new(ZipStream::new(&zip_path, None));
+	let (output_pixels, done) = blockify_images(
+		textures,
+		block_files,
+		options,
+		target,
+		heatmap,
+		progress,
+		Some(Arc::clone(&zip)),
+	);
+	stats.files = done.len();
+	stats.pixels = output_pixels;
+	if pdtthread::cancelled() {
+		pdtlog::print(&format!(
+			"Stopped after blockifying {} of {count} textures, nothing was zipped.",
+			done.len()
+		));
+		return;
+	}
+	let blockified = done.len();
+	zip.finish(&files);
+	log::info!("Saved {zip_path}");
+	pdtlog::print(&format!(
+		"Blockified {blockified} textures ({output_pixels} output pixels)."
+	));
+}
+
+/// Reports what a run would do, without writing anything.
+fn dry_run(
+	textures: Vec<(String, String, String)>, block_files: Vec<String>, options: Arc<Options>,
+	progress: &MultiProgress,
+) {
+	let keys = textures.iter().map(|t| options.palette_key(&t.0)).collect();
+	for (key, palette) in get_palettes(keys, block_files, &options, None, progress) {
+		let subset = key.blocks.map(|b| {
+			let globs = b.iter().map(Pattern::as_str).collect::<Vec<_>>();
+			format!(" from {}", globs.join(", "))
+		});
+		log::info!(
+			"{} usable palette blocks at {}px{}.",
+			palette.blocks.len(),
+			key.block_pixels,
+			subset.unwrap_or_default()
+		);
+	}
+
+	let mut total = 0;
+	for (file, _, source) in &textures {
+		let pixels = options.tile_pixels(options.palette_key(file).block_pixels);
+		let (width, height) = image::image_dimensions(source)
+			.unwrap_or_else(|_| panic!("Failed to load image: {source}"));
+		let (new_width, new_height) = (width * pixels, height * pixels);
+		let bytes = u64::from(new_width) * u64::from(new_height) * 4;
+		total += bytes;
+		pdtlog::print(&format!(
+			"{file}: {width}x{height} -> {new_width}x{new_height} (up to {})",
+			HumanBytes(bytes)
+		));
+	}
+	pdtlog::print(&format!(
+		"Would blockify {} textures, up to {} before png compression.",
+		textures.len(),
+		HumanBytes(total)
+	));
+}
+
+fn progress_bar(progress: &MultiProgress, len: u64, prefix: &str, template: &str) -> ProgressBar {
+	let style = ProgressStyle::with_template(template)
+		.unwrap()
+		.progress_chars("=> ");
+	progress.add(
+		ProgressBar::new(len)
+			.with_style(style)
+			.with_prefix(prefix.to_string()),
+	)
+}
+
+fn get_settings_hash(options: &Options, block_files: &[String]) -> String {
+	let block_hashes = block_files
+		.iter()
+		.map(|f| format!("{}:{}", block_name(f), pdthash::get_hash(f, false)))
+		.collect::<Vec<_>>()
+		.join("\n");
+	let matcher = if cfg!(feature = "simd") {
+		"simd"
+	} else {
+		"scalar"
+	};
+	pdthash::get_string_hash(&format!("{options:?}\n{matcher}\n{block_hashes}"))
+}
+
+/// Finds every file in the input paths along with where it ends up relative
+/// to the output directory and the hash of its contents. Files .pdtignore and
+/// .packignore files list are left out.
+fn hash_source_files(paths: Vec<String>) -> Vec<(String, String, String)> {
+	pdtthread::multithread(source_files(paths), None, |_, (file, source)| {
+		let hash = pdthash::get_hash(&source, false);
+		Some((file, source, hash))
+	})
+}
+
+/// Every file in the input paths along with where it ends up relative to the
+/// output directory.
+fn source_files(paths: Vec<String>) -> Vec<(String, String)> {
+	let filter = FileFilter::new(&[], &[], &pdtfs::PACK_IGNORE_FILES, Symlinks::Follow);
+	let mut sources = vec![];
+	for path in paths {
+		if pdtfs::is_dir(&path) {
+			for file in pdtfs::find_filtered_files_in_dir(&path, true, &None, &filter) {
+				sources.push((pdtfs::relative_path(&path, &file), file));
+			}
+		} else if pdtfs::is_file(&path) {
+			sources.push((block_name(&path).to_string(), path));
+		} else {
+			panic!("Entry passed as file or folder not found.");
+		}
+	}
+	sources
+}
+
+/// Builds a palette for every block size and subset in use, averaging the
+/// blocks once per block size. With a target, a contact sheet of the
+/// averaged blocks is saved next to it for every block size too.
+fn get_palettes(
+	keys: HashSet<PaletteKey>, block_files: Vec<String>, options: &Arc<Options>,
+	preview: Option<&str>, progress: &MultiProgress,
+) -> HashMap<PaletteKey, Arc<Palette>> {
+	let sizes = keys.iter().map(|k| k.block_pixels).collect::<BTreeSet<_>>();
+	let mut skipped = BTreeSet::new();
+	let mut averages = HashMap::new();
+	for size in sizes {
+		let (blocks, reasons) =
+			get_average_colors(block_files.clone(), size, Arc::clone(options), progress);
+		skipped.extend(reasons);
+		averages.insert(size, blocks);
+	}
+	if !skipped.is_empty() {
+		log::warn!(
+			"skipping {} palette blocks:\n  {}",
+			skipped.len(),
+			skipped.into_iter().collect::<Vec<_>>().join("\n  ")
+		);
+	}
+	if let Some(target) = preview {
+		if let Some(dir) = Utf8Path::new(target)
+			.parent()
+			.filter(|d| !d.as_str().is_empty())
+		{
+			fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		}
+		for (size, blocks) in &averages {
+			let path = match *size == options.block_pixels {
+				true => format!("{target}-{PREVIEW_FILE}.png"),
+				false => format!("{target}-{PREVIEW_FILE}-{size}px.png"),
+			};
+			get_palette_preview(blocks)
+				.save(&path)
+				.unwrap_or_else(|_| panic!("Failed to save image: {path}"));
+			log::info!("Saved palette preview: {path}");
+		}
+	}
+	keys.into_iter()
+		.map(|key| {
+			let blocks = averages[&key.block_pixels]
+				.iter()
+				.filter(|b| key.includes(&b.0 .0))
+				.cloned()
+				.collect::<Vec<_>>();
+			if blocks.is_empty() {
+				panic!("No usable palette blocks for {key:?}.");
+			}
+			(key, Arc::new(Palette::new(blocks)))
+		})
+		.collect()
+}
+
+/// Draws every block next to a swatch of its main color, with its name and
+/// the color's hex below, in a grid sorted by name.
+fn get_palette_preview(blocks: &[(Block, RgbaImage)]) -> RgbaImage {
+	const SIZE: u32 = 32;
+	const PAD: u32 = 4;
+	let line_height = pdtfont::GLYPH_HEIGHT + 2;
+	let mut blocks = blocks
+		.iter()
+		.map(|(block, img)| (texture_stem(block_name(&block.0)), block, img))
+		.collect::<Vec<_>>();
+	blocks.sort_by_key(|b| b.0);
+
+	let longest = blocks
+		.iter()
+		.map(|b| pdtfont::text_width(b.0, 1))
+		.max()
+		.unwrap_or(0);
+	let cell_width = (SIZE * 2 + PAD).max(longest) + PAD * 2;
+	let cell_height = SIZE + line_height * 2 + PAD * 3;
+	let columns = (blocks.len() as f64).sqrt().ceil().max(1.0) as u32;
+	let rows = (blocks.len() as u32).div_ceil(columns).max(1);
+	let mut sheet = ImageBuffer::from_pixel(
+		columns * cell_width,
+		rows * cell_height,
+		Rgba([32, 32, 32, 255]),
+	);
+
+	let text = Rgba([255, 255, 255, 255]);
+	for (i, (name, block, img)) in blocks.into_iter().enumerate() {
+		let x = (i as u32 % columns) * cell_width + PAD;
+		let y = (i as u32 / columns) * cell_height + PAD;
+		let img = imageops::resize(img, SIZE, SIZE, FilterType::Nearest);
+		imageops::overlay(&mut sheet, &img, x.into(), y.into());
+		let swatch = ImageBuffer::from_pixel(SIZE, SIZE, block.1[0].1);
+		imageops::overlay(&mut sheet, &swatch, (x + SIZE + PAD).into(), y.into());
+		let [r, g, b, _] = block.1[0].1 .0;
+		let label_y = y + SIZE + PAD;
+		pdtfont::draw_text(&mut sheet, name, x, label_y, 1, text);
+		let hex = format!("#{r:02X}{g:02X}{b:02X}");
+		pdtfont::draw_text(&mut sheet, &hex, x, label_y + line_height, 1, text);
+	}
+	sheet
+}
+
+fn get_average_colors(
+	blocks: Vec<String>, pixels: u32, options: Arc<Options>, progress: &MultiProgress,
+) -> (Vec<(Block, RgbaImage)>, Vec<String>) {
+	let bar = progress_bar(
+		progress,
+		blocks.len() as u64,
+		"palette",
+		"{prefix:>8} [{bar:40}] {pos}/{len} blocks, ETA {eta} {msg}",
+	);
+	let thread_bar = bar.clone();
+	let blocks = pdtthread::multithread(blocks, None, move |_, image| {
+		thread_bar.set_message(block_name(&image).to_string());
+		let block = get_average_color(image, pixels, &options);
+		thread_bar.inc(1);
+		Some(block)
+	});
+	bar.finish_with_message("done");
+
+	let (blocks, skipped): (Vec<_>, Vec<_>) = blocks.into_iter().partition(|b| b.is_ok());
+	(
+		blocks.into_iter().filter_map(|b| b.ok()).collect(),
+		skipped.into_iter().filter_map(|b| b.err()).collect(),
+	)
+}
+
+/// Returns the block and its image scaled to the output block size, or why
+/// it was left out of the palette.
+fn get_average_color(
+	image: String, pixels: u32, options: &Options,
+) -> Result<(Block, RgbaImage), String> {
+	let mut img = open_tinted(&image, options);
+	if let Some(background) = options.background {
+		let mut flat = img.to_rgba8();
+		pdtcolor::flatten_image(&mut flat, background);
+		img = DynamicImage::ImageRgba8(flat);
+	}
+	let (width, height) = img.dimensions();
+	if width != height {
+		return Err(format!("{image}: {width}x{height} is not square"));
+	}
+	let img =
+		match width.cmp(&pixels) {
+			Ordering::Equal => img,
+			Ordering::Greater if options.linear => DynamicImage::ImageRgba8(
+				pdtcolor::resize_linear(&img.to_rgba8(), pixels, pixels, FilterType::Triangle),
+			),
+			Ordering::Greater => img.resize_exact(pixels, pixels, FilterType::Triangle),
+			Ordering::Less => img.resize_exact(pixels, pixels, FilterType::Nearest),
+		};
+
+	let img = img.to_rgba8();
+	let block = pdtmatch::block_colors(&image, &img, options.alpha_mode == AlphaMode::Copy)?;
+	Ok((block, img))
+}
+
+/// Opens an image, applying its tint if it has one.
+fn open_tinted(path: &str, options: &Options) -> DynamicImage {
+	let mut img = pdtcolor::open_srgb(pdtfs::long_path(path))
+		.unwrap_or_else(|_| panic!("Failed to load image: {path}"));
+	if let Some(tint) = options.tint(path) {
+		pdtcolor::tint_image(&mut img, tint);
+	}
+	DynamicImage::ImageRgba8(img)
+}
+
+/// Blockifies the textures, returns the output pixels and the names of the
+/// textures done, which are all of them unless the run was stopped with
+/// Ctrl+C. Textures without a dest are added to the zip as they are encoded.
+fn blockify_images(
+	images: Vec<(String, String, Option<String>)>, block_files: Vec<String>, options: Arc<Options>,
+	target: &str, heatmap_dir: Option<String>, progress: &MultiProgress,
+	zip: Option<Arc<ZipStream>>,
+) -> (u64, Vec<String>) {
+	let keys = images.iter().map(|i| options.palette_key(&i.0)).collect();
+	let palettes = {
+		let _stage = pdtlog::stage("palette");
+		let palettes = get_palettes(keys, block_files, &options, Some(target), progress);
+		match options.preview.is_some() {
+			true => preview_palettes(palettes, &options),
+			false => palettes,
+		}
+	};
+	let image_pixels = images
+		.iter()
+		.map(|(name, i, _)| {
+			let block_pixels = options.tile_pixels(options.palette_key(name).block_pixels);
+			let (width, height) = image::image_dimensions(pdtfs::long_path(i))
+				.unwrap_or_else(|_| panic!("Failed to load image: {i}"));
+			u64::from(width * block_pixels) * u64::from(height * block_pixels)
+		})
+		.collect::<Vec<_>>();
+	let total_pixels = image_pixels.iter().sum();
+	let files = progress_bar(
+		progress,
+		images.len() as u64,
+		"textures",
+		"{prefix:>8} [{bar:40}] {pos}/{len} textures {msg}",
+	);
+	let pixels = progress_bar(
+		progress,
+		total_pixels,
+		"pixels",
+		"{prefix:>8} [{bar:40}] {human_pos}/{human_len} ({per_sec}), ETA {eta}",
+	);
+	let images = images
+		.into_iter()
+		.map(|i| {
+			let key = options.palette_key(&i.0);
+			let palette = Arc::clone(&palettes[&key]);
+			let block_pixels = options.tile_pixels(key.block_pixels);
+			(i, block_pixels, files.clone(), pixels.clone(), palette)
+		})
+		.collect();
+
+	let _stage = pdtlog::stage("blockify");
+	// the largest textures go first, so the threads run out of work at
+	// about the same time instead of waiting on one big texture at the end
+	let done = pdtthread::multithread_weighted(
+		images,
+		&image_pixels,
+		None,
+		move |_, ((name, texture, dest), block_pixels, files, pixels, palette)| {
+			files.set_message(block_name(&texture).to_string());
+			let mut tie_breaker = TieBreaker::new(&options, &name);
+			let forced = options.forced_blocks(&name, &palette);
+
+			let img = open_tinted(&texture, &options);
+			let (width, height) = img.dimensions();
+			let mut new_texture: RgbaImage =
+				ImageBuffer::from_fn(width * block_pixels, height * block_pixels, |_, _| {
+					image::Rgba([0, 0, 0, 0])
+				});
+			let mut heatmap = heatmap_dir.as_ref().map(|_| {
+				let colors = palette.blocks.iter().map(|b| (b.0.clone(), b.1[0].2));
+				(
+					RgbaImage::new(width, height),
+					colors.collect::<HashMap<_, _>>(),
+					0.0,
+				)
+			});
+
+			for pixel in img.pixels() {
+				let a = pixel.2 .0[3];
+				if a == 0 {
+					continue;
+				}
+				let (x, y) = (pixel.0, pixel.1);
+				let rgb = [pixel.2 .0[0], pixel.2 .0[1], pixel.2 .0[2]];
+				let selected = match forced.get(&rgb) {
+					Some(block) => block.clone(),
+					None => {
+						let lab = pdtmatch::rgb_to_lab(pixel);
+						let alpha = (options.alpha_mode == AlphaMode::Match).then_some(a);
+						let tolerance = options.variety.unwrap_or(0.0);
+						tie_breaker.pick(match_pixel(lab, alpha, tolerance, &palette), x, y)
+					}
+				};
+				if let Some((heatmap, colors, worst)) = &mut heatmap {
+					let lab = pdtmatch::rgb_to_lab(pixel);
+					let delta = *DeltaE::new(lab, colors[&selected], DE2000).value() as f64;
+					*worst = f64::max(*worst, delta);
+					heatmap.put_pixel(x, y, heat(delta));
+				}
+				let alpha = (options.alpha_mode == AlphaMode::Copy).then_some(a);
+				pdtmatch::paste_block(&mut new_texture, x, y, &palette.images[&selected], alpha);
+			}
+
+			// jpeg has no alpha channel to save
+			let jpeg = [".jpg".to_string(), ".jpeg".to_string()];
+			let new_texture = match pdtfs::has_extension(&name, &jpeg) {
+				true => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(new_texture).to_rgb8()),
+				false => DynamicImage::ImageRgba8(new_texture),
+			};
+			match dest {
+				Some(dest) => {
+					if let Some(parent) = Utf8Path::new(&dest).parent() {
+						fs::create_dir_all(pdtfs::long_path(parent.as_str()))
+							.unwrap_or_else(|_| panic!("Failed to create {parent} directory."));
+					}
+					new_texture
+						.save(pdtfs::long_path(&dest))
+						.unwrap_or_else(|e| panic!("Failed to save image {dest}: {e}"));
+				}
+				None => {
+					let format = ImageFormat::from_path(&name)
+						.unwrap_or_else(|e| panic!("Failed to encode image {name}: {e}"));
+					let mut data = Cursor::new(vec![]);
+					new_texture
+						.write_to(&mut data, format)
+						.unwrap_or_else(|e| panic!("Failed to encode image {name}: {e}"));
+					let zip = zip.as_ref().expect("No zip to add the texture to.");
+					zip.add(&name.replace(SLASH, "/"), data.get_ref());
+				}
+			}
+			if let (Some(dir), Some((heatmap, _, worst))) = (&heatmap_dir, heatmap) {
+				let path = pdtfs::png_path(Utf8Path::new(dir).join(&name).as_str());
+				if let Some(parent) = Utf8Path::new(&path).parent() {
+					fs::create_dir_all(pdtfs::long_path(parent.as_str()))
+						.unwrap_or_else(|_| panic!("Failed to create {parent} directory."));
+				}
+				heatmap
+					.save(pdtfs::long_path(&path))
+					.unwrap_or_else(|e| panic!("Failed to save image {path}: {e}"));
+				log::debug!("{name}: worst match is {worst:.1} DeltaE");
+			}
+
+			files.inc(1);
+			pixels.inc(u64::from(width * block_pixels) * u64::from(height * block_pixels));
+			pdtlog::file_done("blockify", &name);
+
+			Some(name)
+		},
+	);
+
+	// finishing moves the bar to the end, also when the run was stopped
+	let output_pixels = pixels.position();
+	files.finish_with_message("done");
+	pixels.finish();
+	(output_pixels, done)
+}
+
+/// DeltaE at which a heatmap pixel is fully red, where a block stops looking
+/// like the color it stands for.
+const HEATMAP_MAX: f64 = 20.0;
+
+/// Color of a heatmap pixel, green for a perfect match through yellow to red
+/// for HEATMAP_MAX or more.
+fn heat(delta: f64) -> Rgba<u8> {
+	let t = (delta / HEATMAP_MAX).clamp(0.0, 1.0);
+	let (r, g) = match t < 0.5 {
+		true => (t * 2.0, 1.0),
+		false => (1.0, (1.0 - t) * 2.0),
+	};
+	Rgba([(r * 255.0).round() as u8, (g * 255.0).round() as u8, 0, 255])
+}
+
+/// Scales the block images of the palettes down to the preview size, the
+/// colors they are matched by stay those of the full-size blocks.
+fn preview_palettes(
+	palettes: HashMap<PaletteKey, Arc<Palette>>, options: &Options,
+) -> HashMap<PaletteKey, Arc<Palette>> {
+	palettes
+		.into_iter()
+		.map(|(key, palette)| {
+			let pixels = options.tile_pixels(key.block_pixels);
+			let mut palette = Arc::into_inner(palette).unwrap();
+			for img in palette.images.values_mut() {
+				if img.width() == pixels {
+					continue;
+				}
+				*img = match options.linear {
+					true => pdtcolor::resize_linear(img, pixels, pixels, FilterType::Triangle),
+					false => imageops::resize(img, pixels, pixels, FilterType::Triangle),
+				};
+			}
+			(key, Arc::new(palette))
+		})
+		.collect()
+}
+
+/// How translucent texture pixels and palette blocks are handled.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum AlphaMode {
+	/// Leave translucent blocks out and give every block the alpha of the
+	/// pixel it replaces
+	Copy,
+	/// Match alpha along with color, so translucent pixels become translucent
+	/// blocks like stained glass, and keep the alpha of the blocks
+	Match,
+}
+
+/// How to choose between palette blocks that match a pixel equally well.
+/// Every policy only depends on the block file names, the texture and the
+/// seed, so the same inputs always give byte-identical output.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum TieBreak {
+	/// Pick the block whose file name sorts first
+	Path,
+	/// Pick a block at random, seeded by --seed, the texture and the pixel
+	Seeded,
+	/// Pick the block used least so far in the texture, then by file name
+	LeastUsed,
+}
+
+/// Resolves ties for one texture, tracking how often each block was used.
+struct TieBreaker {
+	policy: TieBreak,
+	seed: u64,
+	/// Path of the texture in the pack, with / on every platform
+	texture: String,
+	palette_dir: String,
+	usage: HashMap<String, u64>,
+	/// Rotate through the matches instead of breaking the tie
+	variety: bool,
+	/// How many pixels were matched before each block was last picked
+	last_used: HashMap<String, u64>,
+	picked: u64,
+}
+
+impl TieBreaker {
+	fn new(options: &Options, texture: &str) -> TieBreaker {
+		TieBreaker {
+			policy: options.tie_break,
+			seed: options.seed,
+			texture: texture.replace(SLASH, "/"),
+			palette_dir: options.palette_dir.clone(),
+			usage: HashMap::new(),
+			variety: options.variety.is_some(),
+			last_used: HashMap::new(),
+			picked: 0,
+		}
+	}
+
+	fn pick(&mut self, mut ties: Vec<String>, x: u32, y: u32) -> String {
+		ties.sort_by_cached_key(|t| (block_name(t).to_string(), self.palette_path(t)));
+		let selected = match self.policy {
+			_ if self.variety && ties.len() > 1 => self.rotate(ties, x, y),
+			TieBreak::Path => ties.swap_remove(0),
+			TieBreak::Seeded => {
+				let key = format!("{}:{}:{x}:{y}", self.seed, self.texture);
+				let index = stable_hash(key.as_bytes()) % ties.len() as u64;
+				ties.swap_remove(index as usize)
+			}
+			TieBreak::LeastUsed => ties
+				.into_iter()
+				.min_by_key(|t| self.usage.get(t).copied().unwrap_or(0))
+				.unwrap(),
+		};
+		*self.usage.entry(selected.clone()).or_insert(0) += 1;
+		self.last_used.insert(selected.clone(), self.picked);
+		self.picked += 1;
+		selected
+	}
+
+	/// Path of a block in the palette, with / on every platform.
+	fn palette_path(&self, block: &str) -> String {
+		let path = match Utf8Path::new(block).strip_prefix(&self.palette_dir) {
+			Ok(path) => path.as_str(),
+			Err(_) => block,
+		};
+		path.replace(SLASH, "/")
+	}
+
+	/// Picks at random, seeded like the seeded tie break, among the matches
+	/// used longest ago, leaving out the most recent half of them so a block
+	/// doesn't repeat right away.
+	fn rotate(&self, mut matches: Vec<String>, x: u32, y: u32) -> String {
+		matches.sort_by_key(|m| self.last_used.get(m).map_or(0, |i| i + 1));
+		let oldest = matches.len() / 2 + 1;
+		let key = format!("{}:{}:{x}:{y}", self.seed, self.texture);
+		let index = stable_hash(key.as_bytes()) % oldest as u64;
+		matches.swap_remove(index as usize)
+	}
+}
+
+/// File name of a path.
+fn block_name(path: &str) -> &str {
+	Utf8Path::new(path).file_name().unwrap_or(path)
+}
+
+/// File name without its image extension.
+fn texture_stem(name: &str) -> &str {
+	match pdtfs::is_image(name) {
+		true => name.rsplit_once('.').map_or(name, |(stem, _)| stem),
+		false => name,
+	}
+}
+
+/// FNV-1a, used instead of the std hasher because that one is not
+/// guaranteed to give the same result across platforms and releases.
+fn stable_hash(bytes: &[u8]) -> u64 {
+	bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+		(hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+	})
+}
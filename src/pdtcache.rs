@@ -0,0 +1,58 @@
+use super::{pdtfs, pdthash};
+use camino::Utf8Path;
+use std::fs;
+
+/// Where build steps keep their outputs by a hash of everything that went
+/// into them, so unchanged inputs are never processed twice.
+#[derive(Clone, Debug)]
+pub struct Cache {
+	pub dir: String,
+}
+
+impl Cache {
+	pub fn new(dir: &str) -> Cache {
+		Cache {
+			dir: pdtfs::trim_path(dir),
+		}
+	}
+
+	/// Key of a step's output, from its inputs and settings. The version of
+	/// pdt is part of it, since a new version may process files differently.
+	pub fn key(parts: &[&str]) -> String {
+		let mut data = env!("CARGO_PKG_VERSION").to_string();
+		for part in parts {
+			data.push('\0');
+			data.push_str(part);
+		}
+		pdthash::get_string_hash(&data)
+	}
+
+	/// Where the output of a step with a key is kept.
+	pub fn path(&self, step: &str, key: &str) -> String {
+		let path = Utf8Path::new(&self.dir)
+			.join(step)
+			.join(&key[..2])
+			.join(key);
+		path.to_string()
+	}
+
+	/// Copies the cached output of a step to output, false if there is none.
+	pub fn get(&self, step: &str, key: &str, output: &str) -> bool {
+		let cached = self.path(step, key);
+		if !pdtfs::is_file(&cached) {
+			return false;
+		}
+		pdtfs::copy_file(&cached, output);
+		true
+	}
+
+	/// Keeps a copy of the output of a step. It is written next to where it
+	/// goes first, so a build stopped halfway never leaves half a file.
+	pub fn put(&self, step: &str, key: &str, output: &str) {
+		let cached = self.path(step, key);
+		let partial = format!("{cached}.{}.part", std::process::id());
+		pdtfs::copy_file(output, &partial);
+		fs::rename(pdtfs::long_path(&partial), pdtfs::long_path(&cached))
+			.unwrap_or_else(|_| panic!("Failed to write file: {cached}"));
+	}
+}
@@ -0,0 +1,243 @@
+use super::pdtcmd::git;
+use super::pdtlog;
+use clap::ArgAction;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CHANGELOG_FILE: &str = "CHANGELOG.md";
+/// Written next to the release zips by build, for publish to use as release
+/// notes.
+pub const RELEASE_NOTES_FILE: &str = "release-notes.md";
+
+/// Sections of the changelog by Conventional Commits type, in order.
+const SECTIONS: [(&str, &str); 4] = [
+	("feat", "Features"),
+	("fix", "Fixes"),
+	("perf", "Performance"),
+	("revert", "Reverts"),
+];
+
+/// A commit, split into its Conventional Commits parts when it follows them.
+#[derive(Clone, Debug)]
+pub struct Commit {
+	pub hash: String,
+	/// feat, fix and so on, None when the subject isn't a Conventional
+	/// Commit
+	pub kind: Option<String>,
+	pub scope: Option<String>,
+	pub description: String,
+	pub breaking: bool,
+}
+
+impl Commit {
+	/// Parses `type(scope)!: description` subjects, anything else is kept
+	/// as is.
+	pub fn parse(hash: &str, subject: &str, body: &str) -> Commit {
+		let breaking_body = body
+			.lines()
+			.any(|l| l.starts_with("BREAKING CHANGE:") || l.starts_with("BREAKING-CHANGE:"));
+		let plain = Commit {
+			hash: hash.to_string(),
+			kind: None,
+			scope: None,
+			description: subject.trim().to_string(),
+			breaking: breaking_body,
+		};
+		let Some((prefix, description)) = subject.split_once(": ") else {
+			return plain;
+		};
+		let (prefix, breaking) = match prefix.strip_suffix('!') {
+			Some(prefix) => (prefix, true),
+			None => (prefix, false),
+		};
+		let (kind, scope) = match prefix.split_once('(') {
+			Some((kind, scope)) => match scope.strip_suffix(')') {
+				Some(scope) => (kind, Some(scope.to_string())),
+				None => return plain,
+			},
+			None => (prefix, None),
+		};
+		if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+			return plain;
+		}
+		Commit {
+			kind: Some(kind.to_lowercase()),
+			scope,
+			description: description.trim().to_string(),
+			breaking: breaking || breaking_body,
+			..plain
+		}
+	}
+
+	fn line(&self) -> String {
+		let short = &self.hash[..self.hash.len().min(7)];
+		match &self.scope {
+			Some(scope) => format!("- **{scope}:** {} ({short})", self.description),
+			None => format!("- {} ({short})", self.description),
+		}
+	}
+}
+
+/// The newest tag reachable from HEAD of the git repository in dir.
+pub fn last_tag(dir: &str) -> Option<String> {
+	git(dir, &["describe", "--tags", "--abbrev=0"]).map(|tag| tag.trim().to_string())
+}
+
+/// Commits after a tag, or all of them, newest first, without merges. None
+/// when dir isn't in a git repository.
+pub fn commits_since(dir: &str, tag: Option<&str>) -> Option<Vec<Commit>> {
+	let range = match tag {
+		Some(tag) => format!("{tag}..HEAD"),
+		None => "HEAD".to_string(),
+	};
+	let log = git(
+		dir,
+		&["log", "--no-merges", "--format=%H%x1f%s%x1f%b%x1e", &range],
+	)?;
+	let commits = log
+		.split('\x1e')
+		.filter_map(|entry| {
+			let mut parts = entry.trim_start().splitn(3, '\x1f');
+			let hash = parts.next().filter(|h| !h.is_empty())?;
+			let subject = parts.next().unwrap_or_default();
+			let body = parts.next().unwrap_or_default();
+			Some(Commit::parse(hash, subject, body))
+		})
+		.collect();
+	Some(commits)
+}
+
+/// Markdown list of changes, grouped by type when the commits follow
+/// Conventional Commits. Types without a section, like docs and chore, are
+/// left out unless they are breaking.
+pub fn render(commits: &[Commit]) -> String {
+	if commits.iter().all(|c| c.kind.is_none()) {
+		let lines = commits.iter().map(Commit::line).collect::<Vec<_>>();
+		return match lines.is_empty() {
+			true => String::new(),
+			false => format!("### Changes\n\n{}\n", lines.join("\n")),
+		};
+	}
+	let mut sections = vec![];
+	let breaking = commits.iter().filter(|c| c.breaking).collect::<Vec<_>>();
+	if !breaking.is_empty() {
+		sections.push(("Breaking changes", breaking));
+	}
+	for (kind, title) in SECTIONS {
+		let list = commits
+			.iter()
+			.filter(|c| !c.breaking && c.kind.as_deref() == Some(kind))
+			.collect::<Vec<_>>();
+		if !list.is_empty() {
+			sections.push((title, list));
+		}
+	}
+	let other = commits
+		.iter()
+		.filter(|c| !c.breaking && c.kind.is_none())
+		.collect::<Vec<_>>();
+	if !other.is_empty() {
+		sections.push(("Other changes", other));
+	}
+	sections
+		.into_iter()
+		.map(|(title, list)| {
+			let lines = list.iter().map(|c| c.line()).collect::<Vec<_>>();
+			format!("### {title}\n\n{}\n", lines.join("\n"))
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Seconds since the unix epoch.
+pub fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+/// Today as YYYY-MM-DD, in UTC.
+pub fn today() -> String {
+	let (year, month, day) = date(now());
+	format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Year, month and day of a unix time, in UTC.
+pub fn date(secs: u64) -> (i64, i64, i64) {
+	// days to civil date, from Howard Hinnant's date algorithms
+	let z = (secs / 86400) as i64 + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + i64::from(month <= 2);
+	(year, month, day)
+}
+
+/// A changelog section for a version, with its heading.
+pub fn section(version: &str, date: &str, changes: &str) -> String {
+	format!("## [{version}] - {date}\n\n{changes}")
+}
+
+/// Adds a section to a changelog above the newest one, keeping any title
+/// and introduction at the top.
+pub fn prepend_section(changelog: &str, section: &str) -> String {
+	if changelog.starts_with("## ") {
+		return format!("{section}\n{changelog}");
+	}
+	match changelog.find("\n## ") {
+		Some(i) => format!("{}{section}\n{}", &changelog[..=i], &changelog[i + 1..]),
+		None if changelog.trim().is_empty() => format!("# Changelog\n\n{section}"),
+		None => format!("{}\n\n{section}", changelog.trim_end()),
+	}
+}
+
+/// The changes since the last tag of the repository the build runs in,
+/// None outside of a git repository.
+pub fn unreleased_changes() -> Option<String> {
+	let tag = last_tag(".");
+	let commits = commits_since(".", tag.as_deref())?;
+	Some(render(&commits))
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ChangelogArgs {
+	#[arg(long)]
+	/// Tag to list the changes since, defaults to the newest one
+	since: Option<String>,
+	#[arg(long, default_value = "Unreleased")]
+	/// Version in the section heading
+	version: String,
+	#[arg(short, long)]
+	/// Add the section to CHANGELOG.md instead of printing it
+	write: bool,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+pub fn run(args: ChangelogArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let tag = args.since.or_else(|| last_tag("."));
+	match &tag {
+		Some(tag) => log::debug!("changes since {tag}"),
+		None => log::debug!("no tags, using every commit"),
+	}
+	let commits = commits_since(".", tag.as_deref()).expect("Not in a git repository.");
+	let section = section(&args.version, &today(), &render(&commits));
+	if !args.write {
+		print!("{section}");
+		return;
+	}
+	let changelog = fs::read_to_string(CHANGELOG_FILE).unwrap_or_default();
+	fs::write(CHANGELOG_FILE, prepend_section(&changelog, &section))
+		.unwrap_or_else(|_| panic!("Failed to write file: {CHANGELOG_FILE}"));
+	log::info!("Added {} changes to {CHANGELOG_FILE}", commits.len());
+}
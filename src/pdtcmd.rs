@@ -1,5 +1,19 @@
 use std::process::{Command, Output};
 
+/// Runs git in a directory and returns what it printed, None if it failed
+/// or isn't installed.
+pub fn git(dir: &str, args: &[&str]) -> Option<String> {
+	let output = Command::new("git")
+		.current_dir(dir)
+		.args(args)
+		.output()
+		.ok()?;
+	match output.status.success() {
+		true => Some(String::from_utf8_lossy(&output.stdout).to_string()),
+		false => None,
+	}
+}
+
 pub fn execute_windows_command(cmd: &str) {
 	let output = Command::new("cmd")
 		.args(["/C", cmd])
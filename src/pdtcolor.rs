@@ -1,17 +1,170 @@
 use colors_transform::{Color, Hsl, Rgb};
+#[cfg(feature = "simd")]
 use deltae::LabValue;
-use image::Rgba;
-
-pub fn rgb_to_lab(pixel: (u32, u32, Rgba<u8>)) -> LabValue {
-	let rgb = [[pixel.2 .0[0], pixel.2 .0[1], pixel.2 .0[2]]];
-	let lab = lab::rgbs_to_labs(&rgb)[0];
-	LabValue {
-		l: lab.l,
-		a: lab.a,
-		b: lab.b,
+use image::codecs::png::PngDecoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageDecoder, ImageResult, Rgba, Rgba32FImage, RgbaImage};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+#[cfg(feature = "simd")]
+use wide::f32x8;
+
+/// An sRGB channel as linear light, from 0 to 1.
+pub fn srgb_to_linear(channel: u8) -> f32 {
+	let c = channel as f32 / 255.0;
+	match c <= 0.04045 {
+		true => c / 12.92,
+		false => ((c + 0.055) / 1.055).powf(2.4),
+	}
+}
+
+/// Linear light from 0 to 1 as an sRGB channel.
+pub fn linear_to_srgb(value: f32) -> u8 {
+	let v = value.clamp(0.0, 1.0);
+	let c = match v <= 0.0031308 {
+		true => v * 12.92,
+		false => 1.055 * v.powf(1.0 / 2.4) - 0.055,
+	};
+	(c * 255.0).round() as u8
+}
+
+/// Resizes an image in linear light, so blending bright and dark pixels
+/// doesn't darken them the way blending the sRGB values does. The colors
+/// are premultiplied, so transparent pixels don't darken the edges either.
+pub fn resize_linear(image: &RgbaImage, width: u32, height: u32, filter: FilterType) -> RgbaImage {
+	let linear = Rgba32FImage::from_fn(image.width(), image.height(), |x, y| {
+		let [r, g, b, a] = image.get_pixel(x, y).0;
+		let a = a as f32 / 255.0;
+		Rgba([
+			srgb_to_linear(r) * a,
+			srgb_to_linear(g) * a,
+			srgb_to_linear(b) * a,
+			a,
+		])
+	});
+	let scaled = imageops::resize(&linear, width, height, filter);
+	RgbaImage::from_fn(width, height, |x, y| {
+		let [r, g, b, a] = scaled.get_pixel(x, y).0;
+		if a <= 0.0 {
+			return Rgba([0, 0, 0, 0]);
+		}
+		Rgba([
+			linear_to_srgb(r / a),
+			linear_to_srgb(g / a),
+			linear_to_srgb(b / a),
+			(a.clamp(0.0, 1.0) * 255.0).round() as u8,
+		])
+	})
+}
+
+/// Resizes an image by averaging the pixels every new pixel covers,
+/// weighted by how much of them it covers, so scaling down by whole numbers
+/// averages every block of pixels. The colors are premultiplied like in
+/// resize_linear, and blended in linear light if asked to.
+pub fn resize_box(image: &RgbaImage, width: u32, height: u32, linear: bool) -> RgbaImage {
+	// the pixels a new pixel covers along an axis, with how much of each
+	let spans = |size: u32, from: u32| -> Vec<Vec<(u32, f64)>> {
+		let scale = from as f64 / size as f64;
+		(0..size)
+			.map(|i| {
+				let (start, end) = (i as f64 * scale, (i + 1) as f64 * scale);
+				(start.floor() as u32..(end.ceil() as u32).min(from))
+					.map(|p| (p, end.min(p as f64 + 1.0) - start.max(p as f64)))
+					.filter(|(_, weight)| *weight > 0.0)
+					.collect()
+			})
+			.collect()
+	};
+	let columns = spans(width, image.width());
+	let rows = spans(height, image.height());
+	let decode = |channel: u8| match linear {
+		true => srgb_to_linear(channel) as f64,
+		false => channel as f64,
+	};
+	let encode = |value: f64| match linear {
+		true => linear_to_srgb(value as f32),
+		false => value.round() as u8,
+	};
+	RgbaImage::from_fn(width, height, |x, y| {
+		let mut sum = [0f64; 4];
+		let mut area = 0.0;
+		for &(py, height) in &rows[y as usize] {
+			for &(px, width) in &columns[x as usize] {
+				let [r, g, b, a] = image.get_pixel(px, py).0;
+				let a = a as f64 * width * height;
+				for (total, channel) in sum.iter_mut().zip([r, g, b]) {
+					*total += decode(channel) * a;
+				}
+				sum[3] += a;
+				area += width * height;
+			}
+		}
+		match sum[3] {
+			0.0 => Rgba([0, 0, 0, 0]),
+			total => Rgba([
+				encode(sum[0] / total),
+				encode(sum[1] / total),
+				encode(sum[2] / total),
+				(total / area).round() as u8,
+			]),
+		}
+	})
+}
+
+/// Reads an image as 8-bit sRGB. 16-bit pngs are rounded to 8 bits rather
+/// than cut off, and pngs with an ICC profile are converted from it.
+pub fn open_srgb(path: impl AsRef<Path>) -> ImageResult<RgbaImage> {
+	let path = path.as_ref();
+	let png = path.extension().and_then(|e| e.to_str());
+	if !png.is_some_and(|e| e.eq_ignore_ascii_case("png")) {
+		return Ok(image::open(path)?.into_rgba8());
+	}
+	let mut decoder = PngDecoder::new(BufReader::new(File::open(path)?))?;
+	let icc = decoder.icc_profile()?;
+	let image = DynamicImage::from_decoder(decoder)?;
+	let Some(icc) = icc else {
+		return Ok(image.into_rgba8());
+	};
+	match profile_to_srgb(&image, &icc) {
+		Ok(converted) => Ok(converted),
+		Err(e) => {
+			log::warn!("Ignoring the color profile of {}: {e}", path.display());
+			Ok(image.into_rgba8())
+		}
 	}
 }
 
+/// Converts the colors of an image from its ICC profile to 8-bit sRGB,
+/// through 16 bits so nothing is lost on the way.
+fn profile_to_srgb(image: &DynamicImage, icc: &[u8]) -> Result<RgbaImage, String> {
+	let profile = ColorProfile::new_from_slice(icc).map_err(|e| format!("{e:?}"))?;
+	let (width, height) = (image.width(), image.height());
+	let (layout, pixels) = match profile.color_space {
+		DataColorSpace::Rgb => (Layout::Rgba, image.to_rgba16().into_raw()),
+		DataColorSpace::Gray => (Layout::GrayAlpha, image.to_luma_alpha16().into_raw()),
+		other => return Err(format!("{other:?} profiles aren't supported")),
+	};
+	let transform = profile
+		.create_transform_16bit(
+			layout,
+			&ColorProfile::new_srgb(),
+			Layout::Rgba,
+			TransformOptions::default(),
+		)
+		.map_err(|e| format!("{e:?}"))?;
+	let mut srgb = vec![0u16; (width * height * 4) as usize];
+	transform
+		.transform(&pixels, &mut srgb)
+		.map_err(|e| format!("{e:?}"))?;
+	let bytes = srgb
+		.into_iter()
+		.map(|v| ((v as u32 * 255 + 32767) / 65535) as u8)
+		.collect();
+	Ok(RgbaImage::from_raw(width, height, bytes).unwrap())
+}
+
 pub fn hex_to_rgb(hex: String) -> Rgb {
 	let hex = if hex.starts_with('#') {
 		hex
@@ -29,3 +182,170 @@ pub fn hex_to_hsl(hex: String) -> Hsl {
 	};
 	Rgb::from_hex_str(&hex).unwrap().to_hsl()
 }
+
+/// Multiplies the color channels by a tint, like the game does for grass
+/// and foliage.
+pub fn tint(pixel: Rgba<u8>, tint: [u8; 3]) -> Rgba<u8> {
+	let [r, g, b, a] = pixel.0;
+	let multiply = |c: u8, t: u8| ((u16::from(c) * u16::from(t) + 127) / 255) as u8;
+	Rgba([
+		multiply(r, tint[0]),
+		multiply(g, tint[1]),
+		multiply(b, tint[2]),
+		a,
+	])
+}
+
+pub fn tint_image(img: &mut RgbaImage, color: [u8; 3]) {
+	for pixel in img.pixels_mut() {
+		*pixel = tint(*pixel, color);
+	}
+}
+
+/// Draws the image over a solid color, leaving it fully opaque.
+pub fn flatten_image(img: &mut RgbaImage, background: [u8; 3]) {
+	for pixel in img.pixels_mut() {
+		let [r, g, b, a] = pixel.0;
+		let over = |c: u8, bg: u8| {
+			((u16::from(c) * u16::from(a) + u16::from(bg) * (255 - u16::from(a)) + 127) / 255) as u8
+		};
+		*pixel = Rgba([
+			over(r, background[0]),
+			over(g, background[1]),
+			over(b, background[2]),
+			255,
+		]);
+	}
+}
+
+/// Samples a biome colormap such as `colormap/grass.png` the same way the
+/// game does for the given temperature and downfall.
+pub fn sample_colormap(colormap: &RgbaImage, temperature: f32, downfall: f32) -> [u8; 3] {
+	let temperature = temperature.clamp(0.0, 1.0);
+	let downfall = downfall.clamp(0.0, 1.0) * temperature;
+	let (width, height) = colormap.dimensions();
+	let x = ((1.0 - temperature) * (width - 1) as f32) as u32;
+	let y = ((1.0 - downfall) * (height - 1) as f32) as u32;
+	let [r, g, b, _] = colormap.get_pixel(x, y).0;
+	[r, g, b]
+}
+
+pub fn hex_to_rgb_array(hex: String) -> [u8; 3] {
+	let (r, g, b) = hex_to_rgb(hex).as_tuple();
+	[r as u8, g as u8, b as u8]
+}
+
+/// Lab colors laid out eight to a lane, so DE2000 distances from one
+/// reference color to all of them can be computed with SIMD.
+#[cfg(feature = "simd")]
+pub struct LabLanes {
+	len: usize,
+	l: Vec<f32x8>,
+	a: Vec<f32x8>,
+	b: Vec<f32x8>,
+}
+
+#[cfg(feature = "simd")]
+impl LabLanes {
+	pub fn new(colors: &[LabValue]) -> LabLanes {
+		let mut lanes = LabLanes {
+			len: colors.len(),
+			l: vec![],
+			a: vec![],
+			b: vec![],
+		};
+		for chunk in colors.chunks(8) {
+			let (mut l, mut a, mut b) = ([0.0; 8], [0.0; 8], [0.0; 8]);
+			for (i, color) in chunk.iter().enumerate() {
+				(l[i], a[i], b[i]) = (color.l, color.a, color.b);
+			}
+			lanes.l.push(f32x8::from(l));
+			lanes.a.push(f32x8::from(a));
+			lanes.b.push(f32x8::from(b));
+		}
+		lanes
+	}
+
+	/// Same as `DeltaE::new(reference, color, DE2000)` for every color,
+	/// quirks of the deltae implementation included.
+	pub fn delta_e_2000(&self, reference: LabValue) -> Vec<f32> {
+		let mut deltas = Vec::with_capacity(self.l.len() * 8);
+		for i in 0..self.l.len() {
+			deltas.extend(delta_e_2000_x8(reference, self.l[i], self.a[i], self.b[i]).to_array());
+		}
+		deltas.truncate(self.len);
+		deltas
+	}
+}
+
+#[cfg(feature = "simd")]
+fn delta_e_2000_x8(reference: LabValue, l1: f32x8, a1: f32x8, b1: f32x8) -> f32x8 {
+	let splat = f32x8::splat;
+	let pow7 = |v: f32x8| {
+		let v2 = v * v;
+		v2 * v2 * v2 * v
+	};
+	let h_prime = |a: f32x8, b: f32x8| {
+		let h = b.atan2(a).to_degrees();
+		h.simd_lt(f32x8::ZERO).select(h + splat(360.0), h)
+	};
+	let cos = |degrees: f32x8| degrees.to_radians().sin_cos().1;
+	let sin = |degrees: f32x8| degrees.to_radians().sin_cos().0;
+	let (l0, a0, b0) = (splat(reference.l), splat(reference.a), splat(reference.b));
+	let twenty_five_pow7 = splat(25_f32.powi(7));
+
+	let chroma_0 = (a0 * a0 + b0 * b0).sqrt();
+	let chroma_1 = (a1 * a1 + b1 * b1).sqrt();
+	let c_bar = (chroma_0 + chroma_1) * splat(0.5);
+	let g = splat(0.5) * (splat(1.0) - (pow7(c_bar) / (pow7(c_bar) + twenty_five_pow7)).sqrt());
+
+	let a_prime_0 = a0 * (splat(1.0) + g);
+	let a_prime_1 = a1 * (splat(1.0) + g);
+	let c_prime_0 = (a_prime_0 * a_prime_0 + b0 * b0).sqrt();
+	let c_prime_1 = (a_prime_1 * a_prime_1 + b1 * b1).sqrt();
+
+	let l_bar_prime = (l0 + l1) * splat(0.5);
+	let c_bar_prime = (c_prime_0 + c_prime_1) * splat(0.5);
+
+	let h_prime_0 = h_prime(a_prime_0, b0);
+	let h_prime_1 = h_prime(a_prime_1, b1);
+	let h_diff = h_prime_0 - h_prime_1;
+	let h_sum = h_prime_0 + h_prime_1;
+	let h_bar_prime = h_diff.abs().simd_gt(splat(180.0)).select(
+		h_diff
+			.simd_lt(splat(360.0))
+			.select(h_sum + splat(360.0), h_sum - splat(360.0)),
+		h_sum,
+	) * splat(0.5);
+
+	let t = splat(1.0) - splat(0.17) * cos(h_bar_prime - splat(30.0))
+		+ splat(0.24) * cos(splat(2.0) * h_bar_prime)
+		+ splat(0.32) * cos(splat(3.0) * h_bar_prime + splat(6.0))
+		- splat(0.20) * cos(splat(4.0) * h_bar_prime - splat(63.0));
+
+	let delta_h = h_prime_1 - h_prime_0;
+	let over = delta_h.simd_gt(splat(180.0));
+	let delta_h = (over & h_prime_1.simd_le(h_prime_0)).select(
+		delta_h + splat(360.0),
+		over.select(delta_h - splat(360.0), delta_h),
+	);
+
+	let delta_l_prime = l1 - l0;
+	let delta_c_prime = c_prime_1 - c_prime_0;
+	let delta_h_prime = splat(2.0) * (c_prime_0 * c_prime_1).sqrt() * sin(delta_h * splat(0.5));
+
+	let l_offset = (l_bar_prime - splat(50.0)) * (l_bar_prime - splat(50.0));
+	let s_l = splat(1.0) + (splat(0.015) * l_offset) / (splat(20.0) + l_offset).sqrt();
+	let s_c = splat(1.0) + splat(0.045) * c_bar_prime;
+	let s_h = splat(1.0) + splat(0.015) * c_bar_prime * t;
+
+	let theta = (h_bar_prime - splat(275.0)) / splat(25.0);
+	let delta_theta = splat(30.0) * (-(theta * theta)).exp();
+	let r_c = splat(2.0) * (pow7(c_bar_prime) / (pow7(c_bar_prime) + twenty_five_pow7)).sqrt();
+	let r_t = -(r_c * sin(splat(2.0) * delta_theta));
+
+	let l = delta_l_prime / s_l;
+	let c = delta_c_prime / s_c;
+	let h = delta_h_prime / s_h;
+	(l * l + c * c + h * h + r_t * c * h).sqrt()
+}
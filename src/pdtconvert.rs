@@ -0,0 +1,371 @@
+use super::{pdtfs, pdtlog, pdtzip};
+use camino::Utf8Path;
+use clap::{ArgAction, Subcommand};
+use image::imageops::{self, FilterType};
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{MAIN_SEPARATOR as SLASH, MAIN_SEPARATOR_STR};
+
+/// Folders of assets/minecraft/textures and where Bedrock has them, under
+/// its textures folder.
+const BEDROCK_FOLDERS: [(&str, &str); 10] = [
+	("block/", "blocks/"),
+	("item/", "items/"),
+	("entity/", "entity/"),
+	("environment/", "environment/"),
+	("particle/", "particle/"),
+	("painting/", "painting/"),
+	("colormap/", "colormap/"),
+	("models/armor/", "models/armor/"),
+	("misc/", "misc/"),
+	("map/", "map/"),
+];
+
+/// Textures Bedrock has under another name, by their path in the textures
+/// folder without .png.
+const BEDROCK_NAMES: [(&str, &str); 80] = [
+	("block/acacia_leaves", "blocks/leaves_acacia"),
+	("block/acacia_log", "blocks/log_acacia"),
+	("block/acacia_log_top", "blocks/log_acacia_top"),
+	("block/acacia_planks", "blocks/planks_acacia"),
+	("block/andesite", "blocks/stone_andesite"),
+	("block/birch_leaves", "blocks/leaves_birch"),
+	("block/birch_log", "blocks/log_birch"),
+	("block/birch_log_top", "blocks/log_birch_top"),
+	("block/birch_planks", "blocks/planks_birch"),
+	("block/bricks", "blocks/brick"),
+	("block/chiseled_stone_bricks", "blocks/stonebrick_carved"),
+	("block/cobweb", "blocks/web"),
+	("block/cracked_stone_bricks", "blocks/stonebrick_cracked"),
+	("block/dandelion", "blocks/flower_dandelion"),
+	("block/dark_oak_leaves", "blocks/leaves_big_oak"),
+	("block/dark_oak_log", "blocks/log_big_oak"),
+	("block/dark_oak_log_top", "blocks/log_big_oak_top"),
+	("block/dark_oak_planks", "blocks/planks_big_oak"),
+	("block/dead_bush", "blocks/deadbush"),
+	("block/diorite", "blocks/stone_diorite"),
+	("block/furnace_front", "blocks/furnace_front_off"),
+	("block/granite", "blocks/stone_granite"),
+	("block/grass_block_side", "blocks/grass_side_carried"),
+	("block/grass_block_top", "blocks/grass_top"),
+	("block/jungle_leaves", "blocks/leaves_jungle"),
+	("block/jungle_log", "blocks/log_jungle"),
+	("block/jungle_log_top", "blocks/log_jungle_top"),
+	("block/jungle_planks", "blocks/planks_jungle"),
+	("block/lily_pad", "blocks/waterlily"),
+	("block/mossy_cobblestone", "blocks/cobblestone_mossy"),
+	("block/mossy_stone_bricks", "blocks/stonebrick_mossy"),
+	("block/nether_bricks", "blocks/nether_brick"),
+	("block/nether_quartz_ore", "blocks/quartz_ore"),
+	("block/note_block", "blocks/noteblock"),
+	("block/oak_leaves", "blocks/leaves_oak"),
+	("block/oak_log", "blocks/log_oak"),
+	("block/oak_log_top", "blocks/log_oak_top"),
+	("block/oak_planks", "blocks/planks_oak"),
+	("block/packed_ice", "blocks/ice_packed"),
+	("block/polished_andesite", "blocks/stone_andesite_smooth"),
+	("block/polished_diorite", "blocks/stone_diorite_smooth"),
+	("block/polished_granite", "blocks/stone_granite_smooth"),
+	("block/poppy", "blocks/flower_rose"),
+	("block/sandstone", "blocks/sandstone_normal"),
+	("block/short_grass", "blocks/tallgrass"),
+	("block/slime_block", "blocks/slime"),
+	("block/smooth_stone", "blocks/stone_slab_top"),
+	("block/spawner", "blocks/mob_spawner"),
+	("block/spruce_leaves", "blocks/leaves_spruce"),
+	("block/spruce_log", "blocks/log_spruce"),
+	("block/spruce_log_top", "blocks/log_spruce_top"),
+	("block/spruce_planks", "blocks/planks_spruce"),
+	("block/stone_bricks", "blocks/stonebrick"),
+	("block/sugar_cane", "blocks/reeds"),
+	("block/terracotta", "blocks/hardened_clay"),
+	("block/torch", "blocks/torch_on"),
+	("item/beef", "items/beef_raw"),
+	("item/book", "items/book_normal"),
+	("item/bow", "items/bow_standby"),
+	("item/bucket", "items/bucket_empty"),
+	("item/chicken", "items/chicken_raw"),
+	("item/clock", "items/clock_item"),
+	("item/compass", "items/compass_item"),
+	("item/cooked_beef", "items/beef_cooked"),
+	("item/cooked_chicken", "items/chicken_cooked"),
+	("item/cooked_porkchop", "items/porkchop_cooked"),
+	("item/enchanted_book", "items/book_enchanted"),
+	("item/fishing_rod", "items/fishing_rod_uncast"),
+	("item/glass_bottle", "items/potion_bottle_empty"),
+	("item/golden_apple", "items/apple_golden"),
+	("item/golden_axe", "items/gold_axe"),
+	("item/golden_pickaxe", "items/gold_pickaxe"),
+	("item/golden_shovel", "items/gold_shovel"),
+	("item/golden_sword", "items/gold_sword"),
+	("item/lava_bucket", "items/bucket_lava"),
+	("item/milk_bucket", "items/bucket_milk"),
+	("item/porkchop", "items/porkchop_raw"),
+	("item/water_bucket", "items/bucket_water"),
+	("item/wooden_pickaxe", "items/wood_pickaxe"),
+	("item/wooden_sword", "items/wood_sword"),
+];
+
+/// Width and height pack icons are scaled to.
+const ICON_SIZE: u32 = 256;
+
+/// Where a Java texture goes in a Bedrock pack, from the table, the names
+/// Bedrock changed or the folder it is in. None for files Bedrock has no
+/// place for, and for textures the table maps to an empty path.
+pub fn bedrock_path(java: &str, table: &BTreeMap<String, String>) -> Option<String> {
+	if let Some(path) = table.get(java) {
+		return Some(path.clone()).filter(|p| !p.is_empty());
+	}
+	let texture = java
+		.strip_prefix("assets/minecraft/textures/")?
+		.strip_suffix(".png")?;
+	if let Some((_, bedrock)) = BEDROCK_NAMES.iter().find(|(j, _)| *j == texture) {
+		return Some(format!("textures/{bedrock}.png"));
+	}
+	BEDROCK_FOLDERS.iter().find_map(|(java, bedrock)| {
+		let rest = texture.strip_prefix(java)?;
+		Some(format!("textures/{bedrock}{rest}.png"))
+	})
+}
+
+/// A name-based (version 5) UUID, so the same pack keeps its UUIDs and
+/// Bedrock updates it instead of installing it again.
+pub fn name_uuid(name: &str) -> String {
+	// the URL namespace of RFC 4122
+	let namespace = [
+		0x6b, 0xa7, 0xb8, 0x11, 0x9d, 0xad, 0x11, 0xd1, 0x80, 0xb4, 0x00, 0xc0, 0x4f, 0xd4, 0x30,
+		0xc8,
+	];
+	let hash = Sha1::new()
+		.chain_update(namespace)
+		.chain_update(name.as_bytes())
+		.finalize();
+	let mut bytes = [0u8; 16];
+	bytes.copy_from_slice(&hash[..16]);
+	bytes[6] = (bytes[6] & 0x0f) | 0x50;
+	bytes[8] = (bytes[8] & 0x3f) | 0x80;
+	let hex = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+	format!(
+		"{}-{}-{}-{}-{}",
+		&hex[..8],
+		&hex[8..12],
+		&hex[12..16],
+		&hex[16..20],
+		&hex[20..]
+	)
+}
+
+/// Plain text of a pack.mcmeta description, which can be text, a text
+/// component or a list of them. Bedrock keeps § formatting codes.
+pub fn description_text(description: &Value) -> String {
+	match description {
+		Value::String(text) => text.clone(),
+		Value::Array(parts) => parts.iter().map(description_text).collect(),
+		Value::Object(component) => {
+			let text = component
+				.get("text")
+				.and_then(Value::as_str)
+				.unwrap_or_default();
+			let extra = component.get("extra").map(description_text);
+			format!("{text}{}", extra.unwrap_or_default())
+		}
+		_ => String::new(),
+	}
+}
+
+/// `[major, minor, patch]` of a version like 1.2.0, as Bedrock writes them.
+fn version_numbers(version: &str) -> [u32; 3] {
+	let mut numbers = [0; 3];
+	let release = version.trim_start_matches('v').split(['-', '+']).next();
+	for (number, part) in numbers
+		.iter_mut()
+		.zip(release.unwrap_or_default().split('.'))
+	{
+		*number = part
+			.parse()
+			.unwrap_or_else(|_| panic!("Version {version} is not major.minor.patch"));
+	}
+	numbers
+}
+
+/// manifest.json of a Bedrock resource pack.
+pub fn bedrock_manifest(
+	name: &str, description: &str, version: [u32; 3], min_engine: [u32; 3],
+) -> Value {
+	json!({
+		"format_version": 2,
+		"header": {
+			"name": name,
+			"description": description,
+			"uuid": name_uuid(&format!("pdt:{name}:header")),
+			"version": version,
+			"min_engine_version": min_engine,
+		},
+		"modules": [{
+			"type": "resources",
+			"uuid": name_uuid(&format!("pdt:{name}:resources")),
+			"version": version,
+		}],
+	})
+}
+
+/// Converts a Java pack, by its path in the pack mapped to where it is read
+/// from, to the files of a Bedrock one, writing the generated ones to the
+/// staging directory.
+pub fn convert_to_bedrock(
+	files: &BTreeMap<String, String>, staging: &str, args: &BedrockOptions,
+) -> BTreeMap<String, String> {
+	let mut bedrock = BTreeMap::new();
+	let mut skipped = 0;
+	let mut animated = 0;
+	for (file, path) in files {
+		let Some(target) = bedrock_path(file, &args.table) else {
+			if file.ends_with(".png") && file != "pack.png" {
+				log::debug!("no Bedrock path for {file}");
+				skipped += 1;
+			}
+			continue;
+		};
+		if files.contains_key(&format!("{file}.mcmeta")) {
+			animated += 1;
+		}
+		if let Some(old) = bedrock.insert(target.clone(), path.clone()) {
+			log::warn!("{file} replaces {old} as {target}");
+		}
+	}
+	if skipped > 0 {
+		log::info!("Left out {skipped} textures Bedrock has no place for");
+	}
+	if animated > 0 {
+		log::warn!("{animated} animated textures are exported as their frame strips, Bedrock needs flipbook_textures.json for them");
+	}
+
+	let write = |name: &str, data: &[u8]| {
+		let staged = format!("{staging}{SLASH}{}", name.replace('/', MAIN_SEPARATOR_STR));
+		fs::write(&staged, data).unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+		(name.to_string(), staged)
+	};
+	fs::create_dir_all(staging).unwrap_or_else(|_| panic!("Failed to create {staging} directory."));
+	let description = match files.get("pack.mcmeta") {
+		Some(path) => {
+			let data =
+				fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+			let mcmeta: Value = serde_json::from_str(&data)
+				.unwrap_or_else(|e| panic!("Failed to parse pack.mcmeta: {e}"));
+			description_text(&mcmeta["pack"]["description"])
+		}
+		None => String::new(),
+	};
+	let manifest = bedrock_manifest(&args.name, &description, args.version, args.min_engine);
+	let manifest = serde_json::to_string_pretty(&manifest).unwrap() + "\n";
+	let (name, staged) = write("manifest.json", manifest.as_bytes());
+	bedrock.insert(name, staged);
+
+	match files.get("pack.png") {
+		Some(path) => {
+			let icon = image::open(path).unwrap_or_else(|_| panic!("Failed to load image: {path}"));
+			let icon = imageops::resize(&icon, ICON_SIZE, ICON_SIZE, FilterType::Nearest);
+			let staged = format!("{staging}{SLASH}pack_icon.png");
+			icon.save(&staged)
+				.unwrap_or_else(|e| panic!("Failed to write image {staged}: {e}"));
+			bedrock.insert("pack_icon.png".to_string(), staged);
+		}
+		None => log::warn!("No pack.png, the Bedrock pack will have no icon"),
+	}
+	bedrock
+}
+
+/// What a Bedrock pack is made with.
+pub struct BedrockOptions {
+	pub name: String,
+	pub version: [u32; 3],
+	pub min_engine: [u32; 3],
+	/// Java paths mapped to Bedrock ones, an empty one leaves the file out
+	pub table: BTreeMap<String, String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ConvertArgs {
+	#[command(subcommand)]
+	format: Format,
+	#[arg(short, long, global = true, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long, global = true)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Format {
+	/// Convert a Java pack to a Bedrock .mcpack
+	Bedrock {
+		/// Pack folder or zip
+		pack: String,
+		#[arg(short, long)]
+		/// Defaults to the pack name with .mcpack
+		output: Option<String>,
+		#[arg(long)]
+		/// Pack name, which also gives its UUIDs. Defaults to the folder or
+		/// zip name
+		name: Option<String>,
+		#[arg(long = "pack-version", default_value = "1.0.0")]
+		/// Version in the manifest
+		pack_version: String,
+		#[arg(long, default_value = "1.20.0")]
+		/// Oldest Bedrock version the pack is made for
+		min_engine: String,
+		#[arg(short, long)]
+		/// Json file mapping Java paths to Bedrock ones, on top of the
+		/// built-in ones. Map a path to "" to leave it out
+		table: Option<String>,
+	},
+}
+
+pub fn run(args: ConvertArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let Format::Bedrock {
+		pack,
+		output,
+		name,
+		pack_version,
+		min_engine,
+		table,
+	} = args.format;
+	let name = name.unwrap_or_else(|| {
+		let name = pack.trim_end_matches(SLASH).rsplit(SLASH).next().unwrap();
+		name.trim_end_matches(".zip").to_string()
+	});
+	let output = output.unwrap_or_else(|| format!("{name}.mcpack"));
+	let table = match table {
+		Some(table) => {
+			let data = fs::read_to_string(&table)
+				.unwrap_or_else(|_| panic!("Failed to read file: {table}"));
+			serde_json::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse {table}: {e}"))
+		}
+		None => BTreeMap::new(),
+	};
+	let options = BedrockOptions {
+		version: version_numbers(&pack_version),
+		min_engine: version_numbers(&min_engine),
+		name,
+		table,
+	};
+	if !pdtzip::is_zip(&pack) && !Utf8Path::new(&pack).is_dir() {
+		panic!("Pack not found: {pack}");
+	}
+	let mut temp_dirs = vec![];
+	let files = pdtzip::pack_files(&pack, &mut temp_dirs);
+	let staging = format!(
+		"{}{SLASH}pdt_bedrock_{}",
+		std::env::temp_dir().display(),
+		std::process::id()
+	);
+	let bedrock = convert_to_bedrock(&files, &staging, &options);
+	pdtzip::zip_files(&bedrock, &output, None);
+	drop(temp_dirs);
+	pdtfs::if_dir_exists_remove_it(&staging);
+	log::info!("Wrote {} files to {output}", bedrock.len());
+}
@@ -0,0 +1,167 @@
+use super::{pdtfs, pdtlog, pdtmojang, pdtzip};
+use camino::Utf8Path;
+use clap::ArgAction;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+/// Kinds of assets the coverage is counted for, by the folder in a
+/// namespace and the extension of their files.
+const CATEGORIES: [(&str, &str, &str); 3] = [
+	("textures", "textures/", ".png"),
+	("sounds", "sounds/", ".ogg"),
+	("models", "models/", ".json"),
+];
+
+/// How much of one kind of vanilla asset a pack replaces.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Category {
+	pub overridden: usize,
+	pub total: usize,
+	pub percent: f64,
+	/// Vanilla files the pack leaves as they are
+	pub untouched: Vec<String>,
+	/// Files of the pack vanilla doesn't have, like custom textures or
+	/// renamed ones that no longer do anything
+	pub extra: Vec<String>,
+}
+
+/// The vanilla coverage of a pack.
+#[derive(Clone, Debug, Serialize)]
+pub struct Coverage {
+	pub pack: String,
+	pub minecraft: String,
+	pub categories: BTreeMap<String, Category>,
+}
+
+/// The category of an asset, None if it isn't one coverage is counted for.
+fn category(file: &str) -> Option<&'static str> {
+	let (_, path) = file.strip_prefix("assets/")?.split_once('/')?;
+	CATEGORIES
+		.iter()
+		.find(|(_, dir, extension)| path.starts_with(dir) && path.ends_with(extension))
+		.map(|(name, _, _)| *name)
+}
+
+/// Compares the files of a pack with the vanilla assets. Only vanilla's
+/// minecraft namespace counts towards the total, pack files in any
+/// namespace without a vanilla file are extra.
+pub fn coverage(
+	pack: &str, minecraft: &str, files: &BTreeSet<String>, vanilla: &BTreeSet<String>,
+) -> Coverage {
+	let mut categories = CATEGORIES
+		.iter()
+		.map(|(name, _, _)| (name.to_string(), Category::default()))
+		.collect::<BTreeMap<_, _>>();
+	let vanilla_files = vanilla
+		.iter()
+		.filter(|f| f.starts_with("assets/minecraft/"));
+	for file in vanilla_files {
+		let Some(name) = category(file) else {
+			continue;
+		};
+		let category = categories.get_mut(name).unwrap();
+		category.total += 1;
+		match files.contains(file) {
+			true => category.overridden += 1,
+			false => category.untouched.push(file.clone()),
+		}
+	}
+	for file in files.iter().filter(|f| !vanilla.contains(*f)) {
+		if let Some(name) = category(file) {
+			categories.get_mut(name).unwrap().extra.push(file.clone());
+		}
+	}
+	for category in categories.values_mut() {
+		category.percent = match category.total {
+			0 => 0.0,
+			total => (category.overridden as f64 * 1000.0 / total as f64).round() / 10.0,
+		};
+	}
+	Coverage {
+		pack: pack.to_string(),
+		minecraft: minecraft.to_string(),
+		categories,
+	}
+}
+
+impl Coverage {
+	/// The coverage as a table, with every untouched and extra file after it
+	/// when asked for.
+	pub fn text(&self, list: bool) -> String {
+		let mut lines = vec![format!("{} (Minecraft {})", self.pack, self.minecraft)];
+		for (name, category) in &self.categories {
+			lines.push(format!(
+				"  {name:<8}  {:>5} of {:<5}  {:>5.1}%  {} without a vanilla file",
+				category.overridden,
+				category.total,
+				category.percent,
+				category.extra.len()
+			));
+		}
+		if list {
+			for (name, category) in &self.categories {
+				if !category.untouched.is_empty() {
+					lines.push(format!("untouched {name}:"));
+					lines.extend(category.untouched.iter().map(|f| format!("  {f}")));
+				}
+				if !category.extra.is_empty() {
+					lines.push(format!("{name} without a vanilla file:"));
+					lines.extend(category.extra.iter().map(|f| format!("  {f}")));
+				}
+			}
+		}
+		lines.join("\n") + "\n"
+	}
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CoverageArgs {
+	/// Pack folders or zips, like the release zips
+	#[arg(required = true)]
+	packs: Vec<String>,
+	#[arg(short, long, default_value = "latest")]
+	/// Minecraft version to compare with, `latest` or `snapshot` for the
+	/// newest ones
+	minecraft: String,
+	#[arg(short, long)]
+	/// List every untouched file and every file without a vanilla one
+	list: bool,
+	#[arg(long, value_name = "FILE")]
+	/// Write the coverage of every pack to a json file
+	json: Option<String>,
+	#[arg(long, value_name = "NAMESPACE")]
+	/// Only count the assets of this namespace, repeat for more. Vanilla only
+	/// has minecraft, the files of other namespaces are all extra
+	namespace: Vec<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+pub fn run(args: CoverageArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let (minecraft, mut vanilla) = pdtmojang::get_all_asset_names(&args.minecraft);
+	vanilla.retain(|file| pdtfs::in_namespaces(file, &args.namespace));
+	let mut temp_dirs = vec![];
+	let mut reports = vec![];
+	for pack in &args.packs {
+		if !pdtzip::is_zip(pack) && !Utf8Path::new(pack).is_dir() {
+			panic!("Pack not found: {pack}");
+		}
+		let files = pdtzip::pack_files(pack, &mut temp_dirs)
+			.into_keys()
+			.filter(|file| pdtfs::in_namespaces(file, &args.namespace))
+			.collect::<BTreeSet<_>>();
+		let report = coverage(pack, &minecraft, &files, &vanilla);
+		print!("{}", report.text(args.list));
+		reports.push(report);
+	}
+	if let Some(path) = &args.json {
+		let data = serde_json::to_string_pretty(&reports).unwrap() + "\n";
+		fs::write(path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+	}
+}
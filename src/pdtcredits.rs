@@ -0,0 +1,231 @@
+use super::pdtcmd::git;
+use super::pdtlog;
+use clap::ArgAction;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+pub const CONTRIBUTORS_FILE: &str = "contributors.toml";
+pub const README_FILE: &str = "README.md";
+/// Around the credits in the README, everything between them is replaced.
+const README_START: &str = "<!-- credits -->";
+const README_END: &str = "<!-- /credits -->";
+
+/// Someone who worked on the pack, from contributors.toml or git.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Contributor {
+	pub name: String,
+	/// What they did, like textures or models
+	#[serde(default)]
+	pub roles: Vec<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	/// Other names they commit as, so git doesn't list them twice
+	#[serde(default, skip_serializing)]
+	pub aliases: Vec<String>,
+	/// Commits in the repository, when git was asked
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub commits: Option<u64>,
+}
+
+/// Everyone who worked on the pack, usually read from contributors.toml.
+///
+/// ```toml
+/// title = "Love & Tolerance"
+///
+/// [[contributor]]
+/// name = "Sollace"
+/// roles = ["textures", "models"]
+/// url = "https://github.com/Sollace"
+/// aliases = ["Sollace Brightlight"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Contributors {
+	/// Name of the pack, in the heading of credits.txt
+	pub title: Option<String>,
+	#[serde(default, rename = "contributor")]
+	pub contributors: Vec<Contributor>,
+}
+
+/// [credits] table of the build config, adds the credits to the base pack.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreditsConfig {
+	#[serde(default = "default_contributors")]
+	pub contributors: String,
+	/// Add everyone with commits in the repository the build runs in
+	#[serde(default)]
+	pub git: bool,
+	/// Path in the pack of the credits as text, none if empty
+	#[serde(default = "default_text")]
+	pub text: String,
+	/// Path in the pack of the credits as json, none if not given
+	pub json: Option<String>,
+}
+
+fn default_contributors() -> String {
+	CONTRIBUTORS_FILE.to_string()
+}
+
+fn default_text() -> String {
+	"credits.txt".to_string()
+}
+
+impl Contributors {
+	pub fn load(path: &str) -> Contributors {
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		toml::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse {path}: {e}"))
+	}
+
+	/// Counts the commits of everyone in the git repository in dir, adding
+	/// the ones who aren't listed yet after the others, most commits first.
+	pub fn add_git_authors(&mut self, dir: &str) {
+		let Some(shortlog) = git(dir, &["shortlog", "-sn", "--no-merges", "HEAD"]) else {
+			log::warn!("Not in a git repository, credits are only from the contributors file");
+			return;
+		};
+		for line in shortlog.lines() {
+			let Some((count, name)) = line.trim().split_once('\t') else {
+				continue;
+			};
+			let Ok(count) = count.trim().parse::<u64>() else {
+				continue;
+			};
+			let name = name.trim();
+			let known = self
+				.contributors
+				.iter_mut()
+				.find(|c| c.name == name || c.aliases.iter().any(|a| a == name));
+			match known {
+				Some(contributor) => *contributor.commits.get_or_insert(0) += count,
+				None => self.contributors.push(Contributor {
+					name: name.to_string(),
+					roles: vec![],
+					url: None,
+					aliases: vec![],
+					commits: Some(count),
+				}),
+			}
+		}
+	}
+
+	/// The credits as plain text, for credits.txt in the pack.
+	pub fn text(&self) -> String {
+		let mut lines = vec![];
+		if let Some(title) = &self.title {
+			lines.push(format!("{title} credits"));
+			lines.push(String::new());
+		}
+		for contributor in &self.contributors {
+			let mut line = contributor.name.clone();
+			if !contributor.roles.is_empty() {
+				line += &format!(" - {}", contributor.roles.join(", "));
+			}
+			if let Some(url) = &contributor.url {
+				line += &format!(" ({url})");
+			}
+			lines.push(line);
+		}
+		lines.join("\n") + "\n"
+	}
+
+	/// The credits as a json list.
+	pub fn json(&self) -> String {
+		serde_json::to_string_pretty(&self.contributors).unwrap() + "\n"
+	}
+
+	/// The credits as a Markdown list, for the README.
+	pub fn markdown(&self) -> String {
+		let lines = self
+			.contributors
+			.iter()
+			.map(|contributor| {
+				let name = match &contributor.url {
+					Some(url) => format!("[{}]({url})", contributor.name),
+					None => contributor.name.clone(),
+				};
+				match contributor.roles.is_empty() {
+					true => format!("- {name}"),
+					false => format!("- {name}: {}", contributor.roles.join(", ")),
+				}
+			})
+			.collect::<Vec<_>>();
+		lines.join("\n") + "\n"
+	}
+}
+
+/// Puts the credits between the credits markers of a README, adding a
+/// Credits section at the end when it has none.
+pub fn update_readme(readme: &str, markdown: &str) -> String {
+	let section = format!("{README_START}\n{markdown}{README_END}");
+	let start = readme.find(README_START);
+	let end = readme.find(README_END);
+	match (start, end) {
+		(Some(start), Some(end)) if start < end => format!(
+			"{}{section}{}",
+			&readme[..start],
+			&readme[end + README_END.len()..]
+		),
+		_ if readme.trim().is_empty() => format!("## Credits\n\n{section}\n"),
+		_ => format!("{}\n\n## Credits\n\n{section}\n", readme.trim_end()),
+	}
+}
+
+/// The contributors of a build config's [credits] table, with the git
+/// authors when it asks for them.
+pub fn load(config: &CreditsConfig) -> Contributors {
+	let mut contributors = Contributors::load(&config.contributors);
+	if config.git {
+		contributors.add_git_authors(".");
+	}
+	contributors
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CreditsArgs {
+	#[arg(short, long, default_value = CONTRIBUTORS_FILE)]
+	/// Contributors file to read
+	contributors: String,
+	#[arg(short, long)]
+	/// Add everyone with commits in the git repository
+	git: bool,
+	#[arg(long)]
+	/// Print the credits as json instead of text
+	json: bool,
+	#[arg(short, long)]
+	/// Update the credits section of README.md instead of printing them
+	readme: bool,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+pub fn run(args: CreditsArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let mut contributors = Contributors::load(&args.contributors);
+	if args.git {
+		contributors.add_git_authors(".");
+	}
+	if !args.readme {
+		match args.json {
+			true => print!("{}", contributors.json()),
+			false => print!("{}", contributors.text()),
+		}
+		return;
+	}
+	let readme = fs::read_to_string(README_FILE).unwrap_or_default();
+	fs::write(
+		README_FILE,
+		update_readme(&readme, &contributors.markdown()),
+	)
+	.unwrap_or_else(|_| panic!("Failed to write file: {README_FILE}"));
+	log::info!(
+		"Added {} contributors to {README_FILE}",
+		contributors.contributors.len()
+	);
+}
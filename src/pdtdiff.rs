@@ -0,0 +1,250 @@
+use super::{pdthash, pdtlog, pdtzip};
+use camino::Utf8Path;
+use clap::ArgAction;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Most changes listed for one json file, the rest are counted.
+const MAX_JSON_CHANGES: usize = 20;
+/// Longest json value shown in a change before it is cut off.
+const MAX_VALUE_LENGTH: usize = 60;
+
+/// How a file is different in the new pack.
+#[derive(Clone, Debug)]
+pub enum Change {
+	Added,
+	Removed,
+	/// With what changed, when that is known
+	Changed(Vec<String>),
+}
+
+/// Every file that is different between two packs, by path in the pack.
+#[derive(Clone, Debug, Default)]
+pub struct PackDiff {
+	pub changes: BTreeMap<String, Change>,
+	/// Json files that only changed formatting
+	pub reformatted: usize,
+}
+
+/// A json value on one line, cut off when it is long.
+fn short(value: &Value) -> String {
+	let text = value.to_string();
+	match text.chars().count() > MAX_VALUE_LENGTH {
+		true => format!(
+			"{}…",
+			text.chars().take(MAX_VALUE_LENGTH).collect::<String>()
+		),
+		false => text,
+	}
+}
+
+/// Changes between two json values, by the json pointer they are at.
+pub fn json_changes(old: &Value, new: &Value, path: &str, changes: &mut Vec<String>) {
+	match (old, new) {
+		(Value::Object(old), Value::Object(new)) => {
+			for (key, value) in old {
+				let pointer = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+				match new.get(key) {
+					Some(new) => json_changes(value, new, &pointer, changes),
+					None => changes.push(format!("- {pointer}")),
+				}
+			}
+			for (key, value) in new.iter().filter(|(k, _)| !old.contains_key(*k)) {
+				let pointer = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+				changes.push(format!("+ {pointer}: {}", short(value)));
+			}
+		}
+		(Value::Array(old_list), Value::Array(new_list)) if old_list.len() == new_list.len() => {
+			for (i, (old, new)) in old_list.iter().zip(new_list).enumerate() {
+				json_changes(old, new, &format!("{path}/{i}"), changes);
+			}
+		}
+		(old, new) if old != new => {
+			let pointer = match path.is_empty() {
+				true => "/",
+				false => path,
+			};
+			changes.push(format!("~ {pointer}: {} → {}", short(old), short(new)));
+		}
+		_ => (),
+	}
+}
+
+/// What changed in a file that is in both packs, None if only its
+/// formatting did.
+fn describe_change(file: &str, old: &str, new: &str) -> Option<Vec<String>> {
+	if file.ends_with(".png") {
+		let old_size = image::image_dimensions(old).ok();
+		let new_size = image::image_dimensions(new).ok();
+		return Some(match (old_size, new_size) {
+			(Some(old), Some(new)) if old != new => {
+				vec![format!("{}x{} → {}x{}", old.0, old.1, new.0, new.1)]
+			}
+			_ => vec![],
+		});
+	}
+	if !file.ends_with(".json") && !file.ends_with(".mcmeta") {
+		return Some(vec![]);
+	}
+	let read = |path: &str| {
+		let data = fs::read_to_string(path).ok()?;
+		serde_json::from_str::<Value>(&data).ok()
+	};
+	let (Some(old), Some(new)) = (read(old), read(new)) else {
+		return Some(vec![]);
+	};
+	let mut changes = vec![];
+	json_changes(&old, &new, "", &mut changes);
+	if changes.is_empty() {
+		return None;
+	}
+	if changes.len() > MAX_JSON_CHANGES {
+		let more = changes.len() - MAX_JSON_CHANGES;
+		changes.truncate(MAX_JSON_CHANGES);
+		changes.push(format!("… and {more} more"));
+	}
+	Some(changes)
+}
+
+/// Compares two packs, by their path in the pack mapped to where they are
+/// read from.
+pub fn diff_packs(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> PackDiff {
+	let mut diff = PackDiff::default();
+	for file in old.keys().filter(|f| !new.contains_key(*f)) {
+		diff.changes.insert(file.clone(), Change::Removed);
+	}
+	for (file, new_path) in new {
+		let Some(old_path) = old.get(file) else {
+			diff.changes.insert(file.clone(), Change::Added);
+			continue;
+		};
+		if pdthash::get_hash(old_path, false) == pdthash::get_hash(new_path, false) {
+			continue;
+		}
+		match describe_change(file, old_path, new_path) {
+			Some(details) => {
+				diff.changes.insert(file.clone(), Change::Changed(details));
+			}
+			None => diff.reformatted += 1,
+		}
+	}
+	diff
+}
+
+impl PackDiff {
+	fn files(&self, added: bool, removed: bool) -> Vec<(&String, &Change)> {
+		self.changes
+			.iter()
+			.filter(|(_, c)| match c {
+				Change::Added => added,
+				Change::Removed => removed,
+				Change::Changed(_) => !added && !removed,
+			})
+			.collect()
+	}
+
+	/// The changes as lines like `git diff --name-status`, with what
+	/// changed under them.
+	pub fn text(&self) -> String {
+		let mut lines = vec![];
+		for (file, change) in &self.changes {
+			match change {
+				Change::Added => lines.push(format!("A {file}")),
+				Change::Removed => lines.push(format!("D {file}")),
+				Change::Changed(details) => {
+					lines.push(format!("M {file}"));
+					lines.extend(details.iter().map(|d| format!("    {d}")));
+				}
+			}
+		}
+		lines.push(self.summary());
+		lines.join("\n") + "\n"
+	}
+
+	/// The changes as a Markdown section for release notes.
+	pub fn markdown(&self) -> String {
+		let mut sections = vec![];
+		let groups = [
+			("Added", self.files(true, false)),
+			("Removed", self.files(false, true)),
+			("Changed", self.files(false, false)),
+		];
+		for (title, files) in groups {
+			if files.is_empty() {
+				continue;
+			}
+			let mut lines = vec![format!("### {title} ({})\n", files.len())];
+			for (file, change) in files {
+				lines.push(format!("- `{file}`"));
+				if let Change::Changed(details) = change {
+					lines.extend(details.iter().map(|d| format!("  - `{d}`")));
+				}
+			}
+			sections.push(lines.join("\n") + "\n");
+		}
+		if sections.is_empty() {
+			return "No changes.\n".to_string();
+		}
+		sections.join("\n")
+	}
+
+	pub fn summary(&self) -> String {
+		let count = |added, removed| self.files(added, removed).len();
+		let mut summary = format!(
+			"{} added, {} removed, {} changed",
+			count(true, false),
+			count(false, true),
+			count(false, false)
+		);
+		if self.reformatted > 0 {
+			summary += &format!(", {} only reformatted", self.reformatted);
+		}
+		summary
+	}
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+	/// Old pack folder or zip
+	old: String,
+	/// New pack folder or zip
+	new: String,
+	#[arg(short, long)]
+	/// Print a Markdown summary for release notes
+	markdown: bool,
+	#[arg(short, long)]
+	/// Write to a file instead of printing
+	output: Option<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+pub fn run(args: DiffArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let mut temp_dirs = vec![];
+	let mut files = |path: &str| {
+		if !pdtzip::is_zip(path) && !Utf8Path::new(path).is_dir() {
+			panic!("Pack not found: {path}");
+		}
+		pdtzip::pack_files(path, &mut temp_dirs)
+	};
+	let old = files(&args.old);
+	let new = files(&args.new);
+	let diff = diff_packs(&old, &new);
+	let report = match args.markdown {
+		true => diff.markdown(),
+		false => diff.text(),
+	};
+	match &args.output {
+		Some(file) => {
+			fs::write(file, report).unwrap_or_else(|_| panic!("Failed to write file: {file}"));
+			log::info!("{}", diff.summary());
+		}
+		None => print!("{report}"),
+	}
+}
@@ -0,0 +1,177 @@
+use super::pdtvalidate::frame_size;
+use super::{pdtfs, pdtlog, pdtthread, pdtzip};
+use camino::Utf8Path;
+use clap::{ArgAction, Subcommand};
+use image::imageops;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{MAIN_SEPARATOR as SLASH, MAIN_SEPARATOR_STR};
+
+/// Written to the gallery folder, listing every release and its previews.
+pub const GALLERY_INDEX: &str = "index.json";
+
+/// A texture of the gallery.
+#[derive(Clone, Debug, Serialize)]
+pub struct Preview {
+	/// Path of the texture in the pack
+	pub texture: String,
+	/// Path of the preview in the folder of its release, separated by `/`
+	pub preview: String,
+	/// Size of the texture, of one frame for animated ones
+	pub width: u32,
+	pub height: u32,
+	/// Frames of the animation, 1 for textures that aren't animated
+	pub frames: u32,
+}
+
+/// The previews of one release.
+#[derive(Clone, Debug, Serialize)]
+pub struct Gallery {
+	pub name: String,
+	pub textures: Vec<Preview>,
+}
+
+/// The frame an animated texture starts on, from its mcmeta, as the x, y,
+/// width and height of it in the texture and the number of frames. None
+/// for textures that aren't animated.
+fn first_frame(mcmeta: &str, width: u32, height: u32) -> Option<([u32; 4], u32)> {
+	let data = fs::read_to_string(mcmeta).ok()?;
+	let json = serde_json::from_str::<Value>(&data).ok()?;
+	let animation = json.get("animation").filter(|a| a.is_object())?;
+	let (frame_width, frame_height) = frame_size(animation, width, height);
+	if frame_width == 0 || frame_height == 0 {
+		return None;
+	}
+	let columns = (width / frame_width).max(1);
+	let count = columns * (height / frame_height);
+	let first = match &animation["frames"][0] {
+		Value::Number(n) => n.as_u64().unwrap_or(0) as u32,
+		frame => frame["index"].as_u64().unwrap_or(0) as u32,
+	};
+	let first = if first < count { first } else { 0 };
+	let (x, y) = (
+		first % columns * frame_width,
+		first / columns * frame_height,
+	);
+	Some(([x, y, frame_width, frame_height], count))
+}
+
+/// Writes a WebP preview of every texture of a pack to a folder, scaled
+/// down to fit in size by size pixels and laid out like the textures. Only
+/// the first frame of animated textures is shown.
+pub fn export_gallery(
+	name: &str, files: &BTreeMap<String, String>, dir: &str, size: u32,
+) -> Gallery {
+	let textures = files
+		.iter()
+		.filter(|(file, _)| file.starts_with("assets/") && file.ends_with(".png"))
+		.filter(|(file, _)| file.split('/').nth(2) == Some("textures"))
+		.map(|(file, path)| {
+			let mcmeta = files.get(&format!("{file}.mcmeta")).cloned();
+			(file.clone(), path.clone(), mcmeta)
+		})
+		.collect::<Vec<_>>();
+	let dir = dir.to_string();
+	let mut textures = pdtthread::multithread(textures, None, move |_, (file, path, mcmeta)| {
+		let image = match image::open(pdtfs::long_path(&path)) {
+			Ok(image) => image.into_rgba8(),
+			Err(e) => {
+				log::warn!("skipping {file}, failed to decode it: {e}");
+				return None;
+			}
+		};
+		let (width, height) = image.dimensions();
+		let frame = mcmeta.and_then(|mcmeta| first_frame(&mcmeta, width, height));
+		let ([x, y, width, height], frames) = frame.unwrap_or(([0, 0, width, height], 1));
+		let mut preview = imageops::crop_imm(&image, x, y, width, height).to_image();
+		if width > size || height > size {
+			let scale = size as f64 / width.max(height) as f64;
+			let scaled = |n: u32| ((n as f64 * scale).round() as u32).max(1);
+			preview = imageops::thumbnail(&preview, scaled(width), scaled(height));
+		}
+		let webp = format!("{}.webp", file.strip_suffix(".png").unwrap());
+		let out = format!("{dir}{SLASH}{}", webp.replace('/', MAIN_SEPARATOR_STR));
+		if let Some(parent) = Utf8Path::new(&out).parent() {
+			fs::create_dir_all(parent)
+				.unwrap_or_else(|_| panic!("Failed to create {parent} directory."));
+		}
+		preview
+			.save(&out)
+			.unwrap_or_else(|e| panic!("Failed to write image {out}: {e}"));
+		log::debug!("exported preview of {file}");
+		Some(Preview {
+			texture: file,
+			preview: webp,
+			width,
+			height,
+			frames,
+		})
+	});
+	textures.sort_by(|a, b| a.texture.cmp(&b.texture));
+	Gallery {
+		name: name.to_string(),
+		textures,
+	}
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ExportArgs {
+	#[command(subcommand)]
+	export: Export,
+	#[arg(short, long, global = true, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long, global = true)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Export {
+	/// Write WebP previews of every texture and an index.json listing them,
+	/// for a texture gallery on the website
+	Gallery {
+		/// Pack folders or release zips, each gets a folder of previews
+		/// named after it
+		#[arg(required = true)]
+		packs: Vec<String>,
+		#[arg(short, long, default_value = "gallery")]
+		/// Folder to write the previews and index.json to
+		output: String,
+		#[arg(short, long, default_value_t = 128)]
+		/// Largest width and height of the previews, smaller textures keep
+		/// theirs
+		size: u32,
+	},
+}
+
+pub fn run(args: ExportArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let Export::Gallery {
+		packs,
+		output,
+		size,
+	} = args.export;
+	pdtfs::if_dir_exists_remove_and_remake_it(&output);
+	let mut temp_dirs = vec![];
+	let mut galleries = vec![];
+	for pack in &packs {
+		if !pdtzip::is_zip(pack) && !Utf8Path::new(pack).is_dir() {
+			panic!("Pack not found: {pack}");
+		}
+		let name = pack.trim_end_matches(SLASH).rsplit(SLASH).next().unwrap();
+		let name = name.trim_end_matches(".zip").to_string();
+		if galleries.iter().any(|g: &Gallery| g.name == name) {
+			panic!("More than one pack is named {name}, their previews would overwrite each other");
+		}
+		let files = pdtzip::pack_files(pack, &mut temp_dirs);
+		let gallery = export_gallery(&name, &files, &format!("{output}{SLASH}{name}"), size);
+		log::info!("Exported {} previews of {name}", gallery.textures.len());
+		galleries.push(gallery);
+	}
+	let index = format!("{output}{SLASH}{GALLERY_INDEX}");
+	let data = serde_json::to_string_pretty(&galleries).unwrap() + "\n";
+	fs::write(&index, data).unwrap_or_else(|_| panic!("Failed to write file: {index}"));
+}
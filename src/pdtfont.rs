@@ -0,0 +1,255 @@
+use super::{pdtfs, pdtjson, pdtlog};
+use camino::Utf8Path;
+use clap::ArgAction;
+use image::{imageops, Rgba, RgbaImage};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::MAIN_SEPARATOR as SLASH;
+
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+
+/// Rows of a 3x5 glyph from top to bottom, the highest bit is the left pixel.
+/// Letters are drawn the same in either case, unknown characters as `?`.
+fn glyph(c: char) -> [u8; 5] {
+	match c.to_ascii_lowercase() {
+		'0' => [7, 5, 5, 5, 7],
+		'1' => [2, 6, 2, 2, 7],
+		'2' => [7, 1, 7, 4, 7],
+		'3' => [7, 1, 7, 1, 7],
+		'4' => [5, 5, 7, 1, 1],
+		'5' => [7, 4, 7, 1, 7],
+		'6' => [7, 4, 7, 5, 7],
+		'7' => [7, 1, 1, 2, 2],
+		'8' => [7, 5, 7, 5, 7],
+		'9' => [7, 5, 7, 1, 7],
+		'a' => [2, 5, 7, 5, 5],
+		'b' => [6, 5, 6, 5, 6],
+		'c' => [3, 4, 4, 4, 3],
+		'd' => [6, 5, 5, 5, 6],
+		'e' => [7, 4, 6, 4, 7],
+		'f' => [7, 4, 6, 4, 4],
+		'g' => [3, 4, 5, 5, 3],
+		'h' => [5, 5, 7, 5, 5],
+		'i' => [7, 2, 2, 2, 7],
+		'j' => [1, 1, 1, 5, 2],
+		'k' => [5, 5, 6, 5, 5],
+		'l' => [4, 4, 4, 4, 7],
+		'm' => [5, 7, 7, 5, 5],
+		'n' => [6, 5, 5, 5, 5],
+		'o' => [2, 5, 5, 5, 2],
+		'p' => [6, 5, 6, 4, 4],
+		'q' => [2, 5, 5, 6, 3],
+		'r' => [6, 5, 6, 5, 5],
+		's' => [3, 4, 2, 1, 6],
+		't' => [7, 2, 2, 2, 2],
+		'u' => [5, 5, 5, 5, 7],
+		'v' => [5, 5, 5, 5, 2],
+		'w' => [5, 5, 7, 7, 5],
+		'x' => [5, 5, 2, 5, 5],
+		'y' => [5, 5, 2, 2, 2],
+		'z' => [7, 1, 2, 4, 7],
+		' ' => [0, 0, 0, 0, 0],
+		'_' => [0, 0, 0, 0, 7],
+		'-' => [0, 0, 7, 0, 0],
+		'.' => [0, 0, 0, 0, 2],
+		',' => [0, 0, 0, 2, 4],
+		':' => [0, 2, 0, 2, 0],
+		'/' => [1, 1, 2, 4, 4],
+		'#' => [5, 7, 5, 7, 5],
+		'%' => [5, 1, 2, 4, 5],
+		'(' => [2, 4, 4, 4, 2],
+		')' => [2, 1, 1, 1, 2],
+		_ => [7, 1, 2, 0, 2],
+	}
+}
+
+/// Width in pixels of the text drawn at the given scale.
+pub fn text_width(text: &str, scale: u32) -> u32 {
+	let chars = text.chars().count() as u32;
+	(chars * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+/// Draws the text with its top left corner at x, y. Pixels that fall
+/// outside the image are left out.
+pub fn draw_text(image: &mut RgbaImage, text: &str, x: u32, y: u32, scale: u32, color: Rgba<u8>) {
+	for (i, c) in text.chars().enumerate() {
+		let glyph_x = x + i as u32 * (GLYPH_WIDTH + 1) * scale;
+		for (row, bits) in glyph(c).iter().enumerate() {
+			for column in 0..GLYPH_WIDTH {
+				if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+					continue;
+				}
+				for dy in 0..scale {
+					for dx in 0..scale {
+						let pixel_x = glyph_x + column * scale + dx;
+						let pixel_y = y + row as u32 * scale + dy;
+						if pixel_x < image.width() && pixel_y < image.height() {
+							image.put_pixel(pixel_x, pixel_y, color);
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+/// The character a glyph image is for, from its file name without the
+/// extension: the character itself, or its code point like `u+00e9` for
+/// ones that can't be in file names.
+pub fn glyph_char(stem: &str) -> Option<char> {
+	let mut chars = stem.chars();
+	if let (Some(c), None) = (chars.next(), chars.next()) {
+		return Some(c);
+	}
+	let lower = stem.to_lowercase();
+	let hex = lower
+		.strip_prefix("u+")
+		.or_else(|| lower.strip_prefix('u'))?;
+	char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+/// Lays glyph images of the same size out in a sheet, in rows of columns
+/// characters sorted by code point. Returns the sheet and its rows of
+/// characters for a bitmap provider, with empty slots as `\0`.
+pub fn glyph_sheet(glyphs: &BTreeMap<char, RgbaImage>, columns: usize) -> (RgbaImage, Vec<String>) {
+	let (width, height) = glyphs
+		.values()
+		.next()
+		.map(|g| g.dimensions())
+		.unwrap_or_default();
+	let columns = columns.min(glyphs.len()).max(1);
+	let rows = glyphs.len().div_ceil(columns);
+	let mut sheet = RgbaImage::new(width * columns as u32, height * rows as u32);
+	let mut chars = vec![String::new(); rows];
+	for (i, (c, glyph)) in glyphs.iter().enumerate() {
+		let (x, y) = ((i % columns) as u32 * width, (i / columns) as u32 * height);
+		imageops::replace(&mut sheet, glyph, x as i64, y as i64);
+		chars[i / columns].push(*c);
+	}
+	if let Some(last) = chars.last_mut() {
+		while last.chars().count() < columns {
+			last.push('\0');
+		}
+	}
+	(sheet, chars)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct FontArgs {
+	/// Folder of glyph images of the same size, named after their character
+	/// or its code point like u+00e9.png
+	glyphs: String,
+	/// Pack folder to add the glyph sheet and the font to
+	pack: String,
+	#[arg(short, long, default_value = "default")]
+	/// Font to add the glyphs to, by resource location like
+	/// minecraft:default
+	font: String,
+	#[arg(long)]
+	/// Name of the glyph sheet in textures/font, defaults to the name of the
+	/// glyphs folder
+	sheet: Option<String>,
+	#[arg(long, default_value_t = 16)]
+	/// Characters in a row of the sheet
+	columns: usize,
+	#[arg(long, default_value_t = 8)]
+	/// Height the glyphs are drawn at
+	height: i64,
+	#[arg(long, default_value_t = 7)]
+	/// Pixels of the glyphs above the line the text is on
+	ascent: i64,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+/// Builds a glyph sheet from a folder of glyph images and adds a bitmap
+/// provider for it to a font, replacing the one for the same sheet.
+pub fn run(args: FontArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	pdtfs::check_if_dir_exists(&args.glyphs);
+	pdtfs::check_if_dir_exists(&args.pack);
+	let mut glyphs = BTreeMap::new();
+	let mut size: Option<(u32, u32)> = None;
+	let dir = pdtfs::check_dir_ends_with_slash(args.glyphs.clone());
+	for file in pdtfs::find_files_in_dir(&dir, false, &Some(vec![".png".to_string()])) {
+		let name = &file[dir.len()..];
+		let Some(c) = glyph_char(name.strip_suffix(".png").unwrap_or(name)) else {
+			log::warn!("{name} isn't named after a character, skipping it");
+			continue;
+		};
+		let glyph = image::open(pdtfs::long_path(&file))
+			.unwrap_or_else(|_| panic!("Failed to load image: {file}"))
+			.into_rgba8();
+		match size {
+			Some(size) if size != glyph.dimensions() => panic!(
+				"{name} is {}x{}, the other glyphs are {}x{}",
+				glyph.width(),
+				glyph.height(),
+				size.0,
+				size.1
+			),
+			_ => size = Some(glyph.dimensions()),
+		}
+		if glyphs.insert(c, glyph).is_some() {
+			panic!("{c} has more than one glyph in {}", args.glyphs);
+		}
+	}
+	if glyphs.is_empty() {
+		panic!("No glyphs found in {}", args.glyphs);
+	}
+	let (namespace, font) = args
+		.font
+		.split_once(':')
+		.unwrap_or(("minecraft", &args.font));
+	let sheet_name = args.sheet.unwrap_or_else(|| {
+		let dir = args.glyphs.trim_end_matches(SLASH);
+		dir.rsplit(SLASH).next().unwrap_or(dir).to_lowercase()
+	});
+	let assets = format!(
+		"{}{SLASH}assets{SLASH}{namespace}",
+		args.pack.trim_end_matches(SLASH)
+	);
+	let (sheet, chars) = glyph_sheet(&glyphs, args.columns);
+	let sheet_path = format!("{assets}{SLASH}textures{SLASH}font{SLASH}{sheet_name}.png");
+	fs::create_dir_all(Utf8Path::new(&sheet_path).parent().unwrap())
+		.unwrap_or_else(|_| panic!("Failed to create directory for {sheet_path}"));
+	sheet
+		.save(&sheet_path)
+		.unwrap_or_else(|_| panic!("Failed to save image: {sheet_path}"));
+
+	let file = format!("{namespace}:font/{sheet_name}.png");
+	let provider = json!({
+		"type": "bitmap",
+		"file": file,
+		"height": args.height,
+		"ascent": args.ascent,
+		"chars": chars,
+	});
+	let font_path = format!("{assets}{SLASH}font{SLASH}{font}.json");
+	let mut json = match fs::read_to_string(&font_path) {
+		Ok(data) => serde_json::from_str::<Value>(&pdtjson::strip_comments(&data))
+			.unwrap_or_else(|e| panic!("Failed to parse {font_path}: {e}")),
+		Err(_) => json!({ "providers": [] }),
+	};
+	let providers = json["providers"]
+		.as_array_mut()
+		.unwrap_or_else(|| panic!("{font_path} has no providers"));
+	match providers.iter_mut().find(|p| p["file"] == file) {
+		Some(existing) => *existing = provider,
+		None => providers.push(provider),
+	}
+	fs::create_dir_all(Utf8Path::new(&font_path).parent().unwrap())
+		.unwrap_or_else(|_| panic!("Failed to create directory for {font_path}"));
+	fs::write(&font_path, pdtjson::format_json(&json.to_string(), "\t"))
+		.unwrap_or_else(|_| panic!("Failed to write file: {font_path}"));
+	log::info!(
+		"Added {} glyphs to {namespace}:{font} from {sheet_path}",
+		glyphs.len()
+	);
+}
@@ -1,48 +1,540 @@
 use super::pdtthread;
 use super::pdttrait::Vector;
-use camino::Utf8Path;
-use fs_extra::dir;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use clap::ValueEnum;
+use glob::{MatchOptions, Pattern};
+use rayon::prelude::*;
+use regex_lite::Regex;
+use serde::Deserialize;
 use std::fs;
-use std::path::MAIN_SEPARATOR as SLASH;
+use std::path::{PathBuf, MAIN_SEPARATOR as SLASH};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Files listing paths to leave out, written like .gitignore.
+pub const IGNORE_FILES: [&str; 3] = [".gitignore", ".pdtignore", ".packignore"];
+/// Ignore files listing the working files kept next to the assets of a
+/// pack, like psds and reference images, which never go in a release.
+pub const PACK_IGNORE_FILES: [&str; 2] = [".pdtignore", ".packignore"];
+
+/// The path to give the file system, which works for every path, also ones
+/// longer than 260 characters on Windows. There it is made absolute and gets
+/// the `\\?\` prefix, which turns the length limit off. Paths shown to the
+/// user stay the way they were given.
+pub fn long_path(path: &str) -> PathBuf {
+	#[cfg(windows)]
+	{
+		let path = path.replace('/', "\\");
+		if path.starts_with(r"\\?\") {
+			return PathBuf::from(path);
+		}
+		// a \\?\ path isn't cleaned up by Windows, absolute resolves the
+		// . and .. in it first
+		if let Ok(absolute) = std::path::absolute(&path) {
+			let absolute = absolute.to_string_lossy();
+			return PathBuf::from(match absolute.strip_prefix(r"\\") {
+				Some(share) => format!(r"\\?\UNC\{share}"),
+				None => format!(r"\\?\{absolute}"),
+			});
+		}
+	}
+	PathBuf::from(path)
+}
+
+/// What is in a folder, by path joined to the folder the way it was given.
+/// Names that aren't valid unicode are skipped with a warning, since every
+/// path is passed around as a string.
+fn read_dir(dir: &str) -> Vec<(String, fs::DirEntry)> {
+	let entries = fs::read_dir(long_path(dir))
+		.unwrap_or_else(|e| panic!("Failed to read {dir} directory: {e}"));
+	entries
+		.filter_map(|entry| {
+			let entry = entry.unwrap_or_else(|e| panic!("Failed to read {dir} directory: {e}"));
+			match entry.file_name().into_string() {
+				Ok(name) => Some((Utf8Path::new(dir).join(name).to_string(), entry)),
+				Err(name) => {
+					log::warn!(
+						"{} in {dir} isn't a unicode name, skipping it",
+						name.to_string_lossy()
+					);
+					None
+				}
+			}
+		})
+		.collect()
+}
+
+/// A path without the separator it may end with, so `out/` is `out` and
+/// the folders and files next to it can be named after it.
+pub fn trim_path(path: &str) -> String {
+	Utf8Path::new(path)
+		.components()
+		.collect::<Utf8PathBuf>()
+		.to_string()
+}
+
+/// Path of a file found in a folder relative to the folder, whether or not
+/// the folder was given with a separator at the end.
+pub fn relative_path(dir: &str, file: &str) -> String {
+	match Utf8Path::new(file).strip_prefix(dir) {
+		Ok(relative) => relative.to_string(),
+		Err(_) => panic!("{file} is not in {dir} directory."),
+	}
+}
+
+/// Path of a file found in a pack folder relative to the folder, separated
+/// by `/` on every platform like the paths in a pack or zip are.
+pub fn pack_path(dir: &str, file: &str) -> String {
+	let relative = relative_path(dir, file);
+	let parts = Utf8Path::new(&relative).components();
+	parts.map(|c| c.as_str()).collect::<Vec<_>>().join("/")
+}
+
+/// Path of a file or folder in the temporary folder of the system.
+pub fn temp_path(name: &str) -> String {
+	let temp = std::env::temp_dir();
+	Utf8Path::new(&*temp.to_string_lossy())
+		.join(name)
+		.to_string()
+}
+
+pub fn is_dir(path: &str) -> bool {
+	long_path(path).is_dir()
+}
+
+pub fn is_file(path: &str) -> bool {
+	long_path(path).is_file()
+}
 
 pub fn check_if_dir_exists(dir: &str) {
-	if !Utf8Path::new(dir).is_dir() {
+	if !is_dir(dir) {
 		panic!("{dir} not found!");
 	}
 }
 
 pub fn if_dir_exists_remove_it(dir: &str) {
-	if Utf8Path::new(dir).is_dir() {
-		fs::remove_dir_all(dir).unwrap_or_else(|_| panic!("Failed to remove {dir} directory!"));
+	if is_dir(dir) {
+		fs::remove_dir_all(long_path(dir))
+			.unwrap_or_else(|_| panic!("Failed to remove {dir} directory!"));
 	}
 }
 
 pub fn if_dir_exists_remove_and_remake_it(dir: &str) {
 	if_dir_exists_remove_it(dir);
-	fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	fs::create_dir_all(long_path(dir))
+		.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
 }
 
 pub fn rename(dir: &str, new_dir: &str) {
-	fs::rename(dir, new_dir).unwrap_or_else(|_| panic!("Failed to rename {dir} to {new_dir}."));
+	fs::rename(long_path(dir), long_path(new_dir))
+		.unwrap_or_else(|_| panic!("Failed to rename {dir} to {new_dir}."));
+}
+
+/// Where a file goes in another folder, keeping its path but leaving out
+/// `.`, `..` and the root, so `./assets/a.png` and `/tmp/assets/a.png` go to
+/// `{dir}/assets/a.png` and `{dir}/tmp/assets/a.png`.
+pub fn path_in_dir(dir: &str, path: &str) -> String {
+	let mut output = Utf8PathBuf::from(dir);
+	for component in Utf8Path::new(path).components() {
+		if let Utf8Component::Normal(part) = component {
+			output.push(part);
+		}
+	}
+	output.to_string()
+}
+
+/// An output folder that is written next to where it goes and only swapped
+/// in once everything is written, so a failed run leaves the previous output
+/// as it was. It is removed again if it is dropped without being committed.
+#[derive(Debug)]
+pub struct StagedDir {
+	pub target: String,
+	pub dir: String,
+}
+
+impl StagedDir {
+	/// Starts staging the target folder in `{target}.tmp-{pid}`.
+	pub fn new(target: &str) -> StagedDir {
+		let target = trim_path(target);
+		let dir = format!("{target}.tmp-{}", std::process::id());
+		if_dir_exists_remove_and_remake_it(&dir);
+		StagedDir { target, dir }
+	}
+
+	/// Swaps the staged folder in. The previous output is moved aside first
+	/// and only removed once the new one is in place.
+	pub fn commit(self) {
+		let old = format!("{}.old-{}", self.target, std::process::id());
+		let had_output = long_path(&self.target).exists();
+		if had_output {
+			if_dir_exists_remove_it(&old);
+			rename(&self.target, &old);
+		}
+		if let Err(e) = fs::rename(long_path(&self.dir), long_path(&self.target)) {
+			if had_output {
+				let _ = fs::rename(long_path(&old), long_path(&self.target));
+			}
+			panic!("Failed to rename {} to {}: {e}", self.dir, self.target);
+		}
+		if had_output {
+			if_dir_exists_remove_it(&old);
+		}
+	}
+}
+
+impl Drop for StagedDir {
+	fn drop(&mut self) {
+		if is_dir(&self.dir) {
+			let _ = fs::remove_dir_all(long_path(&self.dir));
+		}
+	}
+}
+
+static TEMP_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// A folder in the temporary folder of the system that is removed when it
+/// is dropped, also when a run panics.
+#[derive(Debug)]
+pub struct TempDir {
+	pub dir: String,
+}
+
+impl TempDir {
+	/// Makes an empty `{temp}/{name}-{pid}-{n}` folder, counting the ones the
+	/// process made, so runs at the same time never share one.
+	pub fn new(name: &str) -> TempDir {
+		let n = TEMP_DIRS.fetch_add(1, Ordering::Relaxed);
+		let dir = temp_path(&format!("{name}-{}-{n}", std::process::id()));
+		if_dir_exists_remove_and_remake_it(&dir);
+		TempDir { dir }
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		if is_dir(&self.dir) {
+			let _ = fs::remove_dir_all(long_path(&self.dir));
+		}
+	}
+}
+
+/// Image files the tools read. Minecraft only loads png textures, but tga
+/// and jpeg ones show up in older packs.
+pub const IMAGE_EXTENSIONS: [&str; 4] = [".png", ".tga", ".jpg", ".jpeg"];
+
+/// Whether a file has one of the extensions, ignoring case.
+pub fn has_extension(path: &str, extensions: &[String]) -> bool {
+	let path = path.to_lowercase();
+	extensions.iter().any(|e| path.ends_with(&e.to_lowercase()))
+}
+
+pub fn image_extensions() -> Vec<String> {
+	IMAGE_EXTENSIONS.map(|e| e.to_string()).to_vec()
+}
+
+/// Whether a file is a png, tga or jpeg image.
+pub fn is_image(path: &str) -> bool {
+	has_extension(path, &image_extensions())
+}
+
+/// The path with its extension changed to .png.
+pub fn png_path(path: &str) -> String {
+	match path.rsplit_once('.') {
+		Some((stem, _)) if !stem.is_empty() => format!("{stem}.png"),
+		_ => format!("{path}.png"),
+	}
+}
+
+/// A line of an ignore file.
+#[derive(Clone, Debug)]
+pub struct IgnoreRule {
+	pattern: Pattern,
+	/// Folder of the ignore file, relative to the folder searched
+	base: String,
+	/// Starts with !, so it brings back what an earlier rule left out
+	negate: bool,
+	/// Ends with /, so it is only for folders
+	dir_only: bool,
+	/// Has a / before its end, so it is relative to base instead of
+	/// matching names in any folder under it
+	anchored: bool,
+}
+
+impl IgnoreRule {
+	/// Reads a line of an ignore file in the folder base, None for blank
+	/// lines, comments and invalid patterns.
+	pub fn parse(line: &str, base: &str) -> Option<IgnoreRule> {
+		let line = line.trim_end();
+		if line.is_empty() || line.starts_with('#') {
+			return None;
+		}
+		let (negate, line) = match line.strip_prefix('!') {
+			Some(line) => (true, line),
+			None => (false, line.strip_prefix('\\').unwrap_or(line)),
+		};
+		let (dir_only, line) = match line.strip_suffix('/') {
+			Some(line) => (true, line),
+			None => (false, line),
+		};
+		let anchored = line.contains('/');
+		let pattern = Pattern::new(line.trim_start_matches('/'))
+			.map_err(|e| log::warn!("Invalid ignore pattern {line}: {e}"))
+			.ok()?;
+		Some(IgnoreRule {
+			pattern,
+			base: base.to_string(),
+			negate,
+			dir_only,
+			anchored,
+		})
+	}
+
+	/// Whether the rule is for a path relative to the folder searched,
+	/// separated by `/`.
+	fn matches(&self, path: &str, is_dir: bool) -> bool {
+		if self.dir_only && !is_dir {
+			return false;
+		}
+		let relative = match self.base.is_empty() {
+			true => path,
+			false => match path.strip_prefix(&format!("{}/", self.base)) {
+				Some(relative) => relative,
+				None => return false,
+			},
+		};
+		let options = MatchOptions {
+			require_literal_separator: true,
+			..Default::default()
+		};
+		match self.anchored {
+			true => self.pattern.matches_with(relative, options),
+			false => {
+				let name = relative.rsplit('/').next().unwrap();
+				self.pattern.matches_with(name, options)
+			}
+		}
+	}
+}
+
+/// What is done with symlinks, and junctions on Windows, when searching
+/// and copying folders.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Symlinks {
+	/// Use what they point at, unless a folder links back into itself
+	#[default]
+	Follow,
+	/// Leave them out
+	Skip,
+	/// Stop with an error
+	Error,
+}
+
+/// The namespace of a pack file by its path separated by `/`, like minecraft
+/// for assets/minecraft/textures/block/stone.png. None for files outside
+/// assets, like pack.mcmeta.
+pub fn namespace(file: &str) -> Option<&str> {
+	let (namespace, _) = file.strip_prefix("assets/")?.split_once('/')?;
+	Some(namespace)
+}
+
+/// Whether a pack file is in one of the namespaces, every file is if there
+/// are none. Files outside assets are in every namespace.
+pub fn in_namespaces(file: &str, namespaces: &[String]) -> bool {
+	match namespace(file) {
+		Some(namespace) => namespaces.is_empty() || namespaces.iter().any(|n| n == namespace),
+		None => true,
+	}
+}
+
+/// Marks an include or exclude pattern as a regex instead of a glob.
+pub const REGEX_PREFIX: &str = "re:";
+
+/// A glob, or a regex when it starts with `re:`, matched against a path
+/// separated by `/`. Regexes match anywhere in the path unless they are
+/// anchored with ^ and $.
+#[derive(Clone, Debug)]
+pub enum PathPattern {
+	Glob(Pattern),
+	Regex(Regex),
+}
+
+impl PathPattern {
+	pub fn new(pattern: &str) -> PathPattern {
+		match pattern.strip_prefix(REGEX_PREFIX) {
+			Some(regex) => PathPattern::Regex(
+				Regex::new(regex).unwrap_or_else(|e| panic!("Invalid regex {regex}: {e}")),
+			),
+			None => PathPattern::Glob(
+				Pattern::new(pattern).unwrap_or_else(|e| panic!("Invalid glob {pattern}: {e}")),
+			),
+		}
+	}
+
+	pub fn matches(&self, path: &str) -> bool {
+		match self {
+			PathPattern::Glob(glob) => glob.matches(path),
+			PathPattern::Regex(regex) => regex.is_match(path),
+		}
+	}
+}
+
+/// Which files are found, on top of the extensions. Patterns are matched
+/// against paths relative to the folder searched, separated by `/`.
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+	/// Only files matching one of these, every file if empty
+	pub include: Vec<PathPattern>,
+	/// Files matching one of these are left out
+	pub exclude: Vec<PathPattern>,
+	/// Ignore files to read in every folder, like .gitignore
+	pub ignore_files: Vec<String>,
+	pub symlinks: Symlinks,
+	/// Only files in these namespaces of a pack, and the ones outside assets
+	pub namespaces: Vec<String>,
+}
+
+impl FileFilter {
+	pub fn new(
+		include: &[String], exclude: &[String], ignore_files: &[&str], symlinks: Symlinks,
+	) -> FileFilter {
+		let patterns = |patterns: &[String]| patterns.iter().map(|p| PathPattern::new(p)).collect();
+		FileFilter {
+			include: patterns(include),
+			exclude: patterns(exclude),
+			ignore_files: ignore_files.iter().map(|f| f.to_string()).collect(),
+			symlinks,
+			namespaces: vec![],
+		}
+	}
+
+	/// Whether a file passes the include and exclude patterns and is in the
+	/// namespaces.
+	pub fn includes(&self, path: &str) -> bool {
+		(self.include.is_empty() || self.include.iter().any(|p| p.matches(path)))
+			&& !self.exclude.iter().any(|p| p.matches(path))
+			&& in_namespaces(path, &self.namespaces)
+	}
+}
+
+/// The options of a tool for which files it works on.
+#[derive(Debug, Default, clap::Args)]
+pub struct FilterArgs {
+	#[arg(long, value_name = "GLOB")]
+	/// Only use files matching this glob, relative to the folder given, or
+	/// this regex if it starts with re:, repeat for more
+	pub include: Vec<String>,
+	#[arg(long, value_name = "GLOB")]
+	/// Leave out files matching this glob, or this regex if it starts with
+	/// re:, repeat for more
+	pub exclude: Vec<String>,
+	#[arg(long)]
+	/// Don't leave out files listed in .gitignore, .pdtignore and .packignore
+	/// files
+	pub no_ignore: bool,
+	#[arg(long, value_enum, default_value_t = Symlinks::Follow)]
+	/// What to do with symlinks in the folders
+	pub symlinks: Symlinks,
+	#[arg(long, value_name = "NAMESPACE")]
+	/// Only use the assets of this namespace, for a folder that is a pack,
+	/// repeat for more
+	pub namespace: Vec<String>,
+}
+
+impl FilterArgs {
+	pub fn filter(&self) -> FileFilter {
+		let ignore_files = match self.no_ignore {
+			true => &[][..],
+			false => &IGNORE_FILES[..],
+		};
+		FileFilter {
+			namespaces: self.namespace.clone(),
+			..FileFilter::new(&self.include, &self.exclude, ignore_files, self.symlinks)
+		}
+	}
 }
 
 pub fn find_files_in_dir(
 	dir: &str, recursive: bool, extensions: &Option<Vec<String>>,
 ) -> Vec<String> {
+	find_filtered_files_in_dir(dir, recursive, extensions, &FileFilter::default())
+}
+
+/// Like [find_files_in_dir], leaving out the files the filter does.
+pub fn find_filtered_files_in_dir(
+	dir: &str, recursive: bool, extensions: &Option<Vec<String>>, filter: &FileFilter,
+) -> Vec<String> {
+	let mut files = walk_dir(dir, "", recursive, extensions, filter, &[], &[]);
+	files.sort();
+	files
+}
+
+/// Finds the files in a folder, searching the folders in it in parallel.
+/// Ancestors are the real paths of the folders it is in, to tell when a
+/// symlink leads back into one of them.
+fn walk_dir(
+	dir: &str, relative: &str, recursive: bool, extensions: &Option<Vec<String>>,
+	filter: &FileFilter, inherited: &[IgnoreRule], ancestors: &[PathBuf],
+) -> Vec<String> {
+	let mut ancestors = ancestors.to_vec();
+	ancestors.extend(fs::canonicalize(long_path(dir)).ok());
+	let mut rules = inherited.to_vec();
+	for name in &filter.ignore_files {
+		let ignore_file = Utf8Path::new(dir).join(name);
+		if let Ok(data) = fs::read_to_string(long_path(ignore_file.as_str())) {
+			rules.extend(data.lines().filter_map(|l| IgnoreRule::parse(l, relative)));
+		}
+	}
 	let mut files = vec![];
-	let paths = Utf8Path::read_dir_utf8(dir.into()).unwrap();
-	for path in paths {
-		let path = path.unwrap().path().to_string();
-		let utf8_path = Utf8Path::new(&path);
-		if utf8_path.is_dir() && recursive {
-			files = [files, find_files_in_dir(&path, recursive, extensions)].concat();
-		} else if utf8_path.is_file() {
+	let mut dirs = vec![];
+	for (path, entry) in read_dir(dir) {
+		let name = entry.file_name().to_string_lossy().to_string();
+		// only symlinks need another stat to tell what they point at
+		let is_symlink = entry.file_type().map_or(true, |kind| kind.is_symlink());
+		let (is_dir, is_file) = match entry.file_type() {
+			Ok(kind) if !kind.is_symlink() => (kind.is_dir(), kind.is_file()),
+			_ => (entry.path().is_dir(), entry.path().is_file()),
+		};
+		if is_symlink {
+			match filter.symlinks {
+				Symlinks::Follow => (),
+				Symlinks::Skip => {
+					log::debug!("skipping symlink {path}");
+					continue;
+				}
+				Symlinks::Error => panic!("{path} is a symlink, which isn't allowed."),
+			}
+			if !is_dir && !is_file {
+				log::warn!("{path} is a symlink to nothing, skipping it");
+				continue;
+			}
+			let target = fs::canonicalize(entry.path()).ok();
+			if is_dir && target.as_ref().is_some_and(|t| ancestors.contains(t)) {
+				log::warn!("{path} links back to a folder it is in, not following it");
+				continue;
+			}
+		}
+		let file = match relative.is_empty() {
+			true => name.clone(),
+			false => format!("{relative}/{name}"),
+		};
+		let ignored = rules
+			.iter()
+			.rev()
+			.find(|rule| rule.matches(&file, is_dir))
+			.is_some_and(|rule| !rule.negate);
+		if ignored {
+			continue;
+		}
+		if is_dir && recursive {
+			dirs.push((path, file));
+		} else if is_file {
+			if filter.ignore_files.contains(&name) || !filter.includes(&file) {
+				continue;
+			}
 			match *extensions {
 				Some(ref extensions) => {
-					for ext in extensions {
-						if path.ends_with(ext) {
-							files.push(path.to_string());
-						}
+					if has_extension(&path, extensions) {
+						files.push(path.to_string());
 					}
 				}
 				None => {
@@ -51,6 +543,15 @@ pub fn find_files_in_dir(
 			}
 		}
 	}
+	let nested = dirs
+		.par_iter()
+		.map(|(path, file)| {
+			walk_dir(
+				path, file, recursive, extensions, filter, &rules, &ancestors,
+			)
+		})
+		.collect::<Vec<_>>();
+	files.extend(nested.into_iter().flatten());
 	files
 }
 
@@ -64,19 +565,20 @@ pub fn check_dir_ends_with_slash(dir: String) -> String {
 
 pub fn find_files_in_multiple_dirs(
 	dirs: Vec<String>, recursive: bool, extensions: Option<Vec<String>>, exclude_dir_name: bool,
-	announce: bool,
+	announce: bool, filter: &FileFilter,
 ) -> Vec<String> {
+	let filter = filter.clone();
 	let files = pdtthread::multithread(dirs, None, move |thread_num, dir| {
 		if announce {
-			println!("[thread {thread_num:02}] finding files in dir: {}", dir);
+			log::info!("[thread {thread_num:02}] finding files in dir: {}", dir);
 		}
 		let dir_files = if exclude_dir_name {
-			find_files_in_dir(&dir, recursive, &extensions)
+			find_filtered_files_in_dir(&dir, recursive, &extensions, &filter)
 				.iter()
-				.map(|f| f[dir.chars().count()..].to_string())
+				.map(|f| relative_path(&dir, f))
 				.collect()
 		} else {
-			find_files_in_dir(&dir, recursive, &extensions)
+			find_filtered_files_in_dir(&dir, recursive, &extensions, &filter)
 		};
 
 		Some(dir_files)
@@ -85,39 +587,46 @@ pub fn find_files_in_multiple_dirs(
 	files.into_iter().flatten().collect()
 }
 
+/// Files given and the files in the folders given. The filter is only used
+/// in the folders, files given are always kept.
 pub fn get_files_in_list(
 	items: Vec<String>, recursive: bool, extensions: Option<Vec<String>>, exclude_dir_name: bool,
-	announce: bool,
+	announce: bool, filter: &FileFilter,
 ) -> Vec<String> {
 	let files = items
 		.iter()
-		.filter(|f| Utf8Path::new(f).is_file() && f.ends_with(".png"))
+		.filter(|f| is_file(f))
+		.filter(|f| extensions.as_ref().is_none_or(|e| has_extension(f, e)))
 		.map(|f| f.to_string())
 		.collect::<Vec<String>>();
 	let dirs = items
 		.iter()
-		.filter(|f| Utf8Path::new(f).is_dir())
-		.map(|d| {
-			check_if_dir_exists(d);
-			check_dir_ends_with_slash(d.to_string())
-		})
+		.filter(|f| is_dir(f))
+		.map(|d| d.to_string())
 		.collect::<Vec<String>>();
-	find_files_in_multiple_dirs(dirs, recursive, extensions, exclude_dir_name, announce)
-		.extend_vec(files)
-		.sort_and_dedup_vec()
+	find_files_in_multiple_dirs(
+		dirs,
+		recursive,
+		extensions,
+		exclude_dir_name,
+		announce,
+		filter,
+	)
+	.extend_vec(files)
+	.sort_and_dedup_vec()
 }
 
 pub fn create_output_dir(name: &str) -> String {
 	if_dir_exists_remove_and_remake_it(name);
-	format!(".{SLASH}{name}")
+	Utf8Path::new(".").join(name).to_string()
 }
 
 pub fn copy_files_to_dir(folder: String, items: Vec<String>, content_only: bool) {
 	pdtthread::multithread(items, None, move |thread_num, item| {
-		println!("[thread {thread_num:02}] copying: {}", item);
-		if Utf8Path::new(&item).is_dir() {
+		log::debug!("[thread {thread_num:02}] copying: {}", item);
+		if is_dir(&item) {
 			copy_dir_to_dir(&folder, item, content_only);
-		} else if Utf8Path::new(&item).is_file() {
+		} else if is_file(&item) {
 			copy_file_to_dir(&folder, item);
 		} else {
 			panic!("Entry passed as file or folder not found.");
@@ -126,29 +635,60 @@ pub fn copy_files_to_dir(folder: String, items: Vec<String>, content_only: bool)
 	});
 }
 
-pub fn copy_dir_to_dir(output: &String, input: String, content_only: bool) {
-	let mut options = dir::CopyOptions::new();
-	options.content_only = content_only;
-	dir::copy(&input, output, &options).unwrap_or_else(|_| {
-		panic!(
-			"Failed to copy {} directory to {} directory.",
-			&input, &output
-		)
-	});
+pub fn copy_dir_to_dir(output: &str, input: String, content_only: bool) {
+	copy_dir_with_symlinks(output, &input, content_only, Symlinks::Follow);
+}
+
+/// Copies a folder into another one, or only what is in it, file by file in
+/// parallel, doing what the policy says with the symlinks in it.
+pub fn copy_dir_with_symlinks(output: &str, input: &str, content_only: bool, symlinks: Symlinks) {
+	if !is_dir(input) {
+		panic!("Failed to copy {input} directory to {output} directory.");
+	}
+	let output = match content_only {
+		true => Utf8PathBuf::from(output),
+		false => Utf8Path::new(output).join(file_name(input)),
+	};
+	fs::create_dir_all(long_path(output.as_str()))
+		.unwrap_or_else(|_| panic!("Failed to create {output} directory."));
+	let filter = FileFilter {
+		symlinks,
+		..Default::default()
+	};
+	find_filtered_files_in_dir(input, true, &None, &filter)
+		.par_iter()
+		.for_each(|file| {
+			copy_file(file, output.join(relative_path(input, file)).as_str());
+		});
 }
 
 pub fn copy_file_to_dir(output: &String, input: String) {
-	let options = dir::CopyOptions::new();
-	fs_extra::copy_items(&[&input], output, &options)
-		.unwrap_or_else(|_| panic!("Failed to copy {} file to {} directory.", &input, &output));
+	copy_file(
+		&input,
+		Utf8Path::new(output).join(file_name(&input)).as_str(),
+	);
+}
+
+/// Name of the file or folder a path leads to.
+fn file_name(path: &str) -> &str {
+	Utf8Path::new(path)
+		.file_name()
+		.unwrap_or_else(|| panic!("{path} has no file name."))
+}
+
+pub fn copy_file(input: &str, output: &str) {
+	if let Some(parent) = Utf8Path::new(output).parent() {
+		fs::create_dir_all(long_path(parent.as_str()))
+			.unwrap_or_else(|_| panic!("Failed to create {parent} directory."));
+	}
+	fs::copy(long_path(input), long_path(output))
+		.unwrap_or_else(|_| panic!("Failed to copy {input} to {output}."));
 }
 
 pub fn find_dirs_in_dir(dir: &str, recursive: bool) -> Vec<String> {
 	let mut dirs = vec![];
-	let paths = Utf8Path::read_dir_utf8(dir.into()).unwrap();
-	for path in paths {
-		let path = path.unwrap().path().to_string();
-		if Utf8Path::new(&path).is_dir() {
+	for (path, _) in read_dir(dir) {
+		if is_dir(&path) {
 			dirs.push(path.clone());
 			if recursive {
 				dirs = [dirs, find_dirs_in_dir(&path, recursive)].concat();
@@ -159,22 +699,20 @@ pub fn find_dirs_in_dir(dir: &str, recursive: bool) -> Vec<String> {
 }
 
 pub fn delete_files_in_dir(dir: &str, recursive: bool, extensions: &Option<Vec<String>>) {
-	let paths = Utf8Path::read_dir_utf8(dir.into()).unwrap();
-	for path in paths {
-		let path = path.unwrap().path().to_string();
-		if Utf8Path::new(&path).is_dir() && recursive {
+	for (path, _) in read_dir(dir) {
+		if is_dir(&path) && recursive {
 			delete_files_in_dir(&path, recursive, extensions);
-		} else if Utf8Path::new(&path).is_file() {
+		} else if is_file(&path) {
 			match *extensions {
 				Some(ref extensions) => {
 					for ext in extensions {
 						if path.ends_with(ext) {
-							fs::remove_file(path.clone())
+							fs::remove_file(long_path(&path))
 								.unwrap_or_else(|_| panic!("Failed to remove file: {}", path))
 						}
 					}
 				}
-				None => fs::remove_file(path.clone())
+				None => fs::remove_file(long_path(&path))
 					.unwrap_or_else(|_| panic!("Failed to remove file: {}", path)),
 			}
 		}
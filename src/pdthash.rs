@@ -1,14 +1,15 @@
+use sha1::Sha1;
 use sha2::{Digest, Sha256};
 use std::path::MAIN_SEPARATOR as SLASH;
 use std::{fs, io};
 
-use super::pdtthread;
+use super::{pdtfs, pdtthread};
 
 pub fn get_hashes(files: Vec<String>) -> Vec<(String, String)> {
 	pdtthread::multithread(files, None, |thread_num, file| {
-		println!(
+		log::debug!(
 			"[thread {thread_num:02}] getting hash of file: {}",
-			file.split(SLASH).last().unwrap()
+			file.split(SLASH).next_back().unwrap()
 		);
 
 		let hash = get_hash(&file, false);
@@ -18,11 +19,49 @@ pub fn get_hashes(files: Vec<String>) -> Vec<(String, String)> {
 
 pub fn get_hash(filename: &str, announce: bool) -> String {
 	if announce {
-		println!("Getting hash of file: {}", &filename);
+		log::info!("Getting hash of file: {}", &filename);
 	}
-	let mut file = fs::File::open(filename).expect("Failed to open file.");
+	let mut file = fs::File::open(pdtfs::long_path(filename)).expect("Failed to open file.");
 	let mut hasher = Sha256::new();
 	io::copy(&mut file, &mut hasher).expect("Failed to copy file into writer.");
 	let hash = hasher.finalize();
 	format!("{hash:x}")
 }
+
+/// SHA-1 of a file, which is what Minecraft checks server resource packs
+/// with.
+pub fn get_sha1_hash(filename: &str) -> String {
+	let mut file = fs::File::open(pdtfs::long_path(filename)).expect("Failed to open file.");
+	let mut hasher = Sha1::new();
+	io::copy(&mut file, &mut hasher).expect("Failed to copy file into writer.");
+	let hash = hasher.finalize();
+	format!("{hash:x}")
+}
+
+pub fn get_string_hash(string: &str) -> String {
+	get_bytes_hash(string.as_bytes())
+}
+
+/// HMAC-SHA256 of data, which S3 requests are signed with.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+	let mut block = [0u8; 64];
+	match key.len() > block.len() {
+		true => block[..32].copy_from_slice(&Sha256::digest(key)),
+		false => block[..key.len()].copy_from_slice(key),
+	}
+	let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<_>>();
+	let inner = Sha256::new()
+		.chain_update(pad(0x36))
+		.chain_update(data)
+		.finalize();
+	Sha256::new()
+		.chain_update(pad(0x5c))
+		.chain_update(inner)
+		.finalize()
+		.to_vec()
+}
+
+pub fn get_bytes_hash(bytes: &[u8]) -> String {
+	let hash = Sha256::digest(bytes);
+	format!("{hash:x}")
+}
@@ -0,0 +1,164 @@
+use super::pdtcmd::git;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "assets_manifest.json";
+
+/// [history] table of the build config, adds a manifest of where every file
+/// of a release came from to its zip, so a bug report about a texture can be
+/// traced to the commit and contributor that last changed it.
+///
+/// ```toml
+/// [history]
+/// manifest = "assets_manifest.json"
+/// report = true
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HistoryConfig {
+	/// Path in the pack of the manifest
+	#[serde(default = "default_manifest")]
+	pub manifest: String,
+	/// Write the manifest of every release next to its zip too, as
+	/// {release}.assets_manifest.json
+	#[serde(default)]
+	pub report: bool,
+}
+
+fn default_manifest() -> String {
+	MANIFEST_FILE.to_string()
+}
+
+/// Where a file of a release was read from.
+#[derive(Clone, Debug)]
+pub struct Source {
+	pub layer: String,
+	/// Path of the file, or of the zip and the file in it
+	pub path: String,
+	/// File git has the history of, the zip for files of zip layers
+	pub tracked: String,
+}
+
+/// The last commit that changed a file.
+#[derive(Clone, Debug, Serialize)]
+pub struct Revision {
+	pub commit: String,
+	pub author: String,
+	pub date: String,
+}
+
+/// An entry of the manifest. Files git doesn't know of, like ones that
+/// aren't committed yet, have no revision.
+#[derive(Clone, Debug, Serialize)]
+pub struct AssetHistory {
+	pub layer: String,
+	pub source: String,
+	#[serde(flatten)]
+	pub revision: Option<Revision>,
+}
+
+/// Last revisions of the files in the git repositories the layers are in,
+/// read from git once per repository.
+#[derive(Debug, Default)]
+pub struct History {
+	/// Repository of every folder asked about
+	repositories: BTreeMap<PathBuf, Option<PathBuf>>,
+	/// Last revision of every file ever committed, by repository and the
+	/// path in it
+	revisions: BTreeMap<PathBuf, BTreeMap<String, Revision>>,
+}
+
+impl History {
+	/// The last commit that changed a file.
+	pub fn revision(&mut self, path: &str) -> Option<Revision> {
+		let path = fs::canonicalize(path).ok()?;
+		let repository = self.repository(path.parent()?)?;
+		let file = path
+			.strip_prefix(&repository)
+			.ok()?
+			.to_str()?
+			.replace('\\', "/");
+		let revisions = self
+			.revisions
+			.entry(repository.clone())
+			.or_insert_with(|| last_revisions(&repository));
+		revisions.get(&file).cloned()
+	}
+
+	fn repository(&mut self, dir: &Path) -> Option<PathBuf> {
+		self.repositories
+			.entry(dir.to_path_buf())
+			.or_insert_with(|| {
+				let top = git(dir.to_str()?, &["rev-parse", "--show-toplevel"])?;
+				fs::canonicalize(top.trim()).ok()
+			})
+			.clone()
+	}
+
+	/// Where every file of a release came from, for the ones read from a
+	/// layer. Sources are by the path the files map points at.
+	pub fn manifest(
+		&mut self, files: &BTreeMap<String, String>, sources: &BTreeMap<String, Source>,
+	) -> BTreeMap<String, AssetHistory> {
+		files
+			.iter()
+			.filter_map(|(file, path)| {
+				let source = sources.get(path)?;
+				let history = AssetHistory {
+					layer: source.layer.clone(),
+					source: source.path.clone(),
+					revision: self.revision(&source.tracked),
+				};
+				Some((file.clone(), history))
+			})
+			.collect()
+	}
+}
+
+/// Goes through the log of a repository from the newest commit, keeping the
+/// first commit every file is seen in.
+fn last_revisions(repository: &Path) -> BTreeMap<String, Revision> {
+	let mut revisions = BTreeMap::new();
+	let dir = repository.to_str().unwrap_or(".");
+	let format = "--format=%x00%H%x09%an%x09%aI";
+	let Some(log) = git(
+		dir,
+		&[
+			"-c",
+			"core.quotepath=off",
+			"log",
+			format,
+			"--name-only",
+			"HEAD",
+		],
+	) else {
+		log::warn!("Failed to read the git log of {dir}, assets from it have no history");
+		return revisions;
+	};
+	for commit in log.split('\0').skip(1) {
+		let mut lines = commit.lines();
+		let Some(header) = lines.next() else {
+			continue;
+		};
+		let mut fields = header.splitn(3, '\t');
+		let (Some(commit), Some(author), Some(date)) =
+			(fields.next(), fields.next(), fields.next())
+		else {
+			continue;
+		};
+		let revision = Revision {
+			commit: commit.to_string(),
+			author: author.to_string(),
+			date: date.to_string(),
+		};
+		for file in lines.filter(|l| !l.is_empty()) {
+			revisions
+				.entry(file.to_string())
+				.or_insert_with(|| revision.clone());
+		}
+	}
+	log::debug!("{} files have history in {dir}", revisions.len());
+	revisions
+}
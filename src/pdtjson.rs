@@ -0,0 +1,729 @@
+use super::pdtfs::{self, FileFilter, FilterArgs};
+use super::pdtlog::{self, Problem};
+use super::pdtreport::{SizeReport, REPORT_FILE};
+use super::{pdtstdin, pdttrait::Vector};
+use clap::{value_parser, ArgAction, ValueEnum};
+use serde::ser::Serialize;
+use serde::Deserialize;
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+use std::fmt;
+use std::fs;
+use std::path::MAIN_SEPARATOR as SLASH;
+
+/// Help text for the json-format tool.
+pub fn about() -> String {
+	format!(
+		"Format or minify json and mcmeta files in given path(s).
+
+example: .{s}json-format .{s}assets
+example: .{s}json-format -s 2 .{s}assets{s}pack.mcmeta
+example: .{s}json-format -m .{s}assets
+example: .{s}json-format --sort-keys --check .{s}assets",
+		s = SLASH
+	)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct JsonArgs {
+	#[arg(short, long)]
+	/// Minify instead of formatting. Fields of models, blockstates,
+	/// animations and sounds.json set to what Minecraft uses when they
+	/// are missing are left out too
+	minify: bool,
+	#[arg(short, long, value_parser = value_parser!(u8).range(1..=16))]
+	/// Indent with this many spaces instead of tabs [1..16]
+	spaces: Option<u8>,
+	#[arg(long)]
+	/// Sort the keys of every object, so files diff the same however they
+	/// were saved
+	sort_keys: bool,
+	#[arg(short, long, value_enum)]
+	/// Whether files end in a newline, defaults to always when formatting
+	/// and never when minifying
+	newline: Option<Newline>,
+	#[arg(short, long)]
+	/// Don't write anything, fail if a file would change. For pre-commit
+	/// hooks and CI
+	check: bool,
+	#[arg(long)]
+	/// Also print problems as GitHub Actions annotations, so they show up
+	/// on the lines of a pull request
+	annotations: bool,
+	#[arg(long)]
+	/// Format files with // and /* */ comments too, removing the comments.
+	/// Otherwise they are left as they are, builds remove them
+	strip_comments: bool,
+	#[command(flatten)]
+	filter: FilterArgs,
+	#[arg(short, long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = REPORT_FILE)]
+	/// Write the sizes before and after to a json file [default:
+	/// optimize-report.json]
+	report: Option<String>,
+	/// List of files and folders to format
+	paths: Vec<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+#[derive(Copy, Clone)]
+pub enum Json {
+	Format,
+	Minify,
+}
+
+#[derive(Copy, Clone)]
+pub enum Indent {
+	Tab,
+	Space(u8),
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Newline {
+	/// End every file in a newline
+	Always,
+	/// Never end a file in a newline
+	Never,
+	/// Keep the newline at the end of files that have one
+	Keep,
+}
+
+/// Where a json file fails to parse and why.
+#[derive(Clone, Debug)]
+pub struct JsonError {
+	pub file: String,
+	pub line: usize,
+	pub column: usize,
+	pub message: String,
+	/// The line the error is on
+	pub snippet: String,
+}
+
+impl JsonError {
+	/// The line the error is on with a ^ under where it is.
+	pub fn pointer(&self) -> String {
+		let indent = self
+			.snippet
+			.chars()
+			.take(self.column.saturating_sub(1))
+			.map(|c| if c == '\t' { '\t' } else { ' ' })
+			.collect::<String>();
+		format!("    {}\n    {indent}^", self.snippet)
+	}
+}
+
+impl fmt::Display for JsonError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{}:{}:{}: {}\n{}",
+			self.file,
+			self.line,
+			self.column,
+			self.message,
+			self.pointer()
+		)
+	}
+}
+
+/// Parses json, allowing comments, saying where it is broken if it is.
+pub fn parse_json(file: &str, json: &str) -> Result<Value, JsonError> {
+	serde_json::from_str(&strip_comments(json)).map_err(|e| JsonError {
+		file: file.to_string(),
+		line: e.line(),
+		column: e.column(),
+		message: e.to_string().split(" at line ").next().unwrap().to_string(),
+		snippet: json
+			.lines()
+			.nth(e.line().saturating_sub(1))
+			.unwrap_or_default()
+			.to_string(),
+	})
+}
+
+/// How json files are written.
+#[derive(Copy, Clone)]
+pub struct JsonStyle {
+	pub json: Json,
+	pub indent: Indent,
+	pub sort_keys: bool,
+	/// None for the default of the json type
+	pub newline: Option<Newline>,
+}
+
+/// How the build writes json files, from the [json] table of the build
+/// config. The settings are the ones of json-format.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonConfig {
+	/// Minify instead of formatting, leaving out fields set to what
+	/// Minecraft uses when they are missing
+	#[serde(default)]
+	pub minify: bool,
+	/// Indent with this many spaces instead of tabs [1..16]
+	pub spaces: Option<u8>,
+	/// Sort the keys of every object
+	#[serde(default)]
+	pub sort_keys: bool,
+	/// always, never or keep, defaults to always when formatting and never
+	/// when minifying
+	pub newline: Option<Newline>,
+}
+
+impl JsonConfig {
+	pub fn style(&self) -> JsonStyle {
+		JsonStyle {
+			json: match self.minify {
+				true => Json::Minify,
+				false => Json::Format,
+			},
+			indent: match self.spaces {
+				Some(spaces @ 1..=16) => Indent::Space(spaces),
+				Some(spaces) => panic!("Can't indent json with {spaces} spaces, use 1 to 16."),
+				None => Indent::Tab,
+			},
+			sort_keys: self.sort_keys,
+			newline: self.newline,
+		}
+	}
+}
+
+pub fn run(args: JsonArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	pdtlog::set_annotations(args.annotations);
+	let paths = pdtstdin::get_stdin()
+		.unwrap_or_default()
+		.extend_vec(args.paths);
+	let style = JsonConfig {
+		minify: args.minify,
+		spaces: args.spaces,
+		sort_keys: args.sort_keys,
+		newline: args.newline,
+	}
+	.style();
+	let mut report = SizeReport::default();
+	let mut changed = vec![];
+	let mut errors = vec![];
+	let filter = args.filter.filter();
+	for path in paths {
+		changed.extend(json_formatter(
+			path,
+			&style,
+			args.check,
+			args.strip_comments,
+			&filter,
+			&mut report,
+			&mut errors,
+		));
+	}
+	for error in &errors {
+		log::error!("{error}");
+		Problem {
+			file: error.file.clone(),
+			line: error.line,
+			message: error.message.clone(),
+		}
+		.annotate(Some(&error.file));
+	}
+	if !errors.is_empty() {
+		panic!("Found {} invalid json files.", errors.len());
+	}
+	if args.check {
+		for file in &changed {
+			log::warn!("would change: {file}");
+			Problem {
+				file: file.clone(),
+				line: 0,
+				message: "isn't formatted".to_string(),
+			}
+			.annotate(Some(file));
+		}
+		if !changed.is_empty() {
+			panic!("{} files aren't formatted.", changed.len());
+		}
+		log::info!("Every file is formatted.");
+		return;
+	}
+	log::info!("{}", report.table());
+	if let Some(file) = args.report {
+		report.write_json(&file);
+	}
+}
+
+/// Formats or minifies the json and mcmeta files in a folder, or one file,
+/// returning the ones that changed. With check nothing is written. Files
+/// with comments are skipped unless strip_comments is set, since their
+/// comments would be lost. Files that aren't valid json are left as they
+/// are and added to errors.
+pub fn json_formatter(
+	dir_or_file: String, style: &JsonStyle, check: bool, strip_comments: bool, filter: &FileFilter,
+	report: &mut SizeReport, errors: &mut Vec<JsonError>,
+) -> Vec<String> {
+	let recursive = true;
+	let extensions = Some(vec![".json".to_string(), ".mcmeta".to_string()]);
+	let mut files = vec![];
+	if pdtfs::is_dir(&dir_or_file) {
+		files = pdtfs::find_filtered_files_in_dir(&dir_or_file, recursive, &extensions, filter);
+	} else if pdtfs::is_file(&dir_or_file) {
+		files.push(dir_or_file);
+	} else {
+		panic!("Entry passed as file or folder not found.");
+	}
+	let mut changed = vec![];
+	for file in files {
+		log::info!("formatting: {file}");
+		let original =
+			fs::read_to_string(pdtfs::long_path(&file)).expect("Failed to read file to string.");
+		let before = original.len() as u64;
+		if !strip_comments && has_comments(&original) {
+			log::info!("{file} has comments, leaving it as it is");
+			continue;
+		}
+		let value = match parse_json(&file, &original) {
+			Ok(value) => value,
+			Err(e) => {
+				errors.push(e);
+				continue;
+			}
+		};
+		if file.ends_with("pack.mcmeta") {
+			let problems = validate_mcmeta(&value);
+			if !problems.is_empty() {
+				panic!("Invalid {file}:\n  {}", problems.join("\n  "));
+			}
+		}
+		let json_data = style_json(&file.replace(SLASH, "/"), value, &original, style);
+		report.add(&file, before, json_data.len() as u64);
+		if json_data == original {
+			continue;
+		}
+		if !check {
+			fs::write(pdtfs::long_path(&file), json_data).expect("Failed to write json to file.");
+		}
+		changed.push(file);
+	}
+	changed
+}
+
+/// Sorts the keys of every object in a value.
+pub fn sort_keys(value: &mut Value) {
+	match value {
+		Value::Object(map) => {
+			let mut entries = std::mem::take(map).into_iter().collect::<Vec<_>>();
+			entries.sort_by(|a, b| a.0.cmp(&b.0));
+			for (key, mut value) in entries {
+				sort_keys(&mut value);
+				map.insert(key, value);
+			}
+		}
+		Value::Array(list) => list.iter_mut().for_each(sort_keys),
+		_ => (),
+	}
+}
+
+/// Writes the value of a json file in a style. When minifying, the fields
+/// the type of file has defaults for are left out if they are set to them.
+pub fn style_json(file: &str, mut value: Value, json: &str, style: &JsonStyle) -> String {
+	if let Json::Minify = style.json {
+		drop_defaults(file, &mut value);
+	}
+	if style.sort_keys {
+		sort_keys(&mut value);
+	}
+	let mut data = match style.json {
+		Json::Format => {
+			let indent = match style.indent {
+				Indent::Tab => "\t".to_string(),
+				Indent::Space(indent_number) => " ".repeat(indent_number as usize),
+			};
+			let mut writer = Vec::with_capacity(json.len());
+			let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+			let mut serialiser = Serializer::with_formatter(&mut writer, formatter);
+			value
+				.serialize(&mut serialiser)
+				.expect("Failed to serialize json data.");
+			String::from_utf8(writer).expect("Failed to convert utf8 to string.")
+		}
+		Json::Minify => serde_json::to_string(&value).expect("Failed to stringify json."),
+	};
+	let newline = match (style.newline, style.json) {
+		(Some(Newline::Always), _) | (None, Json::Format) => true,
+		(Some(Newline::Never), _) | (None, Json::Minify) => false,
+		(Some(Newline::Keep), _) => json.ends_with('\n'),
+	};
+	if newline {
+		data.push('\n');
+	}
+	data
+}
+
+pub fn format_json(json: &str, indent: &str) -> String {
+	let value = parse_to_value(json);
+	// json.len() is not ideal but its a _goodish_ default
+	let mut writer = Vec::with_capacity(json.len());
+	let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+	let mut serialiser = Serializer::with_formatter(&mut writer, formatter);
+	value
+		.serialize(&mut serialiser)
+		.expect("Failed to serialize json data.");
+	writer.push(b'\n');
+	String::from_utf8(writer).expect("Failed to convert utf8 to string.")
+}
+
+/// Removes the fields of an object that are set to these values, keeping
+/// the order of the rest. Numbers are compared by value, so 1.0 is 1.
+fn remove_values(object: &mut Value, defaults: &[(&str, Value)]) {
+	let is_default = |value: &Value, default: &Value| match (value, default) {
+		(Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+		(value, default) => value == default,
+	};
+	if let Value::Object(map) = object {
+		map.retain(|key, value| {
+			!defaults
+				.iter()
+				.any(|(k, default)| k == key && is_default(value, default))
+		});
+	}
+}
+
+/// The value of the field of an object, if it is its only field.
+fn only_field(object: &Value, key: &str) -> Option<Value> {
+	match object {
+		Value::Object(map) if map.len() == 1 => map.get(key).cloned(),
+		_ => None,
+	}
+}
+
+/// Leaves out fields Minecraft reads the same when they are missing, by the
+/// kind of file, a path separated by `/`. Fields models inherit from their
+/// parent are kept.
+pub fn drop_defaults(file: &str, value: &mut Value) {
+	if file.ends_with(".png.mcmeta") {
+		if let Some(animation) = value.get_mut("animation").filter(|a| a.is_object()) {
+			remove_values(animation, &[("interpolate", false.into())]);
+			let frametime = animation.get("frametime").cloned().unwrap_or(1.into());
+			if let Some(Value::Array(frames)) = animation.get_mut("frames") {
+				for frame in frames {
+					remove_values(frame, &[("time", frametime.clone())]);
+					if let Some(index) = only_field(frame, "index") {
+						*frame = index;
+					}
+				}
+			}
+			remove_values(animation, &[("frametime", 1.into())]);
+		}
+		if let Some(texture) = value.get_mut("texture") {
+			remove_values(texture, &[("blur", false.into()), ("clamp", false.into())]);
+		}
+	} else if file.contains("/models/") && file.ends_with(".json") {
+		if value.get("parent").is_none() {
+			remove_values(value, &[("ambientocclusion", true.into())]);
+		}
+		if let Some(Value::Object(display)) = value.get_mut("display") {
+			for transform in display.values_mut() {
+				remove_values(
+					transform,
+					&[
+						("rotation", serde_json::json!([0, 0, 0])),
+						("translation", serde_json::json!([0, 0, 0])),
+						("scale", serde_json::json!([1, 1, 1])),
+					],
+				);
+			}
+		}
+		let elements = value.get_mut("elements").and_then(Value::as_array_mut);
+		for element in elements.into_iter().flatten() {
+			remove_values(element, &[("shade", true.into())]);
+			if let Some(rotation) = element.get_mut("rotation") {
+				remove_values(rotation, &[("rescale", false.into())]);
+			}
+			if element["rotation"]["angle"] == 0 {
+				element
+					.as_object_mut()
+					.unwrap()
+					.retain(|key, _| key != "rotation");
+			}
+			let faces = element.get_mut("faces").and_then(Value::as_object_mut);
+			for face in faces.into_iter().flatten().map(|(_, face)| face) {
+				remove_values(face, &[("tintindex", (-1).into()), ("rotation", 0.into())]);
+			}
+		}
+	} else if file.contains("/blockstates/") && file.ends_with(".json") {
+		let defaults = [
+			("x", 0.into()),
+			("y", 0.into()),
+			("uvlock", false.into()),
+			("weight", 1.into()),
+		];
+		let drop = |model: &mut Value| match model {
+			Value::Array(list) => list.iter_mut().for_each(|m| remove_values(m, &defaults)),
+			model => remove_values(model, &defaults),
+		};
+		if let Some(Value::Object(variants)) = value.get_mut("variants") {
+			variants.values_mut().for_each(drop);
+		}
+		if let Some(Value::Array(parts)) = value.get_mut("multipart") {
+			parts
+				.iter_mut()
+				.filter_map(|p| p.get_mut("apply"))
+				.for_each(drop);
+		}
+	} else if file.ends_with("/sounds.json") || file == "sounds.json" {
+		let Value::Object(events) = value else {
+			return;
+		};
+		for event in events.values_mut() {
+			remove_values(event, &[("replace", false.into())]);
+			let sounds = event.get_mut("sounds").and_then(Value::as_array_mut);
+			for sound in sounds.into_iter().flatten() {
+				remove_values(
+					sound,
+					&[
+						("volume", 1.into()),
+						("pitch", 1.into()),
+						("weight", 1.into()),
+						("stream", false.into()),
+						("preload", false.into()),
+						("attenuation_distance", 16.into()),
+						("type", "file".into()),
+					],
+				);
+				if let Some(name) = only_field(sound, "name") {
+					*sound = name;
+				}
+			}
+		}
+	}
+}
+
+pub fn minify_json(json: &str) -> String {
+	let value = parse_to_value(json);
+	serde_json::to_string(&value).expect("Failed to stringify json.")
+}
+
+#[inline]
+fn parse_to_value(json: &str) -> Value {
+	serde_json::from_str(&strip_comments(json)).expect("Failed to parse json.")
+}
+
+/// Removes // and /* */ comments and trailing commas, which Minecraft
+/// doesn't accept, from json. Line breaks in comments are kept so errors
+/// point at the same lines.
+pub fn strip_comments(json: &str) -> String {
+	let chars = json.chars().collect::<Vec<_>>();
+	let mut stripped = String::with_capacity(json.len());
+	let mut i = 0;
+	let mut in_string = false;
+	while i < chars.len() {
+		let c = chars[i];
+		let next = chars.get(i + 1).copied();
+		if in_string {
+			stripped.push(c);
+			match c {
+				'\\' => {
+					stripped.extend(next);
+					i += 1;
+				}
+				'"' => in_string = false,
+				_ => (),
+			}
+			i += 1;
+			continue;
+		}
+		match (c, next) {
+			('"', _) => {
+				in_string = true;
+				stripped.push(c);
+			}
+			('/', Some('/')) => {
+				while i < chars.len() && chars[i] != '\n' {
+					i += 1;
+				}
+				continue;
+			}
+			('/', Some('*')) => {
+				i += 2;
+				while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+					if chars[i] == '\n' {
+						stripped.push('\n');
+					}
+					i += 1;
+				}
+				i += 2;
+				continue;
+			}
+			(',', _) => {
+				let rest = next_token(&chars[i + 1..]);
+				if !matches!(rest, Some('}' | ']')) {
+					stripped.push(c);
+				}
+			}
+			_ => stripped.push(c),
+		}
+		i += 1;
+	}
+	stripped
+}
+
+/// The first character that isn't whitespace or in a comment.
+fn next_token(chars: &[char]) -> Option<char> {
+	let mut i = 0;
+	while i < chars.len() {
+		match (chars[i], chars.get(i + 1)) {
+			(c, _) if c.is_whitespace() => i += 1,
+			('/', Some('/')) => {
+				while i < chars.len() && chars[i] != '\n' {
+					i += 1;
+				}
+			}
+			('/', Some('*')) => {
+				i += 2;
+				while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+					i += 1;
+				}
+				i += 2;
+			}
+			(c, _) => return Some(c),
+		}
+	}
+	None
+}
+
+/// Whether json has comments or trailing commas.
+pub fn has_comments(json: &str) -> bool {
+	strip_comments(json) != json
+}
+
+/// pack_format of every release since 1.6.1, later ones are accepted as
+/// they come.
+pub const PACK_FORMATS: [u64; 21] = [
+	1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 13, 15, 18, 22, 32, 34, 42, 46, 55, 63, 64,
+];
+
+/// Formatting codes that can follow a § in text.
+const FORMATTING_CODES: &str = "0123456789abcdefklmnor";
+
+/// Lowest and highest pack_format of a supported_formats or overlay
+/// formats value, a number, `[min, max]` or
+/// `{"min_inclusive": min, "max_inclusive": max}`.
+fn format_range(value: &Value) -> Result<(u64, u64), String> {
+	let range = match value {
+		Value::Number(n) => n.as_u64().map(|n| (n, n)),
+		Value::Array(list) if list.len() == 2 => list[0].as_u64().zip(list[1].as_u64()),
+		Value::Object(range) => range
+			.get("min_inclusive")
+			.and_then(Value::as_u64)
+			.zip(range.get("max_inclusive").and_then(Value::as_u64)),
+		_ => None,
+	};
+	match range {
+		Some((min, max)) if min > max => Err(format!("{min} is higher than {max}")),
+		Some(range) => Ok(range),
+		None => Err(format!(
+			"{value} should be a number, [min, max] or {{\"min_inclusive\": min, \"max_inclusive\": max}}"
+		)),
+	}
+}
+
+/// Problems with the text of a description, like unknown § codes.
+fn check_description_text(text: &str, problems: &mut Vec<String>) {
+	let mut chars = text.chars();
+	while let Some(c) = chars.next() {
+		if c != '§' {
+			continue;
+		}
+		match chars.next() {
+			Some(code) if FORMATTING_CODES.contains(code.to_ascii_lowercase()) => (),
+			Some(code) => problems.push(format!(
+				"pack.description has unknown formatting code §{code}"
+			)),
+			None => problems.push("pack.description ends in a § without a code".to_string()),
+		}
+	}
+	let lines = text.lines().count();
+	if lines > 2 {
+		problems.push(format!(
+			"pack.description has {lines} lines, only 2 are shown in the pack list"
+		));
+	}
+}
+
+/// Checks a pack.mcmeta has what Minecraft needs to list the pack, returning
+/// what is wrong with it.
+pub fn validate_mcmeta(mcmeta: &Value) -> Vec<String> {
+	let mut problems = vec![];
+	let Some(pack) = mcmeta.get("pack").filter(|p| p.is_object()) else {
+		return vec!["missing the pack object".to_string()];
+	};
+	match pack.get("pack_format").map(|f| (f, f.as_u64())) {
+		None => problems.push("pack.pack_format is missing".to_string()),
+		Some((_, Some(format))) => {
+			let newest = PACK_FORMATS[PACK_FORMATS.len() - 1];
+			if format <= newest && !PACK_FORMATS.contains(&format) {
+				let below = PACK_FORMATS.iter().rev().find(|f| **f < format);
+				let above = PACK_FORMATS.iter().find(|f| **f > format).unwrap();
+				let suggestion = match below {
+					Some(below) => format!("{below} or {above}"),
+					None => above.to_string(),
+				};
+				problems.push(format!(
+					"pack.pack_format {format} isn't used by any Minecraft release, did you mean {suggestion}?"
+				));
+			}
+		}
+		Some((value, None)) => problems.push(format!(
+			"pack.pack_format should be a positive whole number, found {value}"
+		)),
+	}
+	match pack.get("description") {
+		None => problems.push("pack.description is missing".to_string()),
+		Some(Value::String(text)) => check_description_text(text, &mut problems),
+		Some(Value::Object(_) | Value::Array(_)) => (),
+		Some(value) => problems.push(format!(
+			"pack.description should be text or a text component, found {value}"
+		)),
+	}
+	if let Some(formats) = pack.get("supported_formats") {
+		match format_range(formats) {
+			Ok((min, max)) => match pack["pack_format"].as_u64() {
+				Some(format) if !(min..=max).contains(&format) => problems.push(format!(
+					"pack.supported_formats {min}..{max} doesn't include pack_format {format}"
+				)),
+				_ => (),
+			},
+			Err(e) => problems.push(format!("pack.supported_formats: {e}")),
+		}
+	}
+	if let Some(overlays) = mcmeta.get("overlays") {
+		let Some(entries) = overlays.get("entries").and_then(Value::as_array) else {
+			problems.push("overlays.entries should be a list".to_string());
+			return problems;
+		};
+		for (i, entry) in entries.iter().enumerate() {
+			match entry.get("directory").and_then(Value::as_str) {
+				Some(dir)
+					if !dir.is_empty()
+						&& dir
+							.chars()
+							.all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-')) => {}
+				Some(dir) => problems.push(format!(
+					"overlays.entries[{i}].directory {dir} can only have a-z, 0-9, _ and -"
+				)),
+				None => problems.push(format!("overlays.entries[{i}].directory is missing")),
+			}
+			match entry.get("formats") {
+				Some(formats) => {
+					if let Err(e) = format_range(formats) {
+						problems.push(format!("overlays.entries[{i}].formats: {e}"));
+					}
+				}
+				None => problems.push(format!("overlays.entries[{i}].formats is missing")),
+			}
+		}
+	}
+	problems
+}
@@ -0,0 +1,185 @@
+use super::{pdtcmd, pdthash, pdtmojang, pdtzip};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+pub const LOCK_FILE: &str = "builder.lock";
+
+/// What builds use from outside of the repository, pinned to exact versions
+/// and hashes in builder.lock so every build of a commit is made of the same
+/// things. The first build writes it, later ones check against it.
+///
+/// ```toml
+/// [minecraft.latest]
+/// version = "1.21.4"
+/// sha1 = "a7e5a6024bfd3cd614625aa05629adf760020304"
+///
+/// [layers.seasons]
+/// path = "addons/seasons.zip"
+/// sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+///
+/// [layers.ponies]
+/// path = "vendor/ponies"
+/// revision = "3f2a1c9e0b7d4a6f8e5c2b1a0d9e8f7c6b5a4d3e"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lock {
+	/// Minecraft versions by what the build config calls them, like latest
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub minecraft: BTreeMap<String, LockedVersion>,
+	/// Layers that are zips or a git repository of their own, by name
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub layers: BTreeMap<String, LockedLayer>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedVersion {
+	pub version: String,
+	/// Of the client jar
+	pub sha1: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedLayer {
+	pub path: String,
+	/// Of the zip
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub sha256: Option<String>,
+	/// Commit checked out, for folders in a git repository of their own
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub revision: Option<String>,
+}
+
+impl fmt::Display for LockedLayer {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (&self.sha256, &self.revision) {
+			(Some(sha256), _) => write!(f, "{} with sha256 {sha256}", self.path),
+			(None, Some(revision)) => write!(f, "{} at {revision}", self.path),
+			(None, None) => write!(f, "{}", self.path),
+		}
+	}
+}
+
+/// Checks what a build uses against builder.lock, collecting what changed
+/// since it was written.
+pub struct Locker {
+	locked: Lock,
+	used: Lock,
+	/// Take the changes into the lock instead of failing
+	update: bool,
+	changes: Vec<String>,
+}
+
+impl Locker {
+	pub fn new(update: bool) -> Locker {
+		let locked = match fs::read_to_string(LOCK_FILE) {
+			Ok(data) => {
+				toml::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse {LOCK_FILE}: {e}"))
+			}
+			Err(_) => Lock::default(),
+		};
+		Locker {
+			locked,
+			used: Lock::default(),
+			update,
+			changes: vec![],
+		}
+	}
+
+	/// The exact Minecraft version to use for a version of the build
+	/// config, the locked one unless updating. The client jar has to have
+	/// the sha1 it was locked with.
+	pub fn minecraft(&mut self, version: &str) -> String {
+		if let Some(used) = self.used.minecraft.get(version) {
+			return used.version.clone();
+		}
+		let locked = self.locked.minecraft.get(version).cloned();
+		let pinned = match (&locked, self.update) {
+			(Some(locked), false) => locked.version.as_str(),
+			_ => version,
+		};
+		let (id, sha1) = pdtmojang::client_jar_sha1(pinned);
+		if let Some(locked) = locked {
+			if locked.version != id {
+				self.change(format!(
+					"Minecraft {version} is {id}, locked {}",
+					locked.version
+				));
+			} else if locked.sha1 != sha1 {
+				self.change(format!(
+					"Minecraft {id} client jar has sha1 {sha1}, locked {}",
+					locked.sha1
+				));
+			}
+		}
+		let used = LockedVersion {
+			version: id.clone(),
+			sha1,
+		};
+		self.used.minecraft.insert(version.to_string(), used);
+		id
+	}
+
+	/// Checks the zip or git revision of a layer. Folders in this
+	/// repository aren't locked, they are part of the commit already.
+	pub fn layer(&mut self, name: &str, path: &str) {
+		let found = if pdtzip::is_zip(path) {
+			LockedLayer {
+				path: path.to_string(),
+				sha256: Some(pdthash::get_hash(path, false)),
+				revision: None,
+			}
+		} else {
+			let top = |dir: &str| pdtcmd::git(dir, &["rev-parse", "--show-toplevel"]);
+			let revision = match top(path) {
+				Some(repo) if Some(&repo) != top(".").as_ref() => {
+					pdtcmd::git(path, &["rev-parse", "HEAD"])
+				}
+				_ => None,
+			};
+			let Some(revision) = revision else {
+				return;
+			};
+			LockedLayer {
+				path: path.to_string(),
+				sha256: None,
+				revision: Some(revision.trim().to_string()),
+			}
+		};
+		if let Some(locked) = self.locked.layers.get(name) {
+			if *locked != found {
+				self.change(format!("Layer {name} is {found}, locked {locked}"));
+			}
+		}
+		self.used.layers.insert(name.to_string(), found);
+	}
+
+	fn change(&mut self, change: String) {
+		match self.update {
+			true => log::info!("{change}, updating {LOCK_FILE}"),
+			false => self.changes.push(change),
+		}
+	}
+
+	/// Fails if anything changed since builder.lock was written, otherwise
+	/// writes what the build uses to it.
+	pub fn finish(self) {
+		if !self.changes.is_empty() {
+			panic!(
+				"{} inputs don't match {LOCK_FILE}:\n  {}\nBuild with --update-lock if the changes are expected.",
+				self.changes.len(),
+				self.changes.join("\n  ")
+			);
+		}
+		if self.used != self.locked {
+			let data = toml::to_string(&self.used).unwrap();
+			fs::write(LOCK_FILE, data)
+				.unwrap_or_else(|_| panic!("Failed to write file: {LOCK_FILE}"));
+			log::info!("Wrote {LOCK_FILE}");
+		}
+	}
+}
@@ -0,0 +1,274 @@
+use clap::ValueEnum;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+static JSON_PROGRESS: AtomicBool = AtomicBool::new(false);
+static ANNOTATIONS: AtomicBool = AtomicBool::new(false);
+
+/// Something wrong in a pack file, found before it is seen in-game.
+#[derive(Clone, Debug)]
+pub struct Problem {
+	/// Path in the pack separated by `/`, or on disk for tools that work on
+	/// folders of files
+	pub file: String,
+	/// Line of the file the problem is on, 0 when it is about all of it
+	pub line: usize,
+	pub message: String,
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.line {
+			0 => write!(f, "{}: {}", self.file, self.message),
+			line => write!(f, "{}:{line}: {}", self.file, self.message),
+		}
+	}
+}
+
+impl Problem {
+	/// Prints the problem as a GitHub Actions error annotation with
+	/// annotations on, on its line of source, the file on disk it was found
+	/// in. Files in temporary folders, like extracted zips and staged files,
+	/// can't be pointed at, so the annotation only has the message then.
+	/// Like [print], it goes to stderr with json progress.
+	pub fn annotate(&self, source: Option<&str>) {
+		if !ANNOTATIONS.load(Ordering::Relaxed) {
+			return;
+		}
+		let temp = std::env::temp_dir();
+		let source = source.filter(|s| !Path::new(s).starts_with(&temp));
+		let mut out: Box<dyn Write> = match json_progress() {
+			true => Box::new(std::io::stderr().lock()),
+			false => Box::new(std::io::stdout().lock()),
+		};
+		let _ = match source {
+			Some(source) => {
+				let line = match self.line {
+					0 => String::new(),
+					line => format!(",line={line}"),
+				};
+				writeln!(
+					out,
+					"::error file={}{line}::{}",
+					escape_property(&repo_path(source)),
+					escape_data(&self.message)
+				)
+			}
+			None => writeln!(out, "::error::{}", escape_data(&self.to_string())),
+		};
+	}
+}
+
+/// Turns GitHub Actions annotations for problems on or off.
+pub fn set_annotations(on: bool) {
+	ANNOTATIONS.store(on, Ordering::Relaxed);
+}
+
+/// A path relative to the folder the tool runs in, which is the repository
+/// in CI, separated by `/`.
+fn repo_path(path: &str) -> String {
+	let path = Path::new(path);
+	let relative = std::env::current_dir()
+		.ok()
+		.and_then(|cwd| path.strip_prefix(cwd).ok().map(PathBuf::from))
+		.unwrap_or_else(|| path.to_path_buf());
+	let relative = relative.to_string_lossy().replace('\\', "/");
+	relative.trim_start_matches("./").to_string()
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(text: &str) -> String {
+	text.replace('%', "%25")
+		.replace('\r', "%0D")
+		.replace('\n', "%0A")
+}
+
+/// Escapes a property of a workflow command, like file.
+fn escape_property(text: &str) -> String {
+	escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// How a tool shows its progress.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Progress {
+	/// Log lines and progress bars
+	#[default]
+	Text,
+	/// A json event per line on stdout, the log lines go to stderr
+	Json,
+}
+
+/// Sets how progress is shown. With json, panics are sent as error events
+/// too, so a failed run always ends with one.
+pub fn set_progress(progress: Progress) {
+	JSON_PROGRESS.store(progress == Progress::Json, Ordering::Relaxed);
+	if progress == Progress::Json {
+		let default_hook = std::panic::take_hook();
+		std::panic::set_hook(Box::new(move |info| {
+			let payload = info.payload();
+			let message = payload
+				.downcast_ref::<String>()
+				.map(String::as_str)
+				.or_else(|| payload.downcast_ref::<&str>().copied())
+				.unwrap_or("panicked");
+			event("error", json!({ "message": message, "fatal": true }));
+			default_hook(info);
+		}));
+	}
+}
+
+pub fn json_progress() -> bool {
+	JSON_PROGRESS.load(Ordering::Relaxed)
+}
+
+/// Writes an event to stdout as a line of json with progress set to json,
+/// like `{"event":"file_done","stage":"optimize","file":"a.png"}`.
+pub fn event(kind: &str, fields: Value) {
+	if !json_progress() {
+		return;
+	}
+	let mut event = json!({ "event": kind });
+	if let (Some(event), Value::Object(fields)) = (event.as_object_mut(), fields) {
+		event.extend(fields);
+	}
+	let mut stdout = std::io::stdout().lock();
+	let _ = writeln!(stdout, "{event}");
+	let _ = stdout.flush();
+}
+
+/// Prints a line that is shown even with --quiet, like the summary at the
+/// end of a run. It goes to stderr with json progress.
+pub fn print(line: &str) {
+	match json_progress() {
+		true => eprintln!("{line}"),
+		false => println!("{line}"),
+	}
+}
+
+/// A file a stage is done with.
+pub fn file_done(stage: &str, file: &str) {
+	event("file_done", json!({ "stage": stage, "file": file }));
+}
+
+/// A step of a tool, with a stage_start event when it starts and a
+/// stage_end event when it is dropped.
+pub struct Stage {
+	name: String,
+	start: Instant,
+}
+
+pub fn stage(name: &str) -> Stage {
+	event("stage_start", json!({ "stage": name }));
+	Stage {
+		name: name.to_string(),
+		start: Instant::now(),
+	}
+}
+
+impl Drop for Stage {
+	fn drop(&mut self) {
+		let seconds = self.start.elapsed().as_secs_f64();
+		event(
+			"stage_end",
+			json!({ "stage": self.name, "seconds": seconds, "ok": !std::thread::panicking() }),
+		);
+	}
+}
+
+/// Logs info and below to stdout and warnings and errors to stderr, so CI
+/// can capture problems separately from regular output. Info from
+/// dependencies, like oxipng describing every file, is only shown with
+/// --verbose.
+struct Logger;
+
+/// Whether a record comes from this crate or the running tool, rather than
+/// a dependency.
+fn is_own_target(target: &str) -> bool {
+	static TOOL: OnceLock<String> = OnceLock::new();
+	let tool = TOOL.get_or_init(|| {
+		std::env::current_exe()
+			.ok()
+			.and_then(|exe| {
+				exe.file_stem()
+					.map(|s| s.to_string_lossy().replace('-', "_"))
+			})
+			.unwrap_or_default()
+	});
+	let crate_name = target.split("::").next().unwrap_or_default();
+	crate_name == "pdt" || crate_name == tool
+}
+
+impl Log for Logger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= log::max_level()
+	}
+
+	fn log(&self, record: &Record) {
+		let level = match record.level() {
+			Level::Info if !is_own_target(record.target()) => Level::Debug,
+			level => level,
+		};
+		if level > log::max_level() {
+			return;
+		}
+		match level {
+			Level::Error => {
+				ERRORS.fetch_add(1, Ordering::Relaxed);
+				event("error", json!({ "message": record.args().to_string() }));
+				eprintln!("error: {}", record.args())
+			}
+			Level::Warn => {
+				WARNINGS.fetch_add(1, Ordering::Relaxed);
+				event("warning", json!({ "message": record.args().to_string() }));
+				eprintln!("warning: {}", record.args())
+			}
+			// stdout only has the events with json progress
+			Level::Info if json_progress() => eprintln!("{}", record.args()),
+			Level::Info => println!("{}", record.args()),
+			Level::Debug | Level::Trace if json_progress() => {
+				eprintln!("[{}] {}", record.target(), record.args())
+			}
+			Level::Debug | Level::Trace => {
+				println!("[{}] {}", record.target(), record.args())
+			}
+		}
+	}
+
+	fn flush(&self) {
+		let _ = std::io::stdout().flush();
+		let _ = std::io::stderr().flush();
+	}
+}
+
+pub fn level_filter(verbose: u8, quiet: bool) -> LevelFilter {
+	match (quiet, verbose) {
+		(true, _) => LevelFilter::Warn,
+		(false, 0) => LevelFilter::Info,
+		(false, 1) => LevelFilter::Debug,
+		(false, _) => LevelFilter::Trace,
+	}
+}
+
+/// How many warnings and errors were logged so far.
+pub fn counts() -> (usize, usize) {
+	(
+		WARNINGS.load(Ordering::Relaxed),
+		ERRORS.load(Ordering::Relaxed),
+	)
+}
+
+/// Installs the default logger. Does nothing if a logger was already set,
+/// so library consumers can install their own first.
+pub fn init(verbose: u8, quiet: bool) {
+	if log::set_logger(&Logger).is_ok() {
+		log::set_max_level(level_filter(verbose, quiet));
+	}
+}
@@ -0,0 +1,275 @@
+use super::pdttrait;
+use deltae::*;
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Cursor;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// A color of a block, how far it is from the rest of the block on average
+/// first.
+pub type Pixel = (f64, Rgba<u8>, LabValue);
+/// A block by name, with its colors from the most to the least
+/// representative.
+pub type Block = (String, Vec<Pixel>);
+
+pub fn rgb_to_lab(pixel: (u32, u32, Rgba<u8>)) -> LabValue {
+	let rgb = [[pixel.2 .0[0], pixel.2 .0[1], pixel.2 .0[2]]];
+	let lab = lab::rgbs_to_labs(&rgb)[0];
+	LabValue {
+		l: lab.l,
+		a: lab.a,
+		b: lab.b,
+	}
+}
+
+/// The colors of a block image sorted from the most representative, the
+/// one closest to every other pixel, or why it can't be in a palette.
+pub fn block_colors(name: &str, img: &RgbaImage, opaque: bool) -> Result<Block, String> {
+	let pixels = img
+		.enumerate_pixels()
+		.map(|(x, y, p)| (x, y, *p))
+		.collect::<Vec<_>>();
+	let pixel_count = pixels.len() as f64;
+	let mut distances: Vec<Pixel> = vec![];
+
+	for pixel in &pixels {
+		let lab = rgb_to_lab(*pixel);
+		let mut distance: f64 = 0.0;
+		for sub_pixel in &pixels {
+			if sub_pixel.2 .0[3] < 255 && opaque {
+				return Err(format!("{name}: not fully opaque"));
+			}
+			let sub_lab = rgb_to_lab(*sub_pixel);
+			let delta: f64 = DeltaE::new(lab, sub_lab, DE2000).value().to_owned().into();
+			distance += delta;
+		}
+		distance /= pixel_count;
+		distances.push((distance, pixel.2, lab));
+	}
+
+	distances.sort_by(|a, b| pdttrait::compare(&a.0, &b.0));
+	distances.dedup();
+
+	match distances.is_empty() {
+		true => Err(format!("{name}: no pixels")),
+		false => Ok((name.to_string(), distances)),
+	}
+}
+
+/// The palette blocks along with whatever the matcher precomputes from them.
+pub struct Palette {
+	pub blocks: Vec<Block>,
+	/// Block images, tinted and scaled to the output block size
+	pub images: HashMap<String, RgbaImage>,
+	/// Average alpha of every block
+	alphas: Vec<f64>,
+	#[cfg(feature = "simd")]
+	lanes: super::pdtcolor::LabLanes,
+}
+
+impl Palette {
+	pub fn new(blocks: Vec<(Block, RgbaImage)>) -> Palette {
+		let (blocks, images): (Vec<_>, Vec<_>) = blocks.into_iter().unzip();
+		let alphas = images
+			.iter()
+			.map(|i| {
+				let total = i.pixels().map(|p| f64::from(p.0[3])).sum::<f64>();
+				total / f64::from(i.width() * i.height())
+			})
+			.collect();
+		let images = blocks.iter().map(|b| b.0.clone()).zip(images).collect();
+		Palette {
+			images,
+			alphas,
+			#[cfg(feature = "simd")]
+			lanes: super::pdtcolor::LabLanes::new(
+				&blocks.iter().map(|b| b.1[0].2).collect::<Vec<_>>(),
+			),
+			blocks,
+		}
+	}
+
+	/// Distance from the color to the main color of every block.
+	#[cfg(feature = "simd")]
+	fn deltas(&self, lab: LabValue) -> Vec<f64> {
+		self.lanes
+			.delta_e_2000(lab)
+			.into_iter()
+			.map(f64::from)
+			.collect()
+	}
+
+	#[cfg(not(feature = "simd"))]
+	fn deltas(&self, lab: LabValue) -> Vec<f64> {
+		self.blocks
+			.iter()
+			.map(|block| *DeltaE::new(lab, block.1[0].2, DE2000).value() as f64)
+			.collect()
+	}
+}
+
+/// Finds the blocks closest to the color, and to the alpha if one is given.
+/// With a tolerance, every block within it of the closest one is returned.
+pub fn match_pixel(
+	lab: LabValue, alpha: Option<u8>, tolerance: f64, palette: &Palette,
+) -> Vec<String> {
+	let mut deltas = palette.deltas(lab);
+	if let Some(alpha) = alpha {
+		// scaled so fully opaque against fully transparent counts as much as
+		// black against white
+		for (delta, block_alpha) in deltas.iter_mut().zip(&palette.alphas) {
+			*delta += (f64::from(alpha) - block_alpha).abs() / 255.0 * 100.0;
+		}
+	}
+	let closest = deltas.iter().copied().fold(f64::INFINITY, f64::min);
+	let matches = palette
+		.blocks
+		.iter()
+		.zip(deltas)
+		.filter(|(_, delta)| *delta <= closest + tolerance)
+		.map(|(block, _)| block)
+		.collect::<Vec<_>>();
+	if tolerance > 0.0 && matches.len() > 1 {
+		return matches.into_iter().map(|b| b.0.clone()).collect();
+	}
+
+	match matches.len() {
+		0 => get_closest_match(lab, palette.blocks.clone()),
+		1 => vec![matches[0].0.clone()],
+		_ => get_closest_match(lab, matches.into_iter().cloned().collect()),
+	}
+}
+
+/// Finds the blocks whose main color is closest, comparing tied blocks on
+/// their next most representative colors. Returns every block still tied
+/// after that.
+fn get_closest_match(lab: LabValue, blocks: Vec<Block>) -> Vec<String> {
+	let mut new_blocks = blocks
+		.into_iter()
+		.map(|block| {
+			let delta = *DeltaE::new(lab, block.1[0].2, DE2000).value() as f64;
+			(delta, block)
+		})
+		.collect::<Vec<_>>();
+	new_blocks.sort_by(|a, b| pdttrait::compare(&a.0, &b.0));
+
+	let matches = new_blocks
+		.iter()
+		.filter(|item| item.0 == new_blocks[0].0)
+		.collect::<Vec<_>>();
+
+	if matches.len() == 1 {
+		return vec![matches[0].1 .0.clone()];
+	}
+	let next_blocks = matches
+		.iter()
+		.filter(|block| block.1 .1.len() > 1)
+		.map(|block| (block.1 .0.to_string(), block.1 .1[1..].to_vec()))
+		.collect::<Vec<_>>();
+	if next_blocks.len() > 1 {
+		return get_closest_match(lab, next_blocks);
+	}
+	matches.iter().map(|block| block.1 .0.clone()).collect()
+}
+
+/// Draws a block image over the pixel at x, y of a texture blockified into
+/// output. With an alpha, every pixel of the block gets it instead of its
+/// own.
+pub fn paste_block(output: &mut RgbaImage, x: u32, y: u32, block: &RgbaImage, alpha: Option<u8>) {
+	let block_pixels = block.width();
+	for (block_x, block_y, block_pixel) in block.enumerate_pixels() {
+		let rgba = match alpha {
+			Some(a) => [block_pixel.0[0], block_pixel.0[1], block_pixel.0[2], a],
+			None => block_pixel.0,
+		};
+		output.put_pixel(
+			x * block_pixels + block_x,
+			y * block_pixels + block_y,
+			Rgba(rgba),
+		);
+	}
+}
+
+/// The blockify matching on images in memory, for running it where there
+/// are no files, like the preview on the website. Built to WebAssembly with
+/// the wasm feature:
+///
+/// ```sh
+/// cargo rustc --lib --crate-type cdylib --target wasm32-unknown-unknown --release --no-default-features --features wasm
+/// ```
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Blockifier {
+	block_pixels: u32,
+	blocks: Vec<(Block, RgbaImage)>,
+	/// Made from the blocks once the first image is blockified
+	palette: Option<Palette>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Blockifier {
+	/// Makes every pixel of the images this many pixels of a block.
+	#[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+	pub fn new(block_pixels: u32) -> Blockifier {
+		Blockifier {
+			block_pixels,
+			blocks: vec![],
+			palette: None,
+		}
+	}
+
+	/// Adds a block to the palette from an encoded square image, like a
+	/// png, left out if it isn't fully opaque.
+	#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = addBlock))]
+	pub fn add_block(&mut self, name: &str, bytes: &[u8]) -> Result<(), String> {
+		let img = image::load_from_memory(bytes)
+			.map_err(|e| format!("{name}: {e}"))?
+			.to_rgba8();
+		let (width, height) = img.dimensions();
+		if width != height {
+			return Err(format!("{name}: {width}x{height} is not square"));
+		}
+		let pixels = self.block_pixels;
+		let img = match width.cmp(&pixels) {
+			Ordering::Equal => img,
+			Ordering::Greater => imageops::resize(&img, pixels, pixels, FilterType::Triangle),
+			Ordering::Less => imageops::resize(&img, pixels, pixels, FilterType::Nearest),
+		};
+		let block = block_colors(name, &img, true)?;
+		self.blocks.push((block, img));
+		self.palette = None;
+		Ok(())
+	}
+
+	/// Blockifies an encoded image, returning it as a png. Transparent
+	/// pixels stay transparent.
+	pub fn blockify(&mut self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+		if self.blocks.is_empty() {
+			return Err("No blocks in the palette".to_string());
+		}
+		let palette = self
+			.palette
+			.get_or_insert_with(|| Palette::new(self.blocks.clone()));
+		let img = image::load_from_memory(bytes)
+			.map_err(|e| e.to_string())?
+			.to_rgba8();
+		let pixels = self.block_pixels;
+		let mut output = RgbaImage::new(img.width() * pixels, img.height() * pixels);
+		for (x, y, pixel) in img.enumerate_pixels() {
+			let a = pixel.0[3];
+			if a == 0 {
+				continue;
+			}
+			let lab = rgb_to_lab((x, y, *pixel));
+			let block = &match_pixel(lab, None, 0.0, palette)[0];
+			paste_block(&mut output, x, y, &palette.images[block], Some(a));
+		}
+		let mut png = Cursor::new(vec![]);
+		output
+			.write_to(&mut png, ImageFormat::Png)
+			.map_err(|e| e.to_string())?;
+		Ok(png.into_inner())
+	}
+}
@@ -0,0 +1,331 @@
+use super::{pdtfs, pdthash, pdtlog, pdtzip};
+use camino::Utf8Path;
+use clap::ArgAction;
+use glob::Pattern;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{MAIN_SEPARATOR as SLASH, MAIN_SEPARATOR_STR};
+
+/// Decides which pack a conflicting file is taken from, from the rules file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+	/// Glob of the paths in the pack the rule is for, like
+	/// `assets/minecraft/textures/block/*`
+	pub files: String,
+	/// Name of the pack to take them from
+	pub pack: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+	#[serde(default, rename = "rule")]
+	pub rules: Vec<Rule>,
+}
+
+impl Rules {
+	pub fn load(path: &str) -> Rules {
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read rules: {path}"));
+		toml::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse rules {path}: {e}"))
+	}
+
+	/// Pack the first rule matching a file picks.
+	fn pack_for(&self, file: &str) -> Option<&str> {
+		self.rules.iter().find_map(|rule| {
+			let pattern = Pattern::new(&rule.files)
+				.unwrap_or_else(|e| panic!("Invalid rule pattern {}: {e}", rule.files));
+			pattern.matches(file).then_some(rule.pack.as_str())
+		})
+	}
+}
+
+/// A pack to merge, by name, with its files.
+pub struct Pack {
+	pub name: String,
+	pub files: BTreeMap<String, String>,
+}
+
+/// Json files Minecraft itself combines across the packs it has loaded, so
+/// they can be combined here too instead of one replacing the other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mergeable {
+	Sounds,
+	Lang,
+	Atlas,
+	Font,
+}
+
+fn mergeable(file: &str) -> Option<Mergeable> {
+	let parts = file.split('/').collect::<Vec<_>>();
+	if !file.ends_with(".json") {
+		return None;
+	}
+	match parts[..] {
+		["assets", _, "sounds.json"] => Some(Mergeable::Sounds),
+		["assets", _, "lang", _] => Some(Mergeable::Lang),
+		["assets", _, "atlases", ..] => Some(Mergeable::Atlas),
+		["assets", _, "font", ..] => Some(Mergeable::Font),
+		_ => None,
+	}
+}
+
+/// Adds the items of a list that it doesn't have yet.
+fn extend_unique(list: &mut Vec<Value>, items: &[Value]) {
+	for item in items {
+		if !list.contains(item) {
+			list.push(item.clone());
+		}
+	}
+}
+
+/// Combines versions of a json file, lowest priority first, the way
+/// Minecraft does: sounds of the same event are added together unless one
+/// replaces them, translations and everything else of later packs win,
+/// atlas sources are added together and font providers of later packs come
+/// first. None if one isn't a json object.
+fn merge_json(kind: Mergeable, versions: &[Value]) -> Option<Value> {
+	let mut merged = Map::new();
+	for version in versions {
+		let version = version.as_object()?;
+		match kind {
+			Mergeable::Sounds => {
+				for (event, entry) in version {
+					let replaces = entry["replace"] == true;
+					match merged.get_mut(event) {
+						Some(Value::Object(old)) if !replaces => {
+							let sounds = entry["sounds"].as_array().cloned().unwrap_or_default();
+							let mut list = old
+								.get("sounds")
+								.and_then(Value::as_array)
+								.cloned()
+								.unwrap_or_default();
+							extend_unique(&mut list, &sounds);
+							for (key, value) in entry.as_object()? {
+								old.insert(key.clone(), value.clone());
+							}
+							old.insert("sounds".to_string(), Value::Array(list));
+						}
+						_ => {
+							merged.insert(event.clone(), entry.clone());
+						}
+					}
+				}
+			}
+			Mergeable::Lang => merged.extend(version.clone()),
+			Mergeable::Atlas | Mergeable::Font => {
+				let key = match kind {
+					Mergeable::Atlas => "sources",
+					_ => "providers",
+				};
+				let items = version[key].as_array().cloned().unwrap_or_default();
+				let mut list = match merged.remove(key) {
+					Some(Value::Array(list)) => list,
+					_ => vec![],
+				};
+				if kind == Mergeable::Font {
+					let mut later = vec![];
+					extend_unique(&mut later, &items);
+					extend_unique(&mut later, &list);
+					list = later;
+				} else {
+					extend_unique(&mut list, &items);
+				}
+				merged.extend(version.clone());
+				merged.insert(key.to_string(), Value::Array(list));
+			}
+		}
+	}
+	Some(Value::Object(merged))
+}
+
+/// How conflicts without a rule are settled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolve {
+	/// The last pack with the file wins
+	Priority,
+	/// Ask which pack to take it from
+	Prompt,
+}
+
+/// Asks which of the packs a file should come from.
+fn prompt(file: &str, packs: &[&str]) -> usize {
+	let stdin = io::stdin();
+	loop {
+		println!("{file} is different in:");
+		for (i, pack) in packs.iter().enumerate() {
+			println!("  {}) {pack}", i + 1);
+		}
+		print!(
+			"Take it from [1-{}, default {}]: ",
+			packs.len(),
+			packs.len()
+		);
+		io::stdout().flush().unwrap();
+		let mut line = String::new();
+		if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+			return packs.len() - 1;
+		}
+		match line.trim() {
+			"" => return packs.len() - 1,
+			choice => match choice.parse::<usize>() {
+				Ok(n) if (1..=packs.len()).contains(&n) => return n - 1,
+				_ => println!("Pick a number from 1 to {}.", packs.len()),
+			},
+		}
+	}
+}
+
+/// Lays packs over each other, later ones over earlier ones. Files that are
+/// the same in every pack that has them are taken as they are, json files
+/// Minecraft combines are combined into the staging directory and other
+/// conflicts are settled by the rules and then by resolve. Returns the
+/// merged files and how many conflicts there were.
+pub fn merge_packs(
+	packs: &[Pack], rules: &Rules, resolve: Resolve, staging: &str,
+) -> (BTreeMap<String, String>, usize) {
+	let mut owners: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+	for pack in packs {
+		for (file, path) in &pack.files {
+			owners
+				.entry(file.as_str())
+				.or_default()
+				.push((pack.name.as_str(), path.as_str()));
+		}
+	}
+	let mut files = BTreeMap::new();
+	let mut conflicts = 0;
+	for (file, versions) in owners {
+		let hashes = versions
+			.iter()
+			.map(|(_, path)| pdthash::get_hash(path, false))
+			.collect::<Vec<_>>();
+		if hashes.iter().all(|h| *h == hashes[0]) {
+			files.insert(file.to_string(), versions[0].1.to_string());
+			continue;
+		}
+		if let Some(kind) = mergeable(file) {
+			let json = versions
+				.iter()
+				.map(|(_, path)| {
+					let data = fs::read_to_string(path).ok()?;
+					serde_json::from_str::<Value>(&data).ok()
+				})
+				.collect::<Option<Vec<_>>>();
+			if let Some(merged) = json.and_then(|json| merge_json(kind, &json)) {
+				log::debug!("combining {file}");
+				let staged = format!("{staging}{SLASH}{}", file.replace('/', MAIN_SEPARATOR_STR));
+				let data = serde_json::to_string_pretty(&merged).unwrap() + "\n";
+				let dir = Utf8Path::new(&staged).parent().unwrap();
+				fs::create_dir_all(dir)
+					.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+				fs::write(&staged, data)
+					.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+				files.insert(file.to_string(), staged);
+				continue;
+			}
+			log::warn!("{file} isn't valid json in every pack, not combining it");
+		}
+		conflicts += 1;
+		let names = versions.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+		let chosen = match rules.pack_for(file) {
+			Some(pack) => match names.iter().position(|name| *name == pack) {
+				Some(i) => i,
+				None => {
+					log::warn!("Rule for {file} picks {pack}, which doesn't have it");
+					names.len() - 1
+				}
+			},
+			None if resolve == Resolve::Prompt => prompt(file, &names),
+			None => names.len() - 1,
+		};
+		log::info!("{file}: taking the one from {}", names[chosen]);
+		files.insert(file.to_string(), versions[chosen].1.to_string());
+	}
+	(files, conflicts)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct MergeArgs {
+	/// Pack folders or zips, later ones over earlier ones
+	#[arg(required = true, num_args = 2..)]
+	packs: Vec<String>,
+	#[arg(short, long)]
+	/// Folder or zip to write the merged pack to
+	output: String,
+	#[arg(short, long)]
+	/// Toml file with [[rule]] tables of files and the pack to take them
+	/// from
+	rules: Option<String>,
+	#[arg(short, long)]
+	/// Ask which pack to take conflicting files from that no rule is for,
+	/// instead of the last one
+	prompt: bool,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+pub fn run(args: MergeArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let rules = args.rules.as_deref().map(Rules::load).unwrap_or_default();
+	let resolve = match args.prompt {
+		true => Resolve::Prompt,
+		false => Resolve::Priority,
+	};
+	let mut temp_dirs = vec![];
+	let packs = args
+		.packs
+		.iter()
+		.map(|path| {
+			if !pdtzip::is_zip(path) && !Utf8Path::new(path).is_dir() {
+				panic!("Pack not found: {path}");
+			}
+			let name = path.trim_end_matches(SLASH).rsplit(SLASH).next().unwrap();
+			Pack {
+				name: name.trim_end_matches(".zip").to_string(),
+				files: pdtzip::pack_files(path, &mut temp_dirs),
+			}
+		})
+		.collect::<Vec<_>>();
+	for rule in &rules.rules {
+		if !packs.iter().any(|p| p.name == rule.pack) {
+			panic!("Rule for {} picks unknown pack {}", rule.files, rule.pack);
+		}
+	}
+	let staging = format!(
+		"{}{SLASH}pdt_merge_{}",
+		std::env::temp_dir().display(),
+		std::process::id()
+	);
+	let (files, conflicts) = merge_packs(&packs, &rules, resolve, &staging);
+	match args.output.to_lowercase().ends_with(".zip") {
+		true => pdtzip::zip_files(&files, &args.output, None),
+		false => {
+			let staged = pdtfs::StagedDir::new(&args.output);
+			for (file, path) in &files {
+				let output = format!(
+					"{}{SLASH}{}",
+					staged.dir,
+					file.replace('/', MAIN_SEPARATOR_STR)
+				);
+				pdtfs::copy_file(path, &output);
+			}
+			staged.commit();
+		}
+	}
+	drop(temp_dirs);
+	pdtfs::if_dir_exists_remove_it(&staging);
+	log::info!(
+		"Merged {} packs into {} with {conflicts} conflicts",
+		packs.len(),
+		args.output
+	);
+}
@@ -0,0 +1,292 @@
+use super::{pdtfs, pdtzip};
+use camino::Utf8Path;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::MAIN_SEPARATOR as SLASH;
+
+pub const VERSION_MANIFEST: &str =
+	"https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+pub const BLOCK_TEXTURES: &str = "assets/minecraft/textures/block";
+/// Where the files of the asset index are downloaded from, by hash
+pub const RESOURCES: &str = "https://resources.download.minecraft.net";
+
+#[derive(Deserialize)]
+struct VersionManifest {
+	latest: LatestVersions,
+	versions: Vec<VersionEntry>,
+}
+
+#[derive(Deserialize)]
+struct LatestVersions {
+	release: String,
+	snapshot: String,
+}
+
+#[derive(Deserialize)]
+struct VersionEntry {
+	id: String,
+	url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionInfo {
+	downloads: VersionDownloads,
+	asset_index: Download,
+}
+
+#[derive(Deserialize)]
+struct AssetIndex {
+	objects: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct VersionDownloads {
+	client: Download,
+}
+
+#[derive(Deserialize)]
+struct Download {
+	sha1: String,
+	url: String,
+}
+
+/// Where downloads are kept between runs, `PDT_CACHE_DIR` if set, otherwise
+/// a pdt folder in the user's cache directory.
+pub fn cache_dir() -> String {
+	if let Ok(dir) = std::env::var("PDT_CACHE_DIR") {
+		return dir;
+	}
+	let base = std::env::var("XDG_CACHE_HOME")
+		.or_else(|_| std::env::var("LOCALAPPDATA"))
+		.or_else(|_| std::env::var("HOME").map(|home| format!("{home}{SLASH}.cache")))
+		.unwrap_or_else(|_| std::env::temp_dir().display().to_string());
+	format!("{base}{SLASH}pdt")
+}
+
+fn get_json<T: for<'de> Deserialize<'de>>(url: &str) -> T {
+	reqwest::blocking::get(url)
+		.and_then(|r| r.error_for_status())
+		.and_then(|r| r.json())
+		.unwrap_or_else(|e| panic!("Failed to download {url}: {e}"))
+}
+
+fn get_bytes(url: &str) -> Vec<u8> {
+	reqwest::blocking::get(url)
+		.and_then(|r| r.error_for_status())
+		.and_then(|r| r.bytes())
+		.unwrap_or_else(|e| panic!("Failed to download {url}: {e}"))
+		.to_vec()
+}
+
+fn get_sha1(data: &[u8]) -> String {
+	format!("{:x}", Sha1::digest(data))
+}
+
+/// The resolved id and the download info of a version, `latest` or
+/// `snapshot` for the newest ones.
+fn get_version_info(version: &str) -> (String, VersionInfo) {
+	log::info!("Looking up Minecraft {version}");
+	let manifest: VersionManifest = get_json(VERSION_MANIFEST);
+	let id = match version {
+		"latest" => manifest.latest.release.clone(),
+		"snapshot" => manifest.latest.snapshot.clone(),
+		_ => version.to_string(),
+	};
+	let entry = manifest
+		.versions
+		.iter()
+		.find(|v| v.id == id)
+		.unwrap_or_else(|| panic!("Minecraft version {id} not found in the version manifest."));
+	(id, get_json(&entry.url))
+}
+
+/// Downloads the client jar of a version, `latest` or `snapshot` for the
+/// newest ones, unless it is cached already. Returns the resolved version
+/// and the path of the jar.
+pub fn get_client_jar(version: &str) -> (String, String) {
+	let (id, info) = get_version_info(version);
+	let jar = download_client_jar(&id, &info.downloads.client);
+	(id, jar)
+}
+
+/// The sha1 of the client jar of a version, `latest` or `snapshot` for the
+/// newest ones, from the cached jar when there is one and otherwise from the
+/// version manifest. Returns the resolved version too.
+pub fn client_jar_sha1(version: &str) -> (String, String) {
+	if !["latest", "snapshot"].contains(&version) {
+		let jar = format!(
+			"{}{SLASH}minecraft{SLASH}{version}{SLASH}client.jar",
+			cache_dir()
+		);
+		if let Ok(data) = fs::read(&jar) {
+			return (version.to_string(), get_sha1(&data));
+		}
+	}
+	let (id, info) = get_version_info(version);
+	(id, info.downloads.client.sha1)
+}
+
+fn download_client_jar(id: &str, client: &Download) -> String {
+	let dir = format!("{}{SLASH}minecraft{SLASH}{id}", cache_dir());
+	let jar = format!("{dir}{SLASH}client.jar");
+	if let Ok(data) = fs::read(&jar) {
+		if get_sha1(&data) == client.sha1 {
+			log::debug!("using cached {jar}");
+			return jar;
+		}
+	}
+
+	log::info!("Downloading Minecraft {id} client jar");
+	let data = reqwest::blocking::get(&client.url)
+		.and_then(|r| r.error_for_status())
+		.and_then(|r| r.bytes())
+		.unwrap_or_else(|e| panic!("Failed to download {}: {e}", client.url));
+	if get_sha1(&data) != client.sha1 {
+		panic!("Downloaded Minecraft {id} client jar does not match its sha1.");
+	}
+	fs::create_dir_all(&dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	fs::write(&jar, data).unwrap_or_else(|_| panic!("Failed to write file: {jar}"));
+	jar
+}
+
+/// Returns a directory with the vanilla block textures of a version,
+/// extracting them from the client jar the first time. Cached versions are
+/// used without going online, except for `latest` and `snapshot`.
+pub fn get_block_textures(version: &str) -> String {
+	let block_dir = |id: &str| format!("{}{SLASH}minecraft{SLASH}{id}{SLASH}block", cache_dir());
+	let is_extracted = |dir: &str| Utf8Path::new(&format!("{dir}{SLASH}.extracted")).is_file();
+	if !["latest", "snapshot"].contains(&version) && is_extracted(&block_dir(version)) {
+		return block_dir(version);
+	}
+
+	let (id, jar) = get_client_jar(version);
+	let dir = block_dir(&id);
+	let done = format!("{dir}{SLASH}.extracted");
+	if is_extracted(&dir) {
+		return dir;
+	}
+	pdtfs::if_dir_exists_remove_and_remake_it(&dir);
+	pdtzip::extract_zip(&jar, &dir, Some(BLOCK_TEXTURES));
+	fs::write(&done, "").unwrap_or_else(|_| panic!("Failed to write file: {done}"));
+	dir
+}
+
+/// Paths of every vanilla asset of a version, like
+/// `assets/minecraft/models/block/stone.json`.
+pub fn get_asset_names(version: &str) -> BTreeSet<String> {
+	let (_, jar) = get_client_jar(version);
+	pdtzip::zip_entries(&jar)
+		.into_iter()
+		.filter(|name| name.starts_with("assets/"))
+		.collect()
+}
+
+/// Paths of every vanilla asset of a version, with the sounds and other
+/// files of the asset index on top of the ones in the client jar. The list
+/// is cached, and cached versions are used without going online, except for
+/// `latest` and `snapshot`. Returns the resolved version too.
+pub fn get_all_asset_names(version: &str) -> (String, BTreeSet<String>) {
+	let list = |id: &str| {
+		format!(
+			"{}{SLASH}minecraft{SLASH}{id}{SLASH}assets.txt",
+			cache_dir()
+		)
+	};
+	if !["latest", "snapshot"].contains(&version) {
+		if let Ok(data) = fs::read_to_string(list(version)) {
+			log::debug!("using cached {}", list(version));
+			return (
+				version.to_string(),
+				data.lines().map(String::from).collect(),
+			);
+		}
+	}
+
+	let (id, info) = get_version_info(version);
+	let index = get_asset_index(&id, &info.asset_index);
+	let jar = download_client_jar(&id, &info.downloads.client);
+	let mut names = pdtzip::zip_entries(&jar)
+		.into_iter()
+		.filter(|name| name.starts_with("assets/"))
+		.collect::<BTreeSet<_>>();
+	names.extend(
+		index
+			.objects
+			.into_keys()
+			.map(|name| format!("assets/{name}")),
+	);
+	let path = list(&id);
+	let data = names.iter().map(|n| format!("{n}\n")).collect::<String>();
+	fs::write(&path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+	(id, names)
+}
+
+fn get_asset_index(id: &str, index: &Download) -> AssetIndex {
+	log::info!("Downloading Minecraft {id} asset index");
+	let data = get_bytes(&index.url);
+	if get_sha1(&data) != index.sha1 {
+		panic!("Downloaded Minecraft {id} asset index does not match its sha1.");
+	}
+	serde_json::from_slice(&data)
+		.unwrap_or_else(|e| panic!("Failed to parse Minecraft {id} asset index: {e}"))
+}
+
+/// The sound events of a version with their subtitle keys, from the
+/// sounds.json of its asset index. Cached like the asset list.
+pub fn get_sound_events(version: &str) -> BTreeMap<String, Option<String>> {
+	let cached = |id: &str| {
+		format!(
+			"{}{SLASH}minecraft{SLASH}{id}{SLASH}sound_events.json",
+			cache_dir()
+		)
+	};
+	if !["latest", "snapshot"].contains(&version) {
+		if let Ok(data) = fs::read_to_string(cached(version)) {
+			if let Ok(events) = serde_json::from_str(&data) {
+				log::debug!("using cached {}", cached(version));
+				return events;
+			}
+		}
+	}
+
+	let (id, info) = get_version_info(version);
+	let index = get_asset_index(&id, &info.asset_index);
+	let hash = index
+		.objects
+		.get("minecraft/sounds.json")
+		.and_then(|object| object["hash"].as_str())
+		.unwrap_or_else(|| panic!("Minecraft {id} asset index has no sounds.json."));
+	log::info!("Downloading Minecraft {id} sounds.json");
+	let data = get_bytes(&format!("{RESOURCES}/{}/{hash}", &hash[..2]));
+	if get_sha1(&data) != hash {
+		panic!("Downloaded Minecraft {id} sounds.json does not match its sha1.");
+	}
+	let sounds: serde_json::Value = serde_json::from_slice(&data)
+		.unwrap_or_else(|e| panic!("Failed to parse Minecraft {id} sounds.json: {e}"));
+	let events = sounds
+		.as_object()
+		.into_iter()
+		.flatten()
+		.map(|(event, entry)| (event.clone(), entry["subtitle"].as_str().map(String::from)))
+		.collect::<BTreeMap<_, _>>();
+	let path = cached(&id);
+	if let Some(dir) = Utf8Path::new(&path).parent() {
+		fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	}
+	let data = serde_json::to_string(&events).unwrap();
+	fs::write(&path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+	events
+}
+
+/// Translation keys of the en_us.json of a version.
+pub fn get_lang_keys(version: &str) -> BTreeSet<String> {
+	let (id, jar) = get_client_jar(version);
+	let data = pdtzip::read_zip_entry(&jar, "assets/minecraft/lang/en_us.json")
+		.unwrap_or_else(|| panic!("Minecraft {id} client jar has no en_us.json."));
+	let lang: BTreeMap<String, serde_json::Value> = serde_json::from_slice(&data)
+		.unwrap_or_else(|e| panic!("Failed to parse Minecraft {id} en_us.json: {e}"));
+	lang.into_keys().collect()
+}
@@ -0,0 +1,559 @@
+use super::pdtfs::{get_files_in_list, FileFilter, FilterArgs};
+use super::pdtlog::Progress;
+use super::pdtreport::{SizeReport, Stats, REPORT_FILE};
+use super::{pdtcolor, pdtfs, pdthash, pdtlog, pdtstdin, pdtthread, pdttrait::Vector};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::ValueEnum;
+use clap::{value_parser, ArgAction};
+use color_quant::NeuQuant;
+use glob::Pattern;
+use oxipng::{optimize, InFile, Options, OutFile};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::num::NonZeroU8;
+use std::path::MAIN_SEPARATOR as SLASH;
+
+/// Help text for the optimize-images tool.
+pub fn about() -> String {
+	format!(
+		"Optimize all png files in given path(s).
+
+example: .{s}optimize-images -l4 a.png .{s}assets{s}
+example: .{s}optimize-images --strip safe a.png b.png
+example: .{s}optimize-images --zopfli=30 .{s}assets{s}
+example: .{s}optimize-images --fast .{s}assets{s}
+example: .{s}optimize-images --quantize --quantize-files '**/block/*' .{s}assets{s}
+example: .{s}optimize-images --out-dir .{s}optimized .{s}assets{s}",
+		s = SLASH
+	)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct OptimizeArgs {
+	/// Compression level [possible values: 0 - 6]
+	#[arg(short, long, default_value_t = 6, value_parser = value_parser!(u8).range(0..=6))]
+	level: u8,
+	/// Which ancillary chunks to strip, Minecraft ignores all of them
+	#[arg(short, long, value_enum, default_value_t = Strip::All)]
+	strip: Strip,
+	/// Ancillary chunk to keep even when stripping, like gAMA, repeat for
+	/// more
+	#[arg(short, long, value_name = "CHUNK")]
+	keep: Vec<String>,
+	/// Try to fix errors when decoding the input file
+	#[arg(long, short)]
+	fix: bool,
+	/// Enable Adam7 interlacing
+	#[arg(long, short)]
+	interlace: bool,
+	/// Compress with Zopfli, doing this many iterations [default: 15].
+	/// Slower, but gives smaller files for releases
+	#[arg(short, long, num_args = 0..=1, require_equals = true, default_missing_value = "15", value_parser = value_parser!(u8).range(1..))]
+	zopfli: Option<u8>,
+	/// Only try the quickest compression, for test builds. Ignores the level
+	#[arg(long, conflicts_with = "zopfli")]
+	fast: bool,
+	/// Reduce pngs to this many colors [default: 256], losing some detail
+	/// but making them much smaller
+	#[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "256", value_parser = value_parser!(u16).range(2..=256))]
+	quantize: Option<u16>,
+	/// Only quantize pngs matching this glob, repeat for more
+	#[arg(long, value_name = "GLOB", requires = "quantize")]
+	quantize_files: Vec<String>,
+	/// Never quantize pngs matching this glob, on top of colormaps and
+	/// gradients, repeat for more
+	#[arg(long, value_name = "GLOB", requires = "quantize")]
+	quantize_exclude: Vec<String>,
+	/// Write the sizes before and after to a json file [default:
+	/// optimize-report.json]
+	#[arg(short, long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = REPORT_FILE)]
+	report: Option<String>,
+	/// Optimize pngs again even if they were already optimized with the
+	/// same settings
+	#[arg(long)]
+	force: bool,
+	/// Convert tga and jpeg images to pngs of the same name and optimize
+	/// those, the originals are removed. Minecraft only loads pngs
+	#[arg(short, long)]
+	convert: bool,
+	/// Write the optimized images to this folder, keeping their paths,
+	/// instead of replacing them
+	#[arg(long, value_name = "DIR")]
+	out_dir: Option<String>,
+	/// Write the totals of the run to a json file, for CI
+	#[arg(long, value_name = "FILE")]
+	stats_json: Option<String>,
+	/// Show progress as log lines, or as json events on stdout
+	#[arg(long, value_enum, default_value_t = Progress::Text)]
+	progress: Progress,
+	#[command(flatten)]
+	filter: FilterArgs,
+	/// List of files and folders to optimize
+	paths: Vec<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+pub fn run(args: OptimizeArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	pdtlog::set_progress(args.progress);
+	pdtthread::cancel_on_ctrl_c();
+	let mut stats = Stats::new("optimize");
+	let paths = pdtstdin::get_stdin()
+		.unwrap_or_default()
+		.extend_vec(args.paths);
+	let mut options = png_options(args.level, &args.strip, &args.keep, args.zopfli, args.fast);
+	options.fix_errors = args.fix;
+	options.interlace = match args.interlace {
+		true => Some(oxipng::Interlacing::Adam7),
+		false => None,
+	};
+	let quantize = args.quantize.map(|colors| {
+		let mut rule = Quantize {
+			colors,
+			exclude: default_quantize_exclude(),
+			..Default::default()
+		};
+		if !args.quantize_files.is_empty() {
+			rule.files = args.quantize_files;
+		}
+		rule.exclude.extend(args.quantize_exclude);
+		rule
+	});
+	let filter = args.filter.filter();
+	let report = optimize_images(
+		&options,
+		quantize.as_ref(),
+		paths,
+		&filter,
+		args.force,
+		args.convert,
+		args.out_dir.as_deref(),
+	);
+	log::info!("{}", report.table());
+	if let Some(file) = args.report {
+		report.write_json(&file);
+	}
+	stats.files = report.files.len();
+	stats.bytes_saved = report.saved();
+	stats.finish(args.stats_json.as_deref());
+}
+
+/// Pngs to reduce to a palette before optimizing them, which is lossy.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quantize {
+	/// Globs of the pngs to quantize
+	#[serde(default = "default_quantize_files")]
+	pub files: Vec<String>,
+	/// Globs of pngs to leave alone even if files matches them
+	#[serde(default = "default_quantize_exclude")]
+	pub exclude: Vec<String>,
+	/// Most colors a quantized png has [2..256]
+	#[serde(default = "default_quantize_colors")]
+	pub colors: u16,
+}
+
+fn default_quantize_files() -> Vec<String> {
+	vec!["**".to_string()]
+}
+
+/// Colormaps and gradients are sampled by color, so losing colors in them
+/// shows up everywhere they are used.
+fn default_quantize_exclude() -> Vec<String> {
+	["**/colormap/**", "**/*gradient*"]
+		.map(|glob| glob.to_string())
+		.to_vec()
+}
+
+fn default_quantize_colors() -> u16 {
+	256
+}
+
+impl Default for Quantize {
+	fn default() -> Self {
+		Quantize {
+			files: default_quantize_files(),
+			exclude: default_quantize_exclude(),
+			colors: default_quantize_colors(),
+		}
+	}
+}
+
+impl Quantize {
+	/// Whether a png, by its path with `/` separators, should be quantized.
+	pub fn matches(&self, file: &str) -> bool {
+		let matches = |globs: &[String]| {
+			globs.iter().any(|glob| {
+				Pattern::new(glob)
+					.unwrap_or_else(|e| panic!("Invalid quantize glob {glob}: {e}"))
+					.matches(file)
+			})
+		};
+		matches(&self.files) && !matches(&self.exclude)
+	}
+}
+
+/// Rewrites a 16-bit png, or one with an ICC profile, as an 8-bit sRGB
+/// png, returns whether it was changed. Has to run before the chunks are
+/// stripped, which would lose the profile and shift the colors.
+pub fn to_srgb8(path: &str) -> bool {
+	let data = fs::read(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+	// the bit depth in IHDR
+	let deep = data.len() > 24 && data[24] == 16;
+	if !deep && !chunk_sizes(&data).contains_key("iCCP") {
+		return false;
+	}
+	pdtcolor::open_srgb(path)
+		.unwrap_or_else(|e| panic!("Failed to read image {path}: {e}"))
+		.save(path)
+		.unwrap_or_else(|e| panic!("Failed to write image {path}: {e}"));
+	true
+}
+
+/// Rewrites an rgb or rgba png as grayscale or with a palette when that
+/// keeps every pixel the same, returns what it became. Pngs with 256 colors
+/// or less get a palette, pngs with only grays become grayscale.
+pub fn reduce_color_type(path: &str) -> Option<&'static str> {
+	let data = fs::read(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+	// the color type in IHDR, 2 is rgb and 6 rgba
+	if data.len() < 26 || ![2, 6].contains(&data[25]) {
+		return None;
+	}
+	let image = image::load_from_memory(&data)
+		.unwrap_or_else(|e| panic!("Failed to read image {path}: {e}"))
+		.into_rgba8();
+	let opaque = image.pixels().all(|p| p.0[3] == 255);
+	let gray = image.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]);
+	let mut palette: Vec<[u8; 4]> = vec![];
+	let mut indexes = BTreeMap::new();
+	let mut indexed = Some(Vec::with_capacity(image.len() / 4));
+	for pixel in image.pixels() {
+		let Some(list) = &mut indexed else {
+			break;
+		};
+		let index = *indexes.entry(pixel.0).or_insert_with(|| {
+			palette.push(pixel.0);
+			palette.len() - 1
+		});
+		match index < 256 {
+			true => list.push(index as u8),
+			false => indexed = None,
+		}
+	}
+	let (color, pixels, what) = match (gray, opaque, indexed) {
+		(true, true, _) => {
+			let pixels = image.pixels().map(|p| p.0[0]).collect();
+			(png::ColorType::Grayscale, pixels, "grayscale")
+		}
+		(_, _, Some(pixels)) => (png::ColorType::Indexed, pixels, "palette"),
+		(true, false, None) => {
+			let pixels = image.pixels().flat_map(|p| [p.0[0], p.0[3]]).collect();
+			(
+				png::ColorType::GrayscaleAlpha,
+				pixels,
+				"grayscale with alpha",
+			)
+		}
+		_ => return None,
+	};
+	let mut output = vec![];
+	let mut encoder = png::Encoder::new(&mut output, image.width(), image.height());
+	encoder.set_color(color);
+	encoder.set_depth(png::BitDepth::Eight);
+	if color == png::ColorType::Indexed {
+		encoder.set_palette(
+			palette
+				.iter()
+				.flat_map(|c| [c[0], c[1], c[2]])
+				.collect::<Vec<_>>(),
+		);
+		let mut alpha = palette.iter().map(|c| c[3]).collect::<Vec<_>>();
+		while alpha.last() == Some(&255) {
+			alpha.pop();
+		}
+		if !alpha.is_empty() {
+			encoder.set_trns(alpha);
+		}
+	}
+	let mut writer = encoder
+		.write_header()
+		.unwrap_or_else(|e| panic!("Failed to write image {path}: {e}"));
+	writer
+		.write_image_data(&pixels)
+		.unwrap_or_else(|e| panic!("Failed to write image {path}: {e}"));
+	writer
+		.finish()
+		.unwrap_or_else(|e| panic!("Failed to write image {path}: {e}"));
+	fs::write(path, output).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+	Some(what)
+}
+
+/// Reduces a png to at most a number of colors with NeuQuant. Pngs that
+/// already have few enough colors are left alone, returns whether it was
+/// changed.
+pub fn quantize(path: &str, colors: u16) -> bool {
+	let image = image::open(path)
+		.unwrap_or_else(|e| panic!("Failed to read image {path}: {e}"))
+		.into_rgba8();
+	let clear = |p: [u8; 4]| match p[3] {
+		0 => [0, 0, 0, 0],
+		_ => p,
+	};
+	let unique = image.pixels().map(|p| clear(p.0)).collect::<HashSet<_>>();
+	if unique.len() <= colors as usize {
+		return false;
+	}
+	// fully transparent pixels get a color of their own
+	let reserved = usize::from(unique.contains(&[0, 0, 0, 0]));
+	let quant = NeuQuant::new(10, colors as usize - reserved, image.as_raw());
+	let mut quantized = image.clone();
+	for (pixel, original) in quantized.pixels_mut().zip(image.pixels()) {
+		quant.map_pixel(&mut pixel.0);
+		// keep the edges of cutout textures sharp
+		if original.0[3] == 0 {
+			pixel.0 = [0, 0, 0, 0];
+		}
+	}
+	quantized
+		.save(path)
+		.unwrap_or_else(|e| panic!("Failed to write image {path}: {e}"));
+	true
+}
+
+/// oxipng settings for a level [0..6], with Zopfli doing a number of
+/// iterations instead of libdeflate if given. Fast only tries one filter at
+/// the lowest compression, whatever the level.
+pub fn png_options(
+	level: u8, strip: &Strip, keep: &[String], zopfli: Option<u8>, fast: bool,
+) -> Options {
+	let mut options = match fast {
+		true => {
+			let mut options = Options::from_preset(0);
+			options.deflate = oxipng::Deflaters::Libdeflater { compression: 1 };
+			options
+		}
+		false => Options::from_preset(level),
+	};
+	if let Some(iterations) = zopfli.and_then(NonZeroU8::new) {
+		options.deflate = oxipng::Deflaters::Zopfli { iterations };
+	}
+	options.strip = strip.headers(keep);
+	options
+}
+
+#[derive(Clone, Debug, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Strip {
+	None,
+	Safe,
+	All,
+}
+
+/// Chunks oxipng keeps when stripping safely, the ones that change how
+/// colors look.
+const SAFE_CHUNKS: [&str; 5] = ["cICP", "iCCP", "sBIT", "sRGB", "pHYs"];
+
+impl Strip {
+	/// What oxipng strips, keeping the chunks named in keep.
+	pub fn headers(&self, keep: &[String]) -> oxipng::Headers {
+		let kept = |chunks: &[&str]| {
+			let list = chunks
+				.iter()
+				.map(|c| c.to_string())
+				.chain(keep.iter().cloned());
+			oxipng::Headers::Keep(list.collect())
+		};
+		match self {
+			Strip::None => oxipng::Headers::None,
+			Strip::Safe if keep.is_empty() => oxipng::Headers::Safe,
+			Strip::Safe => kept(&SAFE_CHUNKS),
+			Strip::All if keep.is_empty() => oxipng::Headers::All,
+			Strip::All => kept(&[]),
+		}
+	}
+}
+
+/// Sizes of the chunks of a png by type, the image data under IDAT.
+pub fn chunk_sizes(data: &[u8]) -> BTreeMap<String, i64> {
+	let mut sizes = BTreeMap::new();
+	let mut at = 8;
+	while at + 12 <= data.len() {
+		let length = u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+		let name = String::from_utf8_lossy(&data[at + 4..at + 8]).to_string();
+		*sizes.entry(name).or_insert(0) += length as i64 + 12;
+		at += length + 12;
+	}
+	sizes
+}
+
+/// Bytes saved by chunk type between two versions of a png. Critical chunks
+/// like IDAT and PLTE are counted together as the image data.
+pub fn savings(before: &[u8], after: &[u8], total: &mut BTreeMap<String, i64>) {
+	let category = |name: &str| match name.starts_with(|c: char| c.is_ascii_uppercase()) {
+		true => "image data".to_string(),
+		false => name.to_string(),
+	};
+	for (name, size) in chunk_sizes(before) {
+		*total.entry(category(&name)).or_insert(0) += size;
+	}
+	for (name, size) in chunk_sizes(after) {
+		*total.entry(category(&name)).or_insert(0) -= size;
+	}
+}
+
+/// Saved bytes by category, like `12345 bytes saved (tEXt 120, image data
+/// 12225)`.
+pub fn savings_report(total: &BTreeMap<String, i64>) -> String {
+	let sum = total.values().sum::<i64>();
+	let parts = total
+		.iter()
+		.filter(|(_, saved)| **saved != 0)
+		.map(|(name, saved)| format!("{name} {saved}"))
+		.collect::<Vec<_>>();
+	match parts.is_empty() {
+		true => format!("{sum} bytes saved"),
+		false => format!("{sum} bytes saved ({})", parts.join(", ")),
+	}
+}
+
+/// Where optimize_images keeps the hashes of the pngs it optimized.
+pub fn manifest_path() -> String {
+	format!(".pdt_build{SLASH}optimized.json")
+}
+
+/// Pngs already optimized, by their full path, mapped to a hash of the
+/// settings and the file as it was after.
+fn load_manifest(path: &str) -> BTreeMap<String, String> {
+	fs::read_to_string(path)
+		.ok()
+		.and_then(|data| serde_json::from_str(&data).ok())
+		.unwrap_or_default()
+}
+
+fn save_manifest(path: &str, manifest: &BTreeMap<String, String>) {
+	if let Some(dir) = Utf8Path::new(path).parent() {
+		fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	}
+	let data = serde_json::to_string_pretty(manifest).unwrap() + "\n";
+	fs::write(path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+}
+
+/// Writes a tga or jpeg image as a png of the same name. Returns false if
+/// it can't be read.
+pub fn convert_to_png(path: &str, output: &str) -> bool {
+	let image = match image::open(path) {
+		Ok(image) => image,
+		Err(e) => {
+			log::error!("Failed to read image {path}: {e}");
+			return false;
+		}
+	};
+	if let Some(dir) = Utf8Path::new(output).parent() {
+		fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	}
+	image
+		.save_with_format(output, image::ImageFormat::Png)
+		.unwrap_or_else(|e| panic!("Failed to write file: {output}: {e}"));
+	true
+}
+
+/// Optimizes every png in the paths, skipping the ones the manifest says
+/// were optimized with the same settings and haven't changed since, unless
+/// forced. Tga and jpeg images are converted to pngs first if convert is
+/// set, and left alone otherwise. With an output folder the images are
+/// copied there and optimized, the originals are kept.
+fn optimize_images(
+	options: &Options, quantize_rule: Option<&Quantize>, paths: Vec<String>, filter: &FileFilter,
+	force: bool, convert: bool, out_dir: Option<&str>,
+) -> SizeReport {
+	let settings = format!("{options:?} {quantize_rule:?}");
+	let manifest_file = manifest_path();
+	let mut manifest = load_manifest(&manifest_file);
+	let entry = |image: &str| {
+		let full = fs::canonicalize(image)
+			.map(|p| p.display().to_string())
+			.unwrap_or_else(|_| image.to_string());
+		let hash =
+			pdthash::get_string_hash(&format!("{settings}\0{}", pdthash::get_hash(image, false)));
+		(full, hash)
+	};
+	let recursive = true;
+	let extensions = Some(pdtfs::image_extensions());
+	const EXCLUDE_DIR_NAME: bool = false;
+	let images = get_files_in_list(paths, recursive, extensions, EXCLUDE_DIR_NAME, true, filter);
+	let pngs = vec![".png".to_string()];
+	let mut total = BTreeMap::new();
+	let mut report = SizeReport::default();
+	let _stage = pdtlog::stage("optimize");
+	let count = images.len();
+	for (done, source) in images.into_iter().enumerate() {
+		if pdtthread::cancelled() {
+			pdtlog::print(&format!(
+				"Stopped after {done} of {count} images, run again to optimize the rest."
+			));
+			break;
+		}
+		let mut image = match out_dir {
+			Some(dir) => pdtfs::path_in_dir(dir, &source),
+			None => source.clone(),
+		};
+		if !pdtfs::has_extension(&source, &pngs) {
+			let png = pdtfs::png_path(&image);
+			if !convert {
+				log::warn!("Minecraft only loads png textures, not optimizing {source}");
+				continue;
+			}
+			if out_dir.is_none() && Utf8Path::new(&png).exists() {
+				log::warn!("{png} already exists, not converting {source}");
+				continue;
+			}
+			if !convert_to_png(&source, &png) {
+				continue;
+			}
+			log::info!("converted {source} to {png}");
+			if out_dir.is_none() {
+				fs::remove_file(&source).unwrap_or_else(|_| panic!("Failed to remove {source}"));
+			}
+			image = png;
+		} else if out_dir.is_some() {
+			pdtfs::copy_file(&source, &image);
+		}
+		let before = fs::read(&image).unwrap_or_else(|_| panic!("Failed to read file: {image}"));
+		let (full, hash) = entry(&image);
+		if !force && manifest.get(&full) == Some(&hash) {
+			log::debug!("already optimized: {image}");
+			report.add(&image, before.len() as u64, before.len() as u64);
+			pdtlog::file_done("optimize", &image);
+			continue;
+		}
+		log::info!("optimizing image: {}", &image);
+		if to_srgb8(&image) {
+			log::debug!("rewritten as 8-bit sRGB: {image}");
+		}
+		if let Some(rule) = quantize_rule.filter(|r| r.matches(&image.replace(SLASH, "/"))) {
+			if quantize(&image, rule.colors) {
+				log::debug!("quantized to {} colors: {image}", rule.colors);
+			}
+		}
+		if let Some(reduced) = reduce_color_type(&image) {
+			log::debug!("rewritten as {reduced}: {image}");
+		}
+		let input = InFile::Path(Utf8PathBuf::from(&image).into());
+		let output = OutFile::Path(Some(Utf8PathBuf::from(&image).into()));
+		optimize(&input, &output, options).expect("Failed to optimize image.");
+		let after = fs::read(&image).unwrap_or_else(|_| panic!("Failed to read file: {image}"));
+		savings(&before, &after, &mut total);
+		report.add(&image, before.len() as u64, after.len() as u64);
+		manifest.insert(full, entry(&image).1);
+		pdtlog::file_done("optimize", &image);
+	}
+	log::info!("{}", savings_report(&total));
+	save_manifest(&manifest_file, &manifest);
+	report
+}
@@ -0,0 +1,165 @@
+use super::pdtcache::Cache;
+use super::pdtlog::{self, Problem};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a stage is run on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+	/// The files of every base pack, addon and override layer, before they
+	/// are laid over each other
+	Layer,
+	/// The files of every release, right before it is zipped
+	Release,
+}
+
+/// Where a stage is run, and with what.
+pub struct StageContext<'a> {
+	/// Name of the layer or release the files are of
+	pub name: &'a str,
+	/// Folder the stage can write the files it makes to, pointing the files
+	/// at them instead of changing the sources
+	pub staging: &'a str,
+	pub cache: Option<&'a Cache>,
+}
+
+/// A step of the build, run on the files of every layer or release by
+/// their path in the pack, mapped to where they are read from.
+pub trait Stage {
+	/// Name of the stage in the [pipeline] table, like optimize-images
+	fn name(&self) -> &str;
+	fn scope(&self) -> Scope {
+		Scope::Layer
+	}
+	/// Extensions of the files the stage works on, it is skipped for the
+	/// layers and releases without any. Empty to always run it
+	fn inputs(&self) -> &[&str] {
+		&[]
+	}
+	/// Changes the files and returns the problems found, which stop the
+	/// build once every stage has run.
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem>;
+}
+
+/// [pipeline] table of the build config, which stages run and in what
+/// order.
+///
+/// ```toml
+/// [pipeline]
+/// stages = ["format-json", "optimize-images", "validate"]
+/// disable = ["validate"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+	/// Stages in the order they run, the ones left out don't run. Every
+	/// stage in its usual order if empty
+	#[serde(default)]
+	pub stages: Vec<String>,
+	/// Stages that don't run
+	#[serde(default)]
+	pub disable: Vec<String>,
+}
+
+/// The stages of a build, in the order they run.
+#[derive(Default)]
+pub struct Pipeline {
+	stages: Vec<Box<dyn Stage>>,
+	/// Stages that aren't run by the pipeline itself, like blockify, which
+	/// can still be configured by name
+	steps: Vec<String>,
+	disabled: BTreeSet<String>,
+}
+
+impl Pipeline {
+	pub fn new() -> Pipeline {
+		Pipeline::default()
+	}
+
+	/// Adds a stage after the others.
+	pub fn add(&mut self, stage: impl Stage + 'static) {
+		self.stages.push(Box::new(stage));
+	}
+
+	/// Adds a stage before another one, or after the others if there is no
+	/// stage with that name.
+	pub fn insert_before(&mut self, name: &str, stage: impl Stage + 'static) {
+		match self.stages.iter().position(|s| s.name() == name) {
+			Some(i) => self.stages.insert(i, Box::new(stage)),
+			None => self.add(stage),
+		}
+	}
+
+	/// Lets a step the build runs itself be turned off by name.
+	pub fn add_step(&mut self, name: &str) {
+		self.steps.push(name.to_string());
+	}
+
+	/// Names of the stages, in the order they run.
+	pub fn names(&self) -> Vec<&str> {
+		self.stages.iter().map(|s| s.name()).collect()
+	}
+
+	/// Orders and turns off stages as the config says. Names that aren't
+	/// stages are an error, except for ones in known, which are the stages
+	/// that were left out because the build config doesn't use them.
+	pub fn configure(&mut self, config: &PipelineConfig, known: &[&str]) {
+		let exists = |name: &String| {
+			self.stages.iter().any(|s| s.name() == name)
+				|| self.steps.contains(name)
+				|| known.contains(&name.as_str())
+		};
+		if let Some(name) = config
+			.stages
+			.iter()
+			.chain(&config.disable)
+			.find(|n| !exists(n))
+		{
+			panic!("Unknown stage {name} in [pipeline]");
+		}
+		self.disabled.extend(config.disable.iter().cloned());
+		if config.stages.is_empty() {
+			return;
+		}
+		let left_out = self
+			.names()
+			.into_iter()
+			.chain(self.steps.iter().map(|s| s.as_str()))
+			.filter(|name| !config.stages.iter().any(|n| n == name))
+			.map(|name| name.to_string())
+			.collect::<Vec<_>>();
+		self.disabled.extend(left_out);
+		self.stages.sort_by_key(|stage| {
+			config
+				.stages
+				.iter()
+				.position(|n| n == stage.name())
+				.unwrap_or(usize::MAX)
+		});
+	}
+
+	/// Whether a stage or step runs.
+	pub fn enabled(&self, name: &str) -> bool {
+		!self.disabled.contains(name)
+	}
+
+	/// Runs the stages of a scope on some files, in order.
+	pub fn run(
+		&self, scope: Scope, files: &mut BTreeMap<String, String>, context: &StageContext,
+	) -> Vec<Problem> {
+		let mut problems = vec![];
+		for stage in &self.stages {
+			if stage.scope() != scope || !self.enabled(stage.name()) {
+				continue;
+			}
+			let inputs = stage.inputs();
+			if !inputs.is_empty() && !files.keys().any(|f| inputs.iter().any(|e| f.ends_with(e))) {
+				continue;
+			}
+			log::debug!("running {} on {}", stage.name(), context.name);
+			let _stage = pdtlog::stage(&format!("{} {}", stage.name(), context.name));
+			problems.extend(stage.run(files, context));
+		}
+		problems
+	}
+}
@@ -0,0 +1,858 @@
+use super::pdtchangelog::{self, CHANGELOG_FILE, RELEASE_NOTES_FILE};
+use super::pdtrelease::{BuildConfig, PublishTarget, CHECKSUMS_FILE, CONFIG_FILE};
+use super::{pdthash, pdtlog};
+use camino::Utf8Path;
+use clap::ValueEnum;
+use clap::{ArgAction, Subcommand};
+use reqwest::blocking::{multipart, Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::MAIN_SEPARATOR as SLASH;
+
+pub const GITHUB_API: &str = "https://api.github.com";
+pub const MODRINTH_API: &str = "https://api.modrinth.com/v2";
+pub const CURSEFORGE_API: &str = "https://minecraft.curseforge.com/api";
+/// Cache-Control of uploaded zips, unless the publish settings change it.
+pub const ZIP_CACHE_CONTROL: &str = "public, max-age=86400";
+/// Cache-Control of the checksum files, which change with every release.
+pub const CHECKSUMS_CACHE_CONTROL: &str = "no-cache";
+
+#[derive(Debug, clap::Args)]
+pub struct PublishArgs {
+	#[command(subcommand)]
+	platform: Platform,
+	#[arg(long, global = true)]
+	/// Build config the releases were built with, defaults to builder.toml
+	config: Option<String>,
+	#[arg(short, long, global = true, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long, global = true)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Platform {
+	/// Create a GitHub release and upload the zips and checksums to it
+	Github(GithubArgs),
+	/// Create a Modrinth version of the project with the zips
+	Modrinth(ModrinthArgs),
+	/// Upload the zips as files of the CurseForge project
+	Curseforge(CurseforgeArgs),
+	/// Upload the zips and checksums to an S3 compatible bucket, like
+	/// Cloudflare R2
+	S3(S3Args),
+	/// Publish to every target in the build config, with their settings
+	All {
+		/// Tag or version number of the release, like v1.2.0
+		version: String,
+	},
+}
+
+#[derive(Debug, Default, clap::Args)]
+pub struct GithubArgs {
+	#[arg(long, value_name = "OWNER/NAME")]
+	/// Repository to release in, defaults to the publish settings or
+	/// GITHUB_REPOSITORY
+	repository: Option<String>,
+	#[arg(short, long)]
+	/// Tag of the release, made from the default branch if it doesn't exist
+	tag: Option<String>,
+	#[arg(long)]
+	/// Title of the release, defaults to the tag
+	title: Option<String>,
+	#[arg(long, value_name = "FILE")]
+	/// Release notes, defaults to the tag's section of CHANGELOG.md next to
+	/// the build config
+	notes: Option<String>,
+	#[arg(long)]
+	/// Create the release as a draft
+	draft: bool,
+	#[arg(long)]
+	/// Mark the release as a prerelease
+	prerelease: bool,
+}
+
+#[derive(Debug, Default, clap::Args)]
+pub struct ModrinthArgs {
+	#[arg(long)]
+	/// Project id or slug, defaults to the publish settings or
+	/// MODRINTH_PROJECT_ID
+	project: Option<String>,
+	#[arg(short = 'n', long)]
+	/// Version number, like 1.2.0
+	version_number: Option<String>,
+	#[arg(long)]
+	/// Name of the version, defaults to the version number
+	name: Option<String>,
+	#[arg(short, long)]
+	/// Minecraft version it works with, repeat for every version. Defaults
+	/// to the publish settings or the targets of the build config
+	game_version: Vec<String>,
+	#[arg(long, value_enum)]
+	/// Defaults to the publish settings or release
+	version_type: Option<VersionType>,
+	#[arg(long, value_name = "FILE")]
+	/// Changelog of the version, defaults to its section of CHANGELOG.md
+	/// next to the build config
+	changelog: Option<String>,
+	#[arg(long, value_name = "NAME")]
+	/// Only upload this zip, instead of every zip of the build
+	zip: Option<String>,
+}
+
+#[derive(Debug, Default, clap::Args)]
+pub struct CurseforgeArgs {
+	#[arg(long)]
+	/// Numeric project id, defaults to the publish settings or
+	/// CURSEFORGE_PROJECT_ID
+	project: Option<u64>,
+	#[arg(short = 'n', long)]
+	/// Version of the release, used to find its changelog section
+	version_number: Option<String>,
+	#[arg(long)]
+	/// Name shown for the files, defaults to the zip names
+	display_name: Option<String>,
+	#[arg(short, long)]
+	/// Minecraft version it works with, repeat for every version. Defaults
+	/// to the publish settings or the targets of the build config
+	game_version: Vec<String>,
+	#[arg(long, value_enum)]
+	/// Defaults to the publish settings or release
+	release_type: Option<VersionType>,
+	#[arg(long, value_name = "FILE")]
+	/// Changelog of the files, defaults to the version's section of
+	/// CHANGELOG.md next to the build config
+	changelog: Option<String>,
+	#[arg(long, value_name = "NAME")]
+	/// Only upload this zip, instead of every zip of the build
+	zip: Option<String>,
+}
+
+#[derive(Debug, Default, clap::Args)]
+pub struct S3Args {
+	#[arg(long)]
+	/// Bucket to upload to, defaults to the publish settings or S3_BUCKET
+	bucket: Option<String>,
+	#[arg(long, value_name = "URL")]
+	/// Endpoint of the storage, like https://<account>.r2.cloudflarestorage.com.
+	/// Defaults to the publish settings, AWS_ENDPOINT_URL or AWS S3 in the
+	/// region
+	endpoint: Option<String>,
+	#[arg(long)]
+	/// Region of the bucket, defaults to the publish settings, AWS_REGION or
+	/// us-east-1. R2 uses auto
+	region: Option<String>,
+	#[arg(long)]
+	/// Folder in the bucket to upload to, like packs/v1.2.0/
+	prefix: Option<String>,
+	#[arg(long)]
+	/// Cache-Control of the zips, defaults to the publish settings or
+	/// "public, max-age=86400"
+	cache_control: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionType {
+	Release,
+	Beta,
+	Alpha,
+}
+
+impl VersionType {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			VersionType::Release => "release",
+			VersionType::Beta => "beta",
+			VersionType::Alpha => "alpha",
+		}
+	}
+}
+
+/// Everything needed to create a GitHub release.
+#[derive(Debug)]
+pub struct GithubRelease {
+	pub repository: String,
+	pub tag: String,
+	pub title: String,
+	pub body: String,
+	pub draft: bool,
+	pub prerelease: bool,
+}
+
+/// Everything needed to create a Modrinth version.
+#[derive(Debug)]
+pub struct ModrinthVersion {
+	pub project: String,
+	pub version_number: String,
+	pub name: String,
+	pub changelog: String,
+	pub game_versions: Vec<String>,
+	pub version_type: VersionType,
+}
+
+/// Everything needed to upload files to an S3 bucket.
+#[derive(Debug)]
+pub struct S3Upload {
+	pub endpoint: String,
+	pub region: String,
+	pub bucket: String,
+	pub prefix: String,
+	pub cache_control: String,
+}
+
+/// Everything needed to upload files to a CurseForge project.
+#[derive(Debug)]
+pub struct CurseforgeUpload {
+	pub project: u64,
+	pub display_name: Option<String>,
+	pub changelog: String,
+	pub game_versions: Vec<String>,
+	pub release_type: VersionType,
+}
+
+#[derive(Deserialize)]
+struct CreatedRelease {
+	html_url: String,
+	upload_url: String,
+}
+
+/// Publishes the releases last built with the build config.
+pub fn run(args: PublishArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let path = args.config.as_deref().unwrap_or(CONFIG_FILE);
+	let config = BuildConfig::load(path);
+	let config_dir = Utf8Path::new(path)
+		.parent()
+		.map(|p| p.as_str())
+		.filter(|p| !p.is_empty())
+		.unwrap_or(".");
+	publish(args.platform, &config, config_dir);
+}
+
+fn publish(platform: Platform, config: &BuildConfig, config_dir: &str) {
+	let files = built_files(&config.output);
+	let zips = |zip: &Option<String>| match zip {
+		Some(zip) => vec![format!("{}{SLASH}{}", config.output, zip)],
+		None => files
+			.iter()
+			.filter(|f| f.ends_with(".zip"))
+			.cloned()
+			.collect(),
+	};
+	match platform {
+		Platform::Github(args) => {
+			let settings = settings_for(config, "github");
+			let release = github_release(args, settings, config, config_dir);
+			let url = publish_github(&release, &files);
+			println!("Published {} files to {url}", files.len());
+		}
+		Platform::Modrinth(args) => {
+			let settings = settings_for(config, "modrinth");
+			let zips = zips(&args.zip);
+			let version = modrinth_version(args, settings, config, config_dir);
+			let url = publish_modrinth(&version, &zips);
+			println!("Published {} files to {url}", zips.len());
+		}
+		Platform::Curseforge(args) => {
+			let settings = settings_for(config, "curseforge");
+			let zips = zips(&args.zip);
+			let upload = curseforge_upload(args, settings, config, config_dir);
+			let ids = publish_curseforge(&upload, config, &zips);
+			println!("Published {} files to CurseForge", ids.len());
+		}
+		Platform::S3(args) => {
+			let settings = settings_for(config, "s3");
+			let upload = s3_upload(args, settings);
+			let urls = publish_s3(&upload, &files);
+			println!(
+				"Published {} files to {}",
+				urls.len(),
+				urls.first()
+					.map(|u| u.rsplit_once('/').unwrap().0)
+					.unwrap_or_default()
+			);
+		}
+		Platform::All { version } => {
+			for target in &config.publish {
+				let version = Some(version.clone());
+				let platform = match target.target.as_str() {
+					"github" => Platform::Github(GithubArgs {
+						tag: version,
+						..Default::default()
+					}),
+					"modrinth" => Platform::Modrinth(ModrinthArgs {
+						version_number: version,
+						..Default::default()
+					}),
+					"curseforge" => Platform::Curseforge(CurseforgeArgs {
+						version_number: version,
+						..Default::default()
+					}),
+					"s3" => Platform::S3(S3Args::default()),
+					other => panic!("Unknown publish target: {other}"),
+				};
+				publish(platform, config, config_dir);
+			}
+		}
+	}
+}
+
+/// The zips of the last build and their checksum files, as listed in
+/// checksums.json.
+pub fn built_files(output: &str) -> Vec<String> {
+	let checksums = format!("{output}{SLASH}{CHECKSUMS_FILE}.json");
+	let data = fs::read_to_string(&checksums)
+		.unwrap_or_else(|_| panic!("No releases found in {output}, run pdt build first."));
+	let zips: BTreeMap<String, serde_json::Value> =
+		serde_json::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse {checksums}: {e}"));
+	zips.into_keys()
+		.map(|zip| format!("{output}{SLASH}{zip}"))
+		.chain(["txt", "json"].map(|e| format!("{output}{SLASH}{CHECKSUMS_FILE}.{e}")))
+		.collect()
+}
+
+/// Settings of the first publish target for a platform in the build config.
+fn settings_for<'a>(config: &'a BuildConfig, platform: &str) -> Option<&'a PublishTarget> {
+	config.publish.iter().find(|p| p.target == platform)
+}
+
+fn setting<T: DeserializeOwned>(target: Option<&PublishTarget>, key: &str) -> Option<T> {
+	let target = target?;
+	let value = target.settings.get(key)?;
+	Some(
+		serde_json::from_value(value.clone())
+			.unwrap_or_else(|e| panic!("Invalid {key} in {} publish settings: {e}", target.target)),
+	)
+}
+
+/// The section of a changelog under the heading for a version, like
+/// `## 1.2.0` or `## [1.2.0] - 2024-05-01`. A leading v is ignored.
+pub fn changelog_section(changelog: &str, version: &str) -> Option<String> {
+	let version = version.trim_start_matches('v');
+	let is_heading = |line: &str| line.starts_with("## ");
+	let is_version = |line: &str| {
+		let Some(heading) = line.strip_prefix("## ") else {
+			return false;
+		};
+		let heading = heading.trim().trim_start_matches(['[', 'v']);
+		heading
+			.strip_prefix(version)
+			.is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '.'))
+	};
+	let mut lines = changelog.lines().skip_while(|line| !is_version(line));
+	lines.next()?;
+	let section = lines
+		.take_while(|line| !is_heading(line))
+		.collect::<Vec<_>>()
+		.join("\n");
+	Some(section.trim().to_string())
+}
+
+/// Notes read from a file if given, otherwise the version's section of the
+/// changelog next to the build config, otherwise the changes since the last
+/// tag written by the build.
+fn release_notes(
+	file: Option<String>, config: &BuildConfig, config_dir: &str, version: &str,
+) -> String {
+	if let Some(file) = file {
+		return fs::read_to_string(&file).unwrap_or_else(|_| panic!("Failed to read file: {file}"));
+	}
+	let changelog = format!("{config_dir}{SLASH}{CHANGELOG_FILE}");
+	let section = fs::read_to_string(changelog)
+		.ok()
+		.and_then(|c| changelog_section(&c, version))
+		.or_else(|| {
+			let built = format!("{}{SLASH}{RELEASE_NOTES_FILE}", config.output);
+			fs::read_to_string(built).ok()
+		});
+	if section.is_none() {
+		log::warn!("No changelog section found for {version}, the release notes are empty");
+	}
+	section.unwrap_or_default()
+}
+
+fn github_release(
+	args: GithubArgs, settings: Option<&PublishTarget>, config: &BuildConfig, config_dir: &str,
+) -> GithubRelease {
+	let repository = args
+		.repository
+		.or_else(|| setting(settings, "repository"))
+		.or_else(|| std::env::var("GITHUB_REPOSITORY").ok())
+		.expect("No GitHub repository given, set one with --repository.");
+	let tag: String = args
+		.tag
+		.or_else(|| setting(settings, "tag"))
+		.expect("No tag given, set one with --tag.");
+	let title = args
+		.title
+		.or_else(|| setting(settings, "title"))
+		.unwrap_or_else(|| tag.clone());
+	let notes = args.notes.or_else(|| setting(settings, "notes"));
+	let body = release_notes(notes, config, config_dir, &tag);
+	GithubRelease {
+		repository,
+		tag,
+		title,
+		body,
+		draft: args.draft || setting(settings, "draft").unwrap_or(false),
+		prerelease: args.prerelease || setting(settings, "prerelease").unwrap_or(false),
+	}
+}
+
+fn send(request: RequestBuilder, what: &str) -> Response {
+	let response = request
+		.send()
+		.unwrap_or_else(|e| panic!("Failed to {what}: {e}"));
+	if !response.status().is_success() {
+		let status = response.status();
+		let body = response.text().unwrap_or_default();
+		panic!("Failed to {what}: {status}\n{body}");
+	}
+	response
+}
+
+fn content_type(file: &str) -> &'static str {
+	match file.rsplit('.').next() {
+		Some("zip") => "application/zip",
+		Some("json") => "application/json",
+		Some("txt") => "text/plain",
+		_ => "application/octet-stream",
+	}
+}
+
+/// Creates a GitHub release and uploads the files to it, authenticated with
+/// GITHUB_TOKEN. Returns the page of the release.
+pub fn publish_github(release: &GithubRelease, files: &[String]) -> String {
+	let token = std::env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN is not set.");
+	let client = Client::new();
+	let github = |request: RequestBuilder| {
+		request
+			.bearer_auth(&token)
+			.header("Accept", "application/vnd.github+json")
+			.header("User-Agent", env!("CARGO_PKG_NAME"))
+	};
+
+	log::info!("Creating release {} in {}", release.tag, release.repository);
+	let url = format!("{GITHUB_API}/repos/{}/releases", release.repository);
+	let request = github(client.post(url)).json(&serde_json::json!({
+		"tag_name": release.tag,
+		"name": release.title,
+		"body": release.body,
+		"draft": release.draft,
+		"prerelease": release.prerelease,
+	}));
+	let created: CreatedRelease = send(request, "create the GitHub release")
+		.json()
+		.unwrap_or_else(|e| panic!("Failed to read the created GitHub release: {e}"));
+
+	let upload_url = created.upload_url.split('{').next().unwrap();
+	for file in files {
+		let name = file.rsplit(SLASH).next().unwrap();
+		log::info!("Uploading {name}");
+		let data = fs::read(file).unwrap_or_else(|_| panic!("Failed to read file: {file}"));
+		let request = github(client.post(upload_url))
+			.query(&[("name", name)])
+			.header("Content-Type", content_type(file))
+			.body(data);
+		send(request, &format!("upload {name}"));
+	}
+	created.html_url
+}
+
+fn modrinth_version(
+	args: ModrinthArgs, settings: Option<&PublishTarget>, config: &BuildConfig, config_dir: &str,
+) -> ModrinthVersion {
+	let project = args
+		.project
+		.or_else(|| setting(settings, "project_id"))
+		.or_else(|| std::env::var("MODRINTH_PROJECT_ID").ok())
+		.expect("No Modrinth project given, set one with --project.");
+	let version_number: String = args
+		.version_number
+		.or_else(|| setting(settings, "version_number"))
+		.expect("No version number given, set one with --version-number.");
+	let game_versions = game_versions(args.game_version, settings, config);
+	let changelog = args.changelog.or_else(|| setting(settings, "changelog"));
+	ModrinthVersion {
+		project,
+		name: args.name.unwrap_or_else(|| version_number.clone()),
+		changelog: release_notes(changelog, config, config_dir, &version_number),
+		version_number,
+		game_versions,
+		version_type: args
+			.version_type
+			.or_else(|| setting(settings, "version_type"))
+			.unwrap_or(VersionType::Release),
+	}
+}
+
+/// Creates a new version of a Modrinth project with the zips, the first one
+/// being the primary file, authenticated with MODRINTH_TOKEN. Returns the
+/// page of the version.
+pub fn publish_modrinth(version: &ModrinthVersion, zips: &[String]) -> String {
+	let token = std::env::var("MODRINTH_TOKEN").expect("MODRINTH_TOKEN is not set.");
+	let names = zips
+		.iter()
+		.map(|zip| zip.rsplit(SLASH).next().unwrap().to_string())
+		.collect::<Vec<_>>();
+	let Some(primary) = names.first() else {
+		panic!("No zips to upload to Modrinth.");
+	};
+	let data = serde_json::json!({
+		"project_id": version.project,
+		"version_number": version.version_number,
+		"name": version.name,
+		"changelog": version.changelog,
+		"game_versions": version.game_versions,
+		"version_type": version.version_type.as_str(),
+		"loaders": ["minecraft"],
+		"dependencies": [],
+		"featured": false,
+		"file_parts": names,
+		"primary_file": primary,
+	});
+	let mut form = multipart::Form::new().text("data", data.to_string());
+	for (zip, name) in zips.iter().zip(&names) {
+		log::debug!("adding {name}");
+		let file = fs::read(zip).unwrap_or_else(|_| panic!("Failed to read file: {zip}"));
+		let part = multipart::Part::bytes(file)
+			.file_name(name.clone())
+			.mime_str(content_type(zip))
+			.unwrap();
+		form = form.part(name.clone(), part);
+	}
+
+	log::info!(
+		"Uploading version {} of {}",
+		version.version_number,
+		version.project
+	);
+	let request = Client::new()
+		.post(format!("{MODRINTH_API}/version"))
+		.header("Authorization", token)
+		.header("User-Agent", env!("CARGO_PKG_NAME"))
+		.multipart(form);
+	let created: serde_json::Value = send(request, "create the Modrinth version")
+		.json()
+		.unwrap_or_else(|e| panic!("Failed to read the created Modrinth version: {e}"));
+	format!(
+		"https://modrinth.com/resourcepack/{}/version/{}",
+		version.project,
+		created["id"].as_str().unwrap_or(&version.version_number)
+	)
+}
+
+/// The game versions to publish for, from the arguments, the publish
+/// settings or the targets of the build config.
+fn game_versions(
+	args: Vec<String>, settings: Option<&PublishTarget>, config: &BuildConfig,
+) -> Vec<String> {
+	let mut versions = args;
+	if versions.is_empty() {
+		versions = setting(settings, "game_versions")
+			.unwrap_or_else(|| config.targets.iter().map(|t| t.minecraft.clone()).collect());
+	}
+	if versions.is_empty() {
+		panic!("No game versions given, set them with --game-version.");
+	}
+	versions
+}
+
+fn curseforge_upload(
+	args: CurseforgeArgs, settings: Option<&PublishTarget>, config: &BuildConfig, config_dir: &str,
+) -> CurseforgeUpload {
+	let project = args
+		.project
+		.or_else(|| setting(settings, "project_id"))
+		.or_else(|| {
+			std::env::var("CURSEFORGE_PROJECT_ID")
+				.ok()
+				.map(|id| id.parse().expect("CURSEFORGE_PROJECT_ID is not a number."))
+		})
+		.expect("No CurseForge project given, set one with --project.");
+	let version_number: String = args
+		.version_number
+		.or_else(|| setting(settings, "version_number"))
+		.expect("No version number given, set one with --version-number.");
+	let changelog = args.changelog.or_else(|| setting(settings, "changelog"));
+	CurseforgeUpload {
+		project,
+		display_name: args
+			.display_name
+			.or_else(|| setting(settings, "display_name")),
+		changelog: release_notes(changelog, config, config_dir, &version_number),
+		game_versions: game_versions(args.game_version, settings, config),
+		release_type: args
+			.release_type
+			.or_else(|| setting(settings, "release_type"))
+			.unwrap_or(VersionType::Release),
+	}
+}
+
+#[derive(Deserialize)]
+struct CurseforgeVersionType {
+	id: u64,
+	slug: String,
+}
+
+#[derive(Deserialize)]
+struct CurseforgeVersion {
+	id: u64,
+	#[serde(rename = "gameVersionTypeID")]
+	game_version_type_id: u64,
+	name: String,
+}
+
+#[derive(Deserialize)]
+struct CurseforgeFile {
+	id: u64,
+}
+
+/// Looks up the CurseForge ids of Minecraft versions, like 1.20.1.
+fn curseforge_version_ids(client: &Client, token: &str, versions: &[String]) -> Vec<u64> {
+	let get = |path: &str| {
+		let request = client
+			.get(format!("{CURSEFORGE_API}/game/{path}"))
+			.header("X-Api-Token", token);
+		send(request, "look up CurseForge game versions")
+	};
+	let types: Vec<CurseforgeVersionType> = get("version-types")
+		.json()
+		.expect("Failed to read CurseForge version types.");
+	let minecraft = types
+		.into_iter()
+		.filter(|t| t.slug.starts_with("minecraft-"))
+		.map(|t| t.id)
+		.collect::<Vec<_>>();
+	let known: Vec<CurseforgeVersion> = get("versions")
+		.json()
+		.expect("Failed to read CurseForge game versions.");
+	versions
+		.iter()
+		.map(|version| {
+			known
+				.iter()
+				.find(|v| &v.name == version && minecraft.contains(&v.game_version_type_id))
+				.unwrap_or_else(|| panic!("Minecraft {version} is not a CurseForge game version."))
+				.id
+		})
+		.collect()
+}
+
+/// Uploads each zip as a file of a CurseForge project, authenticated with
+/// CURSEFORGE_TOKEN. Zips built for one target, named after its Minecraft
+/// version, are only marked as working with that version. Returns the ids
+/// of the uploaded files.
+pub fn publish_curseforge(
+	upload: &CurseforgeUpload, config: &BuildConfig, zips: &[String],
+) -> Vec<u64> {
+	let token = std::env::var("CURSEFORGE_TOKEN").expect("CURSEFORGE_TOKEN is not set.");
+	let client = Client::new();
+	let ids = curseforge_version_ids(&client, &token, &upload.game_versions);
+	let all = upload.game_versions.iter().zip(ids).collect::<Vec<_>>();
+
+	let mut uploaded = vec![];
+	for zip in zips {
+		let name = zip.rsplit(SLASH).next().unwrap();
+		let stem = name.trim_end_matches(".zip");
+		let target = config
+			.targets
+			.iter()
+			.find(|t| stem.ends_with(&format!("-{}", t.minecraft)));
+		let game_versions = all
+			.iter()
+			.filter(|(version, _)| target.is_none_or(|t| &&t.minecraft == version))
+			.map(|(_, id)| *id)
+			.collect::<Vec<_>>();
+		let metadata = serde_json::json!({
+			"changelog": upload.changelog,
+			"changelogType": "markdown",
+			"displayName": upload.display_name.as_deref().unwrap_or(stem),
+			"gameVersions": game_versions,
+			"releaseType": upload.release_type.as_str(),
+		});
+		let file = fs::read(zip).unwrap_or_else(|_| panic!("Failed to read file: {zip}"));
+		let part = multipart::Part::bytes(file)
+			.file_name(name.to_string())
+			.mime_str(content_type(zip))
+			.unwrap();
+		let form = multipart::Form::new()
+			.text("metadata", metadata.to_string())
+			.part("file", part);
+
+		log::info!("Uploading {name} to CurseForge project {}", upload.project);
+		let url = format!("{CURSEFORGE_API}/projects/{}/upload-file", upload.project);
+		let request = client
+			.post(url)
+			.header("X-Api-Token", &token)
+			.multipart(form);
+		let file: CurseforgeFile = send(request, &format!("upload {name}"))
+			.json()
+			.unwrap_or_else(|e| panic!("Failed to read the uploaded CurseForge file: {e}"));
+		uploaded.push(file.id);
+	}
+	uploaded
+}
+
+fn s3_upload(args: S3Args, settings: Option<&PublishTarget>) -> S3Upload {
+	let bucket = args
+		.bucket
+		.or_else(|| setting(settings, "bucket"))
+		.or_else(|| std::env::var("S3_BUCKET").ok())
+		.expect("No bucket given, set one with --bucket.");
+	let region: String = args
+		.region
+		.or_else(|| setting(settings, "region"))
+		.or_else(|| std::env::var("AWS_REGION").ok())
+		.unwrap_or_else(|| "us-east-1".to_string());
+	let endpoint = args
+		.endpoint
+		.or_else(|| setting(settings, "endpoint"))
+		.or_else(|| std::env::var("AWS_ENDPOINT_URL").ok())
+		.unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+	let mut prefix: String = args
+		.prefix
+		.or_else(|| setting(settings, "prefix"))
+		.unwrap_or_default();
+	if !prefix.is_empty() && !prefix.ends_with('/') {
+		prefix.push('/');
+	}
+	S3Upload {
+		endpoint: endpoint.trim_end_matches('/').to_string(),
+		region,
+		bucket,
+		prefix: prefix.trim_start_matches('/').to_string(),
+		cache_control: args
+			.cache_control
+			.or_else(|| setting(settings, "cache_control"))
+			.unwrap_or_else(|| ZIP_CACHE_CONTROL.to_string()),
+	}
+}
+
+/// Percent-encodes a path the way S3 signatures expect, keeping the slashes.
+fn s3_encode(path: &str) -> String {
+	path.bytes()
+		.map(|b| match b {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+				(b as char).to_string()
+			}
+			_ => format!("%{b:02X}"),
+		})
+		.collect()
+}
+
+/// Signs a request with AWS signature version 4 and returns the
+/// Authorization header. Headers have to be lowercase and sorted.
+fn s3_authorization(
+	upload: &S3Upload, key_id: &str, secret: &str, path: &str, headers: &[(&str, String)],
+	time: &str,
+) -> String {
+	let date = &time[..8];
+	let scope = format!("{date}/{}/s3/aws4_request", upload.region);
+	let signed = headers
+		.iter()
+		.map(|(name, _)| *name)
+		.collect::<Vec<_>>()
+		.join(";");
+	let canonical = format!(
+		"PUT\n{path}\n\n{}\n\n{signed}\n{}",
+		headers
+			.iter()
+			.map(|(name, value)| format!("{name}:{}", value.trim()))
+			.collect::<Vec<_>>()
+			.join("\n"),
+		headers
+			.iter()
+			.find(|(name, _)| *name == "x-amz-content-sha256")
+			.map(|(_, value)| value.as_str())
+			.unwrap()
+	);
+	let to_sign = format!(
+		"AWS4-HMAC-SHA256\n{time}\n{scope}\n{}",
+		pdthash::get_string_hash(&canonical)
+	);
+	let mut key = format!("AWS4{secret}").into_bytes();
+	for part in [date, &upload.region, "s3", "aws4_request"] {
+		key = pdthash::hmac_sha256(&key, part.as_bytes());
+	}
+	let signature = pdthash::hmac_sha256(&key, to_sign.as_bytes())
+		.iter()
+		.map(|b| format!("{b:02x}"))
+		.collect::<String>();
+	format!("AWS4-HMAC-SHA256 Credential={key_id}/{scope}, SignedHeaders={signed}, Signature={signature}")
+}
+
+/// Uploads the files to an S3 compatible bucket with path style urls,
+/// authenticated with AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, and
+/// AWS_SESSION_TOKEN when it is set. The checksum files are uploaded last
+/// and never cached, so they don't list zips that aren't there yet. Returns
+/// the urls of the files.
+pub fn publish_s3(upload: &S3Upload, files: &[String]) -> Vec<String> {
+	let key_id = std::env::var("AWS_ACCESS_KEY_ID").expect("AWS_ACCESS_KEY_ID is not set.");
+	let secret = std::env::var("AWS_SECRET_ACCESS_KEY").expect("AWS_SECRET_ACCESS_KEY is not set.");
+	let token = std::env::var("AWS_SESSION_TOKEN").ok();
+	let endpoint = reqwest::Url::parse(&upload.endpoint)
+		.unwrap_or_else(|e| panic!("Invalid endpoint {}: {e}", upload.endpoint));
+	let host = match (endpoint.host_str(), endpoint.port()) {
+		(Some(host), Some(port)) => format!("{host}:{port}"),
+		(Some(host), None) => host.to_string(),
+		_ => panic!("Invalid endpoint {}: no host", upload.endpoint),
+	};
+	let client = Client::new();
+	let mut files = files.to_vec();
+	files.sort_by_key(|f| !f.ends_with(".zip"));
+
+	let mut urls = vec![];
+	for file in files {
+		let name = file.rsplit(SLASH).next().unwrap();
+		let path = s3_encode(&format!(
+			"{}/{}/{}{name}",
+			endpoint.path().trim_end_matches('/'),
+			upload.bucket,
+			upload.prefix
+		));
+		let data = fs::read(&file).unwrap_or_else(|_| panic!("Failed to read file: {file}"));
+		let now = pdtchangelog::now();
+		let (year, month, day) = pdtchangelog::date(now);
+		let secs = now % 86400;
+		let time = format!(
+			"{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+			secs / 3600,
+			secs / 60 % 60,
+			secs % 60
+		);
+		let cache_control = match name.ends_with(".zip") {
+			true => upload.cache_control.clone(),
+			false => CHECKSUMS_CACHE_CONTROL.to_string(),
+		};
+		let mut headers = vec![
+			("cache-control", cache_control),
+			("content-type", content_type(&file).to_string()),
+			("host", host.clone()),
+			("x-amz-content-sha256", pdthash::get_bytes_hash(&data)),
+			("x-amz-date", time.clone()),
+		];
+		if let Some(token) = &token {
+			headers.push(("x-amz-security-token", token.clone()));
+		}
+		let authorization = s3_authorization(upload, &key_id, &secret, &path, &headers, &time);
+		let url = format!("{}://{host}{path}", endpoint.scheme());
+
+		log::info!("Uploading {name} to {url}");
+		let mut request = client
+			.put(&url)
+			.header("Authorization", authorization)
+			.body(data);
+		for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+			request = request.header(name, value);
+		}
+		send(request, &format!("upload {name}"));
+		urls.push(url);
+	}
+	urls
+}
@@ -0,0 +1,189 @@
+use super::{pdtfs, pdtlog};
+use camino::Utf8Path;
+use clap::ArgAction;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{MAIN_SEPARATOR as SLASH, MAIN_SEPARATOR_STR};
+
+/// Weights of the variants, read from the variants folder unless another
+/// file is given.
+pub const VARIANTS_FILE: &str = "variants.toml";
+/// Where OptiFine looks for the variants of a texture, by its path under
+/// textures. Entity Texture Features reads the same files on newer versions.
+pub const RANDOM_DIR: &str = "assets/minecraft/optifine/random";
+
+/// Weights of the variants of every texture.
+///
+/// ```toml
+/// [[texture]]
+/// path = "entity/cow/cow"
+/// weight = 4
+///
+/// [texture.variants]
+/// spotted = 2
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VariantsConfig {
+	#[serde(default, rename = "texture")]
+	pub textures: Vec<TextureWeights>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextureWeights {
+	/// Path of the texture under textures, without .png
+	pub path: String,
+	/// Weight of the pack's own texture, which is always the first variant
+	#[serde(default = "default_weight")]
+	pub weight: u32,
+	/// Weights of the variants by their file name without .png, 1 for the
+	/// ones not listed
+	#[serde(default)]
+	pub variants: BTreeMap<String, u32>,
+}
+
+fn default_weight() -> u32 {
+	1
+}
+
+/// The variants of one texture, in the order they are numbered.
+#[derive(Clone, Debug)]
+pub struct TextureVariants {
+	pub texture: String,
+	/// Name and path of every variant, sorted by name
+	pub variants: Vec<(String, String)>,
+}
+
+/// Finds the variant pngs in a folder laid out like textures, with one
+/// folder per texture named after it, like `entity/cow/cow/spotted.png`.
+pub fn find_variants(dir: &str) -> Vec<TextureVariants> {
+	let dir = pdtfs::check_dir_ends_with_slash(dir.to_string());
+	let mut textures = BTreeMap::<String, Vec<(String, String)>>::new();
+	for file in pdtfs::find_files_in_dir(&dir, true, &Some(vec![".png".to_string()])) {
+		let relative = file[dir.len()..].replace(SLASH, "/");
+		let Some((texture, name)) = relative.rsplit_once('/') else {
+			log::warn!("{relative} isn't in a folder named after its texture, skipping it");
+			continue;
+		};
+		let name = name.strip_suffix(".png").unwrap_or(name).to_string();
+		textures
+			.entry(texture.to_string())
+			.or_default()
+			.push((name, file));
+	}
+	textures
+		.into_iter()
+		.map(|(texture, mut variants)| {
+			variants.sort();
+			TextureVariants { texture, variants }
+		})
+		.collect()
+}
+
+/// The OptiFine properties giving every variant of a texture its weight.
+/// The weights are left out when they are all the same.
+pub fn properties(variants: &TextureVariants, weights: Option<&TextureWeights>) -> String {
+	let count = variants.variants.len() + 1;
+	let mut lines = vec![format!("textures.1=1-{count}")];
+	let weights = std::iter::once(weights.map(|w| w.weight).unwrap_or(1))
+		.chain(variants.variants.iter().map(|(name, _)| {
+			weights
+				.and_then(|w| w.variants.get(name))
+				.copied()
+				.unwrap_or(1)
+		}))
+		.collect::<Vec<_>>();
+	if weights.iter().any(|w| *w != weights[0]) {
+		let weights = weights.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+		lines.push(format!("weights.1={}", weights.join(" ")));
+	}
+	lines.join("\n") + "\n"
+}
+
+/// Copies the variants into a pack as the numbered textures OptiFine wants,
+/// starting at 2, and writes the properties next to them. Returns how many
+/// variants were written.
+pub fn write_variants(pack: &str, variants: &[TextureVariants], config: &VariantsConfig) -> usize {
+	for weights in &config.textures {
+		let Some(found) = variants.iter().find(|v| v.texture == weights.path) else {
+			log::warn!("No variants found for {}", weights.path);
+			continue;
+		};
+		for name in weights.variants.keys() {
+			if !found.variants.iter().any(|(n, _)| n == name) {
+				log::warn!("{} has no variant named {name}", weights.path);
+			}
+		}
+	}
+	let mut written = 0;
+	for texture in variants {
+		let out = format!(
+			"{pack}{SLASH}{}{SLASH}{}",
+			RANDOM_DIR.replace('/', MAIN_SEPARATOR_STR),
+			texture.texture.replace('/', MAIN_SEPARATOR_STR)
+		);
+		for (i, (name, path)) in texture.variants.iter().enumerate() {
+			log::debug!("{}: {name} is variant {}", texture.texture, i + 2);
+			pdtfs::copy_file(path, &format!("{out}{}.png", i + 2));
+			written += 1;
+		}
+		let weights = config.textures.iter().find(|w| w.path == texture.texture);
+		let file = format!("{out}.properties");
+		if let Some(dir) = Utf8Path::new(&file).parent() {
+			fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		}
+		fs::write(&file, properties(texture, weights))
+			.unwrap_or_else(|_| panic!("Failed to write file: {file}"));
+	}
+	written
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RandomArgs {
+	/// Folder of the variants, with a folder for every texture like
+	/// entity/cow/cow
+	variants: String,
+	/// Pack folder to add the variants to
+	pack: String,
+	#[arg(short, long)]
+	/// Weights of the variants, defaults to variants.toml in the variants
+	/// folder if it has one
+	config: Option<String>,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+pub fn run(args: RandomArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	pdtfs::check_if_dir_exists(&args.variants);
+	pdtfs::check_if_dir_exists(&args.pack);
+	let default_config = format!(
+		"{}{SLASH}{VARIANTS_FILE}",
+		args.variants.trim_end_matches(SLASH)
+	);
+	let config = match args.config {
+		Some(path) => Some(path),
+		None => pdtfs::is_file(&default_config).then_some(default_config),
+	};
+	let config = match config {
+		Some(path) => {
+			let data =
+				fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+			toml::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse {path}: {e}"))
+		}
+		None => VariantsConfig::default(),
+	};
+	let variants = find_variants(&args.variants);
+	let written = write_variants(&args.pack, &variants, &config);
+	log::info!(
+		"Added {written} variants of {} textures to {}",
+		variants.len(),
+		args.pack
+	);
+}
@@ -0,0 +1,2927 @@
+use super::pdtaudio::{self, AudioConfig};
+use super::pdtblockify::{self, BlockifyArgs};
+use super::pdtcache::Cache;
+use super::pdtcredits::{self, CreditsConfig};
+use super::pdtfs::{FileFilter, Symlinks, TempDir};
+use super::pdthistory::{History, HistoryConfig, Source};
+use super::pdtjson::{self, JsonConfig, JsonStyle};
+use super::pdtlock::Locker;
+use super::pdtlog::{Problem, Progress};
+use super::pdtoptimize::{self, Quantize, Strip};
+use super::pdtpipeline::{Pipeline, PipelineConfig, Scope, Stage, StageContext};
+use super::pdtreport::Stats;
+use super::pdtvalidate::{self, ValidateConfig, VanillaSounds};
+use super::{
+	pdtchangelog, pdtcmd, pdtcolor, pdtfont, pdtfs, pdthash, pdthistory, pdtlog, pdtmojang,
+	pdtthread, pdtzip,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::ArgAction;
+use glob::Pattern;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use oxipng::{InFile, OutFile};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+
+pub const CONFIG_FILE: &str = "builder.toml";
+pub const CHECKSUMS_FILE: &str = "checksums";
+/// Text files `{{placeholders}}` are filled in.
+pub const TEMPLATE_EXTENSIONS: [&str; 6] =
+	[".json", ".mcmeta", ".txt", ".md", ".lang", ".properties"];
+/// First pack_format with overlays and supported_formats, 1.20.2.
+pub const OVERLAY_PACK_FORMAT: u32 = 18;
+/// The versions pack_format changed at, for files tagged with the versions
+/// they are for.
+pub const VERSION_FORMATS: [(&str, u32); 21] = [
+	("1.6.1", 1),
+	("1.9", 2),
+	("1.11", 3),
+	("1.13", 4),
+	("1.15", 5),
+	("1.16.2", 6),
+	("1.17", 7),
+	("1.18", 8),
+	("1.19", 9),
+	("1.19.3", 12),
+	("1.19.4", 13),
+	("1.20", 15),
+	("1.20.2", 18),
+	("1.20.3", 22),
+	("1.20.5", 32),
+	("1.21", 34),
+	("1.21.2", 42),
+	("1.21.4", 46),
+	("1.21.5", 55),
+	("1.21.6", 63),
+	("1.21.7", 64),
+];
+/// Highest pack_format of the overlays for a version and every later one.
+const NEWER_FORMATS: u32 = i32::MAX as u32;
+
+/// A base pack or addon, either a folder or a zip. Files and folders in it
+/// tagged with the versions they are for, like `stone@1.21+.png`,
+/// `block@1.20.2-1.20.6` or `en_us@1.21.json`, are moved into an overlay for
+/// those versions, listed in pack.mcmeta of the releases that have them.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layer {
+	/// Defaults to the folder or zip name
+	#[serde(default)]
+	pub name: String,
+	pub path: String,
+	/// Namespaces the assets of the layer are in, like create for an addon
+	/// supporting a mod. Assets in other namespaces are left out with a
+	/// warning, any namespace goes if empty
+	#[serde(default)]
+	pub namespaces: Vec<String>,
+}
+
+/// A release zip made of the base pack and some addons, applied in order.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Combination {
+	pub name: String,
+	#[serde(default)]
+	pub addons: Vec<String>,
+	/// Written to pack.mcmeta, instead of the base pack's pack_format.
+	/// Targets have their own
+	pub pack_format: Option<u32>,
+}
+
+/// Which addons can go in a release together, from the [compatibility]
+/// table of the build config. Releases breaking the rules aren't built.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompatibilityConfig {
+	/// Groups of addons only one of which can be in a release, like the mane
+	/// styles or the seasons
+	#[serde(default)]
+	pub exclusive: Vec<Vec<String>>,
+	/// Addons that only work with others in the release too
+	#[serde(default)]
+	pub requires: BTreeMap<String, Vec<String>>,
+}
+
+impl CompatibilityConfig {
+	/// Every rule a set of addons breaks.
+	pub fn problems(&self, addons: &[String]) -> Vec<String> {
+		let mut problems = vec![];
+		for group in &self.exclusive {
+			let found = group
+				.iter()
+				.filter(|a| addons.contains(a))
+				.collect::<Vec<_>>();
+			if found.len() > 1 {
+				let found = found.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+				problems.push(format!("{} can't be used together", found.join(", ")));
+			}
+		}
+		for (addon, required) in &self.requires {
+			if !addons.contains(addon) {
+				continue;
+			}
+			for required in required.iter().filter(|r| !addons.contains(r)) {
+				problems.push(format!("{addon} needs {required}"));
+			}
+		}
+		problems
+	}
+}
+
+/// Builds a release for every combination of addons the [compatibility]
+/// rules allow, from the [combinations] table of the build config. They are
+/// named after the base and their addons, like Love-and-Tolerance-seasonal.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CombinationsConfig {
+	/// Addons to combine, in the order they are applied. Every addon if empty
+	#[serde(default)]
+	pub addons: Vec<String>,
+	/// Most addons in one release, any number if not given
+	pub max: Option<usize>,
+	/// Build the base pack without addons too
+	#[serde(default = "default_true")]
+	pub base: bool,
+}
+
+fn default_true() -> bool {
+	true
+}
+
+/// A Minecraft version every release gets its own zip for.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+	/// Added to the zip names, like Love-and-Tolerance-1.20.1.zip
+	pub minecraft: String,
+	pub pack_format: u32,
+	/// Lowest and highest pack_format the zip says it works with
+	pub supported_formats: Option<[u32; 2]>,
+	/// Folder or zip laid over the releases for this version only, for
+	/// files that are laid out differently in it
+	pub overrides: Option<String>,
+}
+
+/// Files only used by clients with a pack_format in the range, kept in
+/// their own folder of the zip and listed in pack.mcmeta. Left out of
+/// targets older than 1.20.2, which don't know about overlays.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Overlay {
+	/// Folder in the zip, only a-z, 0-9, _ and -
+	pub directory: String,
+	pub formats: [u32; 2],
+	/// Folder or zip with the files of the overlay
+	pub path: String,
+}
+
+/// A layer made by blockifying another one before the releases are built.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockifyStep {
+	/// Name of the addon it becomes
+	pub name: String,
+	/// Name of the layer to blockify
+	pub source: String,
+	/// Folder or zip of block textures
+	pub palette: Option<String>,
+	/// Minecraft version to use the vanilla block textures of instead
+	pub palette_version: Option<String>,
+	pub block_pixels: u32,
+	/// Scale the palette blocks in linear light, see blockify --linear
+	#[serde(default)]
+	pub linear: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptimizeConfig {
+	/// oxipng preset [0..6]
+	#[serde(default = "default_optimize_level")]
+	pub level: u8,
+	/// Which ancillary png chunks to strip, none, safe or all
+	#[serde(default = "default_strip")]
+	pub strip: Strip,
+	/// Chunks to keep even when stripping, like gAMA
+	#[serde(default)]
+	pub keep: Vec<String>,
+	/// Compress with Zopfli, doing this many iterations. Slower, but gives
+	/// smaller zips
+	pub zopfli: Option<u8>,
+	/// Only try the quickest compression, see build --fast
+	#[serde(default)]
+	pub fast: bool,
+	/// Pngs to reduce to fewer colors, the first rule matching a png is used
+	#[serde(default)]
+	pub quantize: Vec<Quantize>,
+	/// Convert tga and jpeg textures to pngs, which are the only ones
+	/// Minecraft loads
+	#[serde(default)]
+	pub convert: bool,
+}
+
+impl OptimizeConfig {
+	pub fn options(&self) -> oxipng::Options {
+		pdtoptimize::png_options(self.level, &self.strip, &self.keep, self.zopfli, self.fast)
+	}
+}
+
+fn default_optimize_level() -> u8 {
+	2
+}
+
+fn default_strip() -> Strip {
+	Strip::All
+}
+
+/// How textures are scaled down.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+	/// Averages the pixels every new pixel covers, every block of pixels
+	/// when scaling by whole numbers
+	Box,
+	Nearest,
+	Triangle,
+	CatmullRom,
+	Gaussian,
+	Lanczos3,
+}
+
+impl Filter {
+	/// Resizes an image with the filter, blending in linear light if asked to.
+	pub fn resize(self, image: &RgbaImage, width: u32, height: u32, linear: bool) -> RgbaImage {
+		let filter = match self {
+			Filter::Box => return pdtcolor::resize_box(image, width, height, linear),
+			Filter::Nearest => FilterType::Nearest,
+			Filter::Triangle => FilterType::Triangle,
+			Filter::CatmullRom => FilterType::CatmullRom,
+			Filter::Gaussian => FilterType::Gaussian,
+			Filter::Lanczos3 => FilterType::Lanczos3,
+		};
+		match linear {
+			true => pdtcolor::resize_linear(image, width, height, filter),
+			false => imageops::resize(image, width, height, filter),
+		}
+	}
+}
+
+/// A lower resolution zip built next to every release, from the [lite]
+/// table of the build config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LiteConfig {
+	/// Resolution of the pack, like 32 for 32x, found from its stone texture
+	/// if not given
+	pub resolution: Option<u32>,
+	/// Resolution of the lite zips, textures are scaled down by the same
+	/// amount so paintings and everything else keep their size
+	#[serde(default = "default_lite_resolution")]
+	pub max_resolution: u32,
+	#[serde(default = "default_filter")]
+	pub filter: Filter,
+	/// Added to the release names for the lite zips
+	#[serde(default = "default_lite_suffix")]
+	pub suffix: String,
+	/// Blend the pixels in linear light instead of as sRGB values, which
+	/// keeps bright details on dark textures from turning muddy
+	#[serde(default)]
+	pub linear: bool,
+}
+
+/// Lower resolution zips of every release, from the [resolutions] table of
+/// the build config. The releases and their scaled copies are named with
+/// their resolution and the version, like `Pack-64x-v1.2.0`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResolutionsConfig {
+	/// Resolution of the pack, found from its stone texture if not given
+	pub source: Option<u32>,
+	/// Resolutions to build, each dividing the one of the pack
+	pub sizes: Vec<u32>,
+	#[serde(default = "default_resolutions_filter")]
+	pub filter: Filter,
+	/// Blend the pixels in linear light, like in [lite]
+	#[serde(default)]
+	pub linear: bool,
+}
+
+fn default_resolutions_filter() -> Filter {
+	Filter::Box
+}
+
+/// pack.png made from a logo for every zip, from the [icon] table of the
+/// build config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IconConfig {
+	/// Image to make pack.png from, of any size. Logos that aren't square
+	/// are centered on a transparent square
+	pub logo: String,
+	/// Width and height of pack.png
+	#[serde(default = "default_icon_size")]
+	pub size: u32,
+	#[serde(default = "default_filter")]
+	pub filter: Filter,
+	/// Write the version in the bottom right corner
+	#[serde(default)]
+	pub stamp_version: bool,
+	/// Scale the logo in linear light, like in [lite]
+	#[serde(default)]
+	pub linear: bool,
+}
+
+fn default_icon_size() -> u32 {
+	128
+}
+
+fn default_lite_resolution() -> u32 {
+	16
+}
+
+fn default_filter() -> Filter {
+	Filter::Lanczos3
+}
+
+fn default_lite_suffix() -> String {
+	"lite".to_string()
+}
+
+/// OptiFine emissive textures to generate, from the [emissive] table of the
+/// build config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmissiveConfig {
+	/// Added to the texture names for the emissive ones
+	#[serde(default = "default_emissive_suffix")]
+	pub suffix: String,
+	/// Textures by their path in the pack, mapped to a mask of the same size
+	/// whose white pixels glow. Grey and transparent pixels glow less
+	pub masks: BTreeMap<String, String>,
+}
+
+fn default_emissive_suffix() -> String {
+	"_e".to_string()
+}
+
+/// An artwork sliced into the numbered tiles of an OptiFine CTM method by
+/// the build, from a [[ctm]] entry of the build config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CtmSheet {
+	/// Image with every tile in a grid, numbered left to right and top to
+	/// bottom
+	pub sheet: String,
+	/// Folder in the pack the tiles and properties go in, like
+	/// assets/minecraft/optifine/ctm/glass
+	pub dir: String,
+	/// Layer the tiles are added to, the base pack if not given
+	pub layer: Option<String>,
+	#[serde(default = "default_ctm_method")]
+	pub method: String,
+	/// Columns and rows of the grid, the width and height of the pattern for
+	/// repeat. Defaults to OptiFine's 12 by 4 template for ctm and to one row
+	/// for the other methods taking a fixed number of tiles
+	pub grid: Option<[u32; 2]>,
+	/// Written to the properties too, like matchBlocks
+	#[serde(default)]
+	pub properties: BTreeMap<String, String>,
+}
+
+fn default_ctm_method() -> String {
+	"ctm".to_string()
+}
+
+/// Where OptiFine reads the emissive suffix from.
+pub const EMISSIVE_PROPERTIES: &str = "assets/minecraft/optifine/emissive.properties";
+
+/// Settings for using the zips as a server resource pack, from the
+/// [server] table of the build config.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+	/// Where players download the zips from, `{file}` is the zip name. Needed
+	/// by `build --server`
+	pub url: Option<String>,
+	/// Where `build --server` uploads the zips to with a PUT request,
+	/// `{file}` is the zip name. PDT_UPLOAD_TOKEN is sent as a bearer token
+	/// when it is set
+	pub upload: Option<String>,
+	/// Players have to accept the pack to join
+	#[serde(default)]
+	pub require: bool,
+	/// Text shown when players are asked to accept the pack
+	pub prompt: Option<String>,
+}
+
+/// Written next to every zip by `build --server`, after the zip name.
+pub const SERVER_PROPERTIES_EXTENSION: &str = ".server.properties";
+
+/// Somewhere the release zips get uploaded to, with settings depending on
+/// the target.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PublishTarget {
+	pub target: String,
+	#[serde(flatten)]
+	pub settings: BTreeMap<String, serde_json::Value>,
+}
+
+/// Everything needed to build the releases, usually read from builder.toml.
+/// Relative paths in it are relative to the file.
+///
+/// ```toml
+/// version = "1.2.0"
+/// version_files = ["Love-and-Tolerance/pack.mcmeta", "credits.txt"]
+/// output = "release_output"
+///
+/// [base]
+/// path = "Love-and-Tolerance"
+///
+/// [[addon]]
+/// name = "seasonal"
+/// path = "addons/seasonal.zip"
+///
+/// [[addon]]
+/// name = "create"
+/// path = "addons/create"
+/// namespaces = ["create"]
+///
+/// [[release]]
+/// name = "Love-and-Tolerance-seasonal"
+/// addons = ["seasonal"]
+///
+/// [[target]]
+/// minecraft = "1.20.1"
+/// pack_format = 15
+/// overrides = "versions/1.20.1"
+///
+/// [[target]]
+/// minecraft = "1.21"
+/// pack_format = 34
+/// supported_formats = [18, 34]
+///
+/// [[overlay]]
+/// directory = "1_21_overlay"
+/// formats = [32, 34]
+/// path = "overlays/1.21"
+///
+/// [files]
+/// exclude = ["**/*.xcf", "**/*.psd", 're:/wip_[^/]*\.png$']
+///
+/// [optimize]
+/// level = 4
+///
+/// [audio]
+/// quality = 0.6
+///
+/// [lite]
+/// max_resolution = 16
+/// linear = true
+///
+/// [resolutions]
+/// sizes = [64, 32]
+///
+/// [icon]
+/// logo = "logo.png"
+/// stamp_version = true
+///
+/// [[ctm]]
+/// sheet = "ctm/glass.png"
+/// dir = "assets/minecraft/optifine/ctm/glass"
+/// properties = { matchBlocks = "glass" }
+///
+/// [emissive.masks]
+/// "assets/minecraft/textures/block/redstone_ore.png" = "masks/redstone_ore.png"
+///
+/// [validate]
+/// minecraft = "1.21"
+/// namespaces = ["minecraft", "create"]
+///
+/// [compatibility]
+/// exclusive = [["winter", "summer"]]
+/// requires = { snow = ["winter"] }
+///
+/// [combinations]
+/// addons = ["winter", "summer", "snow"]
+///
+/// [json]
+/// minify = true
+///
+/// [credits]
+/// contributors = "contributors.toml"
+/// json = "credits.json"
+///
+/// [history]
+/// report = true
+///
+/// [pipeline]
+/// disable = ["audio"]
+///
+/// [server]
+/// url = "https://example.com/packs/{file}"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+	/// Version of the pack, changed by pdt version bump
+	pub version: Option<String>,
+	/// Files with the version in them, like pack.mcmeta or credits.txt,
+	/// updated together with it
+	#[serde(default)]
+	pub version_files: Vec<String>,
+	#[serde(default = "default_output")]
+	pub output: String,
+	/// Warn about addons changing the same file instead of stopping, the
+	/// last addon wins
+	#[serde(default)]
+	pub allow_conflicts: bool,
+	/// Deflate level of the release zips [0..9], 6 if not given
+	pub compression_level: Option<i64>,
+	/// Folder that optimized, converted and blockified files are kept in
+	/// between builds, so only changed files are processed again
+	#[serde(default = "default_cache")]
+	pub cache: Option<String>,
+	/// Which files of the layers are used
+	#[serde(default)]
+	pub files: FilesConfig,
+	pub base: Layer,
+	#[serde(default, rename = "addon")]
+	pub addons: Vec<Layer>,
+	#[serde(default)]
+	pub blockify: Vec<BlockifyStep>,
+	/// Defaults to one release with every addon, named after the base
+	#[serde(default, rename = "release")]
+	pub releases: Vec<Combination>,
+	/// Which addons can't be in a release together
+	#[serde(default)]
+	pub compatibility: CompatibilityConfig,
+	/// Adds a release for every valid combination of addons
+	pub combinations: Option<CombinationsConfig>,
+	/// Without targets every release is built once, as the base pack is
+	#[serde(default, rename = "target")]
+	pub targets: Vec<Target>,
+	#[serde(default, rename = "overlay")]
+	pub overlays: Vec<Overlay>,
+	pub optimize: Option<OptimizeConfig>,
+	/// Converts wav and mp3 sounds to ogg
+	pub audio: Option<AudioConfig>,
+	pub emissive: Option<EmissiveConfig>,
+	#[serde(default)]
+	pub ctm: Vec<CtmSheet>,
+	pub lite: Option<LiteConfig>,
+	pub resolutions: Option<ResolutionsConfig>,
+	pub icon: Option<IconConfig>,
+	/// Checks every release before it is zipped, problems stop the build
+	pub validate: Option<ValidateConfig>,
+	/// Writes credits.txt into the base pack from contributors.toml
+	pub credits: Option<CreditsConfig>,
+	/// How the format-json stage writes json files
+	#[serde(default)]
+	pub json: JsonConfig,
+	/// Adds assets_manifest.json to the releases, with the commit and author
+	/// that last changed every file
+	pub history: Option<HistoryConfig>,
+	/// Which stages of the build run and in what order
+	#[serde(default)]
+	pub pipeline: PipelineConfig,
+	/// Values for `{{name}}` placeholders, on top of version, build_date and
+	/// commit
+	#[serde(default)]
+	pub placeholders: BTreeMap<String, String>,
+	#[serde(default)]
+	pub publish: Vec<PublishTarget>,
+	pub server: Option<ServerConfig>,
+}
+
+/// Which files of the layers go in the releases, from the [files] table.
+/// Files listed in .pdtignore and .packignore files in the layers are always
+/// left out.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilesConfig {
+	/// Globs of the paths in the pack to use, every file if empty. Ones
+	/// starting with re: are regexes
+	#[serde(default)]
+	pub include: Vec<String>,
+	/// Globs of the paths in the pack to leave out, like source files of
+	/// textures, or regexes starting with re:
+	#[serde(default)]
+	pub exclude: Vec<String>,
+	/// Leave out files listed in .gitignore files in the layers too
+	#[serde(default)]
+	pub gitignore: bool,
+	/// follow, skip or error on symlinks in the layers
+	#[serde(default)]
+	pub symlinks: Symlinks,
+	/// Changes made to the files of the layers as they are read
+	#[serde(default)]
+	pub transform: Vec<TransformRule>,
+}
+
+/// A change made to the files of the layers matching a glob, before any
+/// stage runs. Only the first rule a file matches is used.
+///
+/// ```toml
+/// [[files.transform]]
+/// glob = "assets/*/lang/*.json"
+/// transform = "minify-json"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformRule {
+	/// Glob of the paths in the pack
+	pub glob: String,
+	pub transform: Transform,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transform {
+	MinifyJson,
+	/// Indented with tabs
+	FormatJson,
+}
+
+impl Transform {
+	/// The file changed, None if it can't be, like json that doesn't parse.
+	fn apply(self, name: &str, data: &str) -> Option<String> {
+		pdtjson::parse_json(name, &pdtjson::strip_comments(data)).ok()?;
+		Some(match self {
+			Transform::MinifyJson => pdtjson::minify_json(data),
+			Transform::FormatJson => pdtjson::format_json(data, "\t"),
+		})
+	}
+}
+
+impl fmt::Display for Transform {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Transform::MinifyJson => write!(f, "minify-json"),
+			Transform::FormatJson => write!(f, "format-json"),
+		}
+	}
+}
+
+impl FilesConfig {
+	pub fn filter(&self) -> FileFilter {
+		let ignore_files = match self.gitignore {
+			true => &pdtfs::IGNORE_FILES[..],
+			false => &pdtfs::PACK_IGNORE_FILES[..],
+		};
+		FileFilter::new(&self.include, &self.exclude, ignore_files, self.symlinks)
+	}
+}
+
+fn default_output() -> String {
+	"release_output".to_string()
+}
+
+fn default_cache() -> Option<String> {
+	Some(Utf8Path::new(".pdt_build").join("cache").to_string())
+}
+
+impl BuildConfig {
+	/// Reads a build config from toml, or from json if the file ends in .json.
+	pub fn load(path: &str) -> BuildConfig {
+		let data = fs::read_to_string(pdtfs::long_path(path))
+			.unwrap_or_else(|_| panic!("Failed to read config: {path}"));
+		let mut config: BuildConfig = match path.ends_with(".json") {
+			true => serde_json::from_str(&data)
+				.unwrap_or_else(|e| panic!("Failed to parse config {path}: {e}")),
+			false => toml::from_str(&data)
+				.unwrap_or_else(|e| panic!("Failed to parse config {path}: {e}")),
+		};
+		let dir = Utf8Path::new(path)
+			.parent()
+			.map(|p| p.as_str())
+			.unwrap_or("");
+		let resolve = |p: &mut String| match dir.is_empty() || Utf8Path::new(p).is_absolute() {
+			true => (),
+			false => *p = Utf8Path::new(dir).join(&*p).to_string(),
+		};
+		resolve(&mut config.output);
+		if let Some(cache) = &mut config.cache {
+			resolve(cache);
+		}
+		for file in &mut config.version_files {
+			resolve(file);
+		}
+		resolve(&mut config.base.path);
+		for addon in &mut config.addons {
+			resolve(&mut addon.path);
+		}
+		for step in &mut config.blockify {
+			if let Some(palette) = &mut step.palette {
+				resolve(palette);
+			}
+		}
+		for target in &mut config.targets {
+			if let Some(overrides) = &mut target.overrides {
+				resolve(overrides);
+			}
+		}
+		for overlay in &mut config.overlays {
+			resolve(&mut overlay.path);
+		}
+		if let Some(icon) = &mut config.icon {
+			resolve(&mut icon.logo);
+		}
+		if let Some(credits) = &mut config.credits {
+			resolve(&mut credits.contributors);
+		}
+		if let Some(emissive) = &mut config.emissive {
+			emissive.masks.values_mut().for_each(resolve);
+		}
+		for sheet in &mut config.ctm {
+			resolve(&mut sheet.sheet);
+		}
+		config.fill_defaults();
+		config
+	}
+
+	/// Names unnamed layers after their path and adds the default release
+	/// when there are none.
+	pub fn fill_defaults(&mut self) {
+		for layer in std::iter::once(&mut self.base).chain(&mut self.addons) {
+			if layer.name.is_empty() {
+				layer.name = layer_name(&layer.path);
+			}
+		}
+		let addons = self.addons.iter().map(|a| a.name.clone());
+		let blockified = self.blockify.iter().map(|b| b.name.clone());
+		let addons = addons.chain(blockified).collect::<Vec<_>>();
+		if let Some(combinations) = &self.combinations {
+			let addons = match combinations.addons.is_empty() {
+				true => addons,
+				false => combinations.addons.clone(),
+			};
+			let generated = self.valid_combinations(&addons, combinations);
+			self.releases.extend(generated);
+			return;
+		}
+		if self.releases.is_empty() {
+			self.releases.push(Combination {
+				name: self.base.name.clone(),
+				addons,
+				pack_format: None,
+			});
+		}
+	}
+
+	/// Every combination of the addons the compatibility rules allow, with
+	/// the addons in the order given.
+	fn valid_combinations(
+		&self, addons: &[String], config: &CombinationsConfig,
+	) -> Vec<Combination> {
+		if addons.len() > 16 {
+			panic!(
+				"{} addons make too many combinations, list fewer in [combinations]",
+				addons.len()
+			);
+		}
+		let max = config.max.unwrap_or(addons.len());
+		let mut combinations = vec![];
+		for mask in 0..1u32 << addons.len() {
+			let chosen = addons
+				.iter()
+				.enumerate()
+				.filter(|(i, _)| mask & 1 << i != 0)
+				.map(|(_, a)| a.clone())
+				.collect::<Vec<_>>();
+			if chosen.len() > max || (chosen.is_empty() && !config.base) {
+				continue;
+			}
+			let problems = self.compatibility.problems(&chosen);
+			if !problems.is_empty() {
+				log::debug!(
+					"not building {}: {}",
+					chosen.join(", "),
+					problems.join(", ")
+				);
+				continue;
+			}
+			let name = std::iter::once(&self.base.name)
+				.chain(&chosen)
+				.map(|n| n.as_str())
+				.collect::<Vec<_>>()
+				.join("-");
+			combinations.push(Combination {
+				name,
+				addons: chosen,
+				pack_format: None,
+			});
+		}
+		// fewest addons first, so the plain releases come first
+		combinations.sort_by_key(|c| c.addons.len());
+		combinations
+	}
+}
+
+/// Folder or zip name of a path.
+pub fn layer_name(path: &str) -> String {
+	let name = Utf8Path::new(path).file_name().unwrap_or(path);
+	name.trim_end_matches(".zip").to_string()
+}
+
+/// A file that more than one addon in a combination changes differently.
+#[derive(Debug)]
+pub struct Conflict {
+	pub file: String,
+	pub layers: Vec<String>,
+}
+
+/// Files of a layer the filter keeps, see [pdtzip::pack_files].
+fn get_layer_files(
+	layer: &Layer, temp_dirs: &mut Vec<TempDir>, filter: &FileFilter,
+) -> BTreeMap<String, String> {
+	let exists = pdtzip::is_zip(&layer.path) || pdtfs::is_dir(&layer.path);
+	if !exists {
+		panic!("Layer {} not found: {}", layer.name, layer.path);
+	}
+	let mut files = pdtzip::filtered_pack_files(&layer.path, temp_dirs, filter);
+	let outside = files
+		.keys()
+		.filter(|file| !pdtfs::in_namespaces(file, &layer.namespaces))
+		.cloned()
+		.collect::<Vec<_>>();
+	if !outside.is_empty() {
+		log::warn!(
+			"Leaving {} files outside the namespaces of {} out:\n  {}",
+			outside.len(),
+			layer.name,
+			outside.join("\n  ")
+		);
+		for file in outside {
+			files.remove(&file);
+		}
+	}
+	files
+}
+
+/// Lays the addons over the base in order, later layers replacing the files
+/// of earlier ones. Replacing a base file is expected, but two addons
+/// changing the same file differently is a conflict.
+pub fn overlay(
+	base: &BTreeMap<String, String>, addons: &[(&str, &BTreeMap<String, String>)],
+) -> (BTreeMap<String, String>, Vec<Conflict>) {
+	let mut files = base.clone();
+	let mut owners: BTreeMap<&str, Vec<(&str, String)>> = BTreeMap::new();
+	for (name, addon) in addons {
+		for (file, path) in addon.iter() {
+			let hash = pdthash::get_hash(path, false);
+			owners.entry(file).or_default().push((name, hash));
+			files.insert(file.clone(), path.clone());
+		}
+	}
+	let conflicts = owners
+		.into_iter()
+		.filter(|(_, layers)| layers.iter().any(|l| l.1 != layers[0].1))
+		.map(|(file, layers)| Conflict {
+			file: file.to_string(),
+			layers: layers.into_iter().map(|l| l.0.to_string()).collect(),
+		})
+		.collect();
+	(files, conflicts)
+}
+
+/// Copies the pngs of a layer into the staging directory, optimizes the
+/// copies and points the layer at them, quantizing the ones a rule is for
+/// first. Pngs optimized before with the same settings are taken from the
+/// cache. Tga and jpeg images are converted to pngs first if the config
+/// says so.
+fn optimize_layer(
+	files: &mut BTreeMap<String, String>, staging: &str, config: &OptimizeConfig,
+	cache: Option<&Cache>,
+) {
+	if config.convert {
+		convert_layer_images(files, staging);
+	}
+	let options = config.options();
+	let pngs = files
+		.iter()
+		.filter(|(name, _)| name.ends_with(".png"))
+		.map(|(name, path)| {
+			let staged = Utf8Path::new(&staging).join(name).to_string();
+			let colors = config
+				.quantize
+				.iter()
+				.find(|q| q.matches(name))
+				.map(|q| q.colors);
+			(name.clone(), path.clone(), staged, colors)
+		})
+		.collect();
+	let settings = format!(
+		"{} {:?} {:?} {:?} {}",
+		config.level, config.strip, config.keep, config.zopfli, config.fast
+	);
+	let cache = cache.cloned();
+	let optimized =
+		pdtthread::try_multithread(pngs, None, move |_, (name, path, staged, colors)| {
+			let settings = format!("{settings} {colors:?}");
+			let key = Cache::key(&["optimize", &settings, &pdthash::get_hash(&path, false)]);
+			if let Some(cache) = &cache {
+				if cache.get("optimize", &key, &staged) {
+					log::debug!("optimized image from cache: {name}");
+					pdtlog::file_done("optimize", &name);
+					return Ok((name, staged));
+				}
+			}
+			log::debug!("optimizing image: {name}");
+			pdtfs::copy_file(&path, &staged);
+			if pdtoptimize::to_srgb8(&staged) {
+				log::debug!("rewritten as 8-bit sRGB: {name}");
+			}
+			if let Some(colors) = colors {
+				if pdtoptimize::quantize(&staged, colors) {
+					log::debug!("quantized to {colors} colors: {name}");
+				}
+			}
+			if let Some(reduced) = pdtoptimize::reduce_color_type(&staged) {
+				log::debug!("rewritten as {reduced}: {name}");
+			}
+			let file = Utf8PathBuf::from(&staged);
+			oxipng::optimize(
+				&InFile::Path(file.clone().into()),
+				&OutFile::Path(Some(file.into())),
+				&options,
+			)
+			.map_err(|e| format!("Failed to optimize image {path}: {e}"))?;
+			if let Some(cache) = &cache {
+				cache.put("optimize", &key, &staged);
+			}
+			pdtlog::file_done("optimize", &name);
+			Ok::<_, String>((name, staged))
+		})
+		.unwrap_or_else(|errors| panic!("Failed to optimize every png: {errors}"));
+	let mut total = BTreeMap::new();
+	for (name, staged) in &optimized {
+		let read = |path: &str| {
+			fs::read(pdtfs::long_path(path))
+				.unwrap_or_else(|_| panic!("Failed to read file: {path}"))
+		};
+		pdtoptimize::savings(&read(&files[name]), &read(staged), &mut total);
+	}
+	if !optimized.is_empty() {
+		log::info!(
+			"{} pngs, {}",
+			optimized.len(),
+			pdtoptimize::savings_report(&total)
+		);
+	}
+	files.extend(optimized);
+}
+
+/// Converts the tga and jpeg images of a layer to pngs in the staging
+/// directory, unless the layer has a png of the same name already.
+fn convert_layer_images(files: &mut BTreeMap<String, String>, staging: &str) {
+	let images = files
+		.keys()
+		.filter(|name| pdtfs::is_image(name) && !name.ends_with(".png"))
+		.cloned()
+		.collect::<Vec<_>>();
+	for name in images {
+		let png = pdtfs::png_path(&name);
+		if files.contains_key(&png) {
+			log::warn!("{png} is in the layer already, not converting {name}");
+			continue;
+		}
+		let converted = Utf8Path::new(&staging)
+			.join("converted")
+			.join(&png)
+			.to_string();
+		if pdtoptimize::convert_to_png(&files[&name], &converted) {
+			log::debug!("converted {name} to png");
+			files.remove(&name);
+			files.insert(png, converted);
+		}
+	}
+}
+
+/// Converts the wav and mp3 sounds of a layer to ogg files in the staging
+/// directory and puts them in the layer instead of the sources. Sounds
+/// converted before with the same settings are taken from the cache.
+fn convert_layer_audio(
+	files: &mut BTreeMap<String, String>, staging: &str, config: &AudioConfig,
+	cache: Option<&Cache>,
+) {
+	let sources = files
+		.iter()
+		.filter(|(name, _)| name.starts_with("assets/") && name.contains("/sounds/"))
+		.filter(|(name, _)| pdtaudio::is_source(name))
+		.map(|(name, path)| {
+			let ogg = pdtaudio::ogg_path(name);
+			let staged = Utf8Path::new(&staging).join("audio").join(&ogg).to_string();
+			(name.clone(), path.clone(), ogg, staged, config.clone())
+		})
+		.collect::<Vec<_>>();
+	if sources.is_empty() {
+		return;
+	}
+	let cache = cache.cloned();
+	let converted = pdtthread::multithread(
+		sources,
+		None,
+		move |_, (name, path, ogg, staged, config)| {
+			let settings = format!(
+				"{} {} {:?}",
+				config.quality, config.sample_rate, config.loudness
+			);
+			let key = Cache::key(&["audio", &settings, &pdthash::get_hash(&path, false)]);
+			if let Some(cache) = &cache {
+				if cache.get("audio", &key, &staged) {
+					log::debug!("converted sound from cache: {name}");
+					return Some((name, ogg, staged));
+				}
+			}
+			log::debug!("converting sound: {name}");
+			let dir = Utf8Path::new(&staged).parent().unwrap();
+			fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+				.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+			pdtaudio::convert(&path, &staged, &config);
+			if let Some(cache) = &cache {
+				cache.put("audio", &key, &staged);
+			}
+			Some((name, ogg, staged))
+		},
+	);
+	for (name, ogg, staged) in converted {
+		files.remove(&name);
+		if files.contains_key(&ogg) {
+			log::warn!("{name} replaces {ogg} in the same layer");
+		}
+		files.insert(ogg, staged);
+	}
+}
+
+/// Writes the emissive texture of every texture in a layer that has a mask
+/// to the staging directory and adds it next to the texture, with a copy of
+/// its animation if it has one.
+fn generate_emissive(files: &mut BTreeMap<String, String>, staging: &str, config: &EmissiveConfig) {
+	let textures = config
+		.masks
+		.iter()
+		.filter_map(|(texture, mask)| {
+			let path = files.get(texture)?;
+			let stem = texture.strip_suffix(".png").unwrap_or(texture);
+			let emissive = format!("{stem}{}.png", config.suffix);
+			let staged = Utf8Path::new(&staging)
+				.join("emissive")
+				.join(&emissive)
+				.to_string();
+			Some((
+				texture.clone(),
+				path.clone(),
+				mask.clone(),
+				emissive,
+				staged,
+			))
+		})
+		.collect::<Vec<_>>();
+	let generated = pdtthread::multithread(
+		textures,
+		None,
+		|_, (texture, path, mask, emissive, staged)| {
+			log::debug!("generating emissive texture: {emissive}");
+			let mut image = image::open(pdtfs::long_path(&path))
+				.unwrap_or_else(|_| panic!("Failed to load image: {path}"))
+				.into_rgba8();
+			let mask_image = image::open(pdtfs::long_path(&mask))
+				.unwrap_or_else(|_| panic!("Failed to load image: {mask}"))
+				.into_rgba8();
+			if image.dimensions() != mask_image.dimensions() {
+				panic!(
+					"Mask {mask} is {:?}, but {texture} is {:?}",
+					mask_image.dimensions(),
+					image.dimensions()
+				);
+			}
+			for (pixel, mask) in image.pixels_mut().zip(mask_image.pixels()) {
+				let [r, g, b, a] = mask.0;
+				let strength = r.max(g).max(b) as u32 * a as u32 / 255;
+				pixel.0[3] = (pixel.0[3] as u32 * strength / 255) as u8;
+				if pixel.0[3] == 0 {
+					pixel.0 = [0, 0, 0, 0];
+				}
+			}
+			let dir = Utf8Path::new(&staged).parent().unwrap();
+			fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+				.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+			image
+				.save(pdtfs::long_path(&staged))
+				.unwrap_or_else(|e| panic!("Failed to write image {staged}: {e}"));
+			Some((texture, emissive, staged))
+		},
+	);
+	for (texture, emissive, staged) in generated {
+		if let Some(animation) = files.get(&format!("{texture}.mcmeta")).cloned() {
+			files.insert(format!("{emissive}.mcmeta"), animation);
+		}
+		files.insert(emissive, staged);
+	}
+}
+
+/// Slices a CTM sheet into its tiles and writes them with their properties
+/// to the staging directory, for the layer's files to point at.
+fn slice_ctm_sheet(files: &mut BTreeMap<String, String>, staging: &str, sheet: &CtmSheet) {
+	let fixed = pdtvalidate::ctm_tile_count(&sheet.method, 1, 1);
+	let [columns, rows] = match (sheet.grid, fixed) {
+		(Some(grid), _) => grid,
+		(None, Some(_)) if sheet.method.ends_with("repeat") => {
+			panic!("{} needs a grid for {}", sheet.sheet, sheet.method)
+		}
+		(None, Some(47)) => [12, 4],
+		(None, Some(count)) => [count as u32, 1],
+		(None, None) => panic!("{} needs a grid for {}", sheet.sheet, sheet.method),
+	};
+	let image = image::open(pdtfs::long_path(&sheet.sheet))
+		.unwrap_or_else(|_| panic!("Failed to load image: {}", sheet.sheet))
+		.into_rgba8();
+	let (width, height) = image.dimensions();
+	if columns == 0 || rows == 0 || width % columns != 0 || height % rows != 0 {
+		panic!(
+			"{} is {width}x{height}, which doesn't split into {columns} by {rows} tiles",
+			sheet.sheet
+		);
+	}
+	let count = pdtvalidate::ctm_tile_count(&sheet.method, columns, rows)
+		.unwrap_or((columns * rows) as usize);
+	if count > (columns * rows) as usize {
+		panic!(
+			"{} takes {count} tiles, a {columns} by {rows} grid only has {}",
+			sheet.method,
+			columns * rows
+		);
+	}
+	let (tile_width, tile_height) = (width / columns, height / rows);
+	let pack_dir = sheet.dir.trim_end_matches('/');
+	let staged_dir = Utf8Path::new(&staging)
+		.join("ctm")
+		.join(pack_dir)
+		.to_string();
+	fs::create_dir_all(pdtfs::long_path(&staged_dir))
+		.unwrap_or_else(|_| panic!("Failed to create {staged_dir} directory."));
+	for i in 0..count as u32 {
+		let (x, y) = (i % columns * tile_width, i / columns * tile_height);
+		let tile = imageops::crop_imm(&image, x, y, tile_width, tile_height).to_image();
+		let staged = Utf8Path::new(&staged_dir)
+			.join(format!("{i}.png"))
+			.to_string();
+		tile.save(pdtfs::long_path(&staged))
+			.unwrap_or_else(|_| panic!("Failed to save image: {staged}"));
+		files.insert(format!("{pack_dir}/{i}.png"), staged);
+	}
+	let mut lines = vec![
+		format!("method={}", sheet.method),
+		format!("tiles=0-{}", count - 1),
+	];
+	if sheet.method.ends_with("repeat") {
+		lines.push(format!("width={columns}"));
+		lines.push(format!("height={rows}"));
+	}
+	lines.extend(sheet.properties.iter().map(|(k, v)| format!("{k}={v}")));
+	let name = pack_dir.rsplit('/').next().unwrap();
+	let staged = Utf8Path::new(&staged_dir)
+		.join(format!("{name}.properties"))
+		.to_string();
+	fs::write(pdtfs::long_path(&staged), lines.join("\n") + "\n")
+		.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+	files.insert(format!("{pack_dir}/{name}.properties"), staged);
+	log::debug!("sliced {} into {count} tiles in {pack_dir}", sheet.sheet);
+}
+
+/// Adds OptiFine's emissive.properties with the suffix to the base files,
+/// unless the base has its own.
+fn add_emissive_properties(
+	files: &mut BTreeMap<String, String>, staging: &str, config: &EmissiveConfig,
+) {
+	if let Some(path) = files.get(EMISSIVE_PROPERTIES) {
+		let data = fs::read_to_string(pdtfs::long_path(path)).unwrap_or_default();
+		let properties = pdtvalidate::parse_properties(EMISSIVE_PROPERTIES, &data, &mut vec![]);
+		let suffix = properties.get("suffix.emissive").map(|(_, s)| s.as_str());
+		if suffix != Some(config.suffix.as_str()) {
+			log::warn!(
+				"{EMISSIVE_PROPERTIES} of the base doesn't use suffix {}",
+				config.suffix
+			);
+		}
+		return;
+	}
+	let staged = Utf8Path::new(&staging)
+		.join("emissive")
+		.join(EMISSIVE_PROPERTIES)
+		.to_string();
+	let dir = Utf8Path::new(&staged).parent().unwrap();
+	fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+		.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	fs::write(
+		pdtfs::long_path(&staged),
+		format!("suffix.emissive={}\n", config.suffix),
+	)
+	.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+	files.insert(EMISSIVE_PROPERTIES.to_string(), staged);
+}
+
+/// Writes the credits files to the staging directory and adds them to the
+/// base files, in place of any the base has.
+fn add_credits(files: &mut BTreeMap<String, String>, staging: &str, config: &CreditsConfig) {
+	let contributors = pdtcredits::load(config);
+	let text = (!config.text.is_empty()).then(|| (config.text.clone(), contributors.text()));
+	let json = config.json.clone().map(|j| (j, contributors.json()));
+	for (name, data) in text.into_iter().chain(json) {
+		if files.contains_key(&name) {
+			log::debug!("replacing {name} of the base with the generated credits");
+		}
+		let staged = Utf8Path::new(&staging)
+			.join("credits")
+			.join(&name)
+			.to_string();
+		let dir = Utf8Path::new(&staged).parent().unwrap();
+		fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+			.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		fs::write(pdtfs::long_path(&staged), data)
+			.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+		files.insert(name, staged);
+	}
+	log::info!("Credited {} contributors", contributors.contributors.len());
+}
+
+/// Remembers what file of a layer every file its stages left it with was
+/// made from, by the path the files point at, for the history manifest.
+fn add_sources(
+	sources: &mut BTreeMap<String, Source>, layer: &Layer, originals: &BTreeMap<String, String>,
+	files: &BTreeMap<String, String>,
+) {
+	let names = originals
+		.iter()
+		.map(|(name, path)| (path, name))
+		.collect::<BTreeMap<_, _>>();
+	let zip = pdtzip::is_zip(&layer.path);
+	for (file, path) in files {
+		// versioned files are moved to overlays, but still point at the original
+		let name = match names.get(path) {
+			Some(name) => *name,
+			None if originals.contains_key(file) => file,
+			None => continue,
+		};
+		let original = &originals[name];
+		let source = match zip {
+			true => Source {
+				layer: layer.name.clone(),
+				path: format!("{}!/{name}", layer.path),
+				tracked: layer.path.clone(),
+			},
+			false => Source {
+				layer: layer.name.clone(),
+				path: original.clone(),
+				tracked: original.clone(),
+			},
+		};
+		sources.insert(path.clone(), source);
+	}
+}
+
+/// Writes the history manifest of a release to the staging directory and
+/// adds it to the release, in place of any file of the layers at its path.
+fn add_manifest(
+	files: &mut BTreeMap<String, String>, release: &str, staging: &str, config: &HistoryConfig,
+	history: &mut History, sources: &BTreeMap<String, Source>,
+) {
+	if files.contains_key(&config.manifest) {
+		log::debug!("replacing {} with the history manifest", config.manifest);
+	}
+	let manifest = history.manifest(files, sources);
+	let staged = Utf8Path::new(&staging)
+		.join("history")
+		.join(&config.manifest)
+		.to_string();
+	let dir = Utf8Path::new(&staged).parent().unwrap();
+	fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+		.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	let data = serde_json::to_string_pretty(&manifest).unwrap() + "\n";
+	fs::write(pdtfs::long_path(&staged), data)
+		.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+	let unknown = manifest.values().filter(|h| h.revision.is_none()).count();
+	if unknown > 0 {
+		log::warn!("{unknown} files of {release} have no history in git");
+	}
+	files.insert(config.manifest.clone(), staged);
+}
+
+/// Values of the placeholders in text files, `{{version}}`,
+/// `{{build_date}}`, `{{commit}}` and the ones from the config.
+pub fn placeholder_values(config: &BuildConfig) -> BTreeMap<String, String> {
+	let mut values = BTreeMap::new();
+	if let Some(version) = &config.version {
+		values.insert("version".to_string(), version.clone());
+	}
+	values.insert("build_date".to_string(), pdtchangelog::today());
+	if let Some(commit) = pdtcmd::git(".", &["rev-parse", "--short", "HEAD"]) {
+		values.insert("commit".to_string(), commit.trim().to_string());
+	}
+	values.extend(config.placeholders.clone());
+	values
+}
+
+/// Writes copies of the text files with placeholders in them to the staging
+/// directory, with the placeholders filled in, and points the files at
+/// them. Unknown placeholders are left as they are.
+fn fill_placeholders(
+	files: &mut BTreeMap<String, String>, staging: &str, values: &BTreeMap<String, String>,
+) {
+	for (name, path) in files.iter_mut() {
+		if !TEMPLATE_EXTENSIONS.iter().any(|e| name.ends_with(e)) {
+			continue;
+		}
+		let Ok(data) = fs::read_to_string(pdtfs::long_path(&*path)) else {
+			continue;
+		};
+		if !data.contains("{{") {
+			continue;
+		}
+		let mut filled = data.clone();
+		for (key, value) in values {
+			filled = filled.replace(&format!("{{{{{key}}}}}"), value);
+		}
+		if filled.contains("{{") {
+			log::debug!("{name} has placeholders without a value");
+		}
+		if filled == data {
+			continue;
+		}
+		let staged = Utf8Path::new(&staging)
+			.join("templates")
+			.join(name)
+			.to_string();
+		let dir = Utf8Path::new(&staged).parent().unwrap();
+		fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+			.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		fs::write(pdtfs::long_path(&staged), filled)
+			.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+		*path = staged;
+	}
+}
+
+/// Runs the [[files.transform]] rules on the files of a layer in parallel.
+fn transform_files(
+	files: &mut BTreeMap<String, String>, rules: &[TransformRule], staging: &str, layer: &str,
+) {
+	if rules.is_empty() {
+		return;
+	}
+	let patterns = rules
+		.iter()
+		.map(|r| Pattern::new(&r.glob).unwrap_or_else(|e| panic!("Invalid glob {}: {e}", r.glob)))
+		.collect::<Vec<_>>();
+	let mut counts = vec![0; rules.len()];
+	let mut tasks = vec![];
+	for (name, path) in files.iter() {
+		if let Some(i) = patterns.iter().position(|p| p.matches(name)) {
+			counts[i] += 1;
+			tasks.push((name.clone(), path.clone(), rules[i].transform));
+		}
+	}
+	for (rule, count) in rules.iter().zip(counts) {
+		log::info!(
+			"{layer}: {} on {count} files matching {}",
+			rule.transform,
+			rule.glob
+		);
+	}
+	let staging = staging.to_string();
+	let transformed = pdtthread::multithread(tasks, None, move |_, (name, path, transform)| {
+		let data = fs::read_to_string(pdtfs::long_path(&path)).ok()?;
+		let changed = transform.apply(&name, &data)?;
+		if changed == data {
+			return None;
+		}
+		log::debug!("{transform} on {name}");
+		let staged = Utf8Path::new(&staging)
+			.join("transformed")
+			.join(&name)
+			.to_string();
+		let dir = Utf8Path::new(&staged).parent().unwrap();
+		fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+			.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		fs::write(pdtfs::long_path(&staged), changed)
+			.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+		Some((name, staged))
+	});
+	files.extend(transformed);
+}
+
+/// Stages copies of the json files of a layer written in the style of the
+/// [json] table, the way json-format writes them. That takes out comments
+/// and trailing commas too, which Minecraft fails to read. The sources keep
+/// them. Broken json is left as it is, for validation to report.
+fn format_json_files(files: &mut BTreeMap<String, String>, staging: &str, style: &JsonStyle) {
+	for (name, path) in files.iter_mut() {
+		if !name.ends_with(".json") && !name.ends_with(".mcmeta") {
+			continue;
+		}
+		let Ok(data) = fs::read_to_string(pdtfs::long_path(&*path)) else {
+			continue;
+		};
+		let Ok(value) = pdtjson::parse_json(name, &data) else {
+			continue;
+		};
+		let styled = pdtjson::style_json(name, value, &data, style);
+		if styled == data {
+			continue;
+		}
+		log::debug!("formatting {name}");
+		let staged = Utf8Path::new(&staging)
+			.join("formatted")
+			.join(name)
+			.to_string();
+		let dir = Utf8Path::new(&staged).parent().unwrap();
+		fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+			.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		fs::write(pdtfs::long_path(&staged), styled)
+			.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+		*path = staged;
+	}
+}
+
+/// Whether a file is a texture of a pack or of one of its overlays,
+/// including OptiFine ones.
+fn is_texture(file: &str) -> bool {
+	let rest = match file.split_once("assets/") {
+		Some(("", rest)) => rest,
+		Some((overlay, rest)) if !overlay.trim_end_matches('/').contains('/') => rest,
+		_ => return false,
+	};
+	let kind = rest.split('/').nth(1).unwrap_or_default();
+	file.ends_with(".png") && ["textures", "optifine", "mcpatcher"].contains(&kind)
+}
+
+/// Resolution of a pack, the width of its stone texture.
+pub fn pack_resolution(files: &BTreeMap<String, String>) -> Option<u32> {
+	let stone = files.get("assets/minecraft/textures/block/stone.png")?;
+	image::image_dimensions(stone).ok().map(|(width, _)| width)
+}
+
+/// Whether a texture repeats across blocks, so scaling it should wrap
+/// around its edges instead of stopping at them.
+fn is_tiling(file: &str) -> bool {
+	["/textures/block/", "/optifine/ctm/", "/mcpatcher/ctm/"]
+		.iter()
+		.any(|folder| file.contains(folder))
+}
+
+/// Scales an image to a size. The box filter only blends the pixels under
+/// every new one, which keeps pixel art and tiles seamless, the others
+/// scale tiling images as part of a 3x3 grid of themselves so their edges
+/// still match.
+fn scale_image(
+	image: &RgbaImage, width: u32, height: u32, filter: Filter, tiling: bool, linear: bool,
+) -> RgbaImage {
+	if !tiling || matches!(filter, Filter::Box) {
+		return filter.resize(image, width, height, linear);
+	}
+	let (w, h) = image.dimensions();
+	let grid = RgbaImage::from_fn(w * 3, h * 3, |x, y| *image.get_pixel(x % w, y % h));
+	let scaled = filter.resize(&grid, width * 3, height * 3, linear);
+	imageops::crop_imm(&scaled, width, height, width, height).to_image()
+}
+
+/// Scales the textures of a pack from one resolution down to another into
+/// the staging directory and points the files at them. Animation frames
+/// are scaled one by one and the sizes in their mcmeta with them. Textures
+/// that can't be scaled by exactly that much are left as they are.
+fn downscale_textures(
+	files: &mut BTreeMap<String, String>, staging: &str, from: u32, to: u32, filter: Filter,
+	linear: bool,
+) {
+	let textures = files
+		.iter()
+		.filter(|(name, _)| is_texture(name))
+		.map(|(name, path)| {
+			let staged = Utf8Path::new(&staging).join(name).to_string();
+			let animation = files
+				.get(&format!("{name}.mcmeta"))
+				.and_then(|mcmeta| fs::read_to_string(pdtfs::long_path(mcmeta)).ok())
+				.and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+				.filter(|json| json["animation"].is_object());
+			(name.clone(), path.clone(), staged, animation)
+		})
+		.collect::<Vec<_>>();
+	let scaled =
+		pdtthread::multithread(textures, None, move |_, (name, path, staged, animation)| {
+			let image = image::open(pdtfs::long_path(&path))
+				.unwrap_or_else(|_| panic!("Failed to load image: {path}"))
+				.into_rgba8();
+			let (width, height) = image.dimensions();
+			if !(width * to).is_multiple_of(from) || !(height * to).is_multiple_of(from) {
+				log::warn!(
+					"{name} is {width}x{height}, which can't be scaled to {to}x, leaving it as is"
+				);
+				return None;
+			}
+			let (mut frame_width, mut frame_height) = (width, height);
+			if let Some(json) = &animation {
+				let size = |key: &str| json["animation"][key].as_u64().map(|s| s as u32);
+				match (size("width"), size("height")) {
+					(None, None) => {
+						frame_width = width.min(height);
+						frame_height = frame_width;
+					}
+					(w, h) => {
+						frame_width = w.unwrap_or(width);
+						frame_height = h.unwrap_or(height);
+					}
+				}
+			}
+			if !width.is_multiple_of(frame_width) || !height.is_multiple_of(frame_height) {
+				log::warn!("{name} isn't made of whole frames, scaling it as one image");
+				(frame_width, frame_height) = (width, height);
+			}
+			let (scaled_width, scaled_height) = (frame_width * to / from, frame_height * to / from);
+			let tiling = is_tiling(&name);
+			let mut scaled = RgbaImage::new(width * to / from, height * to / from);
+			for y in 0..height / frame_height {
+				for x in 0..width / frame_width {
+					let frame = imageops::crop_imm(
+						&image,
+						x * frame_width,
+						y * frame_height,
+						frame_width,
+						frame_height,
+					)
+					.to_image();
+					let frame =
+						scale_image(&frame, scaled_width, scaled_height, filter, tiling, linear);
+					let (px, py) = ((x * scaled_width) as i64, (y * scaled_height) as i64);
+					imageops::replace(&mut scaled, &frame, px, py);
+				}
+			}
+			let dir = Utf8Path::new(&staged).parent().unwrap();
+			fs::create_dir_all(pdtfs::long_path(dir.as_str()))
+				.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+			scaled
+				.save(pdtfs::long_path(&staged))
+				.unwrap_or_else(|e| panic!("Failed to write image {staged}: {e}"));
+			let mut mcmeta = None;
+			if let Some(mut json) = animation {
+				let mut changed = false;
+				for key in ["width", "height"] {
+					if let Some(size) = json["animation"][key].as_u64() {
+						json["animation"][key] = (size * to as u64 / from as u64).max(1).into();
+						changed = true;
+					}
+				}
+				if changed {
+					let staged = format!("{staged}.mcmeta");
+					let data = serde_json::to_string_pretty(&json).unwrap();
+					fs::write(pdtfs::long_path(&staged), data)
+						.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+					mcmeta = Some(staged);
+				}
+			}
+			Some((name, staged, mcmeta))
+		});
+	for (name, staged, mcmeta) in scaled {
+		if let Some(mcmeta) = mcmeta {
+			files.insert(format!("{name}.mcmeta"), mcmeta);
+		}
+		files.insert(name, staged);
+	}
+}
+
+/// Makes pack.png from the logo, with the version on it if asked to.
+fn make_icon(config: &IconConfig, version: Option<&str>, output: &str) {
+	let logo = image::open(pdtfs::long_path(&config.logo))
+		.unwrap_or_else(|_| panic!("Failed to load image: {}", config.logo))
+		.into_rgba8();
+	let (width, height) = logo.dimensions();
+	let scale = config.size as f64 / width.max(height) as f64;
+	let scaled_width = ((width as f64 * scale).round() as u32).max(1);
+	let scaled_height = ((height as f64 * scale).round() as u32).max(1);
+	let logo = config
+		.filter
+		.resize(&logo, scaled_width, scaled_height, config.linear);
+	let mut icon = RgbaImage::new(config.size, config.size);
+	let x = (config.size - scaled_width) / 2;
+	let y = (config.size - scaled_height) / 2;
+	imageops::overlay(&mut icon, &logo, x as i64, y as i64);
+	if config.stamp_version {
+		let Some(version) = version else {
+			panic!("stamp_version is set in [icon], but there is no version to stamp");
+		};
+		let text = format!("v{version}");
+		let scale = (config.size / 64).max(1);
+		let margin = scale * 2;
+		let text_width = pdtfont::text_width(&text, scale);
+		let text_height = pdtfont::GLYPH_HEIGHT * scale;
+		if text_width + margin * 2 > config.size {
+			log::warn!("{text} doesn't fit on a {0}x{0} pack.png", config.size);
+		}
+		let x = config.size.saturating_sub(text_width + margin);
+		let y = config.size.saturating_sub(text_height + margin);
+		// a shadow like Minecraft's text has, so it shows on any logo
+		pdtfont::draw_text(
+			&mut icon,
+			&text,
+			x + scale,
+			y + scale,
+			scale,
+			Rgba([63, 63, 63, 255]),
+		);
+		pdtfont::draw_text(&mut icon, &text, x, y, scale, Rgba([255, 255, 255, 255]));
+	}
+	icon.save(pdtfs::long_path(output))
+		.unwrap_or_else(|e| panic!("Failed to write image {output}: {e}"));
+}
+
+/// Changes to pack.mcmeta for one zip.
+#[derive(Default)]
+struct McmetaPatch<'a> {
+	pack_format: Option<u32>,
+	supported_formats: Option<[u32; 2]>,
+	overlays: Vec<&'a Overlay>,
+}
+
+/// Writes a patched copy of pack.mcmeta to the staging directory and points
+/// the files at it. Overlay entries replace ones with the same directory.
+fn patch_mcmeta(files: &mut BTreeMap<String, String>, staging: &str, patch: &McmetaPatch) {
+	let Some(path) = files.get("pack.mcmeta") else {
+		panic!("No pack.mcmeta to set the pack_format of.");
+	};
+	let data = fs::read_to_string(pdtfs::long_path(path))
+		.unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+	let mut mcmeta: serde_json::Value =
+		serde_json::from_str(&data).unwrap_or_else(|e| panic!("Failed to parse {path}: {e}"));
+	if let Some(pack_format) = patch.pack_format {
+		mcmeta["pack"]["pack_format"] = pack_format.into();
+	}
+	if let Some(formats) = patch.supported_formats {
+		mcmeta["pack"]["supported_formats"] = formats.to_vec().into();
+	}
+	if !patch.overlays.is_empty() {
+		let mut entries = match mcmeta["overlays"]["entries"].take() {
+			serde_json::Value::Array(entries) => entries,
+			_ => vec![],
+		};
+		entries.retain(|e| {
+			!patch
+				.overlays
+				.iter()
+				.any(|o| e["directory"] == o.directory.as_str())
+		});
+		entries.extend(patch.overlays.iter().map(|o| {
+			serde_json::json!({
+				"formats": o.formats,
+				"directory": o.directory,
+			})
+		}));
+		mcmeta["overlays"]["entries"] = entries.into();
+	}
+	let staged = Utf8Path::new(&staging).join("pack.mcmeta").to_string();
+	let data = serde_json::to_string_pretty(&mcmeta).unwrap();
+	fs::create_dir_all(pdtfs::long_path(staging))
+		.unwrap_or_else(|_| panic!("Failed to create {staging} directory."));
+	fs::write(pdtfs::long_path(&staged), data)
+		.unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+	files.insert("pack.mcmeta".to_string(), staged);
+}
+
+/// Names of the build's own stages, in the order they run, for the
+/// [pipeline] table. Blockify runs before the layers are read, it can only
+/// be turned off.
+pub const STAGES: [&str; 8] = [
+	"blockify",
+	"ctm",
+	"placeholders",
+	"format-json",
+	"emissive",
+	"optimize-images",
+	"audio",
+	"validate",
+];
+
+struct CtmStage {
+	sheets: Vec<CtmSheet>,
+	base: String,
+}
+
+impl Stage for CtmStage {
+	fn name(&self) -> &str {
+		"ctm"
+	}
+
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem> {
+		for sheet in &self.sheets {
+			if sheet.layer.as_ref().unwrap_or(&self.base) == context.name {
+				slice_ctm_sheet(files, context.staging, sheet);
+			}
+		}
+		vec![]
+	}
+}
+
+struct PlaceholdersStage {
+	values: BTreeMap<String, String>,
+}
+
+impl Stage for PlaceholdersStage {
+	fn name(&self) -> &str {
+		"placeholders"
+	}
+
+	fn inputs(&self) -> &[&str] {
+		&TEMPLATE_EXTENSIONS
+	}
+
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem> {
+		fill_placeholders(files, context.staging, &self.values);
+		vec![]
+	}
+}
+
+struct FormatJsonStage(JsonStyle);
+
+impl Stage for FormatJsonStage {
+	fn name(&self) -> &str {
+		"format-json"
+	}
+
+	fn inputs(&self) -> &[&str] {
+		&[".json", ".mcmeta"]
+	}
+
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem> {
+		format_json_files(files, context.staging, &self.0);
+		vec![]
+	}
+}
+
+struct EmissiveStage(EmissiveConfig);
+
+impl Stage for EmissiveStage {
+	fn name(&self) -> &str {
+		"emissive"
+	}
+
+	fn inputs(&self) -> &[&str] {
+		&[".png"]
+	}
+
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem> {
+		generate_emissive(files, context.staging, &self.0);
+		vec![]
+	}
+}
+
+struct OptimizeStage(OptimizeConfig);
+
+impl Stage for OptimizeStage {
+	fn name(&self) -> &str {
+		"optimize-images"
+	}
+
+	fn inputs(&self) -> &[&str] {
+		&pdtfs::IMAGE_EXTENSIONS
+	}
+
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem> {
+		log::info!("Optimizing {}", context.name);
+		optimize_layer(files, context.staging, &self.0, context.cache);
+		vec![]
+	}
+}
+
+struct AudioStage(AudioConfig);
+
+impl Stage for AudioStage {
+	fn name(&self) -> &str {
+		"audio"
+	}
+
+	fn inputs(&self) -> &[&str] {
+		&pdtaudio::AUDIO_EXTENSIONS
+	}
+
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem> {
+		convert_layer_audio(files, context.staging, &self.0, context.cache);
+		vec![]
+	}
+}
+
+struct ValidateStage {
+	config: ValidateConfig,
+	/// Only downloaded once the first release is checked
+	vanilla: OnceLock<Option<BTreeSet<String>>>,
+	sounds: OnceLock<Option<VanillaSounds>>,
+}
+
+impl Stage for ValidateStage {
+	fn name(&self) -> &str {
+		"validate"
+	}
+
+	fn scope(&self) -> Scope {
+		Scope::Release
+	}
+
+	fn run(&self, files: &mut BTreeMap<String, String>, context: &StageContext) -> Vec<Problem> {
+		let vanilla = self
+			.vanilla
+			.get_or_init(|| {
+				self.config
+					.minecraft
+					.as_deref()
+					.map(pdtmojang::get_asset_names)
+			})
+			.as_ref();
+		let name = context.name;
+		log::info!("Validating {name}");
+		// broken json files are listed by the build already
+		let (_, valid) = pdtvalidate::validate_json(files);
+		let mut problems = pdtvalidate::validate_references(&valid, vanilla);
+		problems.extend(pdtvalidate::validate_sounds(&valid));
+		let sounds = self
+			.sounds
+			.get_or_init(|| self.config.minecraft.as_deref().map(VanillaSounds::get));
+		if let Some(sounds) = sounds {
+			problems.extend(pdtvalidate::validate_sound_events(&valid, sounds));
+		}
+		problems.extend(pdtvalidate::validate_animations(&valid));
+		problems.extend(pdtvalidate::validate_optifine(&valid, vanilla));
+		problems.extend(pdtvalidate::validate_fonts(&valid, vanilla));
+		problems.extend(pdtvalidate::custom_model_data(&valid, vanilla).0);
+		problems.extend(pdtvalidate::validate_shaders(&valid, vanilla));
+		let namespaces = &self.config.namespaces;
+		let problems = pdtvalidate::in_namespaces(problems, namespaces);
+		let warnings = pdtvalidate::texture_size_warnings(files);
+		for warning in pdtvalidate::in_namespaces(warnings, namespaces) {
+			log::warn!("{name}: {warning}");
+		}
+		if self.config.dedupe_textures {
+			let duplicates = pdtvalidate::duplicate_textures(files, false);
+			let removed =
+				pdtvalidate::dedupe_textures(files, &duplicates, vanilla, context.staging);
+			log::info!("Left {removed} duplicate textures out of {name}");
+		}
+		if self.config.crop_margins {
+			let croppable = files
+				.iter()
+				.filter(|(file, _)| pdtvalidate::can_crop(file))
+				.map(|(file, path)| (file.clone(), path.clone()))
+				.collect();
+			let margins = pdtvalidate::transparent_margins(&croppable, 0.0);
+			let cropped = pdtvalidate::crop_margins(files, &margins, context.staging);
+			log::info!("Cropped the margins of {cropped} images of {name}");
+		}
+		if self.config.prune_unused {
+			let unused = pdtvalidate::unused_assets(files, vanilla);
+			log::info!("Leaving {} unused files out of {name}", unused.len());
+			for file in unused {
+				files.remove(&format!("{file}.mcmeta"));
+				files.remove(&file);
+			}
+		}
+		problems
+	}
+}
+
+/// The stages the build config asks for, in their usual order. Library
+/// users can add their own to it and configure it before building the
+/// releases with it.
+pub fn default_pipeline(config: &BuildConfig) -> Pipeline {
+	let mut pipeline = Pipeline::new();
+	pipeline.add_step("blockify");
+	if !config.ctm.is_empty() {
+		pipeline.add(CtmStage {
+			sheets: config.ctm.clone(),
+			base: config.base.name.clone(),
+		});
+	}
+	pipeline.add(PlaceholdersStage {
+		values: placeholder_values(config),
+	});
+	pipeline.add(FormatJsonStage(config.json.style()));
+	if let Some(emissive) = &config.emissive {
+		pipeline.add(EmissiveStage(emissive.clone()));
+	}
+	if let Some(optimize) = &config.optimize {
+		pipeline.add(OptimizeStage(optimize.clone()));
+	}
+	if let Some(audio) = &config.audio {
+		pipeline.add(AudioStage(audio.clone()));
+	}
+	if let Some(validate) = &config.validate {
+		pipeline.add(ValidateStage {
+			config: validate.clone(),
+			vanilla: OnceLock::new(),
+			sounds: OnceLock::new(),
+		});
+	}
+	pipeline
+}
+
+/// pack_format of a Minecraft version.
+fn version_format(version: &str) -> u32 {
+	let parse = |v: &str| {
+		let mut parts = v
+			.split('.')
+			.map(|p| {
+				p.parse::<u32>()
+					.unwrap_or_else(|_| panic!("Invalid Minecraft version {version}"))
+			})
+			.collect::<Vec<_>>();
+		while parts.last() == Some(&0) {
+			parts.pop();
+		}
+		parts
+	};
+	let wanted = parse(version);
+	VERSION_FORMATS
+		.iter()
+		.rev()
+		.find(|(v, _)| parse(v) <= wanted)
+		.map(|(_, format)| *format)
+		.unwrap_or_else(|| panic!("Minecraft {version} is older than resource packs"))
+}
+
+/// The versions a file or folder name is tagged with, like 1.21+ for
+/// `stone@1.21+.png`, and the name without them.
+fn version_tag(name: &str) -> Option<(String, &str)> {
+	let (stem, rest) = name.split_once('@')?;
+	let end = rest
+		.find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '+' | '-')))
+		.unwrap_or(rest.len());
+	let tag = rest[..end].trim_end_matches('.');
+	let after = &rest[tag.len()..];
+	if !tag.starts_with(|c: char| c.is_ascii_digit())
+		|| !(after.is_empty() || after.starts_with('.'))
+	{
+		return None;
+	}
+	Some((format!("{stem}{after}"), tag))
+}
+
+/// Lowest and highest pack_format of the versions of a tag: 1.21+ for 1.21
+/// and later, 1.20.2-1.20.6 for a range or 1.21 for one version.
+fn tag_formats(tag: &str) -> [u32; 2] {
+	match (tag.strip_suffix('+'), tag.split_once('-')) {
+		(Some(min), _) => [version_format(min), NEWER_FORMATS],
+		(None, Some((min, max))) => [version_format(min), version_format(max)],
+		(None, None) => [version_format(tag); 2],
+	}
+}
+
+/// Moves the files of a layer tagged with versions, or in a folder that is,
+/// into an overlay for those versions. The overlays are added to overlays
+/// by directory.
+fn split_versioned(files: &mut BTreeMap<String, String>, overlays: &mut BTreeMap<String, Overlay>) {
+	let tagged = files
+		.keys()
+		.filter(|f| f.contains('@'))
+		.cloned()
+		.collect::<Vec<_>>();
+	for file in tagged {
+		let mut tags = vec![];
+		let parts = file
+			.split('/')
+			.map(|part| match version_tag(part) {
+				Some((name, tag)) => {
+					tags.push(tag);
+					name
+				}
+				None => part.to_string(),
+			})
+			.collect::<Vec<_>>();
+		let tag = match tags[..] {
+			[] => continue,
+			[tag] => tag,
+			_ => panic!("{file} is tagged with more than one version"),
+		};
+		let formats = tag_formats(tag);
+		if formats[0] > formats[1] {
+			panic!("{file} is tagged with versions in the wrong order");
+		}
+		if formats[0] < OVERLAY_PACK_FORMAT {
+			panic!("{file} is tagged for versions before 1.20.2, which don't have overlays");
+		}
+		let directory = format!(
+			"v{}",
+			tag.replace('.', "_")
+				.replace('+', "_up")
+				.replace('-', "_to_")
+		);
+		overlays
+			.entry(directory.clone())
+			.or_insert_with(|| Overlay {
+				directory: directory.clone(),
+				formats,
+				path: String::new(),
+			});
+		let path = files.remove(&file).unwrap();
+		files.insert(format!("{directory}/{}", parts.join("/")), path);
+	}
+}
+
+/// The overlays some of the files are in.
+fn overlays_in<'a>(
+	overlays: &'a [Overlay], files: &BTreeMap<String, String>,
+) -> impl Iterator<Item = &'a Overlay> {
+	let dirs = files
+		.keys()
+		.filter_map(|f| f.split_once('/').map(|(dir, _)| dir.to_string()))
+		.collect::<BTreeSet<_>>();
+	overlays.iter().filter(move |o| dirs.contains(&o.directory))
+}
+
+/// Checks targets and overlays make sense before anything is built.
+fn check_targets(config: &BuildConfig) {
+	let rules = &config.compatibility;
+	let named = rules
+		.exclusive
+		.iter()
+		.flatten()
+		.chain(rules.requires.keys());
+	for addon in named.chain(rules.requires.values().flatten()) {
+		let known = config.addons.iter().any(|a| &a.name == addon)
+			|| config.blockify.iter().any(|b| &b.name == addon);
+		if !known {
+			panic!("Unknown addon {addon} in [compatibility]");
+		}
+	}
+	let invalid = config
+		.releases
+		.iter()
+		.filter_map(|release| {
+			let problems = rules.problems(&release.addons);
+			(!problems.is_empty()).then(|| format!("{}: {}", release.name, problems.join(", ")))
+		})
+		.collect::<Vec<_>>();
+	if !invalid.is_empty() {
+		panic!(
+			"Releases with addons that don't go together:\n  {}",
+			invalid.join("\n  ")
+		);
+	}
+	for target in &config.targets {
+		if let Some([min, max]) = target.supported_formats {
+			if !(min..=max).contains(&target.pack_format) {
+				panic!(
+					"Target {} has pack_format {} outside its supported_formats {min}..{max}",
+					target.minecraft, target.pack_format
+				);
+			}
+		}
+	}
+	for overlay in &config.overlays {
+		let valid = |c: char| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-');
+		if overlay.directory.is_empty() || !overlay.directory.chars().all(valid) {
+			panic!(
+				"Overlay directory {} can only have a-z, 0-9, _ and -",
+				overlay.directory
+			);
+		}
+		if overlay.formats[0] > overlay.formats[1] {
+			panic!(
+				"Overlay {} has formats in the wrong order",
+				overlay.directory
+			);
+		}
+	}
+}
+
+/// Builds one zip per release and target in the output directory. Conflicts
+/// are warned about when allowed, the last addon wins, otherwise they stop
+/// the build before anything is written. Returns the zips written.
+pub fn build_releases(config: &BuildConfig) -> Vec<String> {
+	let mut pipeline = default_pipeline(config);
+	pipeline.configure(&config.pipeline, &STAGES);
+	build_releases_with(config, &pipeline)
+}
+
+/// Folder the build stages the files it makes in, removed once the zips are
+/// written.
+fn staging_dir() -> String {
+	pdtfs::temp_path(&format!("pdt_release_{}", std::process::id()))
+}
+
+/// Builds the releases like build_releases, running the stages of a
+/// pipeline on every layer and release instead of the default ones.
+pub fn build_releases_with(config: &BuildConfig, pipeline: &Pipeline) -> Vec<String> {
+	check_targets(config);
+	let stages = pipeline.names().into_iter().filter(|s| pipeline.enabled(s));
+	log::debug!("stages: {}", stages.collect::<Vec<_>>().join(", "));
+	let mut temp_dirs = vec![];
+	let staging = staging_dir();
+	let cache = config.cache.as_deref().map(Cache::new);
+	let filter = config.files.filter();
+	let mut errors = vec![];
+	let mut versioned = BTreeMap::new();
+	let mut history = config.history.as_ref().map(|_| History::default());
+	let mut sources = BTreeMap::new();
+	let mut layer_files = |layer: &Layer| {
+		let _stage = pdtlog::stage(&format!("layer {}", layer.name));
+		let mut files = get_layer_files(layer, &mut temp_dirs, &filter);
+		let originals = files.clone();
+		let dir = Utf8Path::new(&staging).join(&layer.name).to_string();
+		transform_files(&mut files, &config.files.transform, &dir, &layer.name);
+		split_versioned(&mut files, &mut versioned);
+		let context = StageContext {
+			name: &layer.name,
+			staging: &dir,
+			cache: cache.as_ref(),
+		};
+		let problems = pipeline.run(Scope::Layer, &mut files, &context);
+		for problem in &problems {
+			problem.annotate(files.get(&problem.file).map(String::as_str));
+		}
+		errors.extend(problems.iter().map(|p| format!("{}: {p}", layer.name)));
+		if history.is_some() {
+			add_sources(&mut sources, layer, &originals, &files);
+		}
+		files
+	};
+	let mut base_files = layer_files(&config.base);
+	if let Some(emissive) = &config.emissive {
+		let dir = Utf8Path::new(&staging).join(&config.base.name).to_string();
+		add_emissive_properties(&mut base_files, &dir, emissive);
+	}
+	if let Some(credits) = &config.credits {
+		let dir = Utf8Path::new(&staging).join(&config.base.name).to_string();
+		add_credits(&mut base_files, &dir, credits);
+	}
+	let addon_files = config
+		.addons
+		.iter()
+		.map(|a| (a.name.as_str(), layer_files(a)))
+		.collect::<BTreeMap<_, _>>();
+	let target_files = config
+		.targets
+		.iter()
+		.map(|target| {
+			let overrides = target.overrides.as_ref().map(|path| {
+				layer_files(&Layer {
+					name: format!("{}-overrides", target.minecraft),
+					path: path.clone(),
+					namespaces: vec![],
+				})
+			});
+			(target, overrides.unwrap_or_default())
+		})
+		.collect::<Vec<_>>();
+	let overlay_files = config
+		.overlays
+		.iter()
+		.map(|overlay| {
+			let files = layer_files(&Layer {
+				name: format!("{}-overlay", overlay.directory),
+				path: overlay.path.clone(),
+				namespaces: vec![],
+			});
+			let files = files
+				.into_iter()
+				.map(|(file, path)| (format!("{}/{file}", overlay.directory), path))
+				.collect::<BTreeMap<_, _>>();
+			(overlay, files)
+		})
+		.collect::<Vec<_>>();
+	let versioned = versioned.into_values().collect::<Vec<_>>();
+	for overlay in &versioned {
+		log::debug!(
+			"versioned files go in overlay {} for formats {}..{}",
+			overlay.directory,
+			overlay.formats[0],
+			overlay.formats[1]
+		);
+	}
+	if !errors.is_empty() {
+		panic!(
+			"Found {} problems in the layers:\n  {}",
+			errors.len(),
+			errors.join("\n  ")
+		);
+	}
+
+	let mut releases = vec![];
+	for release in &config.releases {
+		let layers = release
+			.addons
+			.iter()
+			.map(|name| {
+				let files = addon_files
+					.get(name.as_str())
+					.unwrap_or_else(|| panic!("Unknown addon {name} in release {}", release.name));
+				(name.as_str(), files)
+			})
+			.collect::<Vec<_>>();
+		let (mut files, conflicts) = overlay(&base_files, &layers);
+		for conflict in conflicts {
+			let message = format!(
+				"{}: {} is changed by {}",
+				release.name,
+				conflict.file,
+				conflict.layers.join(", ")
+			);
+			match config.allow_conflicts {
+				true => log::warn!("{message}"),
+				false => errors.push(message),
+			}
+		}
+		if target_files.is_empty() {
+			for (_, overlay) in &overlay_files {
+				files.extend(overlay.clone());
+			}
+			let patch = McmetaPatch {
+				pack_format: release.pack_format,
+				overlays: overlay_files
+					.iter()
+					.map(|o| o.0)
+					.chain(overlays_in(&versioned, &files))
+					.collect(),
+				..Default::default()
+			};
+			let dir = Utf8Path::new(&staging).join(&release.name).to_string();
+			if let (Some(history), Some(config)) = (&mut history, &config.history) {
+				add_manifest(&mut files, &release.name, &dir, config, history, &sources);
+			}
+			if patch.pack_format.is_some() || !patch.overlays.is_empty() {
+				patch_mcmeta(&mut files, &dir, &patch);
+			}
+			releases.push((release.name.clone(), files));
+			continue;
+		}
+		for (target, overrides) in &target_files {
+			let name = format!("{}-{}", release.name, target.minecraft);
+			let mut files = files.clone();
+			files.extend(overrides.clone());
+			let mut patch = McmetaPatch {
+				pack_format: Some(target.pack_format),
+				supported_formats: target.supported_formats,
+				..Default::default()
+			};
+			if target.pack_format >= OVERLAY_PACK_FORMAT {
+				for (overlay, overlay_files) in &overlay_files {
+					files.extend(overlay_files.clone());
+					patch.overlays.push(overlay);
+				}
+				patch.overlays.extend(overlays_in(&versioned, &files));
+			} else {
+				files.retain(|file, _| {
+					!versioned
+						.iter()
+						.any(|o| file.starts_with(&format!("{}/", o.directory)))
+				});
+			}
+			let dir = Utf8Path::new(&staging).join(&name).to_string();
+			if let (Some(history), Some(config)) = (&mut history, &config.history) {
+				add_manifest(&mut files, &name, &dir, config, history, &sources);
+			}
+			patch_mcmeta(&mut files, &dir, &patch);
+			releases.push((name, files));
+		}
+	}
+	if !errors.is_empty() {
+		panic!(
+			"Found {} conflicts between addons:\n  {}",
+			errors.len(),
+			errors.join("\n  ")
+		);
+	}
+	let originals = releases.len();
+	if let Some(lite) = &config.lite {
+		let mut lite_releases = vec![];
+		for (name, files) in &releases {
+			let Some(resolution) = lite.resolution.or_else(|| pack_resolution(files)) else {
+				panic!("Can't tell the resolution of {name}, set resolution in [lite]");
+			};
+			if resolution <= lite.max_resolution {
+				log::warn!("{name} is already {resolution}x, not building a lite zip of it");
+				continue;
+			}
+			let lite_name = format!("{name}-{}", lite.suffix);
+			log::info!(
+				"Scaling {name} from {resolution}x to {}x",
+				lite.max_resolution
+			);
+			let mut files = files.clone();
+			let dir = Utf8Path::new(&staging).join(&lite_name).to_string();
+			downscale_textures(
+				&mut files,
+				&dir,
+				resolution,
+				lite.max_resolution,
+				lite.filter,
+				lite.linear,
+			);
+			let optimize = config.optimize.as_ref();
+			if let Some(optimize) = optimize.filter(|_| pipeline.enabled("optimize-images")) {
+				let mut scaled = files
+					.iter()
+					.filter(|(_, path)| path.starts_with(&dir))
+					.map(|(name, path)| (name.clone(), path.clone()))
+					.collect();
+				optimize_layer(
+					&mut scaled,
+					&format!("{dir}-optimized"),
+					optimize,
+					cache.as_ref(),
+				);
+				files.extend(scaled);
+			}
+			lite_releases.push((lite_name, files));
+		}
+		releases.extend(lite_releases);
+	}
+	if let Some(resolutions) = &config.resolutions {
+		let version = config
+			.version
+			.as_ref()
+			.map(|v| format!("-v{v}"))
+			.unwrap_or_default();
+		let mut scaled_releases = vec![];
+		for (name, files) in releases.iter_mut().take(originals) {
+			let Some(source) = resolutions.source.or_else(|| pack_resolution(files)) else {
+				panic!("Can't tell the resolution of {name}, set source in [resolutions]");
+			};
+			for &size in &resolutions.sizes {
+				if size >= source || !source.is_multiple_of(size) {
+					panic!(
+						"Can't scale {name} from {source}x to {size}x, sizes must divide {source}"
+					);
+				}
+				let scaled_name = format!("{name}-{size}x{version}");
+				log::info!("Scaling {name} from {source}x to {size}x");
+				let mut scaled = files.clone();
+				let dir = Utf8Path::new(&staging).join(&scaled_name).to_string();
+				downscale_textures(
+					&mut scaled,
+					&dir,
+					source,
+					size,
+					resolutions.filter,
+					resolutions.linear,
+				);
+				let optimize = config.optimize.as_ref();
+				if let Some(optimize) = optimize.filter(|_| pipeline.enabled("optimize-images")) {
+					let mut pngs = scaled
+						.iter()
+						.filter(|(_, path)| path.starts_with(&dir))
+						.map(|(name, path)| (name.clone(), path.clone()))
+						.collect();
+					optimize_layer(
+						&mut pngs,
+						&format!("{dir}-optimized"),
+						optimize,
+						cache.as_ref(),
+					);
+					scaled.extend(pngs);
+				}
+				scaled_releases.push((scaled_name, scaled));
+			}
+			*name = format!("{name}-{source}x{version}");
+		}
+		releases.extend(scaled_releases);
+	}
+	if let Some(icon) = &config.icon {
+		fs::create_dir_all(pdtfs::long_path(&staging))
+			.unwrap_or_else(|_| panic!("Failed to create {staging} directory."));
+		let path = Utf8Path::new(&staging).join("pack.png").to_string();
+		make_icon(icon, config.version.as_deref(), &path);
+		for (_, files) in &mut releases {
+			files.insert("pack.png".to_string(), path.clone());
+		}
+	}
+	// every broken json file is listed once, even if it is in many releases
+	let mut checked = BTreeSet::new();
+	let mut broken = BTreeSet::new();
+	for (name, files) in &releases {
+		let unchecked = files
+			.iter()
+			.filter(|(_, path)| checked.insert(path.to_string()))
+			.map(|(file, path)| (file.clone(), path.clone()))
+			.collect::<BTreeMap<_, _>>();
+		for problem in pdtvalidate::validate_json(&unchecked).0 {
+			problem.annotate(Some(&unchecked[&problem.file]));
+			broken.insert(unchecked[&problem.file].clone());
+			errors.push(format!("{name}: {problem}"));
+		}
+	}
+	for (name, files) in &releases {
+		let Some(path) = files.get("pack.mcmeta") else {
+			errors.push(format!("{name}: no pack.mcmeta"));
+			continue;
+		};
+		if broken.contains(path) {
+			continue;
+		}
+		let data = fs::read_to_string(pdtfs::long_path(path))
+			.unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let mcmeta = pdtjson::parse_json("pack.mcmeta", &data).unwrap();
+		let problems = pdtjson::validate_mcmeta(&mcmeta);
+		errors.extend(problems.iter().map(|p| format!("{name}: pack.mcmeta: {p}")));
+	}
+	for (name, files) in &mut releases {
+		let dir = Utf8Path::new(&staging).join(&name).to_string();
+		let context = StageContext {
+			name,
+			staging: &dir,
+			cache: cache.as_ref(),
+		};
+		let problems = pipeline.run(Scope::Release, files, &context);
+		for problem in &problems {
+			problem.annotate(files.get(&problem.file).map(String::as_str));
+		}
+		errors.extend(problems.iter().map(|p| format!("{name}: {p}")));
+	}
+	if !errors.is_empty() {
+		panic!(
+			"Found {} problems in the releases:\n  {}",
+			errors.len(),
+			errors.join("\n  ")
+		);
+	}
+
+	let output = &config.output;
+	pdtfs::if_dir_exists_remove_and_remake_it(output);
+	let mut zips = vec![];
+	let stage = pdtlog::stage("zip");
+	for (name, files) in releases {
+		let zip = Utf8Path::new(&output)
+			.join(format!("{name}.zip"))
+			.to_string();
+		log::info!("Building {zip}");
+		pdtzip::zip_files(&files, &zip, config.compression_level);
+		pdtlog::file_done("zip", &format!("{name}.zip"));
+		let report = config.history.as_ref().filter(|h| h.report);
+		if let Some(manifest) = report.and_then(|h| files.get(&h.manifest)) {
+			let path = Utf8Path::new(&output)
+				.join(format!("{name}.{}", pdthistory::MANIFEST_FILE))
+				.to_string();
+			fs::copy(pdtfs::long_path(manifest), pdtfs::long_path(&path))
+				.unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+		}
+		zips.push(zip);
+	}
+	drop(stage);
+	drop(temp_dirs);
+	pdtfs::if_dir_exists_remove_it(&staging);
+	zips
+}
+
+/// Hashes of a release zip.
+#[derive(Debug, serde::Serialize)]
+pub struct Checksum {
+	#[serde(skip)]
+	pub file: String,
+	pub sha1: String,
+	pub sha256: String,
+	pub size: u64,
+}
+
+/// Writes checksums.txt, in the format sha1sum and sha256sum can check,
+/// and checksums.json next to the zips.
+pub fn write_checksums(zips: &[String], output: &str) -> Vec<Checksum> {
+	let checksums = pdtthread::multithread(zips.to_vec(), None, |_, zip| {
+		let size = fs::metadata(pdtfs::long_path(&zip))
+			.unwrap_or_else(|_| panic!("{zip} not found!"))
+			.len();
+		Some(Checksum {
+			file: Utf8Path::new(&zip).file_name().unwrap_or(&zip).to_string(),
+			sha1: pdthash::get_sha1_hash(&zip),
+			sha256: pdthash::get_hash(&zip, false),
+			size,
+		})
+	});
+	let lines = |hash: fn(&Checksum) -> &str| {
+		checksums
+			.iter()
+			.map(|c| format!("{}  {}\n", hash(c), c.file))
+			.collect::<String>()
+	};
+	let text = format!(
+		"# SHA-1\n{}# SHA-256\n{}",
+		lines(|c| &c.sha1),
+		lines(|c| &c.sha256)
+	);
+	let json = checksums
+		.iter()
+		.map(|c| (c.file.as_str(), c))
+		.collect::<BTreeMap<_, _>>();
+	let json = serde_json::to_string_pretty(&json).unwrap() + "\n";
+	for (extension, data) in [("txt", text), ("json", json)] {
+		let path = Utf8Path::new(&output)
+			.join(format!("{CHECKSUMS_FILE}.{extension}"))
+			.to_string();
+		fs::write(pdtfs::long_path(&path), data)
+			.unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+	}
+	checksums
+}
+
+/// Escapes a server.properties value the way the server writes them.
+fn properties_value(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace(':', "\\:")
+		.replace('=', "\\=")
+		.replace('\n', "\\n")
+}
+
+/// Lines of server.properties that make a server send a zip to players.
+/// The config needs a url.
+pub fn server_properties(config: &ServerConfig, checksum: &Checksum) -> String {
+	let url = config
+		.url
+		.as_ref()
+		.expect("No url in [server], set where players download the zips from.")
+		.replace("{file}", &checksum.file);
+	let mut lines = vec![
+		format!("resource-pack={}", properties_value(&url)),
+		format!("resource-pack-sha1={}", checksum.sha1),
+		format!("require-resource-pack={}", config.require),
+	];
+	if let Some(prompt) = &config.prompt {
+		// the prompt is a text component
+		let prompt = serde_json::json!({ "text": prompt }).to_string();
+		lines.push(format!(
+			"resource-pack-prompt={}",
+			properties_value(&prompt)
+		));
+	}
+	lines.join("\n") + "\n"
+}
+
+/// Uploads a zip with a PUT request.
+fn upload_zip(url: &str, zip: &str) {
+	let data =
+		fs::read(pdtfs::long_path(zip)).unwrap_or_else(|_| panic!("Failed to read file: {zip}"));
+	let mut request = reqwest::blocking::Client::new()
+		.put(url)
+		.header("Content-Type", "application/zip")
+		.body(data);
+	if let Ok(token) = std::env::var("PDT_UPLOAD_TOKEN") {
+		request = request.bearer_auth(token);
+	}
+	let response = request
+		.send()
+		.unwrap_or_else(|e| panic!("Failed to upload {zip} to {url}: {e}"));
+	if !response.status().is_success() {
+		panic!("Failed to upload {zip} to {url}: {}", response.status());
+	}
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BuildArgs {
+	#[arg(long)]
+	/// Build config, defaults to builder.toml when no --base is given
+	config: Option<String>,
+	#[arg(short, long, conflicts_with = "config")]
+	/// Base pack folder or zip
+	base: Option<String>,
+	#[arg(short, long, value_name = "NAME=PATH")]
+	/// Addon folder or zip, repeat for every addon
+	addon: Vec<String>,
+	#[arg(short, long, value_name = "NAME=ADDON,ADDON")]
+	/// Release zip NAME with the addons applied in order, repeat for every
+	/// release. Defaults to one release with every addon, named after the base
+	combination: Vec<String>,
+	#[arg(short, long, value_name = "NAME")]
+	/// Only build this release, repeat for more
+	release: Vec<String>,
+	#[arg(short, long, visible_alias = "out-dir")]
+	/// Folder to write the release zips to, instead of the one in the build
+	/// config or release_output
+	output: Option<String>,
+	#[arg(long)]
+	/// Warn about addons changing the same file instead of stopping, the
+	/// last addon wins
+	allow_conflicts: bool,
+	#[arg(long)]
+	/// Leave textures and sounds nothing points at out of the zips
+	prune_unused: bool,
+	#[arg(long)]
+	/// Write the server.properties lines for every zip next to it, and
+	/// upload the zips if [server] has an upload url
+	server: bool,
+	#[arg(long)]
+	/// Process every file again instead of using the ones kept from earlier
+	/// builds
+	no_cache: bool,
+	#[arg(long)]
+	/// Optimize pngs as quickly as possible instead of with the [optimize]
+	/// settings, for builds to test with
+	fast: bool,
+	#[command(flatten)]
+	threads: pdtthread::ThreadArgs,
+	#[arg(long, value_name = "FILE")]
+	/// Write the totals of the build to a json file, for CI
+	stats_json: Option<String>,
+	#[arg(long, value_enum, default_value_t = Progress::Text)]
+	/// Show progress as log lines, or as json events on stdout
+	progress: Progress,
+	#[arg(long)]
+	/// Also print problems as GitHub Actions annotations, so they show up
+	/// on the lines of a pull request
+	annotations: bool,
+	#[arg(long)]
+	/// Take Minecraft versions and layers that changed since builder.lock
+	/// was written into it, instead of failing
+	update_lock: bool,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+/// Builds the releases described by the arguments or the build config.
+pub fn build(args: BuildArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	args.threads.apply();
+	pdtlog::set_progress(args.progress);
+	pdtlog::set_annotations(args.annotations);
+	let mut stats = Stats::new("build");
+	// builds of a build config are locked, one-off ones from arguments aren't
+	let locked = args.base.is_none();
+	let mut config = match args.base {
+		Some(base) => build_config_from_args(base, args.addon, args.combination),
+		None => BuildConfig::load(args.config.as_deref().unwrap_or(CONFIG_FILE)),
+	};
+	if let Some(output) = args.output {
+		config.output = output;
+	}
+	config.allow_conflicts |= args.allow_conflicts;
+	if !args.release.is_empty() {
+		for name in &args.release {
+			if !config.releases.iter().any(|r| &r.name == name) {
+				panic!("Unknown release {name}");
+			}
+		}
+		config.releases.retain(|r| args.release.contains(&r.name));
+	}
+	if args.no_cache {
+		config.cache = None;
+	}
+	if let (Some(optimize), true) = (&mut config.optimize, args.fast) {
+		optimize.fast = true;
+		optimize.zopfli = None;
+	}
+	if args.prune_unused {
+		config
+			.validate
+			.get_or_insert_with(Default::default)
+			.prune_unused = true;
+	}
+	if locked {
+		lock_inputs(&mut config, args.update_lock);
+	}
+	let mut pipeline = default_pipeline(&config);
+	pipeline.configure(&config.pipeline, &STAGES);
+	match pipeline.enabled("blockify") {
+		true => {
+			for step in config.blockify.clone() {
+				let _stage = pdtlog::stage(&format!("blockify {}", step.name));
+				let layer = blockify_layer(&step, &config);
+				config.addons.push(layer);
+			}
+		}
+		false if !config.blockify.is_empty() => {
+			log::info!("Blockify is turned off, leaving its addons out of the releases");
+			let blockified = config.blockify.iter().map(|b| &b.name).collect::<Vec<_>>();
+			for release in &mut config.releases {
+				release.addons.retain(|a| !blockified.contains(&a));
+			}
+		}
+		false => {}
+	}
+
+	// everything is written to a staged folder first, the previous output
+	// is only replaced once the whole build went through
+	let output = config.output.clone();
+	let staged = pdtfs::StagedDir::new(&output);
+	config.output = staged.dir.clone();
+	let server = args
+		.server
+		.then(|| config.server.clone().unwrap_or_default());
+	if server.as_ref().is_some_and(|s| s.url.is_none()) {
+		panic!("build --server needs url in [server], where players download the zips from.");
+	}
+	let zips = build_releases_with(&config, &pipeline);
+	let checksums = write_checksums(&zips, &config.output);
+	for checksum in &checksums {
+		log::debug!("{}: sha1 {}", checksum.file, checksum.sha1);
+	}
+	if let Some(server) = server {
+		for (zip, checksum) in zips.iter().zip(&checksums) {
+			if let Some(upload) = &server.upload {
+				let url = upload.replace("{file}", &checksum.file);
+				log::info!("Uploading {} to {url}", checksum.file);
+				upload_zip(&url, zip);
+			}
+			let properties = server_properties(&server, checksum);
+			let path = format!("{zip}{SERVER_PROPERTIES_EXTENSION}");
+			fs::write(pdtfs::long_path(&path), &properties)
+				.unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+			pdtlog::print(&format!("{}:\n{properties}", checksum.file));
+		}
+	}
+	match pdtchangelog::unreleased_changes() {
+		Some(changes) => {
+			let notes = Utf8Path::new(&config.output).join(pdtchangelog::RELEASE_NOTES_FILE);
+			let notes = notes.to_string();
+			fs::write(pdtfs::long_path(&notes), changes)
+				.unwrap_or_else(|_| panic!("Failed to write file: {notes}"));
+		}
+		None => log::debug!("not in a git repository, no release notes written"),
+	}
+	staged.commit();
+	pdtlog::print(&format!("Built {} release zips in {output}.", zips.len()));
+	for target in &config.publish {
+		log::info!("Publish to {0} with: pdt publish {0}", target.target);
+	}
+	stats.files = zips.len();
+	stats.finish(args.stats_json.as_deref());
+}
+
+fn build_config_from_args(base: String, addons: Vec<String>, releases: Vec<String>) -> BuildConfig {
+	let split = |value: &str, what: &str| match value.split_once('=') {
+		Some((name, rest)) => (name.to_string(), rest.to_string()),
+		None => panic!("Expected NAME=... for {what}, found {value}"),
+	};
+	let addons = addons
+		.iter()
+		.map(|addon| {
+			let (name, path) = split(addon, "addon");
+			Layer {
+				name,
+				path,
+				namespaces: vec![],
+			}
+		})
+		.collect();
+	let releases = releases
+		.iter()
+		.map(|release| {
+			let (name, addons) = split(release, "combination");
+			let addons = addons
+				.split(',')
+				.filter(|a| !a.is_empty())
+				.map(|a| a.to_string())
+				.collect();
+			Combination {
+				name,
+				addons,
+				pack_format: None,
+			}
+		})
+		.collect();
+	let mut config = BuildConfig {
+		version: None,
+		version_files: vec![],
+		output: "release_output".to_string(),
+		allow_conflicts: false,
+		compression_level: None,
+		cache: default_cache(),
+		files: FilesConfig::default(),
+		base: Layer {
+			name: String::new(),
+			path: base,
+			namespaces: vec![],
+		},
+		addons,
+		blockify: vec![],
+		releases,
+		compatibility: CompatibilityConfig::default(),
+		combinations: None,
+		targets: vec![],
+		overlays: vec![],
+		optimize: None,
+		audio: None,
+		emissive: None,
+		ctm: vec![],
+		lite: None,
+		resolutions: None,
+		icon: None,
+		validate: None,
+		credits: None,
+		history: None,
+		json: JsonConfig::default(),
+		pipeline: PipelineConfig::default(),
+		placeholders: BTreeMap::new(),
+		publish: vec![],
+		server: None,
+	};
+	config.fill_defaults();
+	config
+}
+
+/// Hash of a file, or of the paths and hashes of every file in a folder.
+fn tree_hash(path: &str) -> String {
+	if !pdtfs::is_dir(path) {
+		return pdthash::get_hash(path, false);
+	}
+	let mut files = pdtfs::find_files_in_dir(path, true, &None);
+	files.sort();
+	let list = files
+		.iter()
+		.map(|file| {
+			let name = pdtfs::pack_path(path, file);
+			format!("{name} {}", pdthash::get_hash(file, false))
+		})
+		.collect::<Vec<_>>();
+	pdthash::get_string_hash(&list.join("\n"))
+}
+
+/// Checks the layers and Minecraft versions of a build against builder.lock,
+/// pinning the versions to the locked ones.
+fn lock_inputs(config: &mut BuildConfig, update: bool) {
+	let mut lock = Locker::new(update);
+	for layer in std::iter::once(&config.base).chain(&config.addons) {
+		lock.layer(&layer.name, &layer.path);
+	}
+	if let Some(version) = config.validate.as_mut().and_then(|v| v.minecraft.as_mut()) {
+		*version = lock.minecraft(version);
+	}
+	for step in &mut config.blockify {
+		if let Some(version) = &mut step.palette_version {
+			*version = lock.minecraft(version);
+		}
+	}
+	lock.finish();
+}
+
+/// Blockifies a layer into a zip in the build's staging folder and returns
+/// it as a layer. When the source and palette haven't changed since a
+/// build, the zip it made then is used.
+fn blockify_layer(step: &BlockifyStep, config: &BuildConfig) -> Layer {
+	let source = std::iter::once(&config.base)
+		.chain(&config.addons)
+		.find(|l| l.name == step.source)
+		.unwrap_or_else(|| panic!("Unknown source {} for blockify {}", step.source, step.name));
+	let cache = config.cache.as_deref().map(Cache::new);
+	let key = cache.as_ref().map(|_| {
+		let palette = match (&step.palette, &step.palette_version) {
+			(Some(palette), _) => tree_hash(palette),
+			(None, version) => version.clone().unwrap_or_default(),
+		};
+		let pixels = step.block_pixels.to_string();
+		let linear = step.linear.to_string();
+		Cache::key(&[
+			"blockify",
+			&pixels,
+			&linear,
+			&palette,
+			&tree_hash(&source.path),
+		])
+	});
+	let output = Utf8Path::new(&staging_dir())
+		.join("blockify")
+		.join(&step.name);
+	let output = output.to_string();
+	let zip = format!("{output}.zip");
+	if let (Some(cache), Some(key)) = (&cache, &key) {
+		// the cached zip has no extension, a copy is what is read as a zip
+		if cache.get("blockify", key, &zip) {
+			log::info!(
+				"Blockified {} into {} before, using the cache",
+				source.name,
+				step.name
+			);
+			return Layer {
+				name: step.name.clone(),
+				path: zip,
+				namespaces: source.namespaces.clone(),
+			};
+		}
+	}
+	let mut args = vec!["--quiet", "--zip-only", "--out-dir", &output];
+	if step.linear {
+		args.push("--linear");
+	}
+	let pixels = step.block_pixels.to_string();
+	match (&step.palette, &step.palette_version) {
+		(Some(palette), None) => args.extend([pixels.as_str(), palette]),
+		(None, Some(version)) => args.extend(["--palette-version", version, &pixels]),
+		_ => panic!(
+			"blockify {} needs either palette or palette_version",
+			step.name
+		),
+	};
+	args.push(&source.path);
+	log::info!("Blockifying {} into {}", source.name, step.name);
+	let args = args.into_iter().map(String::from).collect::<Vec<_>>();
+	pdtblockify::blockify_paths(&BlockifyArgs::parse(&args));
+	if !pdtfs::is_file(&zip) {
+		panic!("Failed to blockify {}", step.name);
+	}
+	if let (Some(cache), Some(key)) = (&cache, &key) {
+		cache.put("blockify", key, &zip);
+	}
+	Layer {
+		name: step.name.clone(),
+		path: zip,
+		namespaces: source.namespaces.clone(),
+	}
+}
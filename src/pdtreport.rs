@@ -0,0 +1,196 @@
+use super::pdtlog;
+use indicatif::HumanDuration;
+use serde::Serialize;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Report file of the optimize tools, unless another one is given.
+pub const REPORT_FILE: &str = "optimize-report.json";
+/// How many of the largest files are listed.
+const LARGEST_FILES: usize = 20;
+
+/// Size of a file before and after it was processed.
+#[derive(Clone, Debug, Serialize)]
+pub struct FileSize {
+	pub file: String,
+	pub before: u64,
+	pub after: u64,
+}
+
+/// Sizes of every file a tool processed.
+#[derive(Clone, Debug, Default)]
+pub struct SizeReport {
+	pub files: Vec<FileSize>,
+}
+
+#[derive(Serialize)]
+struct ReportJson<'a> {
+	files: usize,
+	total_before: u64,
+	total_after: u64,
+	total_saved: i64,
+	largest: Vec<&'a FileSize>,
+	sizes: &'a [FileSize],
+}
+
+/// Totals of a run of a tool, printed at the end and written as json for
+/// CI dashboards.
+#[derive(Clone, Debug, Serialize)]
+pub struct Stats {
+	pub command: String,
+	pub seconds: f64,
+	/// Files processed or written
+	pub files: usize,
+	/// Pixels of the images written
+	pub pixels: u64,
+	pub bytes_saved: i64,
+	pub warnings: usize,
+	pub errors: usize,
+	#[serde(skip)]
+	start: Instant,
+}
+
+impl Stats {
+	/// Starts timing a run of a command.
+	pub fn new(command: &str) -> Stats {
+		Stats {
+			command: command.to_string(),
+			seconds: 0.0,
+			files: 0,
+			pixels: 0,
+			bytes_saved: 0,
+			warnings: 0,
+			errors: 0,
+			start: Instant::now(),
+		}
+	}
+
+	/// The run as one line, leaving out what the command doesn't do.
+	pub fn summary(&self) -> String {
+		let mut parts = vec![format!("{} files", self.files)];
+		if self.pixels > 0 {
+			parts.push(format!("{} pixels written", self.pixels));
+		}
+		if self.bytes_saved != 0 {
+			parts.push(format!("{} saved", human_size(self.bytes_saved)));
+		}
+		parts.push(format!("{} warnings", self.warnings));
+		parts.push(format!("{} errors", self.errors));
+		let time = HumanDuration(Duration::from_secs_f64(self.seconds));
+		format!("Done in {time}: {}", parts.join(", "))
+	}
+
+	/// Stops timing, prints the summary and writes the stats to a json file
+	/// if one is given.
+	pub fn finish(mut self, json: Option<&str>) {
+		self.seconds = self.start.elapsed().as_secs_f64();
+		(self.warnings, self.errors) = pdtlog::counts();
+		pdtlog::print(&self.summary());
+		pdtlog::event("summary", serde_json::to_value(&self).unwrap());
+		if let Some(path) = json {
+			let data = serde_json::to_string_pretty(&self).unwrap() + "\n";
+			fs::write(path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+		}
+	}
+}
+
+/// Bytes as B, KiB or MiB, for tables.
+pub fn human_size(bytes: i64) -> String {
+	let size = bytes.unsigned_abs() as f64;
+	let sign = if bytes < 0 { "-" } else { "" };
+	match size {
+		s if s >= 1024.0 * 1024.0 => format!("{sign}{:.1} MiB", s / 1024.0 / 1024.0),
+		s if s >= 1024.0 => format!("{sign}{:.1} KiB", s / 1024.0),
+		s => format!("{sign}{s} B"),
+	}
+}
+
+impl SizeReport {
+	pub fn add(&mut self, file: &str, before: u64, after: u64) {
+		self.files.push(FileSize {
+			file: file.to_string(),
+			before,
+			after,
+		});
+	}
+
+	pub fn total_before(&self) -> u64 {
+		self.files.iter().map(|f| f.before).sum()
+	}
+
+	pub fn total_after(&self) -> u64 {
+		self.files.iter().map(|f| f.after).sum()
+	}
+
+	pub fn saved(&self) -> i64 {
+		self.total_before() as i64 - self.total_after() as i64
+	}
+
+	/// The files that are still the largest, largest first.
+	pub fn largest(&self) -> Vec<&FileSize> {
+		let mut files = self.files.iter().collect::<Vec<_>>();
+		files.sort_by(|a, b| b.after.cmp(&a.after).then(a.file.cmp(&b.file)));
+		files.truncate(LARGEST_FILES);
+		files
+	}
+
+	/// A table of the largest files and the totals.
+	pub fn table(&self) -> String {
+		let rows = self
+			.largest()
+			.into_iter()
+			.map(|f| {
+				let saved = f.before as i64 - f.after as i64;
+				[
+					f.file.clone(),
+					human_size(f.before as i64),
+					human_size(f.after as i64),
+					human_size(saved),
+				]
+			})
+			.chain(std::iter::once([
+				format!("total of {} files", self.files.len()),
+				human_size(self.total_before() as i64),
+				human_size(self.total_after() as i64),
+				human_size(self.saved()),
+			]))
+			.collect::<Vec<_>>();
+		let header = ["largest files", "before", "after", "saved"].map(|h| h.to_string());
+		let mut widths = header.clone().map(|h| h.chars().count());
+		for row in &rows {
+			for (width, cell) in widths.iter_mut().zip(row) {
+				*width = (*width).max(cell.chars().count());
+			}
+		}
+		let line = |row: &[String; 4]| {
+			format!(
+				"{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+				row[0],
+				row[1],
+				row[2],
+				row[3],
+				w0 = widths[0],
+				w1 = widths[1],
+				w2 = widths[2],
+				w3 = widths[3]
+			)
+		};
+		let mut lines = vec![line(&header)];
+		lines.extend(rows.iter().map(line));
+		lines.join("\n")
+	}
+
+	/// Writes the totals, the largest files and every size as json.
+	pub fn write_json(&self, path: &str) {
+		let report = ReportJson {
+			files: self.files.len(),
+			total_before: self.total_before(),
+			total_after: self.total_after(),
+			total_saved: self.saved(),
+			largest: self.largest(),
+			sizes: &self.files,
+		};
+		let data = serde_json::to_string_pretty(&report).unwrap() + "\n";
+		fs::write(path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+	}
+}
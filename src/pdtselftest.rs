@@ -0,0 +1,297 @@
+use super::pdtaudio::{self, AudioArgs};
+use super::pdtblockify::{self, BlockifyArgs};
+use super::pdtjson::{self, JsonArgs};
+use super::pdtoptimize::{self, OptimizeArgs};
+use super::pdtrelease::{self, BuildArgs};
+use super::pdtvalidate::{self, ValidateArgs};
+use super::{pdtfs, pdtlog, pdtzip};
+use clap::{ArgAction, Parser};
+use image::{Rgba, RgbaImage};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::MAIN_SEPARATOR as SLASH;
+
+#[derive(Debug, clap::Args)]
+pub struct SelftestArgs {
+	#[arg(long)]
+	/// Keep the folder the pack is made in, to look at what went wrong
+	keep: bool,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings, errors and how the steps went
+	quiet: bool,
+}
+
+/// Arguments of a tool, parsed like on the command line.
+#[derive(Parser)]
+#[command(no_binary_name = true)]
+struct Step<T: clap::Args> {
+	#[command(flatten)]
+	args: T,
+}
+
+fn parse<T: clap::Args>(args: &[&str]) -> T {
+	Step::<T>::try_parse_from(args)
+		.unwrap_or_else(|e| panic!("Bad arguments {args:?}: {e}"))
+		.args
+}
+
+const NAMESPACE: &str = "selftest";
+/// Size of the test textures and palette blocks
+const TEXTURE_PIXELS: u32 = 16;
+const BLOCK_PIXELS: u32 = 4;
+
+const BUILD_CONFIG: &str = r#"output = "release_output"
+
+[base]
+name = "selftest"
+path = "base"
+
+[[addon]]
+name = "blocks"
+path = "blockify_output.zip"
+
+[[release]]
+name = "plain"
+
+[[release]]
+name = "blocks"
+addons = ["blocks"]
+
+[optimize]
+
+[audio]
+
+[validate]
+"#;
+
+pub fn run(args: SelftestArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let dir = format!(
+		"{}{SLASH}pdt_selftest_{}",
+		std::env::temp_dir().display(),
+		std::process::id()
+	);
+	pdtfs::if_dir_exists_remove_and_remake_it(&dir);
+	let previous = std::env::current_dir().expect("Failed to find the current directory.");
+	std::env::set_current_dir(&dir).unwrap_or_else(|_| panic!("Failed to enter {dir}"));
+
+	// the logger is only set up once, so the steps log at the level given here
+	let steps: Vec<(&str, fn())> = vec![
+		("make the pack", make_pack),
+		("format json", || {
+			pdtjson::run(parse::<JsonArgs>(&["base"]));
+			pdtjson::run(parse::<JsonArgs>(&["--check", "base"]));
+		}),
+		("optimize images", || {
+			pdtoptimize::run(parse::<OptimizeArgs>(&["--fast", "base"]));
+		}),
+		("optimize audio", || {
+			pdtaudio::run(parse::<AudioArgs>(&["sound.wav"]));
+			assert_file("sound.ogg");
+		}),
+		("blockify", || {
+			let pixels = BLOCK_PIXELS.to_string();
+			// quiet only hides the progress bar here
+			pdtblockify::run(parse::<BlockifyArgs>(&[
+				"--quiet", "--zip", &pixels, "palette", "base",
+			]));
+			assert_file("blockify_output.zip");
+		}),
+		("build", || {
+			pdtrelease::build(parse::<BuildArgs>(&[]));
+			check_releases();
+		}),
+		("validate", || {
+			for check in ["json", "refs", "sounds", "animations", "fonts"] {
+				for zip in ["plain", "blocks"] {
+					let zip = format!("release_output{SLASH}{zip}.zip");
+					pdtvalidate::run(parse::<ValidateArgs>(&[check, &zip]));
+				}
+			}
+		}),
+	];
+	let mut failed = vec![];
+	for (name, step) in &steps {
+		match panic::catch_unwind(AssertUnwindSafe(step)) {
+			Ok(()) => pdtlog::print(&format!("ok: {name}")),
+			Err(_) => {
+				pdtlog::print(&format!("FAILED: {name}"));
+				failed.push(*name);
+				// later steps work on what this one makes
+				break;
+			}
+		}
+	}
+
+	std::env::set_current_dir(&previous)
+		.unwrap_or_else(|_| panic!("Failed to enter {}", previous.display()));
+	match args.keep {
+		true => pdtlog::print(&format!("The pack is in {dir}")),
+		false => pdtfs::if_dir_exists_remove_it(&dir),
+	}
+	if !failed.is_empty() {
+		panic!("Self-test failed at {}.", failed.join(", "));
+	}
+	pdtlog::print(&format!("All {} self-test steps passed.", steps.len()));
+}
+
+/// Writes the test pack in base, a palette of three blocks and a wav to
+/// convert.
+fn make_pack() {
+	let assets = format!("base{SLASH}assets{SLASH}{NAMESPACE}");
+	write(
+		"base/pack.mcmeta",
+		r#"{"pack": {"pack_format": 34, "description": "pdt self-test"}}"#,
+	);
+	write(
+		&format!("{assets}/models/block/tile.json"),
+		r#"{
+	// comments are taken out by the build
+	"parent": "minecraft:block/cube_all",
+	"textures": {"all": "selftest:block/tile"}
+}"#,
+	);
+	write(
+		&format!("{assets}/blockstates/tile.json"),
+		r#"{"variants": {"": {"model": "selftest:block/tile"}}}"#,
+	);
+	write(
+		&format!("{assets}/sounds.json"),
+		r#"{"tile.hum": {"sounds": ["selftest:tile/hum"], "subtitle": "subtitles.selftest.tile.hum"}}"#,
+	);
+	write(
+		&format!("{assets}/lang/en_us.json"),
+		r#"{"subtitles.selftest.tile.hum": "Tile hums"}"#,
+	);
+	write(
+		&format!("{assets}/textures/block/tile.png.mcmeta"),
+		r#"{"animation": {"frametime": 4}}"#,
+	);
+	// two frames, a checkerboard and its inverse
+	let tile = RgbaImage::from_fn(TEXTURE_PIXELS, TEXTURE_PIXELS * 2, |x, y| {
+		let frame = y / TEXTURE_PIXELS;
+		match (x / 4 + y / 4 + frame) % 2 {
+			0 => Rgba([200, 40, 40, 255]),
+			_ => Rgba([40, 40, 200, 255]),
+		}
+	});
+	save(&tile, &format!("{assets}/textures/block/tile.png"));
+	for (name, color) in [
+		("red", [200, 40, 40]),
+		("green", [40, 200, 40]),
+		("blue", [40, 40, 200]),
+	] {
+		let block = RgbaImage::from_pixel(
+			TEXTURE_PIXELS,
+			TEXTURE_PIXELS,
+			Rgba([color[0], color[1], color[2], 255]),
+		);
+		save(&block, &format!("palette/{name}.png"));
+	}
+	fs::write("sound.wav", wav(440.0, 0.25)).expect("Failed to write file: sound.wav");
+	let sounds = format!("{assets}{SLASH}sounds{SLASH}tile");
+	fs::create_dir_all(&sounds).unwrap_or_else(|_| panic!("Failed to create {sounds} directory."));
+	fs::write(format!("{sounds}{SLASH}hum.wav"), wav(220.0, 0.5))
+		.unwrap_or_else(|_| panic!("Failed to write file: {sounds}{SLASH}hum.wav"));
+	write("builder.toml", BUILD_CONFIG);
+}
+
+/// Checks the zips have the files of the pack, changed the way the build
+/// changes them.
+fn check_releases() {
+	let texture = format!("assets/{NAMESPACE}/textures/block/tile.png");
+	for (release, pixels) in [("plain", 1), ("blocks", BLOCK_PIXELS)] {
+		let zip = format!("release_output{SLASH}{release}.zip");
+		assert_file(&zip);
+		let entries = pdtzip::zip_entries(&zip);
+		for file in [
+			"pack.mcmeta".to_string(),
+			format!("assets/{NAMESPACE}/models/block/tile.json"),
+			format!("assets/{NAMESPACE}/sounds/tile/hum.ogg"),
+			texture.clone(),
+		] {
+			if !entries.contains(&file) {
+				panic!("{zip} is missing {file}");
+			}
+		}
+		if entries.iter().any(|e| e.ends_with(".wav")) {
+			panic!("{zip} still has wav files");
+		}
+		let model = entry(&zip, &format!("assets/{NAMESPACE}/models/block/tile.json"));
+		if String::from_utf8_lossy(&model).contains("//") {
+			panic!("{zip} has a model with comments");
+		}
+		let png = image::load_from_memory(&entry(&zip, &texture))
+			.unwrap_or_else(|e| panic!("Failed to read {texture} of {zip}: {e}"));
+		let expected = (TEXTURE_PIXELS * pixels, TEXTURE_PIXELS * 2 * pixels);
+		if (png.width(), png.height()) != expected {
+			panic!(
+				"{texture} of {zip} is {}x{}, not {}x{}",
+				png.width(),
+				png.height(),
+				expected.0,
+				expected.1
+			);
+		}
+	}
+}
+
+fn entry(zip: &str, name: &str) -> Vec<u8> {
+	pdtzip::read_zip_entry(zip, name).unwrap_or_else(|| panic!("{zip} is missing {name}"))
+}
+
+fn assert_file(path: &str) {
+	if !pdtfs::is_file(path) {
+		panic!("{path} wasn't made");
+	}
+}
+
+fn write(path: &str, data: &str) {
+	let path = path.replace('/', std::path::MAIN_SEPARATOR_STR);
+	if let Some((dir, _)) = path.rsplit_once(SLASH) {
+		fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	}
+	fs::write(&path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+}
+
+fn save(image: &RgbaImage, path: &str) {
+	let path = path.replace('/', std::path::MAIN_SEPARATOR_STR);
+	if let Some((dir, _)) = path.rsplit_once(SLASH) {
+		fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+	}
+	image
+		.save(&path)
+		.unwrap_or_else(|e| panic!("Failed to write image {path}: {e}"));
+}
+
+/// A mono 16-bit wav of a sine wave.
+fn wav(frequency: f32, seconds: f32) -> Vec<u8> {
+	let rate: u32 = 44100;
+	let samples = (0..(rate as f32 * seconds) as u32)
+		.map(|i| {
+			let t = i as f32 / rate as f32;
+			((t * frequency * std::f32::consts::TAU).sin() * 0.5 * i16::MAX as f32) as i16
+		})
+		.collect::<Vec<_>>();
+	let size = samples.len() as u32 * 2;
+	let mut data = vec![];
+	data.extend(b"RIFF");
+	data.extend((36 + size).to_le_bytes());
+	data.extend(b"WAVEfmt ");
+	data.extend(16u32.to_le_bytes());
+	data.extend(1u16.to_le_bytes());
+	data.extend(1u16.to_le_bytes());
+	data.extend(rate.to_le_bytes());
+	data.extend((rate * 2).to_le_bytes());
+	data.extend(2u16.to_le_bytes());
+	data.extend(16u16.to_le_bytes());
+	data.extend(b"data");
+	data.extend(size.to_le_bytes());
+	for sample in samples {
+		data.extend(sample.to_le_bytes());
+	}
+	data
+}
@@ -1,46 +1,271 @@
-use std::sync::{Arc, Mutex};
+use clap::ValueEnum;
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once};
 use std::thread::Builder;
 
+/// Environment variable with the number of threads to use, when --threads
+/// isn't given.
+pub const THREADS_ENV: &str = "PDT_THREADS";
+
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+static SCHEDULE: AtomicU8 = AtomicU8::new(0);
+static FAIL_FAST: AtomicBool = AtomicBool::new(false);
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static CANCEL_HANDLER: Once = Once::new();
+
+/// How tasks are handed out to the threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Schedule {
+	/// One task at a time, so a thread that is done takes the next one
+	#[default]
+	Dynamic,
+	/// Chunks of tasks that get smaller as fewer are left, waiting less on
+	/// each other with many small tasks
+	Chunked,
+}
+
+/// The options of a tool for how many threads it uses.
+#[derive(Debug, Default, clap::Args)]
+pub struct ThreadArgs {
+	#[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+	/// Threads to use, defaults to PDT_THREADS or the number of cores
+	pub threads: Option<u16>,
+	#[arg(long, value_enum, default_value_t = Schedule::Dynamic)]
+	/// How tasks are handed out to the threads
+	pub schedule: Schedule,
+	#[arg(long)]
+	/// Stop at the first file that fails, instead of doing the others and
+	/// listing every failure at the end
+	pub fail_fast: bool,
+}
+
+impl ThreadArgs {
+	/// Makes every later [multithread] use these options.
+	pub fn apply(&self) {
+		if let Some(threads) = self.threads {
+			THREADS.store(threads as usize, Ordering::Relaxed);
+		}
+		SCHEDULE.store(self.schedule as u8, Ordering::Relaxed);
+		FAIL_FAST.store(self.fail_fast, Ordering::Relaxed);
+	}
+}
+
+/// Makes Ctrl+C stop handing out tasks instead of stopping the tool, so the
+/// ones being worked on finish and the tool can save what it has done. The
+/// results of the tasks done are returned as usual, check [cancelled] to
+/// tell. A second Ctrl+C stops right away.
+pub fn cancel_on_ctrl_c() {
+	CANCEL_HANDLER.call_once(|| {
+		// only signal safe functions can be called in the handler
+		let handler = || {
+			if CANCELLED.swap(true, Ordering::SeqCst) {
+				unsafe { libc::_exit(130) };
+			}
+			let message = "\nStopping once the files being worked on are done, press Ctrl+C again to stop right away\n";
+			unsafe { libc::write(2, message.as_ptr().cast(), message.len() as _) };
+		};
+		if let Err(e) = unsafe { signal_hook_registry::register(libc::SIGINT, handler) } {
+			log::warn!("Failed to handle Ctrl+C, it will stop right away: {e}");
+		}
+	});
+}
+
+/// Whether Ctrl+C was pressed since [cancel_on_ctrl_c].
+pub fn cancelled() -> bool {
+	CANCELLED.load(Ordering::SeqCst)
+}
+
+/// A task that failed, by its place in the tasks.
+#[derive(Clone, Debug)]
+pub struct TaskError {
+	pub task: usize,
+	pub message: String,
+}
+
+/// Every task that failed in one run of [try_multithread].
+#[derive(Clone, Debug)]
+pub struct TaskErrors {
+	pub errors: Vec<TaskError>,
+	pub tasks: usize,
+	/// Whether the tasks after the first failure were skipped, with
+	/// --fail-fast
+	pub stopped: bool,
+}
+
+impl fmt::Display for TaskErrors {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} of {} tasks failed", self.errors.len(), self.tasks)?;
+		if self.stopped {
+			write!(f, ", the rest were skipped")?;
+		}
+		for error in &self.errors {
+			write!(f, "\n  {}", error.message)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for TaskErrors {}
+
+/// The message a task panicked with.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+	match payload.downcast::<String>() {
+		Ok(message) => *message,
+		Err(payload) => match payload.downcast::<&str>() {
+			Ok(message) => message.to_string(),
+			Err(_) => "panicked".to_string(),
+		},
+	}
+}
+
+/// Threads used when no number is given: --threads, then PDT_THREADS, then
+/// the number of cores.
+pub fn threads() -> usize {
+	match THREADS.load(Ordering::Relaxed) {
+		0 => std::env::var(THREADS_ENV)
+			.ok()
+			.and_then(|n| n.trim().parse().ok())
+			.filter(|n| *n > 0)
+			.unwrap_or_else(num_cpus::get),
+		n => n,
+	}
+}
+
+fn schedule() -> Schedule {
+	match SCHEDULE.load(Ordering::Relaxed) {
+		0 => Schedule::Dynamic,
+		_ => Schedule::Chunked,
+	}
+}
+
+/// Runs a task for every item on a number of threads, the results of the
+/// tasks returning some are kept in the order of the items. If a task
+/// panics, the others still run and every panic is listed in one at the end.
 pub fn multithread<F, I, O>(tasks: Vec<I>, num_threads: Option<usize>, task_fn: F) -> Vec<O>
 where
 	F: Fn(usize, I) -> Option<O> + Send + Clone + 'static,
 	I: Send + 'static,
 	O: Send + 'static,
+{
+	let tasks = tasks.into_iter().enumerate().collect();
+	run(tasks, num_threads, move |thread_num, task| {
+		Ok(task_fn(thread_num, task))
+	})
+	.unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Like [multithread] for tasks that can fail. Every task is still run
+/// unless --fail-fast is given, then the failures are returned together,
+/// panics included.
+pub fn try_multithread<F, I, O, E>(
+	tasks: Vec<I>, num_threads: Option<usize>, task_fn: F,
+) -> Result<Vec<O>, TaskErrors>
+where
+	F: Fn(usize, I) -> Result<O, E> + Send + Clone + 'static,
+	I: Send + 'static,
+	O: Send + 'static,
+	E: fmt::Display,
+{
+	let tasks = tasks.into_iter().enumerate().collect();
+	run(tasks, num_threads, move |thread_num, task| {
+		task_fn(thread_num, task)
+			.map(Some)
+			.map_err(|e| e.to_string())
+	})
+}
+
+/// Like [multithread], starting the tasks with the most work first so
+/// the large ones don't end up alone at the end. Results are still in the
+/// order of the tasks.
+pub fn multithread_weighted<F, I, O>(
+	tasks: Vec<I>, weights: &[u64], num_threads: Option<usize>, task_fn: F,
+) -> Vec<O>
+where
+	F: Fn(usize, I) -> Option<O> + Send + Clone + 'static,
+	I: Send + 'static,
+	O: Send + 'static,
+{
+	let mut tasks = tasks.into_iter().enumerate().collect::<Vec<_>>();
+	tasks.sort_by_key(|(i, _)| std::cmp::Reverse(weights.get(*i).copied().unwrap_or(0)));
+	run(tasks, num_threads, move |thread_num, task| {
+		Ok(task_fn(thread_num, task))
+	})
+	.unwrap_or_else(|e| panic!("{e}"))
+}
+
+fn run<F, I, O>(
+	tasks: Vec<(usize, I)>, num_threads: Option<usize>, task_fn: F,
+) -> Result<Vec<O>, TaskErrors>
+where
+	F: Fn(usize, I) -> Result<Option<O>, String> + Send + Clone + 'static,
+	I: Send + 'static,
+	O: Send + 'static,
 {
 	let num_tasks = tasks.len();
-	let wrapped_tasks = Arc::new(Mutex::new(tasks.into_iter().enumerate()));
-	let num_threads = num_threads.unwrap_or_else(num_cpus::get);
+	let wrapped_tasks = Arc::new(Mutex::new(tasks.into_iter()));
+	let num_threads = num_threads.unwrap_or_else(threads);
+	let schedule = schedule();
+	let fail_fast = FAIL_FAST.load(Ordering::Relaxed);
+	let stop = Arc::new(AtomicBool::new(false));
 	let mut join_handles = Vec::with_capacity(num_threads);
 
 	for thread_num in 0..num_threads {
 		let wrapped_tasks = Arc::clone(&wrapped_tasks);
 		let task_fn = task_fn.clone();
+		let stop = Arc::clone(&stop);
 
 		let builder = Builder::new().name(format!("pdtthread::multithread thread {thread_num}"));
 
 		let join_handle = builder
 			.spawn(move || {
 				let mut results = vec![];
+				let mut errors = vec![];
 
-				loop {
+				while !stop.load(Ordering::Relaxed) && !cancelled() {
 					let mut unlocked_wrapped_tasks = wrapped_tasks.lock().unwrap();
-					let task = unlocked_wrapped_tasks.next();
+					let chunk = match schedule {
+						Schedule::Dynamic => 1,
+						Schedule::Chunked => {
+							(unlocked_wrapped_tasks.len() / (2 * num_threads)).max(1)
+						}
+					};
+					let tasks = unlocked_wrapped_tasks
+						.by_ref()
+						.take(chunk)
+						.collect::<Vec<_>>();
 
 					// unlock as quickly as possible,
 					// let other threads get at the data
 					drop(unlocked_wrapped_tasks);
 
-					match task {
-						Some((i, task)) => {
-							if let Some(res) = task_fn(thread_num, task) {
-								results.push((i, res));
+					if tasks.is_empty() {
+						break;
+					}
+					for (i, task) in tasks {
+						if cancelled() {
+							break;
+						}
+						let result =
+							panic::catch_unwind(AssertUnwindSafe(|| task_fn(thread_num, task)))
+								.unwrap_or_else(|payload| Err(panic_message(payload)));
+						match result {
+							Ok(Some(res)) => results.push((i, res)),
+							Ok(None) => (),
+							Err(message) => {
+								errors.push(TaskError { task: i, message });
+								if fail_fast {
+									stop.store(true, Ordering::Relaxed);
+									break;
+								}
 							}
 						}
-						None => break,
 					}
 				}
 
-				results
+				(results, errors)
 			})
 			.unwrap();
 
@@ -48,12 +273,22 @@ where
 	}
 
 	let mut thread_results = Vec::with_capacity(num_tasks);
+	let mut errors = vec![];
 
 	for thread in join_handles.into_iter() {
-		let res = thread.join().unwrap();
+		let (res, errs) = thread.join().unwrap();
 		res.into_iter().for_each(|e| thread_results.push(e));
+		errors.extend(errs);
 	}
 
+	if !errors.is_empty() {
+		errors.sort_unstable_by_key(|e| e.task);
+		return Err(TaskErrors {
+			errors,
+			tasks: num_tasks,
+			stopped: stop.load(Ordering::Relaxed),
+		});
+	}
 	thread_results.sort_unstable_by_key(|e| e.0);
-	thread_results.into_iter().map(|e| e.1).collect()
+	Ok(thread_results.into_iter().map(|e| e.1).collect())
 }
@@ -0,0 +1,2470 @@
+use super::{pdtfs, pdthash, pdtjson, pdtlog, pdtmojang, pdtthread, pdtzip};
+use camino::Utf8Path;
+use clap::{ArgAction, Subcommand};
+use image::{imageops, RgbaImage};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{MAIN_SEPARATOR as SLASH, MAIN_SEPARATOR_STR};
+
+pub use super::pdtlog::Problem;
+
+/// Checks run on releases before they are zipped, from the [validate]
+/// table of the build config.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidateConfig {
+	/// Minecraft version to check references to vanilla assets against.
+	/// Without it references to minecraft assets missing from the pack are
+	/// assumed to be vanilla
+	pub minecraft: Option<String>,
+	/// Leave textures and sounds nothing points at out of the zips
+	#[serde(default)]
+	pub prune_unused: bool,
+	/// Point models at one copy of byte-identical textures and leave the
+	/// other copies out of the zips
+	#[serde(default)]
+	pub dedupe_textures: bool,
+	/// Crop the fully transparent margins of images the game scales to fit,
+	/// like pack.png
+	#[serde(default)]
+	pub crop_margins: bool,
+	/// Only problems in the files of these namespaces, and the ones outside
+	/// assets, stop the build. Files of other namespaces can still be
+	/// pointed at
+	#[serde(default)]
+	pub namespaces: Vec<String>,
+}
+
+/// The problems in the files of some namespaces, see
+/// [pdtfs::in_namespaces].
+pub fn in_namespaces(problems: Vec<Problem>, namespaces: &[String]) -> Vec<Problem> {
+	problems
+		.into_iter()
+		.filter(|p| pdtfs::in_namespaces(&p.file, namespaces))
+		.collect()
+}
+
+/// An asset another file points at.
+struct Reference<'a> {
+	/// The resource location as written, like `minecraft:block/stone`
+	location: &'a str,
+	kind: &'static str,
+	extension: &'static str,
+	what: &'static str,
+}
+
+/// Path of a resource location, `namespace:path` or just `path` for
+/// minecraft, in a folder of the assets like models or textures.
+pub fn asset_path(location: &str, kind: &str, extension: &str) -> String {
+	let (namespace, path) = location.split_once(':').unwrap_or(("minecraft", location));
+	format!("assets/{namespace}/{kind}/{path}{extension}")
+}
+
+/// Line of the first place the json string is written in a file, 0 if not
+/// found.
+fn line_of(data: &str, value: &str) -> usize {
+	match data.find(&format!("\"{value}\"")) {
+		Some(i) => data[..i].lines().count().max(1),
+		None => 0,
+	}
+}
+
+/// Models of a variant or multipart apply, one or a list of them.
+fn models_of(value: &Value) -> Vec<&str> {
+	match value {
+		Value::Array(list) => list.iter().filter_map(|v| v["model"].as_str()).collect(),
+		value => value["model"].as_str().into_iter().collect(),
+	}
+}
+
+fn models_of_blockstate(blockstate: &Value) -> Vec<&str> {
+	let mut models = vec![];
+	if let Some(variants) = blockstate["variants"].as_object() {
+		models.extend(variants.values().flat_map(models_of));
+	}
+	if let Some(parts) = blockstate["multipart"].as_array() {
+		models.extend(parts.iter().flat_map(|part| models_of(&part["apply"])));
+	}
+	models
+}
+
+fn references_of_model(model: &Value) -> Vec<Reference<'_>> {
+	let mut references = vec![];
+	if let Some(parent) = model["parent"].as_str() {
+		if !parent.starts_with("builtin/") {
+			references.push(Reference {
+				location: parent,
+				kind: "models",
+				extension: ".json",
+				what: "parent model",
+			});
+		}
+	}
+	if let Some(textures) = model["textures"].as_object() {
+		for texture in textures.values() {
+			let texture = texture.as_str().or_else(|| texture["sprite"].as_str());
+			match texture {
+				Some(texture) if !texture.starts_with('#') => references.push(Reference {
+					location: texture,
+					kind: "textures",
+					extension: ".png",
+					what: "texture",
+				}),
+				_ => (),
+			}
+		}
+	}
+	references
+}
+
+/// Checks the blockstates and models of a pack, by their path in the pack
+/// mapped to where they are read from, point at models, parents and
+/// textures that exist in the pack or in vanilla.
+pub fn validate_references(
+	files: &BTreeMap<String, String>, vanilla: Option<&BTreeSet<String>>,
+) -> Vec<Problem> {
+	let exists = |path: &str| {
+		files.contains_key(path)
+			|| match vanilla {
+				Some(vanilla) => vanilla.contains(path),
+				None => path.starts_with("assets/minecraft/"),
+			}
+	};
+	let mut problems = vec![];
+	for (file, path) in files {
+		let Some(rest) = file.strip_prefix("assets/") else {
+			continue;
+		};
+		let kind = rest.split('/').nth(1).unwrap_or_default();
+		if !file.ends_with(".json") || !["blockstates", "models"].contains(&kind) {
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let json: Value = match serde_json::from_str(&data) {
+			Ok(json) => json,
+			Err(e) => {
+				problems.push(Problem {
+					file: file.clone(),
+					line: e.line(),
+					message: format!("invalid json: {e}"),
+				});
+				continue;
+			}
+		};
+		let references = match kind {
+			"blockstates" => models_of_blockstate(&json)
+				.into_iter()
+				.map(|location| Reference {
+					location,
+					kind: "models",
+					extension: ".json",
+					what: "model",
+				})
+				.collect(),
+			_ => references_of_model(&json),
+		};
+		for reference in references {
+			let target = asset_path(reference.location, reference.kind, reference.extension);
+			if !exists(&target) {
+				problems.push(Problem {
+					file: file.clone(),
+					line: line_of(&data, reference.location),
+					message: format!("{} {} not found", reference.what, reference.location),
+				});
+			}
+		}
+	}
+	problems
+}
+
+/// An item model override picking a model by custom_model_data.
+#[derive(Clone, Debug)]
+pub struct CustomModel {
+	/// Item model the override is in, like minecraft:item/stick
+	pub item: String,
+	pub value: i64,
+	/// The other predicates of the override, like pulling
+	pub predicate: String,
+	pub model: String,
+}
+
+/// custom_model_data values an item skips between its lowest and highest.
+#[derive(Clone, Debug)]
+pub struct CustomModelGap {
+	pub item: String,
+	pub missing: Vec<i64>,
+}
+
+/// Finds the item model overrides using custom_model_data, returning the
+/// problems with them, every override and the values each item skips.
+/// Overrides with the same predicates in one item and models that don't
+/// exist in the pack or in vanilla are problems.
+pub fn custom_model_data(
+	files: &BTreeMap<String, String>, vanilla: Option<&BTreeSet<String>>,
+) -> (Vec<Problem>, Vec<CustomModel>, Vec<CustomModelGap>) {
+	let exists = |path: &str| {
+		files.contains_key(path)
+			|| match vanilla {
+				Some(vanilla) => vanilla.contains(path),
+				None => path.starts_with("assets/minecraft/"),
+			}
+	};
+	let mut problems = vec![];
+	let mut models = vec![];
+	let mut gaps = vec![];
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		let ["assets", namespace, "models", "item", ..] = parts[..] else {
+			continue;
+		};
+		if !file.ends_with(".json") {
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let Ok(json) = serde_json::from_str::<Value>(&data) else {
+			// broken json is listed by validate json
+			continue;
+		};
+		let item = format!(
+			"{namespace}:{}",
+			parts[3..].join("/").trim_end_matches(".json")
+		);
+		let mut seen = BTreeMap::<(i64, String), String>::new();
+		let mut values = BTreeSet::new();
+		let overrides = json["overrides"].as_array().into_iter().flatten();
+		for entry in overrides {
+			let Some(predicate) = entry["predicate"].as_object() else {
+				continue;
+			};
+			let Some(value) = predicate.get("custom_model_data") else {
+				continue;
+			};
+			let model = entry["model"].as_str().unwrap_or_default().to_string();
+			let line = line_of(&data, &model);
+			let Some(value) = value.as_f64().filter(|v| v.fract() == 0.0) else {
+				problems.push(Problem {
+					file: file.clone(),
+					line,
+					message: format!("custom_model_data {value} isn't a whole number"),
+				});
+				continue;
+			};
+			let value = value as i64;
+			let mut others = predicate.clone();
+			others.retain(|k, _| k != "custom_model_data");
+			let others = match others.is_empty() {
+				true => String::new(),
+				false => Value::Object(others).to_string(),
+			};
+			if let Some(first) = seen.insert((value, others.clone()), model.clone()) {
+				problems.push(Problem {
+					file: file.clone(),
+					line,
+					message: format!("custom_model_data {value} is used by {first} and {model}"),
+				});
+			}
+			if !exists(&asset_path(&model, "models", ".json")) {
+				problems.push(Problem {
+					file: file.clone(),
+					line,
+					message: format!("model {model} not found"),
+				});
+			}
+			values.insert(value);
+			models.push(CustomModel {
+				item: item.clone(),
+				value,
+				predicate: others,
+				model,
+			});
+		}
+		if let (Some(first), Some(last)) = (values.first(), values.last()) {
+			let missing = (*first..*last)
+				.filter(|v| !values.contains(v))
+				.collect::<Vec<_>>();
+			if !missing.is_empty() {
+				gaps.push(CustomModelGap { item, missing });
+			}
+		}
+	}
+	(problems, models, gaps)
+}
+
+/// The custom_model_data overrides as a Markdown table, for documentation.
+pub fn custom_model_table(models: &[CustomModel]) -> String {
+	let mut lines = vec![
+		"| Item | custom_model_data | Predicates | Model |".to_string(),
+		"| --- | --- | --- | --- |".to_string(),
+	];
+	let mut models = models.iter().collect::<Vec<_>>();
+	models.sort_by(|a, b| (&a.item, a.value).cmp(&(&b.item, b.value)));
+	for model in models {
+		lines.push(format!(
+			"| {} | {} | {} | {} |",
+			model.item,
+			model.value,
+			model.predicate.replace('|', "\\|"),
+			model.model
+		));
+	}
+	lines.join("\n") + "\n"
+}
+
+/// The language every other one is compared with.
+pub const SOURCE_LANGUAGE: &str = "en_us";
+
+/// How complete a language is compared with en_us.
+#[derive(Clone, Debug)]
+pub struct LangReport {
+	pub language: String,
+	/// Keys of en_us without a translation
+	pub missing: Vec<String>,
+	/// Percent of the keys of en_us that are translated, rounded down to
+	/// one decimal
+	pub completion: f64,
+}
+
+/// Arguments a translation takes, by position, from `%s`, `%d` and `%1$s`.
+/// `%%` is a literal %.
+pub fn placeholders(text: &str) -> Result<Vec<usize>, String> {
+	let mut args = vec![];
+	let mut next = 1;
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c != '%' {
+			continue;
+		}
+		let mut digits = String::new();
+		while let Some(d) = chars.next_if(|d| d.is_ascii_digit()) {
+			digits.push(d);
+		}
+		match (digits.is_empty(), chars.next()) {
+			(true, Some('%')) => (),
+			(true, Some('s' | 'd')) => {
+				args.push(next);
+				next += 1;
+			}
+			(false, Some('$')) => match chars.next() {
+				Some('s' | 'd') => args.push(digits.parse().unwrap()),
+				_ => return Err(format!("malformed placeholder %{digits}$")),
+			},
+			(_, Some(other)) => return Err(format!("malformed placeholder %{digits}{other}")),
+			(_, None) => return Err("ends in a % without a placeholder".to_string()),
+		}
+	}
+	args.sort();
+	Ok(args)
+}
+
+/// Reads a language file, adding a problem if it isn't a json object of
+/// text.
+fn read_lang(
+	file: &str, path: &str, problems: &mut Vec<Problem>,
+) -> Option<(String, BTreeMap<String, String>)> {
+	let data = fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+	match serde_json::from_str(&data) {
+		Ok(lang) => Some((data, lang)),
+		Err(e) => {
+			problems.push(Problem {
+				file: file.to_string(),
+				line: e.line(),
+				message: format!("invalid language file: {e}"),
+			});
+			None
+		}
+	}
+}
+
+/// Compares the language files of every namespace with their en_us.json.
+/// Keys that en_us doesn't have and translations with other placeholders
+/// than en_us are problems, missing keys are only reported, as Minecraft
+/// shows en_us for them.
+pub fn validate_lang(files: &BTreeMap<String, String>) -> (Vec<Problem>, Vec<LangReport>) {
+	let mut problems = vec![];
+	let mut namespaces: BTreeMap<&str, BTreeMap<&str, (&str, &str)>> = BTreeMap::new();
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		if let ["assets", namespace, "lang", name] = parts[..] {
+			if let Some(language) = name.strip_suffix(".json") {
+				namespaces
+					.entry(namespace)
+					.or_default()
+					.insert(language, (file, path));
+			}
+		}
+	}
+	let mut totals: BTreeMap<String, (usize, Vec<String>)> = BTreeMap::new();
+	for (namespace, languages) in namespaces {
+		let Some((source_file, source_path)) = languages.get(SOURCE_LANGUAGE) else {
+			log::debug!("{namespace} has no {SOURCE_LANGUAGE}.json, not checking its languages");
+			continue;
+		};
+		let Some((_, source)) = read_lang(source_file, source_path, &mut problems) else {
+			continue;
+		};
+		for (language, (file, path)) in languages {
+			if language == SOURCE_LANGUAGE {
+				continue;
+			}
+			let Some((data, lang)) = read_lang(file, path, &mut problems) else {
+				continue;
+			};
+			let total = totals.entry(language.to_string()).or_default();
+			total.0 += source.len();
+			for key in source.keys().filter(|k| !lang.contains_key(*k)) {
+				total.1.push(format!("{namespace}:{key}"));
+			}
+			for (key, text) in &lang {
+				let problem = |message: String| Problem {
+					file: file.to_string(),
+					line: line_of(&data, key),
+					message,
+				};
+				let Some(source_text) = source.get(key) else {
+					problems.push(problem(format!("{key} isn't in {SOURCE_LANGUAGE}")));
+					continue;
+				};
+				match (placeholders(text), placeholders(source_text)) {
+					(Err(e), _) => problems.push(problem(format!("{key} {e}"))),
+					(Ok(args), Ok(source_args)) if args != source_args => problems.push(problem(
+						format!("{key} has other placeholders than in {SOURCE_LANGUAGE}"),
+					)),
+					_ => (),
+				}
+			}
+		}
+	}
+	let reports = totals
+		.into_iter()
+		.map(|(language, (total, missing))| LangReport {
+			completion: match total {
+				0 => 100.0,
+				total => ((total - missing.len()) * 1000 / total) as f64 / 10.0,
+			},
+			language,
+			missing,
+		})
+		.collect();
+	(problems, reports)
+}
+
+/// Textures and sounds the models, sounds.json, fonts, particles and
+/// OptiFine files of a pack point at, by their path in the pack.
+pub fn referenced_assets(files: &BTreeMap<String, String>) -> BTreeSet<String> {
+	let mut referenced = BTreeSet::new();
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		let kind = match parts[..] {
+			["assets", _, "sounds.json"] => "sounds.json",
+			["assets", _, kind, _, ..] => kind,
+			_ => continue,
+		};
+		if !file.ends_with(".json")
+			|| !["models", "sounds.json", "font", "particles"].contains(&kind)
+		{
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let Ok(json) = serde_json::from_str::<Value>(&data) else {
+			continue;
+		};
+		match kind {
+			"models" => {
+				for reference in references_of_model(&json) {
+					referenced.insert(asset_path(
+						reference.location,
+						reference.kind,
+						reference.extension,
+					));
+				}
+			}
+			"sounds.json" => {
+				let sounds = json
+					.as_object()
+					.into_iter()
+					.flat_map(|events| events.values())
+					.filter_map(|event| event["sounds"].as_array())
+					.flatten();
+				for sound in sounds {
+					let name = match sound {
+						Value::String(name) => Some(name.as_str()),
+						sound if sound["type"] != "event" => sound["name"].as_str(),
+						_ => None,
+					};
+					if let Some(name) = name {
+						referenced.insert(asset_path(name, "sounds", ".ogg"));
+					}
+				}
+			}
+			"font" => {
+				let providers = json["providers"].as_array().into_iter().flatten();
+				for provider in providers {
+					match (provider["type"].as_str(), provider["file"].as_str()) {
+						(Some("bitmap"), Some(file)) => {
+							referenced.insert(asset_path(file, "textures", ""));
+						}
+						(Some("ttf"), Some(file)) => {
+							referenced.insert(asset_path(file, "font", ""));
+						}
+						_ => (),
+					}
+				}
+			}
+			_ => {
+				let textures = json["textures"].as_array().into_iter().flatten();
+				for texture in textures.filter_map(Value::as_str) {
+					referenced.insert(asset_path(texture, "textures/particle", ".png"));
+				}
+			}
+		}
+	}
+	let (optifine, _) = optifine_references(files);
+	referenced.extend(optifine.into_iter().map(|r| r.target));
+	referenced
+}
+
+/// Textures and sounds of a pack that nothing in it points at. Files that
+/// replace vanilla ones are used by the game itself, so they are left out,
+/// which without the vanilla assets means everything in minecraft.
+pub fn unused_assets(
+	files: &BTreeMap<String, String>, vanilla: Option<&BTreeSet<String>>,
+) -> Vec<String> {
+	let referenced = referenced_assets(files);
+	let is_vanilla = |file: &str| match vanilla {
+		Some(vanilla) => vanilla.contains(file),
+		None => file.starts_with("assets/minecraft/"),
+	};
+	files
+		.keys()
+		.filter(|file| {
+			let parts = file.split('/').collect::<Vec<_>>();
+			match parts[..] {
+				["assets", _, "textures", ..] => file.ends_with(".png"),
+				["assets", _, "sounds", ..] => file.ends_with(".ogg"),
+				_ => false,
+			}
+		})
+		.filter(|file| !referenced.contains(*file) && !is_vanilla(file))
+		.cloned()
+		.collect()
+}
+
+/// Textures with the same content.
+#[derive(Clone, Debug)]
+pub struct Duplicates {
+	/// Paths in the pack, the first one is kept when deduplicating
+	pub files: Vec<String>,
+	/// Size of one copy in bytes
+	pub size: u64,
+}
+
+impl Duplicates {
+	/// Bytes taken by every copy but the first.
+	pub fn wasted(&self) -> u64 {
+		self.size * (self.files.len() as u64 - 1)
+	}
+}
+
+/// Textures of a pack that are byte-identical, or with pixels that look the
+/// same when compared by their decoded pixels instead.
+pub fn duplicate_textures(files: &BTreeMap<String, String>, pixels: bool) -> Vec<Duplicates> {
+	let textures = files
+		.iter()
+		.filter(|(file, _)| file.starts_with("assets/") && file.ends_with(".png"))
+		.map(|(file, path)| (file.clone(), path.clone()))
+		.collect::<Vec<_>>();
+	let hashes = pdtthread::multithread(textures, None, move |_, (file, path)| {
+		let size = fs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+		let hash = match pixels {
+			true => match image::open(&path) {
+				Ok(image) => {
+					let image = image.to_rgba8();
+					let mut bytes = format!("{}x{}", image.width(), image.height()).into_bytes();
+					bytes.extend(image.as_raw());
+					pdthash::get_bytes_hash(&bytes)
+				}
+				Err(e) => {
+					log::warn!("skipping {file}, failed to decode it: {e}");
+					return None;
+				}
+			},
+			false => pdthash::get_hash(&path, false),
+		};
+		Some((hash, file, size))
+	});
+	let mut groups: BTreeMap<String, Duplicates> = BTreeMap::new();
+	for (hash, file, size) in hashes {
+		let group = groups.entry(hash).or_insert(Duplicates {
+			files: vec![],
+			size,
+		});
+		group.files.push(file);
+		group.size = group.size.min(size);
+	}
+	let mut duplicates = groups
+		.into_values()
+		.filter(|g| g.files.len() > 1)
+		.map(|mut g| {
+			g.files.sort();
+			g
+		})
+		.collect::<Vec<_>>();
+	duplicates.sort_by(|a, b| a.files.cmp(&b.files));
+	duplicates
+}
+
+/// Resource location of a texture from its path in the pack.
+fn texture_location(file: &str) -> Option<String> {
+	let rest = file.strip_prefix("assets/")?;
+	let (namespace, rest) = rest.split_once('/')?;
+	let path = rest.strip_prefix("textures/")?.strip_suffix(".png")?;
+	Some(format!("{namespace}:{path}"))
+}
+
+/// Points the models of a pack at the first copy of every group of
+/// duplicate textures, writing the changed models to the staging directory,
+/// and leaves out the copies nothing points at anymore. Copies that replace
+/// vanilla textures or are animated are kept. Returns the number of files
+/// left out.
+pub fn dedupe_textures(
+	files: &mut BTreeMap<String, String>, duplicates: &[Duplicates],
+	vanilla: Option<&BTreeSet<String>>, staging: &str,
+) -> usize {
+	let is_vanilla = |file: &str| match vanilla {
+		Some(vanilla) => vanilla.contains(file),
+		None => file.starts_with("assets/minecraft/"),
+	};
+	let mut canonical = BTreeMap::new();
+	for group in duplicates {
+		let Some(keep) = texture_location(&group.files[0]) else {
+			continue;
+		};
+		for copy in &group.files[1..] {
+			if is_vanilla(copy) || files.contains_key(&format!("{copy}.mcmeta")) {
+				continue;
+			}
+			canonical.insert(copy.clone(), keep.clone());
+		}
+	}
+	if canonical.is_empty() {
+		return 0;
+	}
+
+	let models = files
+		.iter()
+		.filter(|(file, _)| file.split('/').nth(2) == Some("models") && file.ends_with(".json"))
+		.map(|(file, path)| (file.clone(), path.clone()))
+		.collect::<Vec<_>>();
+	for (file, path) in models {
+		let data =
+			fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let Ok(mut model) = serde_json::from_str::<Value>(&data) else {
+			continue;
+		};
+		let Some(textures) = model["textures"].as_object_mut() else {
+			continue;
+		};
+		let mut changed = false;
+		for texture in textures.values_mut() {
+			let Some(location) = texture.as_str() else {
+				continue;
+			};
+			if let Some(keep) = canonical.get(&asset_path(location, "textures", ".png")) {
+				*texture = Value::String(keep.clone());
+				changed = true;
+			}
+		}
+		if !changed {
+			continue;
+		}
+		let staged = format!(
+			"{staging}{SLASH}dedupe{SLASH}{}",
+			file.replace('/', MAIN_SEPARATOR_STR)
+		);
+		if let Some(dir) = Utf8Path::new(&staged).parent() {
+			fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		}
+		let data = serde_json::to_string(&model).unwrap();
+		fs::write(&staged, data).unwrap_or_else(|_| panic!("Failed to write file: {staged}"));
+		log::debug!("pointed {file} at deduplicated textures");
+		files.insert(file, staged);
+	}
+
+	let referenced = referenced_assets(files);
+	let mut removed = 0;
+	for copy in canonical.keys() {
+		if !referenced.contains(copy) {
+			files.remove(copy);
+			removed += 1;
+		}
+	}
+	removed
+}
+
+/// Textures with at least this much of their canvas taken up by fully
+/// transparent margins are listed, in percent.
+pub const MARGIN_PERCENT: f64 = 50.0;
+
+/// A texture drawn on a canvas larger than what is on it.
+#[derive(Clone, Debug)]
+pub struct Margins {
+	pub file: String,
+	pub width: u32,
+	pub height: u32,
+	/// x, y, width and height of the smallest box around every pixel that
+	/// isn't fully transparent
+	pub content: [u32; 4],
+}
+
+impl Margins {
+	/// How much of the canvas is outside the content, in percent.
+	pub fn percent(&self) -> f64 {
+		let [_, _, width, height] = self.content;
+		let content = width as f64 * height as f64;
+		100.0 - content * 100.0 / (self.width as f64 * self.height as f64)
+	}
+}
+
+impl fmt::Display for Margins {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let [x, y, width, height] = self.content;
+		write!(
+			f,
+			"{}: only {width}x{height} at {x},{y} of the {}x{} canvas is drawn on, {:.0}% is transparent",
+			self.file,
+			self.width,
+			self.height,
+			self.percent()
+		)
+	}
+}
+
+/// The smallest box around every pixel of an image that isn't fully
+/// transparent, as x, y, width and height. None if every pixel is.
+pub fn content_box(image: &RgbaImage) -> Option<[u32; 4]> {
+	let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+	for (x, y, pixel) in image.enumerate_pixels() {
+		if pixel.0[3] != 0 {
+			left = left.min(x);
+			top = top.min(y);
+			right = right.max(x + 1);
+			bottom = bottom.max(y + 1);
+		}
+	}
+	(left != u32::MAX).then(|| [left, top, right - left, bottom - top])
+}
+
+/// Textures of a pack with at least min_percent of their canvas taken up by
+/// fully transparent margins, the most wasteful first. Fully transparent
+/// textures are left out, packs use them to hide things.
+pub fn transparent_margins(files: &BTreeMap<String, String>, min_percent: f64) -> Vec<Margins> {
+	let textures = files
+		.iter()
+		.filter(|(file, _)| file.ends_with(".png"))
+		.filter(|(file, _)| *file == "pack.png" || file.starts_with("assets/"))
+		.map(|(file, path)| (file.clone(), path.clone()))
+		.collect::<Vec<_>>();
+	let mut margins = pdtthread::multithread(textures, None, move |_, (file, path)| {
+		let image = match image::open(&path) {
+			Ok(image) => image.into_rgba8(),
+			Err(e) => {
+				log::warn!("skipping {file}, failed to decode it: {e}");
+				return None;
+			}
+		};
+		let content = content_box(&image)?;
+		let margins = Margins {
+			file,
+			width: image.width(),
+			height: image.height(),
+			content,
+		};
+		(margins.percent() > 0.0 && margins.percent() >= min_percent).then_some(margins)
+	});
+	margins.sort_by(|a, b| {
+		b.percent()
+			.total_cmp(&a.percent())
+			.then(a.file.cmp(&b.file))
+	});
+	margins
+}
+
+/// Whether the game scales an image to fit, so its margins can be cropped
+/// without changing where anything is drawn. Every other texture is drawn
+/// by its pixel coordinates, or stitched into an atlas the models point into.
+pub fn can_crop(file: &str) -> bool {
+	file == "pack.png"
+}
+
+/// Writes the content of the images in margins that can be cropped to the
+/// staging directory and points the files at them. pack.png is shown as a
+/// square, so it is cropped to the smallest square around its content.
+/// Returns the number of images cropped.
+pub fn crop_margins(
+	files: &mut BTreeMap<String, String>, margins: &[Margins], staging: &str,
+) -> usize {
+	let mut cropped = 0;
+	for margins in margins.iter().filter(|m| can_crop(&m.file)) {
+		let path = &files[&margins.file];
+		let image = image::open(path)
+			.unwrap_or_else(|_| panic!("Failed to load image: {path}"))
+			.into_rgba8();
+		let [x, y, width, height] = margins.content;
+		let content = imageops::crop_imm(&image, x, y, width, height).to_image();
+		let side = width.max(height);
+		if side >= margins.width.max(margins.height) {
+			continue;
+		}
+		let mut square = RgbaImage::new(side, side);
+		let (left, top) = ((side - width) / 2, (side - height) / 2);
+		imageops::overlay(&mut square, &content, left as i64, top as i64);
+		let staged = format!(
+			"{staging}{SLASH}cropped{SLASH}{}",
+			margins.file.replace('/', MAIN_SEPARATOR_STR)
+		);
+		if let Some(dir) = Utf8Path::new(&staged).parent() {
+			fs::create_dir_all(dir).unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+		}
+		square
+			.save(&staged)
+			.unwrap_or_else(|_| panic!("Failed to save image: {staged}"));
+		log::debug!(
+			"cropped {} from {}x{} to {side}x{side}",
+			margins.file,
+			margins.width,
+			margins.height
+		);
+		files.insert(margins.file.clone(), staged);
+		cropped += 1;
+	}
+	cropped
+}
+
+/// What kind of audio a file is, from its first bytes.
+#[derive(Debug, PartialEq)]
+pub enum Audio {
+	/// Ogg Vorbis with this many channels, what Minecraft plays
+	Vorbis(u8),
+	/// Another format, renamed to .ogg
+	Other(&'static str),
+}
+
+/// Reads the first page of an ogg file to find if it is Vorbis and how many
+/// channels it has.
+pub fn audio_kind(data: &[u8]) -> Audio {
+	if !data.starts_with(b"OggS") {
+		return Audio::Other(match data {
+			d if d.starts_with(b"ID3") || d.starts_with(&[0xff, 0xfb]) => "mp3",
+			d if d.starts_with(b"RIFF") => "wav",
+			d if d.starts_with(b"fLaC") => "flac",
+			_ => "not audio Minecraft can play",
+		});
+	}
+	// the page header is 27 bytes, then a table with the size of each segment
+	let segments = data.get(26).copied().unwrap_or_default() as usize;
+	let packet = data.get(27 + segments..).unwrap_or_default();
+	if packet.starts_with(b"\x01vorbis") && packet.len() > 11 {
+		return Audio::Vorbis(packet[11]);
+	}
+	match packet.starts_with(b"OpusHead") {
+		true => Audio::Other("ogg opus"),
+		false => Audio::Other("ogg without vorbis"),
+	}
+}
+
+/// Whether a sound event plays where its sound comes from, so it should be
+/// mono to fade with distance. Music, records and the interface play
+/// everywhere.
+fn is_positional(event: &str, sound: &Value) -> bool {
+	let everywhere = ["ui.", "music.", "music_disc.", "record."];
+	sound["stream"] != true && !everywhere.iter().any(|p| event.starts_with(p))
+}
+
+/// Checks every sounds.json points at ogg files that exist and are Vorbis,
+/// and that sounds played in the world are mono. Sounds in minecraft that
+/// aren't in the pack are assumed to be vanilla.
+pub fn validate_sounds(files: &BTreeMap<String, String>) -> Vec<Problem> {
+	let mut problems = vec![];
+	let mut checked: BTreeMap<String, Audio> = BTreeMap::new();
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		if !matches!(parts[..], ["assets", _, "sounds.json"]) {
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let json: Value = match serde_json::from_str(&data) {
+			Ok(json) => json,
+			Err(e) => {
+				problems.push(Problem {
+					file: file.clone(),
+					line: e.line(),
+					message: format!("invalid json: {e}"),
+				});
+				continue;
+			}
+		};
+		let events = json.as_object().into_iter().flatten();
+		for (event, entry) in events {
+			let sounds = entry["sounds"].as_array().into_iter().flatten();
+			for sound in sounds {
+				let name = match sound {
+					Value::String(name) => name.as_str(),
+					sound if sound["type"] == "event" => continue,
+					sound => match sound["name"].as_str() {
+						Some(name) => name,
+						None => {
+							problems.push(Problem {
+								file: file.clone(),
+								line: line_of(&data, event),
+								message: format!("{event} has a sound without a name"),
+							});
+							continue;
+						}
+					},
+				};
+				let problem = |message: String| Problem {
+					file: file.clone(),
+					line: line_of(&data, name),
+					message,
+				};
+				let ogg = asset_path(name, "sounds", ".ogg");
+				let Some(ogg_path) = files.get(&ogg) else {
+					if !ogg.starts_with("assets/minecraft/") {
+						problems.push(problem(format!("sound {name} of {event} not found")));
+					}
+					continue;
+				};
+				let audio = checked.entry(ogg.clone()).or_insert_with(|| {
+					let data = fs::read(ogg_path)
+						.unwrap_or_else(|_| panic!("Failed to read file: {ogg_path}"));
+					audio_kind(&data)
+				});
+				match audio {
+					Audio::Other(kind) => {
+						problems.push(problem(format!("sound {name} is {kind}, not ogg vorbis")))
+					}
+					Audio::Vorbis(channels) if *channels > 1 && is_positional(event, sound) => {
+						problems.push(problem(format!(
+							"sound {name} of {event} is stereo, so it won't fade with distance"
+						)))
+					}
+					_ => (),
+				}
+			}
+		}
+	}
+	problems
+}
+
+/// The sound events of a Minecraft version with their subtitles, and the
+/// keys of its en_us.json.
+pub struct VanillaSounds {
+	pub version: String,
+	pub events: BTreeMap<String, Option<String>>,
+	pub lang: BTreeSet<String>,
+}
+
+impl VanillaSounds {
+	pub fn get(version: &str) -> VanillaSounds {
+		VanillaSounds {
+			version: version.to_string(),
+			events: pdtmojang::get_sound_events(version),
+			lang: pdtmojang::get_lang_keys(version),
+		}
+	}
+}
+
+/// Checks the events of sounds.json against the vanilla ones: events of
+/// the minecraft namespace nothing plays, new events without sounds, and
+/// subtitles that aren't translated or that a replaced event loses.
+pub fn validate_sound_events(
+	files: &BTreeMap<String, String>, vanilla: &VanillaSounds,
+) -> Vec<Problem> {
+	let mut problems = vec![];
+	let mut lang = BTreeSet::new();
+	for (file, path) in files {
+		if let ["assets", _, "lang", name] = file.split('/').collect::<Vec<_>>()[..] {
+			if name == format!("{SOURCE_LANGUAGE}.json") {
+				let data = fs::read_to_string(path)
+					.unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+				// broken language files are reported by validate_lang
+				let keys: BTreeMap<String, Value> = serde_json::from_str(&data).unwrap_or_default();
+				lang.extend(keys.into_keys());
+			}
+		}
+	}
+	for (file, path) in files {
+		let ["assets", namespace, "sounds.json"] = file.split('/').collect::<Vec<_>>()[..] else {
+			continue;
+		};
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		// broken json is reported by validate_sounds
+		let Ok(json) = serde_json::from_str::<Value>(&data) else {
+			continue;
+		};
+		let version = &vanilla.version;
+		for (event, entry) in json.as_object().into_iter().flatten() {
+			let problem = |message: String| Problem {
+				file: file.to_string(),
+				line: line_of(&data, event),
+				message,
+			};
+			let replaced = match namespace {
+				"minecraft" => vanilla.events.get(event),
+				_ => None,
+			};
+			let empty = entry["sounds"].as_array().is_none_or(|s| s.is_empty());
+			match replaced {
+				None if namespace == "minecraft" => problems.push(problem(format!(
+					"{event} isn't a sound event of Minecraft {version}, only commands can play it"
+				))),
+				None if empty => problems.push(problem(format!("{event} has no sounds"))),
+				_ => (),
+			}
+			match (entry["subtitle"].as_str(), replaced) {
+				(Some(key), _) if !lang.contains(key) && !vanilla.lang.contains(key) => problems
+					.push(problem(format!(
+						"subtitle {key} of {event} isn't in any {SOURCE_LANGUAGE}.json"
+					))),
+				(None, Some(Some(key))) if entry["replace"] == true && !empty => {
+					problems.push(problem(format!(
+						"{event} replaces the sounds without keeping their subtitle {key}"
+					)))
+				}
+				_ => (),
+			}
+		}
+	}
+	problems
+}
+
+/// Size of the frames of an animated texture, the way Minecraft works it
+/// out: the width and height of the animation if given, otherwise square
+/// frames as wide as the texture.
+pub fn frame_size(animation: &Value, width: u32, height: u32) -> (u32, u32) {
+	let given = |key: &str| animation[key].as_u64().map(|n| n as u32);
+	match (given("width"), given("height")) {
+		(Some(w), h) => (w, h.unwrap_or(height)),
+		(None, Some(h)) => (width, h),
+		(None, None) => (width.min(height), width.min(height)),
+	}
+}
+
+/// Checks the fonts of a pack point at glyph sheets, fonts and other font
+/// files that exist in the pack or in vanilla, and that bitmap glyph
+/// sheets split evenly into their grid of characters.
+pub fn validate_fonts(
+	files: &BTreeMap<String, String>, vanilla: Option<&BTreeSet<String>>,
+) -> Vec<Problem> {
+	let exists = |path: &str| {
+		files.contains_key(path)
+			|| match vanilla {
+				Some(vanilla) => vanilla.contains(path),
+				None => path.starts_with("assets/minecraft/"),
+			}
+	};
+	let mut problems = vec![];
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		if !matches!(parts[..], ["assets", _, "font", ..]) || !file.ends_with(".json") {
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let json: Value = match serde_json::from_str(&data) {
+			Ok(json) => json,
+			Err(e) => {
+				problems.push(Problem {
+					file: file.clone(),
+					line: e.line(),
+					message: format!("invalid json: {e}"),
+				});
+				continue;
+			}
+		};
+		let mut problem = |line: usize, message: String| {
+			problems.push(Problem {
+				file: file.clone(),
+				line,
+				message,
+			})
+		};
+		let providers = json["providers"].as_array().into_iter().flatten();
+		for provider in providers {
+			let kind = provider["type"].as_str().unwrap_or_default();
+			let (location, target, what) = match kind {
+				"bitmap" => match provider["file"].as_str() {
+					Some(location) => (
+						location,
+						asset_path(location, "textures", ""),
+						"glyph sheet",
+					),
+					None => {
+						problem(0, "bitmap provider has no file".to_string());
+						continue;
+					}
+				},
+				"ttf" | "unihex" => match provider["file"].as_str() {
+					Some(location) => (location, asset_path(location, "font", ""), "font file"),
+					None => continue,
+				},
+				"reference" => match provider["id"].as_str() {
+					Some(location) => (location, asset_path(location, "font", ".json"), "font"),
+					None => continue,
+				},
+				_ => continue,
+			};
+			let line = line_of(&data, location);
+			if !exists(&target) {
+				problem(line, format!("{what} {location} not found"));
+				continue;
+			}
+			if kind != "bitmap" {
+				continue;
+			}
+			let rows = provider["chars"]
+				.as_array()
+				.into_iter()
+				.flatten()
+				.filter_map(Value::as_str)
+				.collect::<Vec<_>>();
+			let Some(columns) = rows.first().map(|r| r.chars().count()) else {
+				problem(line, format!("{location} has no chars"));
+				continue;
+			};
+			if columns == 0 || rows.iter().any(|r| r.chars().count() != columns) {
+				problem(
+					line_of(&data, rows[0]),
+					"rows of chars aren't all the same length".to_string(),
+				);
+				continue;
+			}
+			let height = provider["height"].as_i64().unwrap_or(8);
+			if let Some(ascent) = provider["ascent"].as_i64().filter(|a| *a > height) {
+				problem(
+					line,
+					format!("ascent {ascent} is more than the height {height}"),
+				);
+			}
+			let Some(sheet) = files.get(&target) else {
+				continue;
+			};
+			let Ok((width, sheet_height)) = image::image_dimensions(sheet) else {
+				problem(line, format!("{location} isn't an image"));
+				continue;
+			};
+			let (columns, rows) = (columns as u32, rows.len() as u32);
+			if width % columns != 0 || sheet_height % rows != 0 {
+				problem(
+					line,
+					format!(
+						"{location} is {width}x{sheet_height}, which doesn't split into {columns} by {rows} glyphs"
+					),
+				);
+			}
+		}
+	}
+	problems
+}
+
+/// GLSL with its comments left out, keeping the lines where they were.
+fn strip_glsl_comments(source: &str) -> String {
+	let mut out = String::with_capacity(source.len());
+	let mut rest = source;
+	while !rest.is_empty() {
+		if let Some(after) = rest.strip_prefix("//") {
+			rest = after.find('\n').map(|i| &after[i..]).unwrap_or("");
+		} else if let Some(after) = rest.strip_prefix("/*") {
+			let end = after.find("*/").map(|i| i + 2).unwrap_or(after.len());
+			out.extend(after[..end].chars().filter(|c| *c == '\n'));
+			rest = &after[end..];
+		} else {
+			let c = rest.chars().next().unwrap();
+			out.push(c);
+			rest = &rest[c.len_utf8()..];
+		}
+	}
+	out
+}
+
+/// Pack paths of the files a shader imports with `#moj_import`.
+fn shader_imports(source: &str) -> Vec<String> {
+	source
+		.lines()
+		.filter_map(|line| line.trim().strip_prefix("#moj_import"))
+		.filter_map(|import| {
+			let import = import.trim();
+			let name = import
+				.strip_prefix('<')
+				.and_then(|i| i.strip_suffix('>'))
+				.or_else(|| import.strip_prefix('"').and_then(|i| i.strip_suffix('"')))?;
+			Some(asset_path(name, "shaders/include", ""))
+		})
+		.collect()
+}
+
+/// Uniforms a shader declares outside of uniform blocks, by name, with
+/// their type.
+fn shader_uniforms(source: &str) -> BTreeMap<String, String> {
+	let mut uniforms = BTreeMap::new();
+	for statement in strip_glsl_comments(source).split(';') {
+		let tokens = statement
+			.split_whitespace()
+			.filter(|t| !["lowp", "mediump", "highp"].contains(t))
+			.collect::<Vec<_>>();
+		let Some(i) = tokens.iter().position(|t| *t == "uniform") else {
+			continue;
+		};
+		let (Some(kind), Some(name)) = (tokens.get(i + 1), tokens.get(i + 2)) else {
+			continue;
+		};
+		if statement.contains('{') {
+			continue;
+		}
+		let name = name.split('[').next().unwrap_or(name);
+		uniforms.insert(name.to_string(), kind.to_string());
+	}
+	uniforms
+}
+
+/// GLSL type of a uniform of a shader program json.
+fn glsl_type(kind: &str, count: u64) -> Option<&'static str> {
+	let index = (count.clamp(1, 4) - 1) as usize;
+	match kind {
+		"float" => Some(["float", "vec2", "vec3", "vec4"][index]),
+		"int" => Some(["int", "ivec2", "ivec3", "ivec4"][index]),
+		"matrix2x2" => Some("mat2"),
+		"matrix3x3" => Some("mat3"),
+		"matrix4x4" => Some("mat4"),
+		_ => None,
+	}
+}
+
+/// Checks the core and post shader programs of a pack point at vertex and
+/// fragment shaders that exist, that their imports exist, and that the
+/// samplers and uniforms they list are the ones the shaders declare, with
+/// the same types. Shaders only in vanilla aren't read.
+pub fn validate_shaders(
+	files: &BTreeMap<String, String>, vanilla: Option<&BTreeSet<String>>,
+) -> Vec<Problem> {
+	let exists = |path: &str| {
+		files.contains_key(path)
+			|| match vanilla {
+				Some(vanilla) => vanilla.contains(path),
+				None => path.starts_with("assets/minecraft/"),
+			}
+	};
+	let read = |file: &str| {
+		files.get(file).map(|path| {
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"))
+		})
+	};
+	let mut problems = vec![];
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		if !matches!(parts[..], ["assets", _, "shaders", "core" | "program", _])
+			|| !file.ends_with(".json")
+		{
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let Ok(json) = serde_json::from_str::<Value>(&data) else {
+			// broken json is listed by validate json
+			continue;
+		};
+		let problem = |line: usize, message: String| Problem {
+			file: file.clone(),
+			line,
+			message,
+		};
+		let dir = file
+			.rsplit_once('/')
+			.map(|(dir, _)| dir)
+			.unwrap_or_default();
+		let mut sources = vec![];
+		let mut complete = true;
+		for (key, extension) in [("vertex", ".vsh"), ("fragment", ".fsh")] {
+			let Some(name) = json[key].as_str() else {
+				problems.push(problem(0, format!("has no {key} shader")));
+				continue;
+			};
+			let shader = match name.contains(':') {
+				true => asset_path(name, "shaders", extension),
+				false => format!("{dir}/{name}{extension}"),
+			};
+			if !exists(&shader) {
+				problems.push(problem(
+					line_of(&data, name),
+					format!("{key} shader {name} not found"),
+				));
+				continue;
+			}
+			let Some(source) = read(&shader) else {
+				complete = false;
+				continue;
+			};
+			for import in shader_imports(&source) {
+				if !exists(&import) {
+					problems.push(Problem {
+						file: shader.clone(),
+						line: 0,
+						message: format!("imported {import} not found"),
+					});
+				}
+				match read(&import) {
+					Some(imported) => sources.push((import, imported)),
+					None => complete = false,
+				}
+			}
+			sources.push((shader, source));
+		}
+		let mut declared = BTreeMap::new();
+		for (shader, source) in &sources {
+			for (name, kind) in shader_uniforms(source) {
+				declared.entry(name).or_insert((shader.clone(), kind));
+			}
+		}
+		let names = |key: &str| {
+			json[key]
+				.as_array()
+				.into_iter()
+				.flatten()
+				.filter_map(|v| v["name"].as_str().map(|n| (n, v)))
+				.collect::<BTreeMap<_, _>>()
+		};
+		let samplers = names("samplers");
+		let uniforms = names("uniforms");
+		for (name, (shader, kind)) in &declared {
+			let is_sampler = kind.contains("sampler");
+			let listed = match is_sampler {
+				true => samplers.contains_key(name.as_str()),
+				false => uniforms.contains_key(name.as_str()),
+			};
+			if !listed {
+				let what = if is_sampler { "sampler" } else { "uniform" };
+				problems.push(problem(
+					0,
+					format!("{shader} uses {what} {name}, which isn't listed"),
+				));
+			}
+		}
+		for (name, uniform) in &uniforms {
+			let Some((shader, kind)) = declared.get(*name) else {
+				continue;
+			};
+			let json_kind = uniform["type"].as_str().unwrap_or_default();
+			let count = uniform["count"].as_u64().unwrap_or(1);
+			match glsl_type(json_kind, count) {
+				Some(expected) if expected != kind => problems.push(problem(
+					line_of(&data, name),
+					format!("uniform {name} is {json_kind} {count}, but {kind} in {shader}"),
+				)),
+				None => problems.push(problem(
+					line_of(&data, name),
+					format!("uniform {name} has unknown type {json_kind}"),
+				)),
+				_ => (),
+			}
+		}
+		if !complete || sources.is_empty() {
+			continue;
+		}
+		for name in samplers.keys() {
+			if !declared.contains_key(*name) {
+				problems.push(problem(
+					line_of(&data, name),
+					format!("sampler {name} isn't in the shaders"),
+				));
+			}
+		}
+		#[cfg(feature = "glslang")]
+		for (shader, source) in &sources {
+			if let Some(error) = glslang(shader, source, &sources) {
+				problems.push(Problem {
+					file: shader.clone(),
+					line: 0,
+					message: error,
+				});
+			}
+		}
+	}
+	problems
+}
+
+/// Compiles a shader with glslangValidator, with its imports put in place
+/// of the `#moj_import` lines. Returns what glslang printed when it fails,
+/// nothing for imports or when glslang isn't installed.
+#[cfg(feature = "glslang")]
+fn glslang(shader: &str, source: &str, sources: &[(String, String)]) -> Option<String> {
+	use std::io::Write;
+	use std::process::{Command, Stdio};
+
+	let stage = match shader.rsplit_once('.').map(|(_, e)| e) {
+		Some("vsh") => "vert",
+		Some("fsh") => "frag",
+		_ => return None,
+	};
+	let inlined = source
+		.lines()
+		.map(|line| match line.trim().strip_prefix("#moj_import") {
+			Some(_) => {
+				let import = shader_imports(line).pop().unwrap_or_default();
+				sources
+					.iter()
+					.find(|(file, _)| *file == import)
+					.map(|(_, s)| s.replace("#version", "// #version"))
+					.unwrap_or_default()
+			}
+			None => line.to_string(),
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+	let mut child = match Command::new("glslangValidator")
+		.args(["--stdin", "-S", stage])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+	{
+		Ok(child) => child,
+		Err(e) => {
+			log::warn!("Failed to run glslangValidator: {e}");
+			return None;
+		}
+	};
+	child
+		.stdin
+		.take()
+		.unwrap()
+		.write_all(inlined.as_bytes())
+		.unwrap_or_else(|e| panic!("Failed to write to glslangValidator: {e}"));
+	let output = child
+		.wait_with_output()
+		.unwrap_or_else(|e| panic!("Failed to run glslangValidator: {e}"));
+	match output.status.success() {
+		true => None,
+		false => Some(format!(
+			"glslang failed:\n{}",
+			String::from_utf8_lossy(&output.stdout).trim()
+		)),
+	}
+}
+
+/// Checks every texture animation is for a texture that exists, that the
+/// texture is a whole number of frames, that the frames it lists exist and
+/// that its frame times and interpolate are valid.
+pub fn validate_animations(files: &BTreeMap<String, String>) -> Vec<Problem> {
+	let mut problems = vec![];
+	for (file, path) in files {
+		if !file.starts_with("assets/") || !file.ends_with(".png.mcmeta") {
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let mut problem = |line: usize, message: String| {
+			problems.push(Problem {
+				file: file.clone(),
+				line,
+				message,
+			})
+		};
+		let json: Value = match serde_json::from_str(&data) {
+			Ok(json) => json,
+			Err(e) => {
+				problem(e.line(), format!("invalid json: {e}"));
+				continue;
+			}
+		};
+		let animation = &json["animation"];
+		if animation.is_null() {
+			continue;
+		}
+		if !animation.is_object() {
+			problem(
+				line_of(&data, "animation"),
+				"animation isn't an object".to_string(),
+			);
+			continue;
+		}
+		let texture = file.trim_end_matches(".mcmeta");
+		let Some(texture_path) = files.get(texture) else {
+			problem(
+				0,
+				format!("animation of {texture}, which isn't in the pack"),
+			);
+			continue;
+		};
+		let is_time = |value: &Value| value.as_u64().is_some_and(|n| n >= 1);
+		if !animation["frametime"].is_null() && !is_time(&animation["frametime"]) {
+			problem(
+				line_of(&data, "frametime"),
+				format!(
+					"frametime {} isn't a whole number of at least 1",
+					animation["frametime"]
+				),
+			);
+		}
+		if !animation["interpolate"].is_null() && !animation["interpolate"].is_boolean() {
+			problem(
+				line_of(&data, "interpolate"),
+				format!(
+					"interpolate {} isn't true or false",
+					animation["interpolate"]
+				),
+			);
+		}
+		for key in ["width", "height"] {
+			if !animation[key].is_null() && !is_time(&animation[key]) {
+				problem(
+					line_of(&data, key),
+					format!(
+						"{key} {} isn't a whole number of at least 1",
+						animation[key]
+					),
+				);
+			}
+		}
+		let Ok((width, height)) = image::image_dimensions(texture_path) else {
+			problem(0, format!("{texture} can't be read as an image"));
+			continue;
+		};
+		let (frame_width, frame_height) = frame_size(animation, width, height);
+		let (frame_width, frame_height) = (frame_width.max(1), frame_height.max(1));
+		if width % frame_width != 0 || height % frame_height != 0 {
+			problem(
+				0,
+				format!(
+					"{texture} is {width}x{height}, which isn't a whole number of \
+					 {frame_width}x{frame_height} frames"
+				),
+			);
+			continue;
+		}
+		let count = (width / frame_width) * (height / frame_height);
+		let frames = match &animation["frames"] {
+			Value::Null => continue,
+			Value::Array(frames) => frames,
+			_ => {
+				problem(line_of(&data, "frames"), "frames isn't a list".to_string());
+				continue;
+			}
+		};
+		let line = line_of(&data, "frames");
+		for (i, frame) in frames.iter().enumerate() {
+			let index = match frame {
+				Value::Object(frame) => {
+					if frame.get("time").is_some_and(|t| !is_time(t)) {
+						problem(
+							line,
+							format!(
+								"frame {i} has time {}, it needs to be at least 1",
+								frame["time"]
+							),
+						);
+					}
+					frame.get("index").and_then(Value::as_u64)
+				}
+				frame => frame.as_u64(),
+			};
+			match index {
+				Some(index) if index >= count as u64 => problem(
+					line,
+					format!("frame {i} is index {index}, but {texture} only has {count} frames"),
+				),
+				Some(_) => (),
+				None => problem(line, format!("frame {i} has no valid index: {frame}")),
+			}
+		}
+	}
+	problems
+}
+
+/// Mipmap level Minecraft uses by default, a texture whose size can't be
+/// halved this many times lowers it for its whole atlas.
+const MIPMAP_LEVELS: u32 = 4;
+
+/// Warns about block and item textures that aren't square, or whose frames
+/// aren't a power of two in size, which lowers the mipmap level of the whole
+/// atlas they are stitched into and makes blocks shimmer in the distance.
+/// These still load, so they aren't counted as problems.
+pub fn texture_size_warnings(files: &BTreeMap<String, String>) -> Vec<Problem> {
+	let mut warnings = vec![];
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		if !matches!(parts[..], ["assets", _, "textures", "block" | "item", ..])
+			|| !file.ends_with(".png")
+		{
+			continue;
+		}
+		let Ok((width, height)) = image::image_dimensions(path) else {
+			continue;
+		};
+		let animation = files
+			.get(&format!("{file}.mcmeta"))
+			.and_then(|mcmeta| fs::read_to_string(mcmeta).ok())
+			.and_then(|data| serde_json::from_str::<Value>(&data).ok())
+			.map(|json| json["animation"].clone())
+			.filter(Value::is_object);
+		let mut warn = |message: String| {
+			warnings.push(Problem {
+				file: file.clone(),
+				line: 0,
+				message,
+			})
+		};
+		let (frame_width, frame_height) = match &animation {
+			Some(animation) => frame_size(animation, width, height),
+			None => {
+				if width != height {
+					warn(format!(
+						"{width}x{height} isn't square, it will be squashed"
+					));
+				}
+				(width, height)
+			}
+		};
+		if frame_width == 0 || frame_height == 0 {
+			continue;
+		}
+		if !frame_width.is_power_of_two() || !frame_height.is_power_of_two() {
+			let levels = frame_width
+				.trailing_zeros()
+				.min(frame_height.trailing_zeros());
+			let what = match animation {
+				Some(_) => "frames are",
+				None => "is",
+			};
+			let message = format!("{frame_width}x{frame_height} {what} not a power of two");
+			match levels < MIPMAP_LEVELS {
+				true => warn(format!(
+					"{message}, this limits the mipmap level of its atlas to {levels}"
+				)),
+				false => warn(message),
+			}
+		}
+	}
+	warnings
+}
+
+/// Folders and files the optifine folder of a namespace can have.
+const OPTIFINE_LAYOUT: [&str; 23] = [
+	"anim",
+	"cem",
+	"cit",
+	"colormap",
+	"ctm",
+	"emissive",
+	"font",
+	"gui",
+	"lightmap",
+	"mob",
+	"random",
+	"sky",
+	"block.properties",
+	"cit.properties",
+	"color.properties",
+	"dynamic_lights.properties",
+	"emissive.properties",
+	"entity.properties",
+	"item.properties",
+	"natural.properties",
+	"renderitems.properties",
+	"shaders.properties",
+	"texture.properties",
+];
+
+/// Connected texture methods OptiFine knows.
+const CTM_METHODS: [&str; 16] = [
+	"ctm",
+	"ctm_compact",
+	"horizontal",
+	"vertical",
+	"horizontal+vertical",
+	"vertical+horizontal",
+	"top",
+	"random",
+	"repeat",
+	"fixed",
+	"overlay",
+	"overlay_ctm",
+	"overlay_random",
+	"overlay_repeat",
+	"overlay_fixed",
+	"sky",
+];
+
+/// How many tiles a CTM method takes, None when any number works. Repeat
+/// methods take one for every tile of their width by height pattern.
+pub fn ctm_tile_count(method: &str, width: u32, height: u32) -> Option<usize> {
+	match method {
+		"ctm" | "overlay_ctm" => Some(47),
+		"ctm_compact" => Some(5),
+		"horizontal" | "vertical" => Some(4),
+		"horizontal+vertical" | "vertical+horizontal" => Some(7),
+		"overlay" => Some(17),
+		"top" | "fixed" | "overlay_fixed" => Some(1),
+		"repeat" | "overlay_repeat" => Some((width * height) as usize),
+		_ => None,
+	}
+}
+
+/// Properties of an OptiFine `.properties` file by key, with the line they
+/// are on. Lines that aren't `key=value` or `key: value` and keys given
+/// twice are problems. `\` escapes a character and continues a line at the
+/// end of one.
+pub fn parse_properties(
+	file: &str, data: &str, problems: &mut Vec<Problem>,
+) -> BTreeMap<String, (usize, String)> {
+	let mut properties = BTreeMap::new();
+	let mut lines = data.lines().enumerate();
+	while let Some((i, line)) = lines.next() {
+		let mut line = line.trim_start().to_string();
+		if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+			continue;
+		}
+		while line.ends_with('\\') && !line.ends_with("\\\\") {
+			line.pop();
+			match lines.next() {
+				Some((_, next)) => line.push_str(next.trim_start()),
+				None => break,
+			}
+		}
+		let mut key = String::new();
+		let mut value = None;
+		let mut chars = line.chars();
+		while let Some(c) = chars.next() {
+			match c {
+				'\\' => key.extend(chars.next()),
+				'=' | ':' => {
+					value = Some(chars.as_str().trim().to_string());
+					break;
+				}
+				c => key.push(c),
+			}
+		}
+		let key = key.trim().to_string();
+		let Some(value) = value.filter(|_| !key.is_empty()) else {
+			problems.push(Problem {
+				file: file.to_string(),
+				line: i + 1,
+				message: format!("{} is not key=value", line.trim()),
+			});
+			continue;
+		};
+		if let Some((first, _)) = properties.get(&key) {
+			problems.push(Problem {
+				file: file.to_string(),
+				line: i + 1,
+				message: format!("{key} is already set on line {first}"),
+			});
+			continue;
+		}
+		properties.insert(key, (i + 1, value));
+	}
+	properties
+}
+
+/// Pack path of a texture or model an OptiFine file points at. `./` and
+/// names without a folder are next to the file, `~/` is the optifine
+/// folder, `namespace:` picks a namespace and anything else is in
+/// assets/minecraft.
+pub fn optifine_path(file: &str, value: &str, extension: &str) -> String {
+	let dir = file
+		.rsplit_once('/')
+		.map(|(dir, _)| dir)
+		.unwrap_or_default();
+	let value = value.trim();
+	let path = if let Some(rest) = value.strip_prefix("./") {
+		format!("{dir}/{rest}")
+	} else if let Some(rest) = value.strip_prefix("~/") {
+		let root = file.splitn(4, '/').take(3).collect::<Vec<_>>().join("/");
+		format!("{root}/{rest}")
+	} else if let Some((namespace, rest)) = value.split_once(':') {
+		format!("assets/{namespace}/{rest}")
+	} else if !value.contains('/') {
+		format!("{dir}/{value}")
+	} else {
+		format!("assets/minecraft/{value}")
+	};
+	match path.ends_with(extension) {
+		true => path,
+		false => format!("{path}{extension}"),
+	}
+}
+
+/// Numbers of a list like `1 3-5 8`, for CTM tiles and random textures.
+fn numbers_of(value: &str) -> Option<Vec<u32>> {
+	let mut numbers = vec![];
+	for part in value.split_whitespace() {
+		match part.split_once('-') {
+			Some((from, to)) => numbers.extend(from.parse::<u32>().ok()?..=to.parse().ok()?),
+			None => numbers.push(part.parse().ok()?),
+		}
+	}
+	Some(numbers)
+}
+
+/// A texture or model an OptiFine file points at.
+#[derive(Clone, Debug)]
+pub struct OptifineReference {
+	pub file: String,
+	pub line: usize,
+	/// Path in the pack of what it points at
+	pub target: String,
+	pub what: &'static str,
+}
+
+/// Parses the OptiFine files of a pack, returning what they point at and
+/// the problems with the files themselves: properties that don't parse,
+/// files in folders OptiFine doesn't read and settings it doesn't know.
+pub fn optifine_references(
+	files: &BTreeMap<String, String>,
+) -> (Vec<OptifineReference>, Vec<Problem>) {
+	let mut references = vec![];
+	let mut problems = vec![];
+	for (file, path) in files {
+		let parts = file.split('/').collect::<Vec<_>>();
+		let ["assets", _, root @ ("optifine" | "mcpatcher"), section, ..] = parts[..] else {
+			continue;
+		};
+		let problem = |line: usize, message: String| Problem {
+			file: file.clone(),
+			line,
+			message,
+		};
+		let valid = |c: char| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-' | '.' | '/');
+		if !file.chars().all(valid) {
+			problems.push(problem(
+				0,
+				"has characters OptiFine can't load, use a-z, 0-9, _, - and .".to_string(),
+			));
+		}
+		if !OPTIFINE_LAYOUT.contains(&section) {
+			problems.push(problem(0, format!("{section} isn't read by OptiFine")));
+			continue;
+		}
+		let (stem, extension) = parts[parts.len() - 1]
+			.rsplit_once('.')
+			.unwrap_or((parts[parts.len() - 1], ""));
+		if ["jem", "jpm"].contains(&extension) && section != "cem" {
+			problems.push(problem(
+				0,
+				format!("CEM models are only read from {root}/cem"),
+			));
+			continue;
+		}
+		let mut reference = |line: usize, target: String, what: &'static str| {
+			references.push(OptifineReference {
+				file: file.clone(),
+				line,
+				target,
+				what,
+			})
+		};
+		if extension == "jem" {
+			let data =
+				fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+			let json: Value = match serde_json::from_str(&data) {
+				Ok(json) => json,
+				Err(e) => {
+					problems.push(problem(e.line(), format!("invalid json: {e}")));
+					continue;
+				}
+			};
+			let models = json["models"].as_array().into_iter().flatten();
+			let textures = std::iter::once(&json).chain(models.clone());
+			for texture in textures.filter_map(|m| m["texture"].as_str()) {
+				let target = optifine_path(file, texture, ".png");
+				reference(line_of(&data, texture), target, "texture");
+			}
+			for model in models.filter_map(|m| m["model"].as_str()) {
+				let target = optifine_path(file, model, ".jpm");
+				reference(line_of(&data, model), target, "model");
+			}
+			continue;
+		}
+		if extension != "properties" {
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		let properties = parse_properties(file, &data, &mut problems);
+		let get = |key: &str| {
+			properties
+				.get(key)
+				.map(|(line, value)| (*line, value.as_str()))
+		};
+		let dir = file
+			.rsplit_once('/')
+			.map(|(dir, _)| dir)
+			.unwrap_or_default();
+		match section {
+			"cit" if parts.len() > 4 => {
+				let kind = get("type").map(|(_, kind)| kind).unwrap_or("item");
+				if !["item", "enchantment", "armor", "elytra"].contains(&kind) {
+					let line = get("type").map(|(line, _)| line).unwrap_or_default();
+					problems.push(problem(line, format!("unknown CIT type {kind}")));
+					continue;
+				}
+				if ["item", "armor"].contains(&kind)
+					&& get("items").is_none()
+					&& get("matchItems").is_none()
+				{
+					problems.push(problem(0, "has no items to apply to".to_string()));
+				}
+				let mut given = false;
+				for (key, (line, value)) in &properties {
+					let base = key.split('.').next().unwrap_or_default();
+					match base {
+						"texture" => {
+							reference(*line, optifine_path(file, value, ".png"), "texture")
+						}
+						"model" => reference(*line, optifine_path(file, value, ".json"), "model"),
+						_ => continue,
+					}
+					given = true;
+				}
+				if !given && kind == "item" {
+					reference(0, format!("{dir}/{stem}.png"), "texture");
+				}
+			}
+			"ctm" => {
+				let method = get("method").map(|(_, method)| method).unwrap_or("ctm");
+				if !CTM_METHODS.contains(&method) {
+					let line = get("method").map(|(line, _)| line).unwrap_or_default();
+					problems.push(problem(line, format!("unknown CTM method {method}")));
+				}
+				let Some((line, tiles)) = get("tiles") else {
+					problems.push(problem(0, "has no tiles".to_string()));
+					continue;
+				};
+				let mut count = 0;
+				for tile in tiles.split_whitespace() {
+					if ["<skip>", "<default>"].contains(&tile) {
+						count += 1;
+						continue;
+					}
+					match numbers_of(tile) {
+						Some(numbers) => {
+							count += numbers.len();
+							for n in numbers {
+								reference(line, format!("{dir}/{n}.png"), "tile");
+							}
+						}
+						None => {
+							count += 1;
+							reference(line, optifine_path(file, tile, ".png"), "tile");
+						}
+					}
+				}
+				let size = |key| get(key).and_then(|(_, v)| v.parse::<u32>().ok());
+				let (width, height) = (size("width"), size("height"));
+				if method.ends_with("repeat") && (width.is_none() || height.is_none()) {
+					problems.push(problem(0, format!("{method} needs a width and height")));
+					continue;
+				}
+				let expected = ctm_tile_count(method, width.unwrap_or(1), height.unwrap_or(1));
+				if let Some(expected) = expected.filter(|e| *e != count) {
+					problems.push(problem(
+						line,
+						format!("{method} takes {expected} tiles, found {count}"),
+					));
+				}
+			}
+			"random" | "mob" => {
+				for (key, (line, value)) in &properties {
+					if !key.starts_with("textures.") && !key.starts_with("skins.") {
+						continue;
+					}
+					let Some(numbers) = numbers_of(value) else {
+						problems.push(problem(*line, format!("{key} isn't a list of numbers")));
+						continue;
+					};
+					// 1 is the texture OptiFine picks the others for
+					for n in numbers.into_iter().filter(|n| *n > 1) {
+						reference(*line, format!("{dir}/{stem}{n}.png"), "texture");
+					}
+				}
+			}
+			"sky" => {
+				let target = match get("source") {
+					Some((line, source)) => (line, optifine_path(file, source, ".png")),
+					None => (0, format!("{dir}/{stem}.png")),
+				};
+				reference(target.0, target.1, "sky texture");
+			}
+			"emissive.properties" => {
+				let Some((line, suffix)) = get("suffix.emissive") else {
+					problems.push(problem(0, "has no suffix.emissive".to_string()));
+					continue;
+				};
+				let suffix = format!("{suffix}.png");
+				for emissive in files.keys().filter(|f| f.ends_with(&suffix)) {
+					let texture = format!("{}.png", &emissive[..emissive.len() - suffix.len()]);
+					if !files.contains_key(&texture) && !texture.starts_with("assets/minecraft/") {
+						problems.push(Problem {
+							file: emissive.clone(),
+							line: 0,
+							message: format!("emissive texture without {texture}"),
+						});
+					}
+					reference(line, emissive.clone(), "emissive texture");
+				}
+			}
+			_ => (),
+		}
+	}
+	(references, problems)
+}
+
+/// Checks the OptiFine CIT, CTM, CEM, random entity, sky and emissive
+/// files of a pack parse, are where OptiFine reads them and point at
+/// textures and models that exist.
+pub fn validate_optifine(
+	files: &BTreeMap<String, String>, vanilla: Option<&BTreeSet<String>>,
+) -> Vec<Problem> {
+	let (references, mut problems) = optifine_references(files);
+	for reference in references {
+		let found = files.contains_key(&reference.target)
+			|| match vanilla {
+				Some(vanilla) => vanilla.contains(&reference.target),
+				None => reference.target.starts_with("assets/minecraft/textures/"),
+			};
+		if !found {
+			problems.push(Problem {
+				file: reference.file,
+				line: reference.line,
+				message: format!("{} {} not found", reference.what, reference.target),
+			});
+		}
+	}
+	problems
+}
+
+/// Checks every json and mcmeta file of a pack parses, returning where the
+/// broken ones are and the files without them, for the other checks.
+pub fn validate_json(files: &BTreeMap<String, String>) -> (Vec<Problem>, BTreeMap<String, String>) {
+	let mut problems = vec![];
+	let mut valid = files.clone();
+	for (file, path) in files {
+		if !file.ends_with(".json") && !file.ends_with(".mcmeta") {
+			continue;
+		}
+		let data =
+			fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+		if let Err(e) = pdtjson::parse_json(file, &data) {
+			problems.push(Problem {
+				file: file.clone(),
+				line: e.line,
+				message: format!(
+					"invalid json at column {}: {}\n{}",
+					e.column,
+					e.message,
+					e.pointer()
+				),
+			});
+			valid.remove(file);
+		}
+	}
+	(problems, valid)
+}
+
+/// Runs every check of the config on a pack.
+pub fn validate_pack(files: &BTreeMap<String, String>, config: &ValidateConfig) -> Vec<Problem> {
+	let vanilla = config.minecraft.as_deref().map(pdtmojang::get_asset_names);
+	let (mut problems, files) = validate_json(files);
+	problems.extend(validate_references(&files, vanilla.as_ref()));
+	problems.extend(validate_sounds(&files));
+	if let Some(version) = &config.minecraft {
+		problems.extend(validate_sound_events(&files, &VanillaSounds::get(version)));
+	}
+	problems.extend(validate_animations(&files));
+	problems.extend(validate_fonts(&files, vanilla.as_ref()));
+	problems.extend(custom_model_data(&files, vanilla.as_ref()).0);
+	problems.extend(validate_shaders(&files, vanilla.as_ref()));
+	problems.extend(validate_optifine(&files, vanilla.as_ref()));
+	in_namespaces(problems, &config.namespaces)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ValidateArgs {
+	#[command(subcommand)]
+	check: Check,
+	#[arg(long, global = true, value_name = "NAMESPACE")]
+	/// Only report problems in the assets of this namespace, repeat for more.
+	/// Assets of other namespaces can still be pointed at
+	namespace: Vec<String>,
+	#[arg(long, global = true)]
+	/// Also print problems as GitHub Actions annotations, so they show up
+	/// on the lines of a pull request
+	annotations: bool,
+	#[arg(short, long, global = true, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long, global = true)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Check {
+	/// Check sounds.json points at Vorbis ogg files, mono for sounds in the
+	/// world, and with a Minecraft version that its events play and have
+	/// subtitles
+	Sounds {
+		#[arg(short, long)]
+		/// Minecraft version to also check the sound events and subtitles
+		/// against
+		minecraft: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Check every json and mcmeta file parses, listing where the broken
+	/// ones are
+	Json {
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Check texture animations fit their textures and list frames that
+	/// exist
+	Animations {
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Warn about block and item textures that aren't square or a power of
+	/// two, which breaks mipmapping
+	Textures {
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// List textures drawn on a much larger canvas, with fully transparent
+	/// margins around them
+	Margins {
+		#[arg(long, default_value_t = MARGIN_PERCENT)]
+		/// Least part of the canvas the margins take up, in percent
+		min_percent: f64,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// List textures that are in the pack more than once
+	Duplicates {
+		#[arg(long)]
+		/// Compare the decoded pixels instead of the file contents, to also
+		/// find copies saved differently
+		pixels: bool,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Compare every language file with en_us.json
+	Lang {
+		#[arg(long, value_name = "FILE")]
+		/// Write how much of every language is translated, in percent, to a
+		/// json file
+		completion: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// List textures and sounds nothing in the pack points at
+	Unused {
+		#[arg(short, long)]
+		/// Minecraft version whose assets are used by the game itself
+		minecraft: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Check OptiFine CIT, CTM, CEM, random entity and sky files
+	Optifine {
+		#[arg(short, long)]
+		/// Minecraft version to check references to vanilla assets against
+		minecraft: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Check fonts point at glyph sheets that exist and split into their
+	/// grid of characters
+	Fonts {
+		#[arg(short, long)]
+		/// Minecraft version to check references to vanilla assets against
+		minecraft: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Check item model overrides using custom_model_data for duplicate
+	/// values and missing models, and list the values items skip
+	Cmd {
+		#[arg(short, long)]
+		/// Minecraft version to check references to vanilla assets against
+		minecraft: Option<String>,
+		#[arg(long, value_name = "FILE")]
+		/// Write every override as a Markdown table, for documentation
+		table: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Check shader programs point at shaders that exist and list the
+	/// samplers and uniforms they use. Built with the glslang feature, the
+	/// shaders are compiled with glslangValidator too
+	Shaders {
+		#[arg(short, long)]
+		/// Minecraft version to check references to vanilla assets against
+		minecraft: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+	/// Check blockstates and models point at models and textures that exist
+	Refs {
+		#[arg(short, long)]
+		/// Minecraft version to check references to vanilla assets against
+		minecraft: Option<String>,
+		/// Pack folders or zips
+		paths: Vec<String>,
+	},
+}
+
+pub fn run(args: ValidateArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	pdtlog::set_annotations(args.annotations);
+	let mut temp_dirs = vec![];
+	let mut count = 0;
+	let namespaces = args.namespace;
+	let scoped = |problems: Vec<Problem>| in_namespaces(problems, &namespaces);
+	match args.check {
+		Check::Refs { minecraft, paths } => {
+			let config = ValidateConfig {
+				minecraft,
+				namespaces: namespaces.clone(),
+				..Default::default()
+			};
+			for path in &paths {
+				log::info!("Validating {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				for problem in validate_pack(&files, &config) {
+					report_problem(&problem, &files, path);
+					count += 1;
+				}
+			}
+		}
+		Check::Optifine { minecraft, paths } => {
+			let vanilla = minecraft.as_deref().map(pdtmojang::get_asset_names);
+			for path in &paths {
+				log::info!("Validating OptiFine files of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				for problem in scoped(validate_optifine(&files, vanilla.as_ref())) {
+					report_problem(&problem, &files, path);
+					count += 1;
+				}
+			}
+		}
+		Check::Unused { minecraft, paths } => {
+			let vanilla = minecraft.as_deref().map(pdtmojang::get_asset_names);
+			for path in &paths {
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				let mut unused = unused_assets(&files, vanilla.as_ref());
+				unused.retain(|file| pdtfs::in_namespaces(file, &namespaces));
+				for file in &unused {
+					println!("{file}");
+				}
+				log::info!("{path}: {} unused files", unused.len());
+			}
+		}
+		Check::Sounds { minecraft, paths } => {
+			let vanilla = minecraft.as_deref().map(VanillaSounds::get);
+			for path in &paths {
+				log::info!("Validating sounds of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				let mut problems = validate_sounds(&files);
+				if let Some(vanilla) = &vanilla {
+					problems.extend(validate_sound_events(&files, vanilla));
+				}
+				for problem in scoped(problems) {
+					report_problem(&problem, &files, path);
+					count += 1;
+				}
+			}
+		}
+		Check::Json { paths } => {
+			for path in &paths {
+				log::info!("Validating json of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				for problem in scoped(validate_json(&files).0) {
+					report_problem(&problem, &files, path);
+					count += 1;
+				}
+			}
+		}
+		Check::Fonts { minecraft, paths } => {
+			let vanilla = minecraft.as_deref().map(pdtmojang::get_asset_names);
+			for path in &paths {
+				log::info!("Validating fonts of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				for problem in scoped(validate_fonts(&files, vanilla.as_ref())) {
+					report_problem(&problem, &files, path);
+					count += 1;
+				}
+			}
+		}
+		Check::Cmd {
+			minecraft,
+			table,
+			paths,
+		} => {
+			let vanilla = minecraft.as_deref().map(pdtmojang::get_asset_names);
+			let mut all = vec![];
+			for path in &paths {
+				log::info!("Validating custom_model_data of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				let (problems, models, gaps) = custom_model_data(&files, vanilla.as_ref());
+				let problems = scoped(problems);
+				for problem in &problems {
+					report_problem(problem, &files, path);
+				}
+				count += problems.len();
+				for gap in &gaps {
+					let missing = gap
+						.missing
+						.iter()
+						.map(|v| v.to_string())
+						.collect::<Vec<_>>();
+					log::warn!(
+						"{} skips custom_model_data {}",
+						gap.item,
+						missing.join(", ")
+					);
+				}
+				log::info!("{path}: {} custom models", models.len());
+				all.extend(models);
+			}
+			if let Some(file) = table {
+				fs::write(&file, custom_model_table(&all))
+					.unwrap_or_else(|_| panic!("Failed to write file: {file}"));
+			}
+		}
+		Check::Shaders { minecraft, paths } => {
+			let vanilla = minecraft.as_deref().map(pdtmojang::get_asset_names);
+			for path in &paths {
+				log::info!("Validating shaders of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				for problem in scoped(validate_shaders(&files, vanilla.as_ref())) {
+					report_problem(&problem, &files, path);
+					count += 1;
+				}
+			}
+		}
+		Check::Animations { paths } => {
+			for path in &paths {
+				log::info!("Validating animations of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				for problem in scoped(validate_animations(&files)) {
+					report_problem(&problem, &files, path);
+					count += 1;
+				}
+			}
+		}
+		Check::Textures { paths } => {
+			for path in &paths {
+				log::info!("Checking texture sizes of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				for warning in scoped(texture_size_warnings(&files)) {
+					log::warn!("{warning}");
+				}
+			}
+		}
+		Check::Margins { min_percent, paths } => {
+			for path in &paths {
+				log::info!("Checking texture margins of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				let mut margins = transparent_margins(&files, min_percent);
+				margins.retain(|m| pdtfs::in_namespaces(&m.file, &namespaces));
+				for texture in &margins {
+					log::warn!("{texture}");
+				}
+				let croppable = margins.iter().filter(|m| can_crop(&m.file)).count();
+				log::info!(
+					"{path}: {} textures with margins, crop_margins in [validate] crops {croppable}",
+					margins.len()
+				);
+			}
+		}
+		Check::Duplicates { pixels, paths } => {
+			for path in &paths {
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				let mut duplicates = duplicate_textures(&files, pixels);
+				duplicates.retain(|d| d.files.iter().any(|f| pdtfs::in_namespaces(f, &namespaces)));
+				for group in &duplicates {
+					println!("{} bytes each:", group.size);
+					for file in &group.files {
+						println!("  {file}");
+					}
+				}
+				let wasted: u64 = duplicates.iter().map(Duplicates::wasted).sum();
+				log::info!(
+					"{path}: {} textures have copies, wasting {:.1} KiB",
+					duplicates.len(),
+					wasted as f64 / 1024.0
+				);
+			}
+		}
+		Check::Lang { completion, paths } => {
+			let mut completions = BTreeMap::new();
+			for path in &paths {
+				log::info!("Validating languages of {path}");
+				let files = pdtzip::pack_files(path, &mut temp_dirs);
+				let (problems, reports) = validate_lang(&files);
+				let problems = scoped(problems);
+				for problem in &problems {
+					report_problem(problem, &files, path);
+				}
+				count += problems.len();
+				for report in reports {
+					if !report.missing.is_empty() {
+						log::warn!(
+							"{} is missing {} keys",
+							report.language,
+							report.missing.len()
+						);
+					}
+					for key in &report.missing {
+						log::debug!("{} is missing {key}", report.language);
+					}
+					log::info!("{}: {:.1}%", report.language, report.completion);
+					completions.insert(report.language, report.completion);
+				}
+			}
+			if let Some(file) = completion {
+				let json = serde_json::to_string_pretty(&completions).unwrap() + "\n";
+				fs::write(&file, json).unwrap_or_else(|_| panic!("Failed to write file: {file}"));
+			}
+		}
+	}
+	drop(temp_dirs);
+	if count > 0 {
+		panic!("Found {count} problems.");
+	}
+	println!("No problems found.");
+}
+
+/// Logs a problem found in the files of the pack at path, and annotates
+/// it. Problems in zips are put on the zip, as their files are extracted
+/// somewhere temporary.
+fn report_problem(problem: &Problem, files: &BTreeMap<String, String>, path: &str) {
+	log::error!("{problem}");
+	if pdtzip::is_zip(path) {
+		Problem {
+			file: path.to_string(),
+			line: 0,
+			message: problem.to_string(),
+		}
+		.annotate(Some(path));
+	} else {
+		problem.annotate(files.get(&problem.file).map(String::as_str));
+	}
+}
@@ -0,0 +1,117 @@
+use super::pdtcmd::git;
+use super::pdtlog;
+use super::pdtrelease::{BuildConfig, CONFIG_FILE};
+use clap::{ArgAction, Subcommand, ValueEnum};
+use std::fs;
+
+#[derive(Debug, clap::Args)]
+pub struct VersionArgs {
+	#[command(subcommand)]
+	command: Option<VersionCommand>,
+	#[arg(long, global = true)]
+	/// Build config with the version, defaults to builder.toml
+	config: Option<String>,
+	#[arg(short, long, global = true, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long, global = true)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VersionCommand {
+	/// Raise the version everywhere it appears, commit it and tag the commit
+	Bump {
+		#[arg(value_enum)]
+		/// Part of the version to raise
+		part: Part,
+	},
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Part {
+	Major,
+	Minor,
+	Patch,
+}
+
+/// Raises a major.minor.patch version, dropping anything after the patch
+/// number like -beta.1.
+pub fn bump_version(version: &str, part: Part) -> String {
+	let release = version.split(['-', '+']).next().unwrap();
+	let numbers = release
+		.split('.')
+		.map(|n| n.parse::<u64>())
+		.collect::<Result<Vec<_>, _>>()
+		.ok()
+		.filter(|n| n.len() == 3)
+		.unwrap_or_else(|| panic!("Version {version} is not major.minor.patch"));
+	let (major, minor, patch) = (numbers[0], numbers[1], numbers[2]);
+	match part {
+		Part::Major => format!("{}.0.0", major + 1),
+		Part::Minor => format!("{major}.{}.0", minor + 1),
+		Part::Patch => format!("{major}.{minor}.{}", patch + 1),
+	}
+}
+
+/// Replaces the version line of a build config, toml or json.
+fn set_config_version(path: &str, old: &str, new: &str) {
+	let data = fs::read_to_string(path).unwrap_or_else(|_| panic!("Failed to read config: {path}"));
+	let (from, to) = match path.ends_with(".json") {
+		true => (
+			format!("\"version\": \"{old}\""),
+			format!("\"version\": \"{new}\""),
+		),
+		false => (
+			format!("version = \"{old}\""),
+			format!("version = \"{new}\""),
+		),
+	};
+	if !data.contains(&from) {
+		panic!("Failed to find {from} in {path}");
+	}
+	let data = data.replacen(&from, &to, 1);
+	fs::write(path, data).unwrap_or_else(|_| panic!("Failed to write file: {path}"));
+}
+
+pub fn run(args: VersionArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let path = args.config.as_deref().unwrap_or(CONFIG_FILE);
+	let config = BuildConfig::load(path);
+	let Some(old) = config.version.clone() else {
+		panic!("No version in {path}");
+	};
+	let Some(VersionCommand::Bump { part }) = args.command else {
+		println!("{old}");
+		return;
+	};
+
+	let status = git(".", &["status", "--porcelain"]).expect("Not in a git repository.");
+	if !status.trim().is_empty() {
+		panic!("The working tree has uncommitted changes, commit or stash them first.");
+	}
+	let new = bump_version(&old, part);
+	set_config_version(path, &old, &new);
+	for file in &config.version_files {
+		let data =
+			fs::read_to_string(file).unwrap_or_else(|_| panic!("Failed to read file: {file}"));
+		if !data.contains(&old) {
+			log::warn!("{file} doesn't have version {old} in it");
+			continue;
+		}
+		log::debug!("updating {file}");
+		fs::write(file, data.replace(&old, &new))
+			.unwrap_or_else(|_| panic!("Failed to write file: {file}"));
+	}
+
+	let tag = format!("v{new}");
+	let files = std::iter::once(path).chain(config.version_files.iter().map(|f| f.as_str()));
+	let add = ["add", "--"].into_iter().chain(files).collect::<Vec<_>>();
+	git(".", &add).expect("Failed to add the version changes to git.");
+	git(".", &["commit", "-m", &format!("Release {tag}")])
+		.expect("Failed to commit the version changes.");
+	git(".", &["tag", &tag])
+		.unwrap_or_else(|| panic!("Failed to create tag {tag}, does it exist?"));
+	println!("Bumped {old} to {new} and tagged {tag}.");
+}
@@ -0,0 +1,259 @@
+use super::pdtrelease::{BuildConfig, Layer, OptimizeConfig, CONFIG_FILE};
+use super::{pdtfs, pdtjson, pdtlog};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::ArgAction;
+use notify::{RecursiveMode, Watcher};
+use oxipng::{InFile, OutFile};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{MAIN_SEPARATOR as SLASH, MAIN_SEPARATOR_STR};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for more changes before staging, editors often write a
+/// file more than once when saving.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// A layer folder being watched, lowest priority first.
+struct WatchedLayer {
+	name: String,
+	dir: Utf8PathBuf,
+}
+
+/// Where the files of a release are staged and how.
+struct Stage {
+	output: String,
+	optimize: Option<OptimizeConfig>,
+}
+
+impl Stage {
+	fn path(&self, file: &str) -> String {
+		format!(
+			"{}{SLASH}{}",
+			self.output,
+			file.replace('/', MAIN_SEPARATOR_STR)
+		)
+	}
+
+	/// Copies a file into the output, formatting json and optimizing pngs on
+	/// the way. Broken files are copied as they are, so a half written file
+	/// doesn't stop the watch.
+	fn stage(&self, file: &str, source: &Utf8Path) {
+		let output = self.path(file);
+		if file.ends_with(".json") || file.ends_with(".mcmeta") {
+			let formatted = fs::read_to_string(source).ok().and_then(|data| {
+				let data = pdtjson::strip_comments(&data);
+				serde_json::from_str::<serde_json::Value>(&data)
+					.map_err(|e| log::error!("{file} isn't valid json: {e}"))
+					.ok()
+					.map(|_| pdtjson::format_json(&data, "\t"))
+			});
+			if let Some(formatted) = formatted {
+				let dir = Utf8Path::new(&output).parent().unwrap();
+				fs::create_dir_all(dir)
+					.unwrap_or_else(|_| panic!("Failed to create {dir} directory."));
+				fs::write(&output, formatted)
+					.unwrap_or_else(|_| panic!("Failed to write file: {output}"));
+				return;
+			}
+		}
+		pdtfs::copy_file(source.as_str(), &output);
+		if let (Some(config), true) = (&self.optimize, file.ends_with(".png")) {
+			let options = config.options();
+			let path = Utf8PathBuf::from(&output);
+			let result = oxipng::optimize(
+				&InFile::Path(path.clone().into()),
+				&OutFile::Path(Some(path.into())),
+				&options,
+			);
+			if let Err(e) = result {
+				log::error!("Failed to optimize image {file}: {e}");
+			}
+		}
+	}
+
+	/// Removes a file, or a folder that was removed from the layers.
+	fn remove(&self, file: &str) {
+		let output = self.path(file);
+		if Utf8Path::new(&output).is_file() {
+			fs::remove_file(&output).unwrap_or_else(|_| panic!("Failed to remove {output}"));
+		} else {
+			pdtfs::if_dir_exists_remove_it(&output);
+		}
+	}
+}
+
+/// The layers of a release that can be watched, zips and blockified addons
+/// are left out.
+fn watched_layers(config: &BuildConfig, release: &str) -> Vec<WatchedLayer> {
+	let combination = config
+		.releases
+		.iter()
+		.find(|r| r.name == release)
+		.unwrap_or_else(|| panic!("Unknown release {release}"));
+	let addons = combination.addons.iter().filter_map(|name| {
+		let addon = config.addons.iter().find(|a| &a.name == name);
+		if addon.is_none() {
+			log::warn!("{name} is made by the build, watching without it");
+		}
+		addon
+	});
+	std::iter::once(&config.base)
+		.chain(addons)
+		.filter_map(|layer: &Layer| {
+			if !Utf8Path::new(&layer.path).is_dir() {
+				log::warn!("{} isn't a folder, watching without it", layer.path);
+				return None;
+			}
+			let dir = fs::canonicalize(&layer.path)
+				.unwrap_or_else(|_| panic!("Failed to find {}", layer.path));
+			let dir = Utf8PathBuf::try_from(dir)
+				.unwrap_or_else(|_| panic!("{} isn't a utf-8 path", layer.path));
+			Some(WatchedLayer {
+				name: layer.name.clone(),
+				dir,
+			})
+		})
+		.collect()
+}
+
+/// Where a file of the pack comes from, the last layer having it wins.
+fn source_of(layers: &[WatchedLayer], file: &str) -> Option<(String, Utf8PathBuf)> {
+	layers.iter().rev().find_map(|layer| {
+		let path = layer.dir.join(file.replace('/', MAIN_SEPARATOR_STR));
+		path.is_file().then(|| (layer.name.clone(), path))
+	})
+}
+
+/// Path in the pack of a changed file, None if it isn't in a layer.
+fn pack_path(layers: &[WatchedLayer], path: &Utf8Path) -> Option<String> {
+	layers.iter().find_map(|layer| {
+		let relative = path.strip_prefix(&layer.dir).ok()?;
+		Some(relative.as_str().replace(SLASH, "/"))
+	})
+}
+
+#[derive(Debug, clap::Args)]
+pub struct WatchArgs {
+	#[arg(long)]
+	/// Build config to watch the layers of, defaults to builder.toml
+	config: Option<String>,
+	#[arg(short, long)]
+	/// Release to stage, defaults to the first one of the build config
+	release: Option<String>,
+	#[arg(short, long)]
+	/// Folder to stage the pack in, like a pack folder in
+	/// .minecraft/resourcepacks. Defaults to a watch folder in the output
+	output: Option<String>,
+	#[arg(long)]
+	/// Don't optimize pngs even if the build config does, for faster
+	/// staging
+	no_optimize: bool,
+	#[arg(short, long, action = ArgAction::Count)]
+	/// Print more output, repeat for even more
+	verbose: u8,
+	#[arg(short, long)]
+	/// Only print warnings and errors
+	quiet: bool,
+}
+
+/// Stages a release into a folder, then watches its layers and stages every
+/// file again when it changes, until stopped.
+pub fn run(args: WatchArgs) {
+	pdtlog::init(args.verbose, args.quiet);
+	let path = args.config.as_deref().unwrap_or(CONFIG_FILE);
+	let config = BuildConfig::load(path);
+	let release = args
+		.release
+		.unwrap_or_else(|| config.releases[0].name.clone());
+	let layers = watched_layers(&config, &release);
+	let stage = Stage {
+		output: args
+			.output
+			.unwrap_or_else(|| format!("{}{SLASH}watch", config.output)),
+		optimize: config.optimize.clone().filter(|_| !args.no_optimize),
+	};
+
+	pdtfs::if_dir_exists_remove_and_remake_it(&stage.output);
+	let filter = config.files.filter();
+	let mut files = BTreeSet::new();
+	for layer in &layers {
+		let dir = pdtfs::check_dir_ends_with_slash(layer.dir.to_string());
+		for file in pdtfs::find_filtered_files_in_dir(&dir, true, &None, &filter) {
+			files.insert(file[dir.len()..].replace(SLASH, "/"));
+		}
+	}
+	for file in &files {
+		if let Some((_, source)) = source_of(&layers, file) {
+			stage.stage(file, &source);
+		}
+	}
+	log::info!(
+		"Staged {} files of {release} in {}",
+		files.len(),
+		stage.output
+	);
+
+	let (sender, receiver) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(sender)
+		.unwrap_or_else(|e| panic!("Failed to start watching: {e}"));
+	for layer in &layers {
+		watcher
+			.watch(layer.dir.as_std_path(), RecursiveMode::Recursive)
+			.unwrap_or_else(|e| panic!("Failed to watch {}: {e}", layer.dir));
+	}
+	println!(
+		"Watching {} layers of {release}, stop with Ctrl+C",
+		layers.len()
+	);
+
+	while let Ok(event) = receiver.recv() {
+		let mut changed = BTreeMap::new();
+		let mut add = |event: notify::Result<notify::Event>| match event {
+			Ok(event) => {
+				for path in event.paths {
+					let Ok(path) = Utf8PathBuf::try_from(path) else {
+						continue;
+					};
+					// only the globs are checked here, ignore files are
+					// read again when the watch is started
+					let file = pack_path(&layers, &path).filter(|f| filter.includes(f));
+					if let Some(file) = file {
+						changed.insert(file, path);
+					}
+				}
+			}
+			Err(e) => log::error!("Watch error: {e}"),
+		};
+		add(event);
+		while let Ok(event) = receiver.recv_timeout(SETTLE_TIME) {
+			add(event);
+		}
+		for (file, path) in changed {
+			if path.is_dir() {
+				// a folder moved in, stage everything in it
+				let dir = pdtfs::check_dir_ends_with_slash(path.to_string());
+				for inner in pdtfs::find_files_in_dir(&dir, true, &None) {
+					let inner = format!("{file}/{}", inner[dir.len()..].replace(SLASH, "/"));
+					if !filter.includes(&inner) {
+						continue;
+					}
+					if let Some((_, source)) = source_of(&layers, &inner) {
+						stage.stage(&inner, &source);
+					}
+				}
+				continue;
+			}
+			match source_of(&layers, &file) {
+				Some((layer, source)) => {
+					log::info!("{file} changed, staging it from {layer}");
+					stage.stage(&file, &source);
+				}
+				None => {
+					log::info!("{file} was removed");
+					stage.remove(&file);
+				}
+			}
+		}
+	}
+}
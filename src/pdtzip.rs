@@ -0,0 +1,265 @@
+use super::pdtfs::{self, FileFilter, Symlinks, TempDir};
+use super::pdthash;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+pub fn is_zip(path: &str) -> bool {
+	path.to_lowercase().ends_with(".zip") && pdtfs::is_file(path)
+}
+
+/// Extracts the entries under prefix (or all of them) into dir, with the
+/// prefix taken off their paths. Entries that would end up outside of dir
+/// are left out.
+pub fn extract_zip(zip: &str, dir: &str, prefix: Option<&str>) {
+	let file = fs::File::open(pdtfs::long_path(zip))
+		.unwrap_or_else(|_| panic!("Failed to open zip: {zip}"));
+	let mut archive = ZipArchive::new(file).unwrap_or_else(|_| panic!("Failed to read zip: {zip}"));
+	for i in 0..archive.len() {
+		let mut entry = archive
+			.by_index(i)
+			.unwrap_or_else(|_| panic!("Failed to read entry {i} of zip: {zip}"));
+		if entry.is_dir() {
+			continue;
+		}
+		let Some(name) = entry.enclosed_name() else {
+			log::warn!("skipping unsafe zip entry: {}", entry.name());
+			continue;
+		};
+		// zips made on Windows may separate with \ instead of /
+		let name = name.to_string_lossy().replace('\\', "/");
+		let name = match prefix {
+			Some(prefix) => match Utf8Path::new(&name).strip_prefix(prefix) {
+				Ok(name) if !name.as_str().is_empty() => name.to_path_buf(),
+				_ => continue,
+			},
+			None => Utf8PathBuf::from(name),
+		};
+		let output = Utf8Path::new(dir).join(name);
+		log::debug!("extracting: {output}");
+		if let Some(parent) = output.parent() {
+			fs::create_dir_all(pdtfs::long_path(parent.as_str()))
+				.unwrap_or_else(|_| panic!("Failed to create {parent} directory."));
+		}
+		let mut file = fs::File::create(pdtfs::long_path(output.as_str()))
+			.unwrap_or_else(|_| panic!("Failed to create file: {output}"));
+		io::copy(&mut entry, &mut file).unwrap_or_else(|_| panic!("Failed to extract: {output}"));
+	}
+}
+
+/// What an entry of a zip holds, None if there is no entry by that name.
+pub fn read_zip_entry(zip: &str, name: &str) -> Option<Vec<u8>> {
+	let file = fs::File::open(pdtfs::long_path(zip))
+		.unwrap_or_else(|_| panic!("Failed to open zip: {zip}"));
+	let mut archive = ZipArchive::new(file).unwrap_or_else(|_| panic!("Failed to read zip: {zip}"));
+	let mut entry = archive.by_name(name).ok()?;
+	let mut data = vec![];
+	entry
+		.read_to_end(&mut data)
+		.unwrap_or_else(|_| panic!("Failed to read {name} of zip: {zip}"));
+	Some(data)
+}
+
+/// Names of the files in a zip, separated by `/`.
+pub fn zip_entries(zip: &str) -> Vec<String> {
+	let file = fs::File::open(pdtfs::long_path(zip))
+		.unwrap_or_else(|_| panic!("Failed to open zip: {zip}"));
+	let archive = ZipArchive::new(file).unwrap_or_else(|_| panic!("Failed to read zip: {zip}"));
+	archive
+		.file_names()
+		.filter(|name| !name.ends_with('/'))
+		.map(|name| name.to_string())
+		.collect()
+}
+
+/// Files of a pack folder or zip, by their path in the pack (separated by
+/// `/`) mapped to where they are read from. Zips are extracted to a
+/// temporary directory, which is added to temp_dirs and removed when they
+/// are dropped, so the caller keeps them while it reads the files.
+pub fn pack_files(path: &str, temp_dirs: &mut Vec<TempDir>) -> BTreeMap<String, String> {
+	filtered_pack_files(path, temp_dirs, &FileFilter::default())
+}
+
+/// Like [pack_files], leaving out the files the filter does.
+pub fn filtered_pack_files(
+	path: &str, temp_dirs: &mut Vec<TempDir>, filter: &FileFilter,
+) -> BTreeMap<String, String> {
+	let dir = if is_zip(path) {
+		let temp = extract_zip_to_temp(path);
+		let dir = temp.dir.clone();
+		temp_dirs.push(temp);
+		dir
+	} else if pdtfs::is_dir(path) {
+		path.to_string()
+	} else {
+		panic!("Pack not found: {path}");
+	};
+	pdtfs::find_filtered_files_in_dir(&dir, true, &None, filter)
+		.into_iter()
+		.map(|file| (pdtfs::pack_path(&dir, &file), file))
+		.collect()
+}
+
+/// Extracts the zip into a temporary directory named after its hash, which
+/// is removed when it is dropped.
+pub fn extract_zip_to_temp(zip: &str) -> TempDir {
+	let hash = pdthash::get_hash(zip, false);
+	let temp = TempDir::new(&format!("pdt_{}", &hash[..16]));
+	extract_zip(zip, &temp.dir, None);
+	temp
+}
+
+/// Writes every file in dir to a new zip, except the ones whose path in the
+/// pack (separated by `/`) is in exclude and the ones .pdtignore and
+/// .packignore files leave out.
+pub fn zip_dir(dir: &str, zip: &str, exclude: &[&str], level: Option<i64>) {
+	let filter = FileFilter::new(&[], &[], &pdtfs::PACK_IGNORE_FILES, Symlinks::Follow);
+	let files = pdtfs::find_filtered_files_in_dir(dir, true, &None, &filter)
+		.into_iter()
+		.map(|path| (pdtfs::pack_path(dir, &path), path))
+		.filter(|(name, _)| !exclude.contains(&name.as_str()))
+		.collect();
+	zip_files(&files, zip, level);
+}
+
+/// Writes a new zip from entry names, separated by `/`, mapped to the files
+/// they are read from. The same files always give a byte-identical zip, on
+/// any platform: entries are sorted by name, timestamps are all 1980-01-01
+/// (the earliest a zip can hold) and permissions are all rw-r--r--.
+/// The deflate level goes from 0 to 9, 6 if not given.
+pub fn zip_files(files: &BTreeMap<String, String>, zip: &str, level: Option<i64>) {
+	let options = file_options(level);
+	let mut writer = create_zip(zip);
+	for (name, path) in files {
+		add_file(&mut writer, zip, name, path, options);
+	}
+	writer
+		.finish()
+		.unwrap_or_else(|_| panic!("Failed to write zip: {zip}"));
+}
+
+fn file_options(level: Option<i64>) -> SimpleFileOptions {
+	if let Some(level) = level {
+		if !(0..=9).contains(&level) {
+			panic!("Zip compression level must be in 0..9, found {level}");
+		}
+	}
+	SimpleFileOptions::default()
+		.compression_method(CompressionMethod::Deflated)
+		.compression_level(level)
+		.last_modified_time(DateTime::default())
+		.unix_permissions(0o644)
+}
+
+fn create_zip(zip: &str) -> ZipWriter<fs::File> {
+	let file = fs::File::create(pdtfs::long_path(zip))
+		.unwrap_or_else(|_| panic!("Failed to create zip: {zip}"));
+	ZipWriter::new(file)
+}
+
+fn add_file(
+	writer: &mut ZipWriter<fs::File>, zip: &str, name: &str, path: &str, options: SimpleFileOptions,
+) {
+	let mut file = fs::File::open(pdtfs::long_path(path))
+		.unwrap_or_else(|_| panic!("Failed to read file: {path}"));
+	writer
+		.start_file(name, options)
+		.unwrap_or_else(|_| panic!("Failed to add {name} to zip: {zip}"));
+	io::copy(&mut file, writer).unwrap_or_else(|_| panic!("Failed to add {name} to zip: {zip}"));
+}
+
+/// A zip whose entries are added from any thread as they are made, so they
+/// never all have to be in memory. They are compressed into `{zip}.tmp-{pid}`
+/// in the order they come in, and [ZipStream::finish] copies them from there
+/// as they are, sorted by name like zip_files, so the zip is the same whatever
+/// order they were made in. The temporary zip is removed when it is dropped.
+pub struct ZipStream {
+	zip: String,
+	temp: String,
+	options: SimpleFileOptions,
+	writer: Mutex<Option<ZipWriter<fs::File>>>,
+}
+
+impl ZipStream {
+	pub fn new(zip: &str, level: Option<i64>) -> ZipStream {
+		let temp = format!("{zip}.tmp-{}", std::process::id());
+		// opened to read as well, finish reads the entries back
+		let file = fs::OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.open(pdtfs::long_path(&temp))
+			.unwrap_or_else(|_| panic!("Failed to create zip: {temp}"));
+		ZipStream {
+			zip: zip.to_string(),
+			options: file_options(level),
+			writer: Mutex::new(Some(ZipWriter::new(file))),
+			temp,
+		}
+	}
+
+	/// Compresses an entry into the temporary zip.
+	pub fn add(&self, name: &str, data: &[u8]) {
+		let mut writer = self.writer.lock().unwrap();
+		let writer = writer.as_mut().expect("Zip was already finished.");
+		let temp = &self.temp;
+		writer
+			.start_file(name, self.options)
+			.unwrap_or_else(|_| panic!("Failed to add {name} to zip: {temp}"));
+		writer
+			.write_all(data)
+			.unwrap_or_else(|_| panic!("Failed to add {name} to zip: {temp}"));
+	}
+
+	/// Writes the zip from the added entries and the files, entry names
+	/// mapped to the files they are read from, all sorted by name.
+	pub fn finish(&self, files: &BTreeMap<String, String>) {
+		let (zip, temp) = (&self.zip, &self.temp);
+		let writer = self.writer.lock().unwrap().take();
+		let added = writer
+			.expect("Zip was already finished.")
+			.finish()
+			.unwrap_or_else(|_| panic!("Failed to write zip: {temp}"));
+		let mut added =
+			ZipArchive::new(added).unwrap_or_else(|_| panic!("Failed to read zip: {temp}"));
+
+		let mut entries = files
+			.iter()
+			.map(|(name, path)| (name.clone(), Some(path)))
+			.collect::<BTreeMap<_, _>>();
+		entries.extend(added.file_names().map(|name| (name.to_string(), None)));
+		let mut writer = create_zip(zip);
+		for (name, path) in entries {
+			match path {
+				Some(path) => add_file(&mut writer, zip, &name, path, self.options),
+				None => {
+					let i = added.index_for_name(&name).unwrap();
+					let entry = added
+						.by_index_raw(i)
+						.unwrap_or_else(|_| panic!("Failed to read {name} of zip: {temp}"));
+					writer
+						.raw_copy_file(entry)
+						.unwrap_or_else(|_| panic!("Failed to add {name} to zip: {zip}"));
+				}
+			}
+		}
+		writer
+			.finish()
+			.unwrap_or_else(|_| panic!("Failed to write zip: {zip}"));
+	}
+}
+
+impl Drop for ZipStream {
+	fn drop(&mut self) {
+		// the file is closed first, Windows can't remove an open one
+		if let Ok(writer) = self.writer.get_mut() {
+			writer.take();
+		}
+		let _ = fs::remove_file(pdtfs::long_path(&self.temp));
+	}
+}